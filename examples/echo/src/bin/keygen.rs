@@ -27,7 +27,7 @@ const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz01
 
 /// Encode bytes as base64
 fn encode_base64(data: &[u8]) -> String {
-    let mut output = String::with_capacity((data.len() + 2) / 3 * 4);
+    let mut output = String::with_capacity(data.len().div_ceil(3) * 4);
 
     for chunk in data.chunks(3) {
         let b0 = chunk[0] as usize;
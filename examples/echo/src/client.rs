@@ -3,13 +3,19 @@
 //! A NOMAD echo client that performs proper cryptographic handshake
 //! and encrypts all messages using the derived session keys.
 
+use std::io;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use nomad_protocol::core::SyncState;
+use nomad_protocol::core::{CryptoError, ProtocolVersion, SyncState, VersionRange};
 use nomad_protocol::crypto::{
-    CryptoSession, InitiatorHandshake, Role, SessionId, SessionKeys, StaticKeypair,
+    CryptoSession, HandshakeProfile, InitiatorHandshake, Role, SessionId, SessionKeys,
+    StaticKeypair,
+};
+use nomad_protocol::transport::{
+    sizes, CloseFrame, CloseReason, FramePacer, HandshakeFlags, HandshakeValidation, NomadSocket,
+    PayloadHeader, RetryFrame, TimestampTracker,
 };
 use tokio::net::UdpSocket;
 use tokio::sync::RwLock;
@@ -24,6 +30,80 @@ mod msg_type {
     pub const HANDSHAKE_RESP: u8 = 0x02;
     /// Encrypted data frame - Type 0x03
     pub const DATA: u8 = 0x03;
+    /// Rekey request (client -> server) and reply (server -> client), or an
+    /// empty request from the server asking us to rekey - Type 0x04
+    pub const REKEY: u8 = 0x04;
+    /// Session close (either direction) - Type 0x05
+    pub const CLOSE: u8 = 0x05;
+    /// Stateless retry (server -> client) - Type 0x06
+    pub const RETRY: u8 = 0x06;
+    /// Path challenge to a new client address (server -> client) - Type 0x07
+    pub const PATH_CHALLENGE: u8 = 0x07;
+    /// Echoed path challenge token (client -> server) - Type 0x08
+    pub const PATH_RESPONSE: u8 = 0x08;
+    /// Keepalive (client -> server), answered in kind (server -> client) - Type 0x09
+    pub const KEEPALIVE: u8 = 0x09;
+}
+
+/// Retry schedule for establishing a session.
+///
+/// Delays grow by `multiplier` from `initial_delay` up to `max_delay`, each
+/// spread by a random `jitter` fraction so many clients restarting at once
+/// don't retry in lockstep.
+#[derive(Debug, Clone, Copy)]
+pub struct BackoffPolicy {
+    /// Delay before the first retry.
+    pub initial_delay: Duration,
+    /// Cap on the delay between attempts.
+    pub max_delay: Duration,
+    /// Factor the delay grows by after each failure.
+    pub multiplier: f64,
+    /// Random spread of each delay, as a fraction (0.2 = ±20%).
+    pub jitter: f64,
+    /// Stop retrying once this much time has passed since the first attempt.
+    pub deadline: Duration,
+}
+
+impl BackoffPolicy {
+    /// Create a policy doubling from `initial_delay` up to `max_delay`.
+    pub fn new(initial_delay: Duration, max_delay: Duration, deadline: Duration) -> Self {
+        Self {
+            initial_delay,
+            max_delay,
+            multiplier: 2.0,
+            jitter: 0.2,
+            deadline,
+        }
+    }
+
+    /// Set the random spread of each delay.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Delay before retry number `attempt` (from 0), before jitter.
+    pub fn base_delay(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.powi(attempt.min(i32::MAX as u32) as i32);
+        self.initial_delay.mul_f64(factor).min(self.max_delay)
+    }
+
+    /// Delay before retry number `attempt` (from 0), with jitter.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let spread = (rand::random::<f64>() * 2.0 - 1.0) * self.jitter;
+        self.base_delay(attempt).mul_f64((1.0 + spread).max(0.0))
+    }
+}
+
+impl Default for BackoffPolicy {
+    fn default() -> Self {
+        Self::new(
+            Duration::from_millis(200),
+            Duration::from_secs(5),
+            Duration::from_secs(30),
+        )
+    }
 }
 
 /// Client configuration.
@@ -31,6 +111,10 @@ mod msg_type {
 pub struct EchoClientConfig {
     /// Server address.
     pub server_addr: SocketAddr,
+    /// Server host name. When set, [`EchoClient::connect`] resolves it and
+    /// races its addresses on `server_addr`'s port (happy eyeballs), then
+    /// records the winner in `server_addr`.
+    pub server_host: Option<String>,
     /// Server public key (32 bytes).
     pub server_public_key: [u8; 32],
     /// Local bind address (0 = auto).
@@ -39,16 +123,29 @@ pub struct EchoClientConfig {
     pub client_keypair: Option<StaticKeypair>,
     /// Enable persistent mode (stay connected after test).
     pub persistent: bool,
+    /// How to treat unknown handshake flag bits.
+    pub handshake_validation: HandshakeValidation,
+    /// Handshake security profile (anonymous skips the client keypair).
+    pub handshake_profile: HandshakeProfile,
+    /// Retry schedule for [`EchoClient::reconnect`].
+    pub reconnect_policy: BackoffPolicy,
+    /// How long before `REKEY_AFTER_TIME` the send path starts a rekey.
+    pub rekey_margin: Duration,
 }
 
 impl std::fmt::Debug for EchoClientConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("EchoClientConfig")
             .field("server_addr", &self.server_addr)
+            .field("server_host", &self.server_host)
             .field("server_public_key", &"[redacted]")
             .field("bind_addr", &self.bind_addr)
             .field("client_keypair", &self.client_keypair.as_ref().map(|_| "[keypair]"))
             .field("persistent", &self.persistent)
+            .field("handshake_validation", &self.handshake_validation)
+            .field("handshake_profile", &self.handshake_profile)
+            .field("reconnect_policy", &self.reconnect_policy)
+            .field("rekey_margin", &self.rekey_margin)
             .finish()
     }
 }
@@ -57,10 +154,15 @@ impl Default for EchoClientConfig {
     fn default() -> Self {
         Self {
             server_addr: "127.0.0.1:19999".parse().unwrap(),
+            server_host: None,
             server_public_key: [0u8; 32],
             bind_addr: "0.0.0.0:0".parse().unwrap(),
             client_keypair: None,
             persistent: false,
+            handshake_validation: HandshakeValidation::default(),
+            handshake_profile: HandshakeProfile::default(),
+            reconnect_policy: BackoffPolicy::default(),
+            rekey_margin: Duration::from_secs(10),
         }
     }
 }
@@ -74,8 +176,27 @@ pub struct EchoClient {
     sequence: Arc<RwLock<u64>>,
     last_server_seq: Arc<RwLock<u64>>,
     client_keypair: StaticKeypair,
+    /// Response received and decrypted but not yet returned to the caller.
+    pending_response: Option<EchoState>,
+    /// Decides when an idle session needs a keepalive.
+    pacer: FramePacer,
+    /// Timestamps for RTT sampling.
+    timestamps: TimestampTracker,
+    /// When we last received a frame from the server.
+    last_received: Instant,
+    /// The server asked for a rekey; the next send runs the exchange.
+    rekey_requested: bool,
 }
 
+/// How often the persistent loop checks whether a keepalive is due.
+const KEEPALIVE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long to wait for the server's REKEY reply before resending.
+const REKEY_REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// REKEY requests sent before giving up on a rekey.
+const REKEY_ATTEMPTS: usize = 3;
+
 impl EchoClient {
     /// Create a new echo client.
     pub fn new(config: EchoClientConfig) -> Self {
@@ -92,62 +213,173 @@ impl EchoClient {
             sequence: Arc::new(RwLock::new(0)),
             last_server_seq: Arc::new(RwLock::new(0)),
             client_keypair,
+            pending_response: None,
+            pacer: FramePacer::new(),
+            timestamps: TimestampTracker::new(),
+            last_received: Instant::now(),
+            rekey_requested: false,
         }
     }
 
     /// Connect to the server and perform Noise_IK handshake.
+    ///
+    /// With `server_host` set, every resolved address gets a handshake
+    /// attempt (IPv6 first, IPv4 after a short delay) and the first to
+    /// complete wins; `bind_addr` is not used in that case.
     pub async fn connect(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let socket = UdpSocket::bind(self.config.bind_addr).await?;
-        socket.connect(self.config.server_addr).await?;
+        let (socket, (session_id, version, crypto)) = match &self.config.server_host {
+            Some(host) => {
+                let (config, client_keypair) = (&self.config, &self.client_keypair);
+                let ((socket, handshake), winner) = NomadSocket::connect_happy_eyeballs(
+                    host,
+                    config.server_addr.port(),
+                    |socket, _| async move {
+                        let socket = socket.socket_arc();
+                        let handshake = Self::perform_handshake(config, client_keypair, &socket)
+                            .await
+                            .map_err(|e| io::Error::other(e.to_string()))?;
+                        Ok((socket, handshake))
+                    },
+                )
+                .await?;
+                let socket =
+                    Arc::try_unwrap(socket).map_err(|_| "Happy eyeballs socket still shared")?;
+                self.config.server_addr = winner;
+                (socket, handshake)
+            }
+            None => {
+                let socket = UdpSocket::bind(self.config.bind_addr).await?;
+                socket.connect(self.config.server_addr).await?;
+                let handshake =
+                    Self::perform_handshake(&self.config, &self.client_keypair, &socket).await?;
+                (socket, handshake)
+            }
+        };
         eprintln!(
             "Connected to server {} from {}",
             self.config.server_addr,
             socket.local_addr()?
         );
+        eprintln!(
+            "Handshake complete, session_id: {:02x?}, protocol version: {}",
+            session_id.as_bytes(),
+            version
+        );
 
-        // Perform Noise_IK handshake
-        let session_id = self.perform_handshake(&socket).await?;
-        eprintln!("Handshake complete, session_id: {:02x?}", session_id.as_bytes());
-
+        self.crypto = Some(crypto);
         self.socket = Some(socket);
+        self.last_received = Instant::now();
+        self.pacer.on_frame_sent();
         Ok(())
     }
 
+    /// Connect, retrying failed handshakes with backoff.
+    ///
+    /// Each attempt binds a fresh socket and runs a full handshake. Gives up
+    /// with the last error once the next retry would pass the policy's
+    /// deadline (an attempt itself may wait up to the 5 s handshake timeout).
+    pub async fn connect_with_retry(
+        &mut self,
+        policy: BackoffPolicy,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let start = Instant::now();
+        let mut attempt = 0;
+        loop {
+            let error = match self.connect().await {
+                Ok(()) => return Ok(()),
+                Err(e) => e,
+            };
+            let delay = policy.delay(attempt);
+            if start.elapsed() + delay >= policy.deadline {
+                return Err(format!("Connect failed after {} attempts: {}", attempt + 1, error).into());
+            }
+            eprintln!("Connect attempt {} failed: {}; retrying in {:?}", attempt + 1, error, delay);
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Re-establish the session with a new handshake.
+    ///
+    /// Retries per `reconnect_policy`. The application state and sequence
+    /// numbers are kept, so the next message continues from the last known
+    /// version rather than starting over.
+    pub async fn reconnect(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.disconnect();
+        self.pacer = FramePacer::new();
+        self.timestamps = TimestampTracker::new();
+        self.connect_with_retry(self.config.reconnect_policy).await
+    }
+
     /// Perform the Noise_IK handshake.
     ///
     /// Wire format per specs/1-SECURITY.md:
     /// - HandshakeInit: [Type:1][Reserved:1][Version:2][Noise message...]
     /// - HandshakeResp: [Type:1][Reserved:1][SessionID:6][Noise message...]
+    ///
+    /// The init payload offers our supported version range; the response
+    /// payload carries the version the server chose.
     async fn perform_handshake(
-        &mut self,
+        config: &EchoClientConfig,
+        client_keypair: &StaticKeypair,
         socket: &UdpSocket,
-    ) -> Result<SessionId, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<
+        (SessionId, ProtocolVersion, CryptoSession),
+        Box<dyn std::error::Error + Send + Sync>,
+    > {
         // Create initiator handshake state
-        let mut handshake =
-            InitiatorHandshake::new(&self.client_keypair, &self.config.server_public_key)?;
+        let mut handshake = match config.handshake_profile {
+            HandshakeProfile::Authenticated => {
+                InitiatorHandshake::new(client_keypair, &config.server_public_key)?
+            }
+            HandshakeProfile::Anonymous => {
+                InitiatorHandshake::anonymous(&config.server_public_key)?
+            }
+        };
 
-        // Build handshake initiation with state type ID as payload
-        let payload = EchoState::STATE_TYPE_ID.as_bytes();
-        let noise_message = handshake.write_message(payload)?;
+        // Build handshake initiation: [VersionRange:4][State type ID...]
+        let mut payload = VersionRange::SUPPORTED.encode().to_vec();
+        payload.extend_from_slice(EchoState::STATE_TYPE_ID.as_bytes());
+        let noise_message = handshake.write_message(&payload)?;
 
         // Build packet per spec: [Type:1][Reserved:1][Version:2][Noise message...]
-        let mut packet = Vec::with_capacity(4 + noise_message.len());
-        packet.push(msg_type::HANDSHAKE_INIT);  // Type 0x01
-        packet.push(0x00);                       // Reserved
-        packet.extend_from_slice(&0x0001u16.to_le_bytes());  // Protocol version 1.0
-        packet.extend_from_slice(&noise_message);
+        let init_packet = |cookie: Option<&[u8]>| {
+            let mut packet =
+                Vec::with_capacity(4 + sizes::RETRY_COOKIE_SIZE + noise_message.len());
+            packet.push(msg_type::HANDSHAKE_INIT);  // Type 0x01
+            let flags = if cookie.is_some() { HandshakeFlags::COOKIE } else { HandshakeFlags::NONE };
+            packet.push(flags.as_byte());
+            packet.extend_from_slice(&ProtocolVersion::CURRENT.as_u16().to_le_bytes());
+            packet.extend_from_slice(cookie.unwrap_or_default());
+            packet.extend_from_slice(&noise_message);
+            packet
+        };
+        let packet = init_packet(None);
         socket.send(&packet).await?;
 
         eprintln!("Sent handshake init ({} bytes)", packet.len());
 
-        // Wait for handshake response
+        // Wait for handshake response, answering at most one retry
         let mut buf = [0u8; 65535];
-        let recv_result = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf)).await;
+        let mut retried = false;
+        let len = loop {
+            let recv_result =
+                tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf)).await;
+            let len = match recv_result {
+                Ok(Ok(len)) => len,
+                Ok(Err(e)) => return Err(format!("Receive error: {}", e).into()),
+                Err(_) => return Err("Handshake timeout".into()),
+            };
 
-        let len = match recv_result {
-            Ok(Ok(len)) => len,
-            Ok(Err(e)) => return Err(format!("Receive error: {}", e).into()),
-            Err(_) => return Err("Handshake timeout".into()),
+            if buf[..len].first() != Some(&msg_type::RETRY) || retried {
+                break len;
+            }
+            // Echo the server's cookie; the Noise message is unchanged
+            // since the server kept no state for the first init
+            let retry = RetryFrame::from_bytes(&buf[..len])?;
+            socket.send(&init_packet(Some(&retry.cookie))).await?;
+            retried = true;
+            eprintln!("Server requested retry, resent handshake init with cookie");
         };
 
         let data = &buf[..len];
@@ -159,7 +391,10 @@ impl EchoClient {
         if data[0] != msg_type::HANDSHAKE_RESP {
             return Err(format!("Unexpected response type: {:02x}", data[0]).into());
         }
-        let _reserved = data[1];
+        let flags = HandshakeFlags::parse(data[1], config.handshake_validation)?;
+        if !flags.is_valid() {
+            eprintln!("Ignoring unknown handshake flags: 0x{:02x}", flags.unknown_bits());
+        }
 
         // Extract session ID from header (in the clear)
         let mut session_id_bytes = [0u8; 6];
@@ -170,14 +405,24 @@ impl EchoClient {
         let noise_response = &data[8..];
         let (server_payload, handshake_result) = handshake.read_message(noise_response)?;
 
+        // Server payload: [Chosen version:2][Ack...]
+        if server_payload.len() < 2 {
+            return Err("HandshakeResp payload missing protocol version".into());
+        }
+        let version = ProtocolVersion::new(u16::from_le_bytes([server_payload[0], server_payload[1]]));
+        if !VersionRange::SUPPORTED.contains(version) {
+            return Err(format!("Server chose unsupported protocol version {}", version).into());
+        }
+
         eprintln!(
             "Received handshake response, session_id: {:02x?}, server payload: {:?}",
             session_id.as_bytes(),
-            String::from_utf8_lossy(&server_payload)
+            String::from_utf8_lossy(&server_payload[2..])
         );
 
-        // Derive session keys
-        let session_keys = SessionKeys::derive(&handshake_result)?;
+        // Derive session keys, bound to the state type we asked for
+        let session_keys =
+            SessionKeys::derive_for_state_type(&handshake_result, EchoState::STATE_TYPE_ID)?;
 
         // Create crypto session
         let crypto = CryptoSession::new(
@@ -186,19 +431,29 @@ impl EchoClient {
             session_keys.initiator_key,
             session_keys.responder_key,
             handshake_result.handshake_hash,
-        );
+        )
+        .with_protocol_version(version.as_u16())
+        .with_peer_public_key(config.server_public_key);
 
-        self.crypto = Some(crypto);
-        Ok(session_id)
+        Ok((session_id, version, crypto))
     }
 
     /// Send an encrypted message to the server.
+    ///
+    /// When the keys are within `rekey_margin` of their rekey limit, the
+    /// REKEY exchange runs first and the message is then sent under the
+    /// new epoch (see [`rekey`](Self::rekey)). A send that still runs out
+    /// of counters rekeys and retries once (see
+    /// [`encrypt_or_rekey`](Self::encrypt_or_rekey)). Once the keys are due
+    /// but the epoch limit has been reached, the client sends CLOSE with
+    /// [`CloseReason::KeyExpired`], disconnects and returns an error; a new
+    /// handshake is required to continue.
     pub async fn send_message(
         &mut self,
         message: &[u8],
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let socket = self.socket.as_ref().ok_or("Not connected")?;
-        let crypto = self.crypto.as_mut().ok_or("No crypto session")?;
+        self.rekey_if_due().await?;
+        self.socket.as_ref().ok_or("Not connected")?;
 
         // Increment sequence
         let seq = {
@@ -219,11 +474,11 @@ impl EchoClient {
         plaintext.extend_from_slice(&seq.to_le_bytes());
         plaintext.extend_from_slice(message);
 
-        // Encrypt the frame
-        let (nonce_counter, ciphertext) = crypto.encrypt_frame(msg_type::DATA, 0x00, &plaintext)?;
+        let (nonce_counter, ciphertext) = self.encrypt_or_rekey(msg_type::DATA, &plaintext).await?;
 
         // Build packet: [type:1][session_id:6][nonce:8][ciphertext...]
-        let session_id = crypto.session_id();
+        let socket = self.socket.as_ref().ok_or("Not connected")?;
+        let session_id = self.crypto.as_ref().ok_or("No crypto session")?.session_id();
         let mut packet = Vec::with_capacity(15 + ciphertext.len());
         packet.push(msg_type::DATA);
         packet.extend_from_slice(session_id.as_bytes());
@@ -231,6 +486,7 @@ impl EchoClient {
         packet.extend_from_slice(&ciphertext);
 
         socket.send(&packet).await?;
+        self.pacer.on_frame_sent();
         eprintln!(
             "Sent encrypted message: seq={}, nonce={}, msg={:?}",
             seq,
@@ -241,74 +497,346 @@ impl EchoClient {
         Ok(())
     }
 
-    /// Receive an encrypted response from the server.
-    pub async fn recv_response(
+    /// Encrypt a DATA or KEEPALIVE frame, recovering once from an
+    /// exhausted send counter.
+    ///
+    /// These frames stop `CONTROL_FRAME_HEADROOM` counters short of the
+    /// hard limit, which leaves room for the REKEY exchange. When they run
+    /// out the client rekeys and encrypts the frame again under the new
+    /// epoch. At the epoch limit it sends CLOSE with
+    /// [`CloseReason::KeyExpired`] instead and returns an error; a failed
+    /// rekey disconnects.
+    async fn encrypt_or_rekey(
         &mut self,
-        timeout: Duration,
-    ) -> Result<Option<EchoState>, Box<dyn std::error::Error + Send + Sync>> {
-        let socket = self.socket.as_ref().ok_or("Not connected")?;
+        frame_type: u8,
+        plaintext: &[u8],
+    ) -> Result<(u64, Vec<u8>), Box<dyn std::error::Error + Send + Sync>> {
         let crypto = self.crypto.as_mut().ok_or("No crypto session")?;
+        match crypto.encrypt_frame(frame_type, 0x00, plaintext) {
+            Err(CryptoError::CounterExhaustion) => {}
+            result => return Ok(result?),
+        }
+        if !crypto.can_rekey() {
+            self.close(CloseReason::KeyExpired).await?;
+            return Err("Session closed: send counter exhausted at the key epoch limit".into());
+        }
+
+        eprintln!("Send counter exhausted, rekeying");
+        if let Err(e) = self.rekey().await {
+            self.disconnect();
+            return Err(format!("Session closed: rekey after counter exhaustion failed ({})", e).into());
+        }
+        let crypto = self.crypto.as_mut().ok_or("No crypto session")?;
+        Ok(crypto.encrypt_frame(frame_type, 0x00, plaintext)?)
+    }
+
+    /// Rekey if the keys are close to their soft limit or the server asked.
+    ///
+    /// A failed exchange is not fatal while the current keys are still
+    /// valid: the caller sends under them and the next send tries again.
+    /// Only expired keys disconnect the client. Keys that are due but have
+    /// no epoch left close the session with [`CloseReason::KeyExpired`].
+    async fn rekey_if_due(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let crypto = self.crypto.as_ref().ok_or("No crypto session")?;
+        let due = crypto.should_rekey() || self.rekey_requested;
+        if due && !crypto.can_rekey() {
+            self.close(CloseReason::KeyExpired).await?;
+            return Err("Session closed: key epoch limit reached".into());
+        }
+        let soon = crypto.needs_rekey_soon(self.config.rekey_margin) || self.rekey_requested;
+        if !soon || !crypto.can_rekey() {
+            return Ok(());
+        }
+
+        if let Err(e) = self.rekey().await {
+            if self.crypto.as_ref().is_some_and(|crypto| crypto.keys_expired()) {
+                self.disconnect();
+                return Err(format!("Session closed: keys expired before rekey ({})", e).into());
+            }
+            eprintln!("Rekey failed, continuing on current keys: {}", e);
+        }
+        Ok(())
+    }
 
+    /// Move the session to the next key epoch.
+    ///
+    /// Sends a REKEY request carrying the next epoch under the current
+    /// keys and switches once the server's reply arrives, resending the
+    /// request up to [`REKEY_ATTEMPTS`] times. Responses that arrive in
+    /// the meantime are parked for [`recv_response`](Self::recv_response)
+    /// rather than dropped.
+    pub async fn rekey(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let crypto = self.crypto.as_ref().ok_or("No crypto session")?;
+        let pending = crypto.begin_rekey()?;
+        let epoch = crypto.epoch() + 1;
         let mut buf = [0u8; 65535];
 
-        let result = tokio::time::timeout(timeout, socket.recv(&mut buf)).await;
+        for attempt in 0..REKEY_ATTEMPTS {
+            // Build packet: [type:1][session_id:6][nonce:8][ciphertext([epoch:4])]
+            let crypto = self.crypto.as_mut().ok_or("No crypto session")?;
+            let (nonce_counter, ciphertext) =
+                crypto.encrypt_frame(msg_type::REKEY, 0x00, &epoch.to_le_bytes())?;
+            let mut packet = Vec::with_capacity(15 + ciphertext.len());
+            packet.push(msg_type::REKEY);
+            packet.extend_from_slice(crypto.session_id().as_bytes());
+            packet.extend_from_slice(&nonce_counter.to_le_bytes());
+            packet.extend_from_slice(&ciphertext);
+
+            let socket = self.socket.as_ref().ok_or("Not connected")?;
+            socket.send(&packet).await?;
+            self.pacer.on_frame_sent();
+            eprintln!("Sent rekey request: epoch={}, attempt={}", epoch, attempt + 1);
 
-        match result {
-            Ok(Ok(len)) => {
+            let deadline = Instant::now() + REKEY_REPLY_TIMEOUT;
+            loop {
+                let socket = self.socket.as_ref().ok_or("Not connected")?;
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let len = match tokio::time::timeout(remaining, socket.recv(&mut buf)).await {
+                    Ok(Ok(len)) => len,
+                    Ok(Err(e)) => return Err(e.into()),
+                    Err(_) => break, // Timeout, resend
+                };
                 let data = &buf[..len];
 
-                // Minimum: type(1) + session_id(6) + nonce(8) + tag(16)
-                if data.len() < 31 {
-                    eprintln!("Response too short: {} bytes", len);
-                    return Ok(None);
+                if data.first() != Some(&msg_type::REKEY) {
+                    match self.process_response(data) {
+                        Ok(Some(response)) => self.pending_response = Some(response),
+                        Ok(None) => {}
+                        Err(e) => eprintln!("Dropped frame during rekey: {}", e),
+                    }
+                    continue;
                 }
 
-                let msg_type = data[0];
-                if msg_type != msg_type::DATA {
-                    eprintln!("Unexpected message type: {:02x}", msg_type);
-                    return Ok(None);
+                // Minimum: type(1) + session_id(6) + nonce(8) + tag(16)
+                if len < 31 {
+                    continue;
                 }
-
-                // Parse header
-                let _session_id = &data[1..7];
+                let crypto = self.crypto.as_mut().ok_or("No crypto session")?;
                 let nonce_counter = u64::from_le_bytes(data[7..15].try_into()?);
-                let ciphertext = &data[15..];
+                let Ok(plaintext) = crypto.decrypt_frame(msg_type::REKEY, 0x00, nonce_counter, &data[15..])
+                else {
+                    continue;
+                };
+                if plaintext != epoch.to_le_bytes() {
+                    continue;
+                }
 
-                // Decrypt
-                let plaintext = crypto.decrypt_frame(msg_type::DATA, 0x00, nonce_counter, ciphertext)?;
+                crypto.complete_rekey(pending)?;
+                self.rekey_requested = false;
+                self.last_received = Instant::now();
+                eprintln!("Rekeyed to epoch {}", epoch);
+                return Ok(());
+            }
+        }
 
-                // Parse plaintext: [server_seq:8][acked_seq:8][payload...]
-                if plaintext.len() < 16 {
-                    eprintln!("Plaintext too short: {} bytes", plaintext.len());
-                    return Ok(None);
-                }
+        Err(format!("No rekey reply after {} attempts", REKEY_ATTEMPTS).into())
+    }
 
-                let server_seq = u64::from_le_bytes(plaintext[0..8].try_into()?);
-                let acked_seq = u64::from_le_bytes(plaintext[8..16].try_into()?);
-                let payload = &plaintext[16..];
-
-                eprintln!(
-                    "Received encrypted response: server_seq={}, acked={}, msg={:?}",
-                    server_seq,
-                    acked_seq,
-                    String::from_utf8_lossy(payload)
-                );
-
-                // Update last server sequence
-                {
-                    let mut last = self.last_server_seq.write().await;
-                    if server_seq > *last {
-                        *last = server_seq;
-                    }
-                }
+    /// Receive an encrypted response from the server.
+    ///
+    /// # Cancellation safety
+    ///
+    /// This method is cancel-safe. The only await point that can consume a
+    /// datagram is `UdpSocket::recv`, which is itself cancel-safe. Once a
+    /// datagram is received it is decrypted and parsed synchronously, and the
+    /// result is parked in the client before any further await. If the
+    /// future is dropped after that point, the next call returns the parked
+    /// response instead of losing it.
+    pub async fn recv_response(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<EchoState>, Box<dyn std::error::Error + Send + Sync>> {
+        if self.pending_response.is_none() {
+            let socket = self.socket.as_ref().ok_or("Not connected")?;
+            let mut buf = [0u8; 65535];
+
+            let len = match tokio::time::timeout(timeout, socket.recv(&mut buf)).await {
+                Ok(Ok(len)) => len,
+                Ok(Err(e)) => return Err(e.into()),
+                Err(_) => return Ok(None), // Timeout
+            };
+
+            // No await between here and parking the response
+            match self.process_response(&buf[..len])? {
+                Some(response) => self.pending_response = Some(response),
+                None => return Ok(None),
+            }
+        }
+
+        let server_seq = self
+            .pending_response
+            .as_ref()
+            .map(|response| response.sequence)
+            .unwrap_or_default();
+
+        // Update last server sequence
+        {
+            let mut last = self.last_server_seq.write().await;
+            if server_seq > *last {
+                *last = server_seq;
+            }
+        }
+
+        Ok(self.pending_response.take())
+    }
+
+    /// Decrypt and parse a response datagram.
+    ///
+    /// Synchronous on purpose: see the cancellation notes on
+    /// [`recv_response`](Self::recv_response).
+    fn process_response(
+        &mut self,
+        data: &[u8],
+    ) -> Result<Option<EchoState>, Box<dyn std::error::Error + Send + Sync>> {
+        let crypto = self.crypto.as_mut().ok_or("No crypto session")?;
+
+        // Minimum: type(1) + session_id(6) + nonce(8) + tag(16)
+        if data.len() < 31 {
+            eprintln!("Response too short: {} bytes", data.len());
+            return Ok(None);
+        }
+
+        let msg_type = data[0];
+        if msg_type == msg_type::PATH_CHALLENGE {
+            self.answer_path_challenge(data)?;
+            return Ok(None);
+        }
+        if msg_type == msg_type::KEEPALIVE {
+            self.process_keepalive_reply(data)?;
+            return Ok(None);
+        }
+        if msg_type == msg_type::REKEY {
+            self.process_rekey_request(data)?;
+            return Ok(None);
+        }
+        if msg_type != msg_type::DATA {
+            eprintln!("Unexpected message type: {:02x}", msg_type);
+            return Ok(None);
+        }
+
+        // Parse header
+        let _session_id = &data[1..7];
+        let nonce_counter = u64::from_le_bytes(data[7..15].try_into()?);
+        let ciphertext = &data[15..];
+
+        // Decrypt
+        let plaintext = crypto.decrypt_frame(msg_type::DATA, 0x00, nonce_counter, ciphertext)?;
+        self.last_received = Instant::now();
+
+        // Parse plaintext: [server_seq:8][acked_seq:8][payload...]
+        if plaintext.len() < 16 {
+            eprintln!("Plaintext too short: {} bytes", plaintext.len());
+            return Ok(None);
+        }
+
+        let server_seq = u64::from_le_bytes(plaintext[0..8].try_into()?);
+        let acked_seq = u64::from_le_bytes(plaintext[8..16].try_into()?);
+        let payload = &plaintext[16..];
+
+        eprintln!(
+            "Received encrypted response: server_seq={}, acked={}, msg={:?}",
+            server_seq,
+            acked_seq,
+            String::from_utf8_lossy(payload)
+        );
+
+        Ok(Some(EchoState {
+            message: payload.to_vec(),
+            sequence: server_seq,
+        }))
+    }
+
+    /// Echo a path challenge back so the server accepts our new address.
+    ///
+    /// Uses a non-blocking send to stay synchronous; a dropped response
+    /// just means the server challenges again on our next frame.
+    fn answer_path_challenge(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let crypto = self.crypto.as_mut().ok_or("No crypto session")?;
+        let socket = self.socket.as_ref().ok_or("Not connected")?;
+
+        let nonce_counter = u64::from_le_bytes(data[7..15].try_into()?);
+        let token = crypto.decrypt_frame(msg_type::PATH_CHALLENGE, 0x00, nonce_counter, &data[15..])?;
+        self.last_received = Instant::now();
+        let (nonce, ciphertext) = crypto.encrypt_frame(msg_type::PATH_RESPONSE, 0x00, &token)?;
+
+        let mut packet = Vec::with_capacity(15 + ciphertext.len());
+        packet.push(msg_type::PATH_RESPONSE);
+        packet.extend_from_slice(&data[1..7]);
+        packet.extend_from_slice(&nonce.to_le_bytes());
+        packet.extend_from_slice(&ciphertext);
+        socket.try_send(&packet)?;
+
+        eprintln!("Answered path challenge");
+        Ok(())
+    }
+
+    /// Handle the server's answer to a keepalive.
+    ///
+    /// Plaintext: [server_seq:8][acked_seq:8][payload header:10]. Refreshes
+    /// `last_received` and takes an RTT sample from the timestamp echo.
+    fn process_keepalive_reply(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let crypto = self.crypto.as_mut().ok_or("No crypto session")?;
+        let nonce_counter = u64::from_le_bytes(data[7..15].try_into()?);
+        let plaintext = crypto.decrypt_frame(msg_type::KEEPALIVE, 0x00, nonce_counter, &data[15..])?;
+        self.last_received = Instant::now();
+
+        if plaintext.len() < 16 {
+            return Err("Keepalive reply too short".into());
+        }
+        let header = PayloadHeader::from_bytes(&plaintext[16..])?;
+        if let Some(rtt) = self.timestamps.on_receive(header.timestamp, header.timestamp_echo) {
+            eprintln!("Keepalive answered: rtt={:?}", rtt);
+        }
+        Ok(())
+    }
 
-                Ok(Some(EchoState {
-                    message: payload.to_vec(),
-                    sequence: server_seq,
-                }))
+    /// Handle a REKEY frame outside of [`rekey`](Self::rekey).
+    ///
+    /// An empty plaintext is the server asking for a rekey because its own
+    /// send counter ran out; the exchange runs on our next send. Anything
+    /// else is a retransmitted reply to a finished rekey and is ignored.
+    fn process_rekey_request(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let crypto = self.crypto.as_mut().ok_or("No crypto session")?;
+        let nonce_counter = u64::from_le_bytes(data[7..15].try_into()?);
+        let plaintext = crypto.decrypt_frame(msg_type::REKEY, 0x00, nonce_counter, &data[15..])?;
+        self.last_received = Instant::now();
+
+        if plaintext.is_empty() {
+            self.rekey_requested = true;
+            eprintln!("Server requested a rekey");
+        }
+        Ok(())
+    }
+
+    /// Process every datagram already queued on the socket.
+    ///
+    /// Used while idle: keepalive replies and path challenges are handled,
+    /// and echoes arriving after [`echo`](Self::echo) gave up on them are
+    /// dropped rather than parked, so the next echo doesn't return a stale
+    /// response.
+    fn drain_incoming(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut buf = [0u8; 65535];
+        loop {
+            let socket = self.socket.as_ref().ok_or("Not connected")?;
+            let len = match socket.try_recv(&mut buf) {
+                Ok(len) => len,
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e.into()),
+            };
+            match self.process_response(&buf[..len]) {
+                Ok(Some(late)) => eprintln!("Dropped late echo: seq={}", late.sequence),
+                Ok(None) => {}
+                Err(e) => eprintln!("Dropped frame: {}", e),
             }
-            Ok(Err(e)) => Err(e.into()),
-            Err(_) => Ok(None), // Timeout
         }
     }
 
@@ -331,7 +859,54 @@ impl EchoClient {
         Err("No response from server after 3 attempts".into())
     }
 
+    /// Send a KEEPALIVE frame.
+    ///
+    /// Carries the current sequence number and a payload header with our
+    /// timestamp and the latest timestamp echo. The server refreshes the
+    /// session and answers with a KEEPALIVE of its own, which refreshes
+    /// `last_received` and yields an RTT sample once read. Like
+    /// [`send_message`](Self::send_message), rekeys first when due, so an
+    /// idle session doesn't run into `REJECT_AFTER_TIME`.
+    pub async fn send_keepalive(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.rekey_if_due().await?;
+        self.socket.as_ref().ok_or("Not connected")?;
+        let seq = *self.sequence.read().await;
+
+        // Build plaintext: [sequence:8][payload header:10]
+        let timestamp = self.timestamps.now();
+        let header = PayloadHeader::new(timestamp, self.timestamps.timestamp_echo(), 0);
+        let mut plaintext = Vec::with_capacity(18);
+        plaintext.extend_from_slice(&seq.to_le_bytes());
+        plaintext.extend_from_slice(&header.to_bytes());
+
+        let (nonce_counter, ciphertext) =
+            self.encrypt_or_rekey(msg_type::KEEPALIVE, &plaintext).await?;
+
+        // Build packet: [type:1][session_id:6][nonce:8][ciphertext...]
+        let socket = self.socket.as_ref().ok_or("Not connected")?;
+        let session_id = self.crypto.as_ref().ok_or("No crypto session")?.session_id();
+        let mut packet = Vec::with_capacity(15 + ciphertext.len());
+        packet.push(msg_type::KEEPALIVE);
+        packet.extend_from_slice(session_id.as_bytes());
+        packet.extend_from_slice(&nonce_counter.to_le_bytes());
+        packet.extend_from_slice(&ciphertext);
+
+        socket.send(&packet).await?;
+        self.timestamps.on_send(timestamp);
+        self.pacer.on_frame_sent();
+        eprintln!("Sent keepalive: seq={}, nonce={}", seq, nonce_counter);
+
+        Ok(())
+    }
+
     /// Run in persistent mode - stay connected and echo stdin.
+    ///
+    /// Sends a keepalive whenever the session has been idle for the
+    /// keepalive interval, so the server doesn't reap it, and reads the
+    /// socket while waiting for input so the server's replies keep the
+    /// session alive on our side too. A session that died (nothing heard
+    /// for the dead interval, or closed after key exhaustion) is
+    /// re-established with [`reconnect`](Self::reconnect).
     pub async fn run_persistent(&mut self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         use tokio::io::{AsyncBufReadExt, BufReader};
 
@@ -340,9 +915,42 @@ impl EchoClient {
         let stdin = tokio::io::stdin();
         let reader = BufReader::new(stdin);
         let mut lines = reader.lines();
+        let mut keepalive_check = tokio::time::interval(KEEPALIVE_CHECK_INTERVAL);
 
         loop {
-            match lines.next_line().await {
+            // `next_line` and `readable` are cancel-safe, so losing the race
+            // loses no input
+            let readable = async {
+                match &self.socket {
+                    Some(socket) => socket.readable().await,
+                    None => std::future::pending().await,
+                }
+            };
+            let line = tokio::select! {
+                line = lines.next_line() => line,
+                Ok(()) = readable => {
+                    if let Err(e) = self.drain_incoming() {
+                        eprintln!("✗ Receive failed: {}", e);
+                    }
+                    continue;
+                }
+                _ = keepalive_check.tick() => {
+                    if !self.is_connected() || self.pacer.is_connection_dead(self.last_received) {
+                        eprintln!("Session lost, reconnecting");
+                        if let Err(e) = self.reconnect().await {
+                            eprintln!("✗ Reconnect failed: {}", e);
+                            return Err(e);
+                        }
+                    } else if self.pacer.take_keepalive(self.last_received).is_some()
+                        && let Err(e) = self.send_keepalive().await
+                    {
+                        eprintln!("✗ Keepalive failed: {}", e);
+                    }
+                    continue;
+                }
+            };
+
+            match line {
                 Ok(Some(text)) => {
                     if text.is_empty() {
                         continue;
@@ -385,10 +993,42 @@ impl EchoClient {
         self.socket.is_some() && self.crypto.is_some()
     }
 
+    /// Close the session: send CLOSE with `reason` and disconnect.
+    ///
+    /// The CLOSE carries the last server sequence we saw as its final ack.
+    /// If the keys can no longer encrypt, the server isn't told and will
+    /// reap the session once it goes idle.
+    pub async fn close(
+        &mut self,
+        reason: CloseReason,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let final_ack = *self.last_server_seq.read().await;
+        if let (Some(socket), Some(crypto)) = (self.socket.as_ref(), self.crypto.as_mut()) {
+            let plaintext = CloseFrame::encode_plaintext(reason, final_ack);
+            if let Ok((nonce_counter, ciphertext)) = crypto.encrypt_frame(msg_type::CLOSE, 0x00, &plaintext) {
+                // Build packet: [type:1][session_id:6][nonce:8][ciphertext...]
+                let mut packet = Vec::with_capacity(15 + ciphertext.len());
+                packet.push(msg_type::CLOSE);
+                packet.extend_from_slice(crypto.session_id().as_bytes());
+                packet.extend_from_slice(&nonce_counter.to_le_bytes());
+                packet.extend_from_slice(&ciphertext);
+
+                let sent = socket.send(&packet).await;
+                self.disconnect();
+                sent?;
+                return Ok(());
+            }
+        }
+        self.disconnect();
+        Ok(())
+    }
+
     /// Disconnect from server.
     pub fn disconnect(&mut self) {
         self.socket = None;
         self.crypto = None;
+        self.pending_response = None;
+        self.rekey_requested = false;
     }
 }
 
@@ -402,4 +1042,388 @@ mod tests {
         assert_eq!(config.server_addr.port(), 19999);
         assert!(!config.persistent);
     }
+
+    #[test]
+    fn test_backoff_policy_delays() {
+        let policy = BackoffPolicy::new(
+            Duration::from_millis(100),
+            Duration::from_millis(500),
+            Duration::from_secs(5),
+        );
+        let delays: Vec<_> = (0..5).map(|attempt| policy.base_delay(attempt).as_millis()).collect();
+        assert_eq!(delays, vec![100, 200, 400, 500, 500]);
+
+        for _ in 0..20 {
+            let delay = policy.delay(1);
+            assert!(delay >= Duration::from_millis(160) && delay <= Duration::from_millis(240));
+        }
+
+        // Without jitter every delay is exact
+        let exact = policy.with_jitter(0.0);
+        assert_eq!(exact.delay(1), exact.base_delay(1));
+    }
+
+    /// An echo server that answers the first `failures` handshake inits
+    /// with garbage.
+    async fn flaky_server(failures: usize) -> (SocketAddr, [u8; 32]) {
+        use crate::server::{EchoServer, EchoServerConfig};
+
+        let server = EchoServer::new(EchoServerConfig::default());
+        let public_key = *server.public_key();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 65535];
+            let mut rejected = 0;
+            while let Ok((len, from)) = socket.recv_from(&mut buf).await {
+                if buf[0] == msg_type::HANDSHAKE_INIT && rejected < failures {
+                    rejected += 1;
+                    let _ = socket.send_to(&[0xFF], from).await;
+                    continue;
+                }
+                let _ = server.handle_message(&socket, from, &buf[..len]).await;
+            }
+        });
+        (addr, public_key)
+    }
+
+    fn fast_policy() -> BackoffPolicy {
+        BackoffPolicy::new(
+            Duration::from_millis(10),
+            Duration::from_millis(50),
+            Duration::from_secs(5),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_retry_after_failed_handshakes() {
+        let (server_addr, server_public_key) = flaky_server(2).await;
+        let mut client = EchoClient::new(EchoClientConfig {
+            server_addr,
+            server_public_key,
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        });
+
+        client.connect_with_retry(fast_policy()).await.unwrap();
+        assert!(client.is_connected());
+        assert_eq!(client.echo(b"hi").await.unwrap().message, b"hi");
+
+        // A server that never completes a handshake exhausts the deadline
+        let (server_addr, server_public_key) = flaky_server(usize::MAX).await;
+        let mut client = EchoClient::new(EchoClientConfig {
+            server_addr,
+            server_public_key,
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        });
+        let policy = BackoffPolicy::new(
+            Duration::from_millis(10),
+            Duration::from_millis(20),
+            Duration::from_millis(100),
+        );
+        assert!(client.connect_with_retry(policy).await.is_err());
+        assert!(!client.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_connect_races_resolved_host() {
+        let (server_addr, server_public_key) = flaky_server(0).await;
+        let mut client = EchoClient::new(EchoClientConfig {
+            server_addr: SocketAddr::from(([0, 0, 0, 0], server_addr.port())),
+            server_host: Some("127.0.0.1".to_string()),
+            server_public_key,
+            ..Default::default()
+        });
+
+        client.connect().await.unwrap();
+        assert_eq!(client.config.server_addr, server_addr);
+        assert_eq!(client.echo(b"hi").await.unwrap().message, b"hi");
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_preserves_state() {
+        let (server_addr, server_public_key) = flaky_server(0).await;
+        let mut client = EchoClient::new(EchoClientConfig {
+            server_addr,
+            server_public_key,
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            reconnect_policy: fast_policy(),
+            ..Default::default()
+        });
+        client.connect().await.unwrap();
+        client.echo(b"before").await.unwrap();
+        let session_before = *client.crypto.as_ref().unwrap().session_id();
+
+        client.reconnect().await.unwrap();
+        assert_ne!(*client.crypto.as_ref().unwrap().session_id(), session_before);
+        let state = client.state().await;
+        assert_eq!((state.message.as_slice(), state.sequence), (&b"before"[..], 1));
+
+        // The sequence continues on the new session
+        let response = client.echo(b"after").await.unwrap();
+        assert_eq!(response.message, b"after");
+        assert_eq!(client.state().await.sequence, 2);
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_reply_refreshes_last_received() {
+        let (server_addr, server_public_key) = flaky_server(0).await;
+        let mut client = EchoClient::new(EchoClientConfig {
+            server_addr,
+            server_public_key,
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        });
+        client.connect().await.unwrap();
+        client.echo(b"hello").await.unwrap();
+
+        // Idle long enough that the session would look dead
+        let stale = Instant::now() - nomad_protocol::core::DEAD_INTERVAL;
+        client.last_received = stale;
+        assert!(client.pacer.is_connection_dead(client.last_received));
+
+        client.send_keepalive().await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            // Readiness can be spurious; drain until the reply is in
+            while client.last_received == stale {
+                client.socket.as_ref().unwrap().readable().await.unwrap();
+                client.drain_incoming().unwrap();
+            }
+        })
+        .await
+        .expect("no keepalive reply");
+
+        assert!(!client.pacer.is_connection_dead(client.last_received));
+        assert!(client.pending_response.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rekeys_mid_stream() {
+        use nomad_protocol::core::REKEY_AFTER_MESSAGES;
+
+        let (server_addr, server_public_key) = flaky_server(0).await;
+        let mut client = EchoClient::new(EchoClientConfig {
+            server_addr,
+            server_public_key,
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        });
+        client.connect().await.unwrap();
+
+        // Treat every third frame of an epoch as the soft rekey limit
+        let crypto = client.crypto.as_mut().unwrap();
+        crypto.set_rekey_message_margin(REKEY_AFTER_MESSAGES - 3);
+        assert_eq!(crypto.epoch(), 0);
+
+        for i in 0..8 {
+            let message = format!("message {}", i);
+            let response = client.echo(message.as_bytes()).await.unwrap();
+            assert_eq!(response.message, message.as_bytes());
+        }
+
+        // Rekeyed before messages 3 and 6 (the REKEY request goes out
+        // under the old epoch, so each epoch carries three messages)
+        assert_eq!(client.crypto.as_ref().unwrap().epoch(), 2);
+        assert_eq!(client.state().await.sequence, 8);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_recv_keeps_datagram() {
+        use nomad_protocol::crypto::SessionKey;
+
+        let session_id = SessionId::generate();
+        let initiator_key = SessionKey::from_bytes([0x01; 32]);
+        let responder_key = SessionKey::from_bytes([0x02; 32]);
+        let mut server_crypto = CryptoSession::new(
+            session_id,
+            Role::Responder,
+            responder_key.clone(),
+            initiator_key.clone(),
+            [0x42; 32],
+        );
+
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(server.local_addr().unwrap()).await.unwrap();
+
+        let mut client = EchoClient::new(EchoClientConfig::default());
+        client.crypto = Some(CryptoSession::new(
+            session_id,
+            Role::Initiator,
+            initiator_key,
+            responder_key,
+            [0x42; 32],
+        ));
+        let client_addr = socket.local_addr().unwrap();
+        client.socket = Some(socket);
+
+        // Block the post-receive await so the datagram is in flight when cancelled
+        let seq_lock = client.last_server_seq.clone();
+        let guard = seq_lock.read().await;
+
+        let mut plaintext = Vec::new();
+        plaintext.extend_from_slice(&1u64.to_le_bytes());
+        plaintext.extend_from_slice(&0u64.to_le_bytes());
+        plaintext.extend_from_slice(b"hello");
+        let (nonce, ciphertext) = server_crypto
+            .encrypt_frame(msg_type::DATA, 0x00, &plaintext)
+            .unwrap();
+        let mut packet = vec![msg_type::DATA];
+        packet.extend_from_slice(session_id.as_bytes());
+        packet.extend_from_slice(&nonce.to_le_bytes());
+        packet.extend_from_slice(&ciphertext);
+        server.send_to(&packet, client_addr).await.unwrap();
+
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(100),
+            client.recv_response(Duration::from_secs(5)),
+        )
+        .await;
+        assert!(cancelled.is_err());
+        drop(guard);
+
+        // The datagram was consumed by the cancelled call but not lost
+        let response = client
+            .recv_response(Duration::from_millis(100))
+            .await
+            .unwrap()
+            .expect("parked response");
+        assert_eq!(response.message, b"hello");
+        assert_eq!(response.sequence, 1);
+        assert_eq!(*client.last_server_seq.read().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_close_notifies_server() {
+        use nomad_protocol::crypto::SessionKey;
+
+        let session_id = SessionId::generate();
+        let initiator_key = SessionKey::from_bytes([0x01; 32]);
+        let responder_key = SessionKey::from_bytes([0x02; 32]);
+        let mut server_crypto = CryptoSession::new(
+            session_id,
+            Role::Responder,
+            responder_key.clone(),
+            initiator_key.clone(),
+            [0x42; 32],
+        );
+
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(server.local_addr().unwrap()).await.unwrap();
+
+        let mut client = EchoClient::new(EchoClientConfig::default());
+        client.crypto = Some(CryptoSession::new(
+            session_id,
+            Role::Initiator,
+            initiator_key,
+            responder_key,
+            [0x42; 32],
+        ));
+        client.socket = Some(socket);
+        *client.last_server_seq.write().await = 7;
+
+        client.close(CloseReason::KeyExpired).await.unwrap();
+        assert!(!client.is_connected());
+
+        let mut buf = [0u8; 1500];
+        let (len, _) = tokio::time::timeout(Duration::from_secs(5), server.recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(buf[0], msg_type::CLOSE);
+        assert_eq!(&buf[1..7], session_id.as_bytes());
+        let nonce = u64::from_le_bytes(buf[7..15].try_into().unwrap());
+        let plaintext = server_crypto
+            .decrypt_frame(msg_type::CLOSE, 0x00, nonce, &buf[15..len])
+            .unwrap();
+        let (reason, final_ack) = CloseFrame::decode_plaintext(&plaintext).unwrap();
+        assert_eq!(reason, CloseReason::KeyExpired);
+        assert_eq!(final_ack, 7);
+    }
+
+    #[tokio::test]
+    async fn test_counter_exhaustion_rekeys_and_retries() {
+        use nomad_protocol::core::CONTROL_FRAME_HEADROOM;
+
+        let (server_addr, server_public_key) = flaky_server(0).await;
+        let mut client = EchoClient::new(EchoClientConfig {
+            server_addr,
+            server_public_key,
+            bind_addr: "127.0.0.1:0".parse().unwrap(),
+            ..Default::default()
+        });
+        client.connect().await.unwrap();
+
+        // Seed the session two DATA frames short of its counter limit
+        let crypto = client.crypto.as_mut().unwrap();
+        crypto.set_send_counter_limit(CONTROL_FRAME_HEADROOM + 2);
+
+        for i in 0..5 {
+            let message = format!("message {}", i);
+            let response = client.echo(message.as_bytes()).await.unwrap();
+            assert_eq!(response.message, message.as_bytes());
+        }
+
+        // Messages 2 and 4 ran out of counters, rekeyed and went out on retry
+        assert!(client.is_connected());
+        assert_eq!(client.crypto.as_ref().unwrap().epoch(), 2);
+        assert_eq!(client.state().await.sequence, 5);
+    }
+
+    #[tokio::test]
+    async fn test_counter_exhaustion_at_epoch_limit_closes() {
+        use nomad_protocol::core::CONTROL_FRAME_HEADROOM;
+        use nomad_protocol::crypto::SessionKey;
+
+        let session_id = SessionId::generate();
+        let initiator_key = SessionKey::from_bytes([0x01; 32]);
+        let responder_key = SessionKey::from_bytes([0x02; 32]);
+        let mut server_crypto = CryptoSession::new(
+            session_id,
+            Role::Responder,
+            responder_key.clone(),
+            initiator_key.clone(),
+            [0x42; 32],
+        );
+
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(server.local_addr().unwrap()).await.unwrap();
+
+        // One DATA frame left and no epoch to rekey into
+        let mut crypto =
+            CryptoSession::new(session_id, Role::Initiator, initiator_key, responder_key, [0x42; 32]);
+        crypto.set_send_counter_limit(CONTROL_FRAME_HEADROOM + 1);
+        crypto.set_epoch_limit(0);
+        let mut client = EchoClient::new(EchoClientConfig::default());
+        client.crypto = Some(crypto);
+        client.socket = Some(socket);
+
+        client.send_message(b"last").await.unwrap();
+        assert!(client.send_message(b"over").await.is_err());
+        assert!(!client.is_connected());
+
+        let mut buf = [0u8; 1500];
+        let mut frames = Vec::new();
+        for _ in 0..2 {
+            let (len, _) = tokio::time::timeout(Duration::from_secs(5), server.recv_from(&mut buf))
+                .await
+                .unwrap()
+                .unwrap();
+            let nonce = u64::from_le_bytes(buf[7..15].try_into().unwrap());
+            let plaintext = server_crypto
+                .decrypt_frame(buf[0], 0x00, nonce, &buf[15..len])
+                .unwrap();
+            frames.push((buf[0], plaintext));
+        }
+
+        // The last message, then a CLOSE on a reserved counter
+        assert_eq!(frames[0].0, msg_type::DATA);
+        assert_eq!(&frames[0].1[8..], b"last");
+        assert_eq!(frames[1].0, msg_type::CLOSE);
+        let (reason, _) = CloseFrame::decode_plaintext(&frames[1].1).unwrap();
+        assert_eq!(reason, CloseReason::KeyExpired);
+    }
 }
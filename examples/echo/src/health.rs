@@ -8,11 +8,13 @@ use std::sync::Arc;
 
 use axum::{
     extract::State,
-    http::StatusCode,
+    http::{header, StatusCode},
     response::IntoResponse,
     routing::get,
     Json, Router,
 };
+use nomad_protocol::core::{Metrics, PROTOCOL_VERSION};
+use nomad_protocol::extensions::supported_extensions;
 use tokio::sync::RwLock;
 
 /// Health status.
@@ -30,10 +32,30 @@ pub struct HealthStatus {
     pub connected: Option<bool>,
 }
 
+/// Capabilities of this build.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Capabilities {
+    /// Protocol version.
+    pub protocol_version: u16,
+    /// Type IDs of compiled-in extensions.
+    pub extensions: Vec<u16>,
+}
+
+impl Capabilities {
+    /// Capabilities of the running binary.
+    pub fn current() -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            extensions: supported_extensions().iter().map(|ext| ext.ext_type).collect(),
+        }
+    }
+}
+
 /// Shared state for health endpoint.
 #[derive(Clone)]
 pub struct HealthState {
     inner: Arc<RwLock<HealthStateInner>>,
+    metrics: Option<Arc<Metrics>>,
 }
 
 struct HealthStateInner {
@@ -53,6 +75,7 @@ impl HealthState {
                 sessions: Some(0),
                 connected: None,
             })),
+            metrics: None,
         }
     }
 
@@ -65,9 +88,16 @@ impl HealthState {
                 sessions: None,
                 connected: Some(false),
             })),
+            metrics: None,
         }
     }
 
+    /// Serve `metrics` at `/metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Update session count (server mode).
     pub async fn set_sessions(&self, count: usize) {
         let mut inner = self.inner.write().await;
@@ -119,6 +149,24 @@ async fn live_handler() -> impl IntoResponse {
     StatusCode::OK
 }
 
+/// Capabilities handler (compiled-in protocol features).
+async fn capabilities_handler() -> impl IntoResponse {
+    Json(Capabilities::current())
+}
+
+/// Prometheus metrics handler (404 when no registry is attached).
+async fn metrics_handler(State(state): State<HealthState>) -> impl IntoResponse {
+    match &state.metrics {
+        Some(metrics) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+            metrics.encode_prometheus(),
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
 /// Start the health check server.
 pub async fn start_health_server(
     bind_addr: SocketAddr,
@@ -128,6 +176,8 @@ pub async fn start_health_server(
         .route("/health", get(health_handler))
         .route("/ready", get(ready_handler))
         .route("/live", get(live_handler))
+        .route("/capabilities", get(capabilities_handler))
+        .route("/metrics", get(metrics_handler))
         .with_state(state);
 
     eprintln!("Health server listening on http://{}", bind_addr);
@@ -167,4 +217,15 @@ mod tests {
         let status = state.status().await;
         assert_eq!(status.sessions, Some(5));
     }
+
+    #[test]
+    fn test_capabilities() {
+        use nomad_protocol::extensions::{ext_type, supported_extensions};
+
+        let caps = Capabilities::current();
+        assert_eq!(caps.protocol_version, PROTOCOL_VERSION);
+        assert_eq!(caps.extensions.first(), Some(&ext_type::COMPRESSION));
+        assert_eq!(caps.extensions.len(), supported_extensions().len());
+        assert!(caps.extensions.windows(2).all(|w| w[0] < w[1]));
+    }
 }
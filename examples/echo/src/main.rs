@@ -12,6 +12,11 @@
 //! - `NOMAD_BIND_ADDR`: Bind address (server only, default: 0.0.0.0)
 //! - `NOMAD_HEALTH_PORT`: Health check port (both, default: 8080)
 //! - `NOMAD_PERSISTENT`: "true" for persistent client mode (client only)
+//! - `NOMAD_SERVER_PRIVATE_KEY`: Base64-encoded server private key (server only;
+//!   the public key is derived when `NOMAD_SERVER_PUBLIC_KEY` is unset)
+//! - `NOMAD_IDLE_TIMEOUT_SECS`: Evict sessions silent this long (server only)
+//! - `NOMAD_RETRY_COOKIE_SECS`: Require a retry round trip, with cookies valid
+//!   this long (server only)
 //!
 //! # Key Management
 //!
@@ -43,6 +48,8 @@ mod state;
 
 use std::env;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use client::{EchoClient, EchoClientConfig};
 use health::{start_health_server, HealthState};
@@ -71,7 +78,7 @@ fn parse_key(env_var: &str) -> Option<[u8; 32]> {
 fn encode_base64(data: &[u8]) -> String {
     const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
 
-    let mut output = String::with_capacity((data.len() + 2) / 3 * 4);
+    let mut output = String::with_capacity(data.len().div_ceil(3) * 4);
 
     for chunk in data.chunks(3) {
         let b0 = chunk[0] as usize;
@@ -194,23 +201,46 @@ async fn run_server(
             eprintln!("Using provided server keypair from environment");
             StaticKeypair::from_bytes(private_key, public_key)
         } else {
-            eprintln!("Warning: NOMAD_SERVER_PRIVATE_KEY set but no NOMAD_SERVER_PUBLIC_KEY");
-            eprintln!("Generating new keypair instead");
-            StaticKeypair::generate()
+            eprintln!("Deriving server public key from NOMAD_SERVER_PRIVATE_KEY");
+            EchoServerConfig::from_private_key(bind_addr, private_key).keypair
         }
     } else {
         StaticKeypair::generate()
     };
 
-    let public_key_b64 = encode_base64(keypair.public_key());
+    let mut config = EchoServerConfig::new(bind_addr, keypair);
+    if let Some(secs) = env::var("NOMAD_IDLE_TIMEOUT_SECS").ok().and_then(|s| s.parse().ok()) {
+        config = config.idle_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = env::var("NOMAD_RETRY_COOKIE_SECS").ok().and_then(|s| s.parse().ok()) {
+        config = config.retry_cookie_lifetime(Duration::from_secs(secs));
+    }
+    let server = Arc::new(EchoServer::new(config));
 
     eprintln!("=== Server Public Key (for clients) ===");
-    eprintln!("{}", public_key_b64);
+    eprintln!("{}", encode_base64(server.public_key()));
     eprintln!("========================================");
 
-    let config = EchoServerConfig::new(bind_addr, keypair);
-    let server = EchoServer::new(config);
-    let health_state = HealthState::server();
+    let health_state = HealthState::server().with_metrics(server.metrics());
+
+    // Report the session count on the health endpoint
+    let reporter = server.clone();
+    let sessions_state = health_state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            sessions_state.set_sessions(reporter.session_count().await).await;
+        }
+    });
+
+    // Stop accepting datagrams on Ctrl-C
+    let stopper = server.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            stopper.stop().await;
+        }
+    });
 
     // Start health server in background
     let health_addr: SocketAddr = format!("0.0.0.0:{}", health_port).parse()?;
@@ -231,11 +261,6 @@ async fn run_client(
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let server_host = env::var("NOMAD_SERVER_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
 
-    // Resolve hostname to socket address (supports both IP and DNS names)
-    let server_addr: SocketAddr = tokio::net::lookup_host(format!("{}:{}", server_host, port))
-        .await?
-        .next()
-        .ok_or("Failed to resolve server address")?;
 
     // Get server public key
     let server_public_key = match parse_key("NOMAD_SERVER_PUBLIC_KEY") {
@@ -255,12 +280,16 @@ async fn run_client(
         .map(|v| v == "true" || v == "1")
         .unwrap_or(false);
 
+    // The host (IP or DNS name) is resolved on connect, racing IPv6 and
+    // IPv4 addresses; only the port of server_addr is used until then
     let config = EchoClientConfig {
-        server_addr,
+        server_addr: SocketAddr::from(([0, 0, 0, 0], port)),
+        server_host: Some(server_host),
         server_public_key,
         bind_addr: "0.0.0.0:0".parse()?,
         client_keypair: None, // Generate fresh keypair
         persistent,
+        ..Default::default()
     };
 
     let health_state = HealthState::client();
@@ -276,7 +305,7 @@ async fn run_client(
 
     // Connect and run client
     let mut client = EchoClient::new(config.clone());
-    client.connect().await?;
+    client.connect_with_retry(config.reconnect_policy).await?;
     health_state.set_connected(true).await;
 
     eprintln!("Echo client connected with encrypted session.");
@@ -297,7 +326,7 @@ async fn run_client(
         for msg in &test_messages {
             match client.echo(msg.as_bytes()).await {
                 Ok(response) => {
-                    let echoed = String::from_utf8_lossy(&response.message);
+                    let echoed = response.message_str();
                     if response.message == msg.as_bytes() {
                         eprintln!("✓ Echo matched: {:?}", echoed);
                     } else {
@@ -313,6 +342,7 @@ async fn run_client(
             tokio::time::sleep(std::time::Duration::from_millis(200)).await;
         }
 
+        eprintln!("Final state: {:?}", client.state().await.message_str());
         eprintln!("Echo client test complete");
     }
 
@@ -6,10 +6,19 @@
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use nomad_protocol::core::SyncState;
+use nomad_protocol::core::{
+    CryptoError, Metrics, SyncState, VersionRange, DEAD_INTERVAL, MAX_HANDSHAKE_MESSAGE_SIZE,
+};
 use nomad_protocol::crypto::{
-    CryptoSession, ResponderHandshake, Role, SessionId, SessionKeys, StaticKeypair,
+    CookieGenerator, CryptoSession, HandshakeProfile, ResponderHandshake, Role, SessionId,
+    SessionKeys, StaticKeypair,
+};
+use nomad_protocol::server::{SessionState, MAX_SESSION_ID_ATTEMPTS};
+use nomad_protocol::transport::{
+    sizes, CloseFrame, CloseReason, HandshakeFlags, HandshakeValidation, MigrationState,
+    PayloadHeader, RetryFrame, TimestampTracker, PATH_CHALLENGE_SIZE,
 };
 use tokio::net::UdpSocket;
 use tokio::sync::RwLock;
@@ -24,8 +33,25 @@ mod msg_type {
     pub const HANDSHAKE_RESP: u8 = 0x02;
     /// Encrypted data frame - Type 0x03
     pub const DATA: u8 = 0x03;
+    /// Rekey request (client -> server) and reply (server -> client), or an
+    /// empty request asking the client to rekey (server -> client) - Type 0x04
+    pub const REKEY: u8 = 0x04;
+    /// Graceful close - Type 0x05
+    pub const CLOSE: u8 = 0x05;
+    /// Path challenge to a new client address (server -> client) - Type 0x07
+    pub const PATH_CHALLENGE: u8 = 0x07;
+    /// Echoed path challenge token (client -> server) - Type 0x08
+    pub const PATH_RESPONSE: u8 = 0x08;
+    /// Keepalive (client -> server), answered in kind (server -> client) - Type 0x09
+    pub const KEEPALIVE: u8 = 0x09;
 }
 
+/// How long a closed session lingers to answer retransmitted CLOSE frames.
+pub const CLOSE_LINGER: Duration = Duration::from_secs(2);
+
+/// Maximum time between reaper scans.
+pub const REAPER_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Server configuration.
 #[derive(Clone)]
 pub struct EchoServerConfig {
@@ -33,12 +59,43 @@ pub struct EchoServerConfig {
     pub bind_addr: SocketAddr,
     /// Server keypair.
     pub keypair: StaticKeypair,
+    /// How to treat unknown handshake flag bits.
+    pub handshake_validation: HandshakeValidation,
+    /// Handshake security profile clients must use.
+    pub handshake_profile: HandshakeProfile,
+    /// Evict sessions that send nothing for this long.
+    pub idle_timeout: Duration,
+    /// Accept 0-RTT early data in handshake inits (replayable; off by default).
+    pub accept_early_data: bool,
+    /// Require a retry cookie before creating handshake state, with cookies
+    /// valid for this long (None = no retry).
+    pub retry_cookie_lifetime: Option<Duration>,
 }
 
 impl EchoServerConfig {
     /// Create config with a specific keypair.
     pub fn new(bind_addr: SocketAddr, keypair: StaticKeypair) -> Self {
-        Self { bind_addr, keypair }
+        Self {
+            bind_addr,
+            keypair,
+            handshake_validation: HandshakeValidation::default(),
+            handshake_profile: HandshakeProfile::default(),
+            idle_timeout: DEAD_INTERVAL,
+            accept_early_data: false,
+            retry_cookie_lifetime: None,
+        }
+    }
+
+    /// Require a stateless retry round trip before each handshake.
+    pub fn retry_cookie_lifetime(mut self, lifetime: Duration) -> Self {
+        self.retry_cookie_lifetime = Some(lifetime);
+        self
+    }
+
+    /// Set the idle timeout after which silent sessions are evicted.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
     }
 
     /// Create config from raw private key bytes.
@@ -48,14 +105,10 @@ impl EchoServerConfig {
             eprintln!("Using generated keypair (zero key provided)");
             StaticKeypair::generate()
         } else {
-            // Derive public key from private key using snow
-            let builder = snow::Builder::new("Noise_IK_25519_ChaChaPoly_BLAKE2s".parse().unwrap());
-            let kp = builder.generate_keypair().unwrap();
-            // Use the provided private key with a computed public key
-            // Note: This is a simplification - in production you'd derive the public key properly
-            StaticKeypair::from_bytes(private_key, *kp.public.as_slice().try_into().unwrap_or(&[0u8; 32]))
+            let public_key = StaticKeypair::public_key_from_private(&private_key);
+            StaticKeypair::from_bytes(private_key, public_key)
         };
-        Self { bind_addr, keypair }
+        Self::new(bind_addr, keypair)
     }
 }
 
@@ -64,14 +117,19 @@ impl Default for EchoServerConfig {
         Self {
             bind_addr: "0.0.0.0:19999".parse().unwrap(),
             keypair: StaticKeypair::generate(),
+            handshake_validation: HandshakeValidation::default(),
+            handshake_profile: HandshakeProfile::default(),
+            idle_timeout: DEAD_INTERVAL,
+            accept_early_data: false,
+            retry_cookie_lifetime: None,
         }
     }
 }
 
 /// Session state for a connected client.
 struct ClientSession {
-    /// Client address.
-    addr: SocketAddr,
+    /// Validated client address and any pending path challenge.
+    migration: MigrationState,
     /// Crypto session for this client.
     crypto: CryptoSession,
     /// Current state.
@@ -80,18 +138,68 @@ struct ClientSession {
     last_client_seq: u64,
     /// Our sequence number.
     server_seq: u64,
+    /// Lifecycle phase (`Active` or `Closing`).
+    phase: SessionState,
+    /// When the client's CLOSE was first received.
+    closing_since: Option<Instant>,
+    /// Highest server sequence the client acked in its CLOSE.
+    final_ack: Option<u64>,
+    /// Why the client closed.
+    close_reason: Option<CloseReason>,
+    /// Epoch and packet of our last REKEY reply, resent if the request is
+    /// retransmitted after we already switched keys.
+    rekey_reply: Option<(u32, Vec<u8>)>,
+    /// Frame type and plaintext of a reply held back until the client
+    /// rekeys, because our send counter ran out.
+    deferred_reply: Option<(u8, Vec<u8>)>,
+    /// When we last received a valid frame from the client.
+    last_activity: Instant,
+    /// Timestamps for answering keepalives.
+    timestamps: TimestampTracker,
 }
 
 impl ClientSession {
     fn new(addr: SocketAddr, crypto: CryptoSession) -> Self {
         Self {
-            addr,
+            migration: MigrationState::new(addr),
             crypto,
             state: EchoState::new(),
             last_client_seq: 0,
             server_seq: 0,
+            phase: SessionState::Active,
+            closing_since: None,
+            final_ack: None,
+            close_reason: None,
+            rekey_reply: None,
+            deferred_reply: None,
+            last_activity: Instant::now(),
+            timestamps: TimestampTracker::new(),
         }
     }
+
+    /// Address replies are sent to.
+    fn addr(&self) -> SocketAddr {
+        self.migration.current_address()
+    }
+
+    /// Whether the session should be evicted at `now`.
+    fn is_expired(&self, now: Instant, idle_timeout: Duration) -> bool {
+        match self.closing_since {
+            Some(since) => now.saturating_duration_since(since) >= CLOSE_LINGER,
+            None => now.saturating_duration_since(self.last_activity) >= idle_timeout,
+        }
+    }
+}
+
+/// Remove expired sessions, returning how many were removed.
+fn reap_expired(
+    sessions: &mut HashMap<[u8; 6], ClientSession>,
+    now: Instant,
+    idle_timeout: Duration,
+) -> usize {
+    let before = sessions.len();
+    sessions.retain(|_, session| !session.is_expired(now, idle_timeout));
+    before - sessions.len()
 }
 
 /// Echo server with Noise_IK handshake support.
@@ -99,8 +207,10 @@ pub struct EchoServer {
     config: EchoServerConfig,
     /// Sessions indexed by session ID
     sessions: Arc<RwLock<HashMap<[u8; 6], ClientSession>>>,
-    /// Pending handshakes indexed by client address
-    pending_handshakes: Arc<RwLock<HashMap<SocketAddr, ResponderHandshake>>>,
+    /// Retry cookie issuer, if retry is required
+    cookies: Option<CookieGenerator>,
+    /// Aggregate counters for the metrics endpoint
+    metrics: Arc<Metrics>,
     running: Arc<RwLock<bool>>,
 }
 
@@ -112,9 +222,10 @@ impl EchoServer {
             config.keypair.public_key()
         );
         Self {
+            cookies: config.retry_cookie_lifetime.map(CookieGenerator::new),
             config,
             sessions: Arc::new(RwLock::new(HashMap::new())),
-            pending_handshakes: Arc::new(RwLock::new(HashMap::new())),
+            metrics: Arc::new(Metrics::new()),
             running: Arc::new(RwLock::new(false)),
         }
     }
@@ -124,12 +235,39 @@ impl EchoServer {
         self.config.keypair.public_key()
     }
 
+    /// Get the server's metrics registry.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// Send a datagram, counting it in the metrics.
+    async fn send_to(&self, socket: &UdpSocket, packet: &[u8], addr: SocketAddr) -> std::io::Result<()> {
+        socket.send_to(packet, addr).await?;
+        self.metrics.frame_sent();
+        self.metrics.bytes_sent(packet.len());
+        Ok(())
+    }
+
     /// Run the echo server.
+    ///
+    /// # Cancellation safety
+    ///
+    /// Waiting for a datagram is cancel-safe: `UdpSocket::recv_from` never
+    /// consumes a datagram unless it completes, and the 100ms timeout around
+    /// it only drops a receive that has not yet produced anything. Once a
+    /// datagram has been received it is always handled to completion before
+    /// the loop checks for shutdown, so [`stop`](Self::stop) never abandons
+    /// one half-processed.
+    ///
+    /// Dropping the `run` future itself is *not* cancel-safe: handling awaits
+    /// the session table and the reply send, and a datagram being handled at
+    /// that moment is lost. Use [`stop`](Self::stop) for a clean shutdown.
     pub async fn run(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let socket = UdpSocket::bind(self.config.bind_addr).await?;
         eprintln!("Echo server listening on {}", self.config.bind_addr);
 
         *self.running.write().await = true;
+        let reaper = self.spawn_reaper();
 
         let mut buf = [0u8; 65535];
 
@@ -160,12 +298,13 @@ impl EchoServer {
             }
         }
 
+        reaper.abort();
         eprintln!("Echo server stopped");
         Ok(())
     }
 
     /// Handle an incoming message.
-    async fn handle_message(
+    pub(crate) async fn handle_message(
         &self,
         socket: &UdpSocket,
         addr: SocketAddr,
@@ -174,16 +313,33 @@ impl EchoServer {
         if data.is_empty() {
             return Ok(());
         }
+        self.metrics.bytes_received(data.len());
 
         let msg_type = data[0];
 
         match msg_type {
             msg_type::HANDSHAKE_INIT => {
-                self.handle_handshake_init(socket, addr, &data[1..]).await
+                let result = self.handle_handshake_init(socket, addr, &data[1..]).await;
+                if result.is_err() {
+                    self.metrics.handshake_failed();
+                }
+                result
             }
             msg_type::DATA => {
                 self.handle_data(socket, addr, &data[1..]).await
             }
+            msg_type::KEEPALIVE => {
+                self.handle_keepalive(socket, addr, &data[1..]).await
+            }
+            msg_type::REKEY => {
+                self.handle_rekey(socket, &data[1..]).await
+            }
+            msg_type::CLOSE => {
+                self.handle_close(socket, &data[1..]).await
+            }
+            msg_type::PATH_RESPONSE => {
+                self.handle_path_response(addr, &data[1..]).await
+            }
             _ => {
                 eprintln!("Unknown message type from {}: 0x{:02x}", addr, msg_type);
                 Ok(())
@@ -196,6 +352,10 @@ impl EchoServer {
     /// Wire format per specs/1-SECURITY.md:
     /// - HandshakeInit: [Type:1][Reserved:1][Version:2][Noise message...]
     /// - HandshakeResp: [Type:1][Reserved:1][SessionID:6][Noise message...]
+    ///
+    /// With retry enabled, an init without a valid cookie for its source
+    /// address gets a Retry frame and no handshake state is created.
+    /// Oversized inits are rejected before either happens.
     async fn handle_handshake_init(
         &self,
         socket: &UdpSocket,
@@ -209,69 +369,139 @@ impl EchoServer {
         if data.len() < 3 {
             return Err("HandshakeInit too short for header".into());
         }
-        let _reserved = data[0];
-        let version = u16::from_le_bytes([data[1], data[2]]);
-        let noise_message = &data[3..];
+        let flags = HandshakeFlags::parse(data[0], self.config.handshake_validation)?;
+        if !flags.is_valid() {
+            eprintln!("Ignoring unknown handshake flags from {}: 0x{:02x}", addr, flags.unknown_bits());
+        }
+        let preferred = u16::from_le_bytes([data[1], data[2]]);
+        let mut noise_message = &data[3..];
+        let cookie = if flags.has_cookie() {
+            let cookie = noise_message
+                .get(..sizes::RETRY_COOKIE_SIZE)
+                .ok_or("HandshakeInit too short for cookie")?;
+            noise_message = &noise_message[sizes::RETRY_COOKIE_SIZE..];
+            Some(cookie)
+        } else {
+            None
+        };
+        if noise_message.len() > MAX_HANDSHAKE_MESSAGE_SIZE {
+            return Err(CryptoError::HandshakeMessageTooLarge {
+                size: noise_message.len(),
+                max: MAX_HANDSHAKE_MESSAGE_SIZE,
+            }
+            .into());
+        }
+
+        if let Some(cookies) = &self.cookies
+            && !cookie.is_some_and(|cookie| cookies.validate(cookie, addr))
+        {
+            let retry = RetryFrame::new(cookies.issue(addr));
+            self.send_to(socket, &retry.to_bytes(), addr).await?;
+            eprintln!("Sent retry to {}", addr);
+            return Ok(());
+        }
 
-        eprintln!("Protocol version: 0x{:04x}, noise message: {} bytes", version, noise_message.len());
+        eprintln!("Preferred version: 0x{:04x}, noise message: {} bytes", preferred, noise_message.len());
 
         // Create responder handshake
-        let mut handshake = ResponderHandshake::new(&self.config.keypair)?;
+        let mut handshake =
+            ResponderHandshake::with_profile(&self.config.keypair, self.config.handshake_profile)?
+                .with_early_data(self.config.accept_early_data);
 
         // Process initiator's Noise message (ephemeral + encrypted static + encrypted payload)
-        let (client_payload, client_public_key) = handshake.read_message(noise_message)?;
+        let (client_payload, client_public_key) = if flags.has_early_data() {
+            handshake.read_initiator_message_with_early_data(noise_message)?
+        } else {
+            handshake.read_initiator_message(noise_message)?
+        };
+        if let Some(early_data) = handshake.early_data() {
+            // Replayable: the echo server only logs it
+            eprintln!("Accepted {} bytes of early data from {}", early_data.len(), addr);
+        }
 
-        eprintln!(
-            "Client {} requests state type: {:?}, pubkey: {:02x?}",
-            addr,
-            String::from_utf8_lossy(&client_payload),
-            &client_public_key[..8]
-        );
+        match client_public_key {
+            Some(key) => eprintln!(
+                "Client {} requests state type: {:?}, pubkey: {:02x?}",
+                addr,
+                String::from_utf8_lossy(&client_payload),
+                &key[..8]
+            ),
+            None => eprintln!(
+                "Anonymous client {} requests state type: {:?}",
+                addr,
+                String::from_utf8_lossy(&client_payload)
+            ),
+        }
+
+        // Client payload: [VersionRange:4][State type ID...]
+        let offered = VersionRange::decode(&client_payload)?;
+        let version = VersionRange::SUPPORTED.negotiate(offered)?;
+        let state_type = &client_payload[nomad_protocol::core::VERSION_RANGE_SIZE..];
 
         // Verify state type
-        if client_payload != EchoState::STATE_TYPE_ID.as_bytes() {
+        if state_type != EchoState::STATE_TYPE_ID.as_bytes() {
             eprintln!(
                 "Unknown state type from {}: {:?}",
                 addr,
-                String::from_utf8_lossy(&client_payload)
+                String::from_utf8_lossy(state_type)
             );
             return Err("Unknown state type".into());
         }
 
-        // Generate session ID
-        let session_id = SessionId::generate();
+        // Generate a session ID no live session uses. The lock is held
+        // until the session is stored, so a concurrent handshake can't pick
+        // the same ID in between.
+        let mut sessions = self.sessions.write().await;
+        let session_id = (0..MAX_SESSION_ID_ATTEMPTS)
+            .map(|_| SessionId::generate())
+            .find(|id| !sessions.contains_key(id.as_bytes()))
+            .ok_or("no unused session ID")?;
 
-        // Build response payload (encrypted part): just acknowledgment
+        // Build response payload (encrypted part): chosen version and acknowledgment
         // Session ID goes in the clear header, not here
-        let response_payload = b"OK";
+        let mut response_payload = version.as_u16().to_le_bytes().to_vec();
+        response_payload.extend_from_slice(b"OK");
 
         // Complete handshake - this produces: [Responder Ephemeral:32][Encrypted Payload...]
-        let (noise_response, handshake_result) = handshake.write_message(response_payload)?;
+        let (noise_response, handshake_result) = handshake.write_message(&response_payload)?;
 
-        // Derive session keys
-        let session_keys = SessionKeys::derive(&handshake_result)?;
+        // Derive session keys, bound to the state type we serve
+        let session_keys =
+            SessionKeys::derive_for_state_type(&handshake_result, EchoState::STATE_TYPE_ID)?;
 
         // Create crypto session (server is responder)
-        let crypto = CryptoSession::new(
+        let mut crypto = CryptoSession::new(
             session_id,
             Role::Responder,
             session_keys.responder_key,
             session_keys.initiator_key,
             handshake_result.handshake_hash,
-        );
+        )
+        .with_profile(self.config.handshake_profile)
+        .with_protocol_version(version.as_u16());
+        if let Some(key) = client_public_key {
+            crypto = crypto.with_peer_public_key(key);
+        }
 
         // Store session
-        let session = ClientSession::new(addr, crypto);
-        self.sessions.write().await.insert(*session_id.as_bytes(), session);
+        sessions.insert(*session_id.as_bytes(), ClientSession::new(addr, crypto));
+        self.metrics.set_sessions_active(sessions.len());
+        drop(sessions);
 
         // Build response per spec: [Type:1][Reserved:1][SessionID:6][Noise response...]
         let mut packet = Vec::with_capacity(8 + noise_response.len());
         packet.push(msg_type::HANDSHAKE_RESP);  // Type 0x02
-        packet.push(0x00);                       // Reserved
+        let response_flags = if handshake_result.early_data().is_some() {
+            HandshakeFlags::EARLY_DATA
+        } else {
+            HandshakeFlags::NONE
+        };
+        packet.push(response_flags.as_byte());
         packet.extend_from_slice(session_id.as_bytes());  // Session ID (6 bytes, in clear)
         packet.extend_from_slice(&noise_response);        // Noise response (ephemeral + encrypted)
 
-        socket.send_to(&packet, addr).await?;
+        self.send_to(socket, &packet, addr).await?;
+        self.metrics.handshake_succeeded();
 
         eprintln!(
             "Handshake complete with {}, session_id: {:02x?}",
@@ -283,6 +513,12 @@ impl EchoServer {
     }
 
     /// Handle encrypted data.
+    ///
+    /// Replies always go to the session's validated address. An
+    /// authenticated frame from a different source only starts path
+    /// validation: the new address gets a PATH_CHALLENGE and becomes the
+    /// reply address once the client echoes it back, so a rewritten source
+    /// address cannot redirect the session.
     async fn handle_data(
         &self,
         socket: &UdpSocket,
@@ -305,11 +541,15 @@ impl EchoServer {
             .get_mut(&session_id_bytes)
             .ok_or("Unknown session")?;
 
-        // Update client address (for roaming)
-        session.addr = addr;
+        // A closing session accepts no more data
+        if session.phase != SessionState::Active {
+            return Ok(());
+        }
 
         // Decrypt
         let plaintext = session.crypto.decrypt_frame(msg_type::DATA, 0x00, nonce_counter, ciphertext)?;
+        session.last_activity = Instant::now();
+        self.challenge_if_roamed(socket, session, addr, data.len()).await?;
 
         // Parse plaintext: [sequence:8][payload...]
         if plaintext.len() < 8 {
@@ -345,9 +585,20 @@ impl EchoServer {
             response_plaintext.extend_from_slice(&client_seq.to_le_bytes());
             response_plaintext.extend_from_slice(payload);
 
-            // Encrypt
+            if self.close_if_keys_exhausted(socket, session).await? {
+                return Ok(());
+            }
+
             let (resp_nonce, resp_ciphertext) =
-                session.crypto.encrypt_frame(msg_type::DATA, 0x00, &response_plaintext)?;
+                match session.crypto.encrypt_frame(msg_type::DATA, 0x00, &response_plaintext) {
+                    Ok(frame) => frame,
+                    Err(CryptoError::CounterExhaustion) => {
+                        return self
+                            .request_rekey(socket, session, msg_type::DATA, response_plaintext)
+                            .await;
+                    }
+                    Err(e) => return Err(e.into()),
+                };
 
             // Build packet: [type:1][session_id:6][nonce:8][ciphertext...]
             let mut packet = Vec::with_capacity(15 + resp_ciphertext.len());
@@ -356,14 +607,427 @@ impl EchoServer {
             packet.extend_from_slice(&resp_nonce.to_le_bytes());
             packet.extend_from_slice(&resp_ciphertext);
 
-            socket.send_to(&packet, addr).await?;
+            self.send_to(socket, &packet, session.addr()).await?;
 
-            eprintln!("Echoed back to {}: seq={}", addr, session.server_seq);
+            eprintln!("Echoed back to {}: seq={}", session.addr(), session.server_seq);
         }
 
         Ok(())
     }
 
+    /// Handle a client keepalive.
+    ///
+    /// Keepalives carry [sequence:8][payload header:10] and are always
+    /// answered with a KEEPALIVE of our own,
+    /// [server_seq:8][acked_seq:8][payload header:10], echoing the client's
+    /// timestamp. That tells an idle client the session is alive and gives
+    /// it an RTT sample.
+    async fn handle_keepalive(
+        &self,
+        socket: &UdpSocket,
+        addr: SocketAddr,
+        data: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // Parse header: [session_id:6][nonce:8][ciphertext...]
+        if data.len() < 14 {
+            return Err("Keepalive packet too short".into());
+        }
+
+        let mut session_id_bytes = [0u8; 6];
+        session_id_bytes.copy_from_slice(&data[0..6]);
+        let nonce_counter = u64::from_le_bytes(data[6..14].try_into()?);
+        let ciphertext = &data[14..];
+
+        let mut sessions = self.sessions.write().await;
+        let session = sessions
+            .get_mut(&session_id_bytes)
+            .ok_or("Unknown session")?;
+
+        if session.phase != SessionState::Active {
+            return Ok(());
+        }
+
+        let plaintext =
+            session.crypto.decrypt_frame(msg_type::KEEPALIVE, 0x00, nonce_counter, ciphertext)?;
+        session.last_activity = Instant::now();
+        self.challenge_if_roamed(socket, session, addr, data.len()).await?;
+
+        if plaintext.len() < 8 {
+            return Err("Keepalive plaintext too short".into());
+        }
+        let header = PayloadHeader::from_bytes(&plaintext[8..])?;
+        session.timestamps.on_receive(header.timestamp, header.timestamp_echo);
+
+        if self.close_if_keys_exhausted(socket, session).await? {
+            return Ok(());
+        }
+
+        // Reply: [server_seq:8][acked_seq:8][payload header:10]
+        let timestamp = session.timestamps.now();
+        let reply_header = PayloadHeader::new(timestamp, session.timestamps.timestamp_echo(), 0);
+        let mut reply_plaintext = Vec::with_capacity(26);
+        reply_plaintext.extend_from_slice(&session.server_seq.to_le_bytes());
+        reply_plaintext.extend_from_slice(&session.last_client_seq.to_le_bytes());
+        reply_plaintext.extend_from_slice(&reply_header.to_bytes());
+        let (reply_nonce, reply_ciphertext) =
+            match session.crypto.encrypt_frame(msg_type::KEEPALIVE, 0x00, &reply_plaintext) {
+                Ok(frame) => frame,
+                Err(CryptoError::CounterExhaustion) => {
+                    return self
+                        .request_rekey(socket, session, msg_type::KEEPALIVE, reply_plaintext)
+                        .await;
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+        let mut packet = Vec::with_capacity(15 + reply_ciphertext.len());
+        packet.push(msg_type::KEEPALIVE);
+        packet.extend_from_slice(&session_id_bytes);
+        packet.extend_from_slice(&reply_nonce.to_le_bytes());
+        packet.extend_from_slice(&reply_ciphertext);
+
+        self.send_to(socket, &packet, session.addr()).await?;
+        session.timestamps.on_send(timestamp);
+        Ok(())
+    }
+
+    /// Close the session if its keys are due for a rekey but no epoch is left.
+    ///
+    /// Sends CLOSE with [`CloseReason::KeyExpired`] while the keys can still
+    /// encrypt it, then moves the session to `Closing` so the reaper removes
+    /// it after [`CLOSE_LINGER`]. Returns whether the session was closed.
+    async fn close_if_keys_exhausted(
+        &self,
+        socket: &UdpSocket,
+        session: &mut ClientSession,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        if !session.crypto.should_rekey() || session.crypto.can_rekey() {
+            return Ok(false);
+        }
+        self.close_key_expired(socket, session).await?;
+        Ok(true)
+    }
+
+    /// Recover from a reply that ran out of send counters.
+    ///
+    /// Only the client starts the REKEY exchange, so we ask for one with an
+    /// empty REKEY on a counter reserved for control frames and hold the
+    /// reply back; [`handle_rekey`](Self::handle_rekey) sends it once under
+    /// the new epoch. Past the epoch limit the session is closed with
+    /// [`CloseReason::KeyExpired`] instead.
+    async fn request_rekey(
+        &self,
+        socket: &UdpSocket,
+        session: &mut ClientSession,
+        frame_type: u8,
+        plaintext: Vec<u8>,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if !session.crypto.can_rekey() {
+            return self.close_key_expired(socket, session).await;
+        }
+        session.deferred_reply = Some((frame_type, plaintext));
+
+        let (nonce, ciphertext) = session.crypto.encrypt_frame(msg_type::REKEY, 0x00, &[])?;
+        let mut packet = Vec::with_capacity(15 + ciphertext.len());
+        packet.push(msg_type::REKEY);
+        packet.extend_from_slice(session.crypto.session_id().as_bytes());
+        packet.extend_from_slice(&nonce.to_le_bytes());
+        packet.extend_from_slice(&ciphertext);
+
+        self.send_to(socket, &packet, session.addr()).await?;
+        eprintln!("Send counter exhausted for {}, requested a rekey", session.addr());
+        Ok(())
+    }
+
+    /// Send CLOSE with [`CloseReason::KeyExpired`] and move the session to
+    /// `Closing`.
+    async fn close_key_expired(
+        &self,
+        socket: &UdpSocket,
+        session: &mut ClientSession,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        session.phase = SessionState::Closing;
+        session.closing_since.get_or_insert_with(Instant::now);
+
+        let plaintext = CloseFrame::encode_plaintext(CloseReason::KeyExpired, session.last_client_seq);
+        let (nonce, ciphertext) = session.crypto.encrypt_frame(msg_type::CLOSE, 0x00, &plaintext)?;
+        let mut packet = Vec::with_capacity(15 + ciphertext.len());
+        packet.push(msg_type::CLOSE);
+        packet.extend_from_slice(session.crypto.session_id().as_bytes());
+        packet.extend_from_slice(&nonce.to_le_bytes());
+        packet.extend_from_slice(&ciphertext);
+
+        self.send_to(socket, &packet, session.addr()).await?;
+        eprintln!("Closing session with {}: key epoch limit reached", session.addr());
+        Ok(())
+    }
+
+    /// Start path validation if an authenticated frame came from a new address.
+    ///
+    /// The new address gets a PATH_CHALLENGE; replies keep going to the
+    /// validated address until the client echoes it back.
+    async fn challenge_if_roamed(
+        &self,
+        socket: &UdpSocket,
+        session: &mut ClientSession,
+        addr: SocketAddr,
+        received: usize,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        session.migration.on_receive(addr, received);
+        session.migration.expire_validation();
+        if addr == session.addr() || session.migration.pending_address() == Some(addr) {
+            return Ok(());
+        }
+
+        match session.migration.begin_validation(addr) {
+            Ok(token) => {
+                let (nonce, ciphertext) =
+                    session.crypto.encrypt_frame(msg_type::PATH_CHALLENGE, 0x00, &token)?;
+                let mut packet = Vec::with_capacity(15 + ciphertext.len());
+                packet.push(msg_type::PATH_CHALLENGE);
+                packet.extend_from_slice(session.crypto.session_id().as_bytes());
+                packet.extend_from_slice(&nonce.to_le_bytes());
+                packet.extend_from_slice(&ciphertext);
+                // Anti-amplification: never send more than the address sent us
+                if session.migration.can_send(addr, packet.len()) {
+                    self.send_to(socket, &packet, addr).await?;
+                    session.migration.on_send(addr, packet.len());
+                    eprintln!("Validating new address {} (current {})", addr, session.addr());
+                }
+            }
+            Err(e) => eprintln!("Not validating {}: {}", addr, e),
+        }
+        Ok(())
+    }
+
+    /// Handle a PATH_RESPONSE echoing a path challenge token.
+    ///
+    /// Commits the roam to the challenged address when the token matches.
+    async fn handle_path_response(
+        &self,
+        addr: SocketAddr,
+        data: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // Parse header: [session_id:6][nonce:8][ciphertext...]
+        if data.len() < 14 {
+            return Err("Path response packet too short".into());
+        }
+
+        let mut session_id_bytes = [0u8; 6];
+        session_id_bytes.copy_from_slice(&data[0..6]);
+        let nonce_counter = u64::from_le_bytes(data[6..14].try_into()?);
+        let ciphertext = &data[14..];
+
+        let mut sessions = self.sessions.write().await;
+        let session = sessions
+            .get_mut(&session_id_bytes)
+            .ok_or("Unknown session")?;
+
+        // Decrypt: plaintext is [token:8]
+        let plaintext =
+            session.crypto.decrypt_frame(msg_type::PATH_RESPONSE, 0x00, nonce_counter, ciphertext)?;
+        let token: [u8; PATH_CHALLENGE_SIZE] = plaintext
+            .as_slice()
+            .try_into()
+            .map_err(|_| "Path response has wrong token size")?;
+        session.last_activity = Instant::now();
+
+        if session.migration.on_path_response(token) {
+            eprintln!("Client migrated to {} (response from {})", session.addr(), addr);
+        }
+
+        Ok(())
+    }
+
+    /// Handle a client's REKEY request.
+    ///
+    /// The request carries the epoch the client wants to move to. We stage
+    /// the new keys, reply with the same epoch under the current keys (the
+    /// client hasn't switched yet), then switch. A retransmitted request for
+    /// the epoch we already moved to decrypts under the retained old keys
+    /// and gets the cached reply again, since we can no longer encrypt
+    /// under the old epoch.
+    async fn handle_rekey(
+        &self,
+        socket: &UdpSocket,
+        data: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // Parse header: [session_id:6][nonce:8][ciphertext...]
+        if data.len() < 14 {
+            return Err("Rekey packet too short".into());
+        }
+
+        let mut session_id_bytes = [0u8; 6];
+        session_id_bytes.copy_from_slice(&data[0..6]);
+        let nonce_counter = u64::from_le_bytes(data[6..14].try_into()?);
+        let ciphertext = &data[14..];
+
+        let mut sessions = self.sessions.write().await;
+        let session = sessions
+            .get_mut(&session_id_bytes)
+            .ok_or("Unknown session")?;
+
+        if session.phase != SessionState::Active {
+            return Ok(());
+        }
+
+        // Decrypt: plaintext is [epoch:4]
+        let plaintext = session.crypto.decrypt_frame(msg_type::REKEY, 0x00, nonce_counter, ciphertext)?;
+        let epoch = u32::from_le_bytes(
+            plaintext
+                .as_slice()
+                .try_into()
+                .map_err(|_| "Rekey request has wrong size")?,
+        );
+        session.last_activity = Instant::now();
+
+        if let Some((replied_epoch, packet)) = &session.rekey_reply
+            && *replied_epoch == epoch
+            && epoch == session.crypto.epoch()
+        {
+            self.send_to(socket, packet, session.addr()).await?;
+            return Ok(());
+        }
+        if epoch != session.crypto.epoch() + 1 {
+            return Err(format!(
+                "Rekey to epoch {} requested at epoch {}",
+                epoch,
+                session.crypto.epoch()
+            )
+            .into());
+        }
+
+        let pending = session.crypto.begin_rekey()?;
+        let (reply_nonce, reply_ciphertext) =
+            session.crypto.encrypt_frame(msg_type::REKEY, 0x00, &epoch.to_le_bytes())?;
+        session.crypto.complete_rekey(pending)?;
+
+        let mut packet = Vec::with_capacity(15 + reply_ciphertext.len());
+        packet.push(msg_type::REKEY);
+        packet.extend_from_slice(&session_id_bytes);
+        packet.extend_from_slice(&reply_nonce.to_le_bytes());
+        packet.extend_from_slice(&reply_ciphertext);
+
+        self.send_to(socket, &packet, session.addr()).await?;
+        eprintln!("Rekeyed session with {} to epoch {}", session.addr(), epoch);
+        session.rekey_reply = Some((epoch, packet));
+
+        // Retry, once, the reply that ran out of counters
+        if let Some((frame_type, plaintext)) = session.deferred_reply.take() {
+            let (nonce, ciphertext) = session.crypto.encrypt_frame(frame_type, 0x00, &plaintext)?;
+            let mut packet = Vec::with_capacity(15 + ciphertext.len());
+            packet.push(frame_type);
+            packet.extend_from_slice(&session_id_bytes);
+            packet.extend_from_slice(&nonce.to_le_bytes());
+            packet.extend_from_slice(&ciphertext);
+            self.send_to(socket, &packet, session.addr()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Handle a graceful close.
+    ///
+    /// Records the client's final ack, moves the session to `Closing` and
+    /// replies with our own CLOSE carrying the highest client sequence we
+    /// acked. The session lingers for [`CLOSE_LINGER`] so a retransmitted
+    /// CLOSE (if our reply was lost) is answered again, then the reaper
+    /// removes it. The reply goes to the validated client address, not the
+    /// datagram's source.
+    async fn handle_close(
+        &self,
+        socket: &UdpSocket,
+        data: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // Parse header: [session_id:6][nonce:8][ciphertext...]
+        if data.len() < 14 {
+            return Err("Close packet too short".into());
+        }
+
+        let mut session_id_bytes = [0u8; 6];
+        session_id_bytes.copy_from_slice(&data[0..6]);
+        let nonce_counter = u64::from_le_bytes(data[6..14].try_into()?);
+        let ciphertext = &data[14..];
+
+        let mut sessions = self.sessions.write().await;
+        let session = sessions
+            .get_mut(&session_id_bytes)
+            .ok_or("Unknown session")?;
+
+        // Decrypt: plaintext is [reason:1][final_ack:8] (or legacy [final_ack:8])
+        let plaintext = session.crypto.decrypt_frame(msg_type::CLOSE, 0x00, nonce_counter, ciphertext)?;
+        let (reason, final_ack) = CloseFrame::decode_plaintext(&plaintext)?;
+
+        session.final_ack = Some(final_ack);
+        session.close_reason = Some(reason);
+        session.phase = SessionState::Closing;
+        session.closing_since.get_or_insert_with(Instant::now);
+
+        // Reply with our own close: [reason:1][server_final_ack:8]
+        let reply_plaintext = CloseFrame::encode_plaintext(CloseReason::Graceful, session.last_client_seq);
+        let (reply_nonce, reply_ciphertext) =
+            session.crypto.encrypt_frame(msg_type::CLOSE, 0x00, &reply_plaintext)?;
+
+        let mut packet = Vec::with_capacity(15 + reply_ciphertext.len());
+        packet.push(msg_type::CLOSE);
+        packet.extend_from_slice(&session_id_bytes);
+        packet.extend_from_slice(&reply_nonce.to_le_bytes());
+        packet.extend_from_slice(&reply_ciphertext);
+
+        self.send_to(socket, &packet, session.addr()).await?;
+
+        eprintln!(
+            "Closing session with {}: reason={:?}, final_ack={}, acked client seq={}",
+            session.addr(),
+            reason,
+            final_ack,
+            session.last_client_seq
+        );
+
+        Ok(())
+    }
+
+    /// Spawn the background task that evicts expired sessions.
+    ///
+    /// Scans at least every [`REAPER_INTERVAL`] (more often for short idle
+    /// timeouts) and exits once the server stops running.
+    fn spawn_reaper(&self) -> tokio::task::JoinHandle<()> {
+        let sessions = self.sessions.clone();
+        let running = self.running.clone();
+        let metrics = self.metrics.clone();
+        let idle_timeout = self.config.idle_timeout;
+        let interval = REAPER_INTERVAL.min(idle_timeout / 2).max(Duration::from_millis(10));
+
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                if !*running.read().await {
+                    break;
+                }
+                let mut sessions = sessions.write().await;
+                let removed = reap_expired(&mut sessions, Instant::now(), idle_timeout);
+                metrics.set_sessions_active(sessions.len());
+                drop(sessions);
+                if removed > 0 {
+                    eprintln!("Evicted {} expired session(s)", removed);
+                }
+            }
+        })
+    }
+
+    /// Evict expired sessions as of `now`.
+    ///
+    /// Removes sessions that have lingered [`CLOSE_LINGER`] after a close,
+    /// and sessions idle for longer than the configured idle timeout.
+    /// Returns the number of sessions removed.
+    #[cfg(test)]
+    async fn reap_sessions_at(&self, now: Instant) -> usize {
+        let mut sessions = self.sessions.write().await;
+        let removed = reap_expired(&mut sessions, now, self.config.idle_timeout);
+        self.metrics.set_sessions_active(sessions.len());
+        removed
+    }
+
     /// Stop the server.
     pub async fn stop(&self) {
         *self.running.write().await = false;
@@ -384,4 +1048,542 @@ mod tests {
         let config = EchoServerConfig::default();
         assert_eq!(config.bind_addr.port(), 19999);
     }
+
+    /// Install a session with fixed keys, returning the client's side.
+    async fn install_session(server: &EchoServer, addr: SocketAddr) -> CryptoSession {
+        use nomad_protocol::crypto::SessionKey;
+
+        let session_id = SessionId::generate();
+        let initiator_key = SessionKey::from_bytes([0x01; 32]);
+        let responder_key = SessionKey::from_bytes([0x02; 32]);
+        let server_crypto = CryptoSession::new(
+            session_id,
+            Role::Responder,
+            responder_key.clone(),
+            initiator_key.clone(),
+            [0x42; 32],
+        );
+        server
+            .sessions
+            .write()
+            .await
+            .insert(*session_id.as_bytes(), ClientSession::new(addr, server_crypto));
+
+        CryptoSession::new(session_id, Role::Initiator, initiator_key, responder_key, [0x42; 32])
+    }
+
+    /// Encrypt `plaintext` into a client frame of type `ty`.
+    fn client_frame(client_crypto: &mut CryptoSession, ty: u8, plaintext: &[u8]) -> Vec<u8> {
+        let (nonce, ciphertext) = client_crypto.encrypt_frame(ty, 0x00, plaintext).unwrap();
+        let mut packet = vec![ty];
+        packet.extend_from_slice(client_crypto.session_id().as_bytes());
+        packet.extend_from_slice(&nonce.to_le_bytes());
+        packet.extend_from_slice(&ciphertext);
+        packet
+    }
+
+    /// Build a client DATA frame: [seq:8][payload...]
+    fn data_frame(client_crypto: &mut CryptoSession, seq: u64, payload: &[u8]) -> Vec<u8> {
+        let mut plaintext = seq.to_le_bytes().to_vec();
+        plaintext.extend_from_slice(payload);
+        client_frame(client_crypto, msg_type::DATA, &plaintext)
+    }
+
+    /// Receive one frame and decrypt it, returning (type, plaintext).
+    async fn recv_frame(socket: &UdpSocket, client_crypto: &mut CryptoSession) -> (u8, Vec<u8>) {
+        let mut buf = [0u8; 1500];
+        let len = tokio::time::timeout(Duration::from_secs(5), socket.recv(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        let nonce = u64::from_le_bytes(buf[7..15].try_into().unwrap());
+        let plaintext = client_crypto.decrypt_frame(buf[0], 0x00, nonce, &buf[15..len]).unwrap();
+        (buf[0], plaintext)
+    }
+
+    #[tokio::test]
+    async fn test_spoofed_source_does_not_hijack_replies() {
+        let server = EchoServer::new(EchoServerConfig::default());
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let attacker_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_addr = client_socket.local_addr().unwrap();
+        let attacker_addr = attacker_socket.local_addr().unwrap();
+        let mut client_crypto = install_session(&server, client_addr).await;
+
+        // A genuine frame whose source address was rewritten on the way
+        let packet = data_frame(&mut client_crypto, 1, b"hello");
+        server.handle_message(&server_socket, attacker_addr, &packet).await.unwrap();
+
+        // The echo still goes to the validated client address
+        let (ty, reply) = recv_frame(&client_socket, &mut client_crypto).await;
+        assert_eq!(ty, msg_type::DATA);
+        assert_eq!(&reply[16..], b"hello");
+
+        // The spoofed address only gets a challenge it cannot answer
+        let mut buf = [0u8; 1500];
+        attacker_socket.recv(&mut buf).await.unwrap();
+        assert_eq!(buf[0], msg_type::PATH_CHALLENGE);
+        let bogus = client_frame(&mut client_crypto, msg_type::PATH_RESPONSE, &[0u8; PATH_CHALLENGE_SIZE]);
+        server.handle_message(&server_socket, attacker_addr, &bogus).await.unwrap();
+
+        let sessions = server.sessions.read().await;
+        let session = sessions.values().next().unwrap();
+        assert_eq!(session.addr(), client_addr);
+        assert_eq!(session.migration.pending_address(), Some(attacker_addr));
+    }
+
+    #[tokio::test]
+    async fn test_validated_roam_updates_address() {
+        let server = EchoServer::new(EchoServerConfig::default());
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let old_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let new_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let old_addr = old_socket.local_addr().unwrap();
+        let new_addr = new_socket.local_addr().unwrap();
+        let mut client_crypto = install_session(&server, old_addr).await;
+
+        // Client roams: first frame from the new address is echoed to the old one
+        let packet = data_frame(&mut client_crypto, 1, b"roam");
+        server.handle_message(&server_socket, new_addr, &packet).await.unwrap();
+        let (ty, _) = recv_frame(&old_socket, &mut client_crypto).await;
+        assert_eq!(ty, msg_type::DATA);
+
+        // The new address answers the challenge
+        let (ty, token) = recv_frame(&new_socket, &mut client_crypto).await;
+        assert_eq!(ty, msg_type::PATH_CHALLENGE);
+        let response = client_frame(&mut client_crypto, msg_type::PATH_RESPONSE, &token);
+        server.handle_message(&server_socket, new_addr, &response).await.unwrap();
+
+        // Replies now follow the client
+        let packet = data_frame(&mut client_crypto, 2, b"moved");
+        server.handle_message(&server_socket, new_addr, &packet).await.unwrap();
+        let (ty, reply) = recv_frame(&new_socket, &mut client_crypto).await;
+        assert_eq!(ty, msg_type::DATA);
+        assert_eq!(&reply[16..], b"moved");
+        assert_eq!(server.sessions.read().await.values().next().unwrap().addr(), new_addr);
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_answered() {
+        let server = EchoServer::new(EchoServerConfig::default());
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let mut client_crypto = install_session(&server, addr).await;
+
+        let packet = data_frame(&mut client_crypto, 1, b"hello");
+        server.handle_message(&server_socket, addr, &packet).await.unwrap();
+        recv_frame(&socket, &mut client_crypto).await;
+
+        // Idle keepalive repeating seq 1 gets a reply echoing our timestamp
+        let mut plaintext = 1u64.to_le_bytes().to_vec();
+        plaintext.extend_from_slice(&PayloadHeader::new(1234, 0, 0).to_bytes());
+        let keepalive = client_frame(&mut client_crypto, msg_type::KEEPALIVE, &plaintext);
+        server.handle_message(&server_socket, addr, &keepalive).await.unwrap();
+
+        let (ty, reply) = recv_frame(&socket, &mut client_crypto).await;
+        assert_eq!(ty, msg_type::KEEPALIVE);
+        assert_eq!(u64::from_le_bytes(reply[0..8].try_into().unwrap()), 1);
+        assert_eq!(u64::from_le_bytes(reply[8..16].try_into().unwrap()), 1);
+        let header = PayloadHeader::from_bytes(&reply[16..]).unwrap();
+        assert_eq!(header.timestamp_echo, 1234);
+    }
+
+    #[tokio::test]
+    async fn test_counter_exhaustion_defers_reply_until_rekey() {
+        use nomad_protocol::core::CONTROL_FRAME_HEADROOM;
+
+        let server = EchoServer::new(EchoServerConfig::default());
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let mut client_crypto = install_session(&server, addr).await;
+        for session in server.sessions.write().await.values_mut() {
+            session.crypto.set_send_counter_limit(CONTROL_FRAME_HEADROOM + 1);
+        }
+
+        let packet = data_frame(&mut client_crypto, 1, b"first");
+        server.handle_message(&server_socket, addr, &packet).await.unwrap();
+        recv_frame(&socket, &mut client_crypto).await;
+
+        // No counter left for the next echo: the server asks for a rekey instead
+        let packet = data_frame(&mut client_crypto, 2, b"held");
+        server.handle_message(&server_socket, addr, &packet).await.unwrap();
+        let (ty, request) = recv_frame(&socket, &mut client_crypto).await;
+        assert_eq!(ty, msg_type::REKEY);
+        assert!(request.is_empty());
+
+        // The client's REKEY exchange releases the held echo under epoch 1
+        let pending = client_crypto.begin_rekey().unwrap();
+        let rekey = client_frame(&mut client_crypto, msg_type::REKEY, &1u32.to_le_bytes());
+        server.handle_message(&server_socket, addr, &rekey).await.unwrap();
+        let (ty, reply) = recv_frame(&socket, &mut client_crypto).await;
+        assert_eq!(ty, msg_type::REKEY);
+        assert_eq!(reply, 1u32.to_le_bytes());
+        client_crypto.complete_rekey(pending).unwrap();
+
+        let (ty, echo) = recv_frame(&socket, &mut client_crypto).await;
+        assert_eq!(ty, msg_type::DATA);
+        assert_eq!(&echo[16..], b"held");
+        assert_eq!(client_crypto.epoch(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_counter_exhaustion_at_epoch_limit_closes() {
+        use nomad_protocol::core::CONTROL_FRAME_HEADROOM;
+
+        let server = EchoServer::new(EchoServerConfig::default());
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let mut client_crypto = install_session(&server, addr).await;
+        for session in server.sessions.write().await.values_mut() {
+            session.crypto.set_send_counter_limit(CONTROL_FRAME_HEADROOM);
+            session.crypto.set_epoch_limit(0);
+        }
+
+        let packet = data_frame(&mut client_crypto, 1, b"hello");
+        server.handle_message(&server_socket, addr, &packet).await.unwrap();
+        let (ty, plaintext) = recv_frame(&socket, &mut client_crypto).await;
+        assert_eq!(ty, msg_type::CLOSE);
+        let (reason, _) = CloseFrame::decode_plaintext(&plaintext).unwrap();
+        assert_eq!(reason, CloseReason::KeyExpired);
+        assert_eq!(
+            server.sessions.read().await.values().next().unwrap().phase,
+            SessionState::Closing
+        );
+    }
+
+    #[tokio::test]
+    async fn test_close_removes_session() {
+        let server = EchoServer::new(EchoServerConfig::default());
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_addr = client_socket.local_addr().unwrap();
+
+        let mut client_crypto = install_session(&server, client_addr).await;
+        assert_eq!(server.session_count().await, 1);
+
+        // Client sends a legacy CLOSE carrying only its final ack
+        let (nonce, ciphertext) = client_crypto
+            .encrypt_frame(msg_type::CLOSE, 0x00, &7u64.to_le_bytes())
+            .unwrap();
+        let mut packet = vec![msg_type::CLOSE];
+        packet.extend_from_slice(client_crypto.session_id().as_bytes());
+        packet.extend_from_slice(&nonce.to_le_bytes());
+        packet.extend_from_slice(&ciphertext);
+        server.handle_message(&server_socket, client_addr, &packet).await.unwrap();
+
+        {
+            let sessions = server.sessions.read().await;
+            let session = sessions.values().next().unwrap();
+            assert_eq!(session.phase, SessionState::Closing);
+            assert_eq!(session.final_ack, Some(7));
+            assert_eq!(session.close_reason, Some(CloseReason::Graceful));
+        }
+
+        // Server answers with its own CLOSE
+        let mut buf = [0u8; 1500];
+        let len = client_socket.recv(&mut buf).await.unwrap();
+        assert_eq!(buf[0], msg_type::CLOSE);
+        let reply_nonce = u64::from_le_bytes(buf[7..15].try_into().unwrap());
+        let reply = client_crypto
+            .decrypt_frame(msg_type::CLOSE, 0x00, reply_nonce, &buf[15..len])
+            .unwrap();
+        assert_eq!(CloseFrame::decode_plaintext(&reply).unwrap(), (CloseReason::Graceful, 0));
+
+        // Session lingers, then is reaped
+        assert_eq!(server.reap_sessions_at(Instant::now()).await, 0);
+        server.reap_sessions_at(Instant::now() + CLOSE_LINGER).await;
+        assert_eq!(server.session_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_close_reply_goes_to_validated_address() {
+        let server = EchoServer::new(EchoServerConfig::default());
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let attacker_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_addr = client_socket.local_addr().unwrap();
+        let attacker_addr = attacker_socket.local_addr().unwrap();
+        let mut client_crypto = install_session(&server, client_addr).await;
+
+        // A genuine CLOSE whose source address was rewritten on the way
+        let plaintext = CloseFrame::encode_plaintext(CloseReason::Graceful, 0);
+        let packet = client_frame(&mut client_crypto, msg_type::CLOSE, &plaintext);
+        server.handle_message(&server_socket, attacker_addr, &packet).await.unwrap();
+
+        let (ty, _) = recv_frame(&client_socket, &mut client_crypto).await;
+        assert_eq!(ty, msg_type::CLOSE);
+        let mut buf = [0u8; 1500];
+        let spoofed =
+            tokio::time::timeout(Duration::from_millis(100), attacker_socket.recv(&mut buf)).await;
+        assert!(spoofed.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reaper_evicts_idle_sessions() {
+        let server = EchoServer::new(EchoServerConfig::default());
+        install_session(&server, "127.0.0.1:4000".parse().unwrap()).await;
+
+        assert_eq!(server.reap_sessions_at(Instant::now()).await, 0);
+        assert_eq!(server.reap_sessions_at(Instant::now() + DEAD_INTERVAL).await, 1);
+        assert_eq!(server.session_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_reaper_task_evicts_stale_session() {
+        let config = EchoServerConfig::default().idle_timeout(Duration::from_millis(50));
+        let server = EchoServer::new(config);
+        install_session(&server, "127.0.0.1:4000".parse().unwrap()).await;
+
+        *server.running.write().await = true;
+        let reaper = server.spawn_reaper();
+        assert_eq!(server.session_count().await, 1);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(server.session_count().await, 0);
+
+        server.stop().await;
+        reaper.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retry_before_handshake_state() {
+        use nomad_protocol::core::ProtocolVersion;
+        use nomad_protocol::crypto::InitiatorHandshake;
+
+        let config = EchoServerConfig::default().retry_cookie_lifetime(Duration::from_secs(30));
+        let server = EchoServer::new(config);
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_addr = client_socket.local_addr().unwrap();
+
+        let client_keypair = StaticKeypair::generate();
+        let mut handshake = InitiatorHandshake::new(&client_keypair, server.public_key()).unwrap();
+        let mut payload = VersionRange::SUPPORTED.encode().to_vec();
+        payload.extend_from_slice(EchoState::STATE_TYPE_ID.as_bytes());
+        let noise_message = handshake.write_message(&payload).unwrap();
+        let init = |flags: HandshakeFlags, cookie: &[u8]| {
+            let mut packet = vec![msg_type::HANDSHAKE_INIT, flags.as_byte()];
+            packet.extend_from_slice(&ProtocolVersion::CURRENT.as_u16().to_le_bytes());
+            packet.extend_from_slice(cookie);
+            packet.extend_from_slice(&noise_message);
+            packet
+        };
+
+        // No cookie: Retry, and no session
+        server
+            .handle_message(&server_socket, client_addr, &init(HandshakeFlags::NONE, &[]))
+            .await
+            .unwrap();
+        let mut buf = [0u8; 1500];
+        let len = client_socket.recv(&mut buf).await.unwrap();
+        let retry = RetryFrame::from_bytes(&buf[..len]).unwrap();
+        assert_eq!(server.session_count().await, 0);
+
+        // Echoed cookie from another address: Retry again
+        let other: SocketAddr = "127.0.0.1:9".parse().unwrap();
+        server
+            .handle_message(&server_socket, other, &init(HandshakeFlags::COOKIE, &retry.cookie))
+            .await
+            .unwrap();
+        assert_eq!(server.session_count().await, 0);
+
+        // Echoed cookie from the original address: handshake completes
+        server
+            .handle_message(&server_socket, client_addr, &init(HandshakeFlags::COOKIE, &retry.cookie))
+            .await
+            .unwrap();
+        let len = client_socket.recv(&mut buf).await.unwrap();
+        assert_eq!(buf[0], msg_type::HANDSHAKE_RESP);
+        handshake.read_message(&buf[8..len]).unwrap();
+        assert_eq!(server.session_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_init_rejected_before_retry() {
+        use nomad_protocol::core::ProtocolVersion;
+
+        let config = EchoServerConfig::default().retry_cookie_lifetime(Duration::from_secs(30));
+        let server = EchoServer::new(config);
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_addr = client_socket.local_addr().unwrap();
+
+        let mut packet = vec![msg_type::HANDSHAKE_INIT, HandshakeFlags::NONE.as_byte()];
+        packet.extend_from_slice(&ProtocolVersion::CURRENT.as_u16().to_le_bytes());
+        packet.extend_from_slice(&[0u8; MAX_HANDSHAKE_MESSAGE_SIZE + 1]);
+        let err = server.handle_message(&server_socket, client_addr, &packet).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CryptoError>(),
+            Some(CryptoError::HandshakeMessageTooLarge { .. })
+        ));
+
+        // Neither a Retry nor a session was produced
+        let mut buf = [0u8; 1500];
+        let reply = tokio::time::timeout(Duration::from_millis(50), client_socket.recv(&mut buf));
+        assert!(reply.await.is_err());
+        assert_eq!(server.session_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_metrics_after_handshake_and_frames() {
+        use nomad_protocol::core::ProtocolVersion;
+        use nomad_protocol::crypto::InitiatorHandshake;
+
+        let server = EchoServer::new(EchoServerConfig::default());
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_addr = client_socket.local_addr().unwrap();
+
+        // Handshake
+        let client_keypair = StaticKeypair::generate();
+        let mut handshake = InitiatorHandshake::new(&client_keypair, server.public_key()).unwrap();
+        let mut payload = VersionRange::SUPPORTED.encode().to_vec();
+        payload.extend_from_slice(EchoState::STATE_TYPE_ID.as_bytes());
+        let mut packet = vec![msg_type::HANDSHAKE_INIT, HandshakeFlags::NONE.as_byte()];
+        packet.extend_from_slice(&ProtocolVersion::CURRENT.as_u16().to_le_bytes());
+        packet.extend_from_slice(&handshake.write_message(&payload).unwrap());
+        server.handle_message(&server_socket, client_addr, &packet).await.unwrap();
+
+        // A garbage init fails
+        let garbage = [msg_type::HANDSHAKE_INIT, 0, 0, 0, 1, 2, 3];
+        assert!(server.handle_message(&server_socket, client_addr, &garbage).await.is_err());
+
+        // A few echoed frames over an installed session
+        let mut client_crypto = install_session(&server, client_addr).await;
+        for seq in 1..=3 {
+            let packet = data_frame(&mut client_crypto, seq, b"ping");
+            server.handle_message(&server_socket, client_addr, &packet).await.unwrap();
+        }
+
+        let text = server.metrics().encode_prometheus();
+        let value = |name: &str| -> f64 {
+            text.lines()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+                .unwrap_or_else(|| panic!("missing {name} in:\n{text}"))
+                .parse()
+                .unwrap()
+        };
+        assert_eq!(value("nomad_handshakes_total{result=\"success\"}"), 1.0);
+        assert_eq!(value("nomad_handshakes_total{result=\"failure\"}"), 1.0);
+        assert_eq!(value("nomad_sessions_active"), 1.0);
+        assert_eq!(value("nomad_frames_sent_total"), 4.0);
+        assert!(value("nomad_bytes_received_total") > 0.0);
+        assert!(value("nomad_bytes_sent_total") > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_from_private_key_advertises_matching_public_key() {
+        use nomad_protocol::core::ProtocolVersion;
+        use nomad_protocol::crypto::InitiatorHandshake;
+
+        let private_key = [0x11; 32];
+        let config = EchoServerConfig::from_private_key("127.0.0.1:0".parse().unwrap(), private_key);
+        let expected = x25519_dalek::PublicKey::from(&x25519_dalek::StaticSecret::from(private_key));
+        assert_eq!(config.keypair.public_key(), expected.as_bytes());
+
+        // A client that trusts the advertised key completes a handshake
+        let server = EchoServer::new(config);
+        let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client_addr = client_socket.local_addr().unwrap();
+
+        let client_keypair = StaticKeypair::generate();
+        let mut handshake = InitiatorHandshake::new(&client_keypair, server.public_key()).unwrap();
+        let mut payload = VersionRange::SUPPORTED.encode().to_vec();
+        payload.extend_from_slice(EchoState::STATE_TYPE_ID.as_bytes());
+        let mut packet = vec![msg_type::HANDSHAKE_INIT, HandshakeFlags::NONE.as_byte()];
+        packet.extend_from_slice(&ProtocolVersion::CURRENT.as_u16().to_le_bytes());
+        packet.extend_from_slice(&handshake.write_message(&payload).unwrap());
+        server.handle_message(&server_socket, client_addr, &packet).await.unwrap();
+
+        let mut buf = [0u8; 1500];
+        let len = client_socket.recv(&mut buf).await.unwrap();
+        assert_eq!(buf[0], msg_type::HANDSHAKE_RESP);
+        handshake.read_message(&buf[8..len]).unwrap();
+        assert_eq!(server.session_count().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_records_client_public_key() {
+        use nomad_protocol::core::ProtocolVersion;
+        use nomad_protocol::crypto::InitiatorHandshake;
+
+        let client_keypair = StaticKeypair::generate();
+        for profile in [HandshakeProfile::Authenticated, HandshakeProfile::Anonymous] {
+            let server = EchoServer::new(EchoServerConfig {
+                handshake_profile: profile,
+                ..EchoServerConfig::default()
+            });
+            let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let client_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let client_addr = client_socket.local_addr().unwrap();
+
+            let mut handshake = match profile {
+                HandshakeProfile::Authenticated => {
+                    InitiatorHandshake::new(&client_keypair, server.public_key()).unwrap()
+                }
+                HandshakeProfile::Anonymous => {
+                    InitiatorHandshake::anonymous(server.public_key()).unwrap()
+                }
+            };
+            let mut payload = VersionRange::SUPPORTED.encode().to_vec();
+            payload.extend_from_slice(EchoState::STATE_TYPE_ID.as_bytes());
+            let mut packet = vec![msg_type::HANDSHAKE_INIT, HandshakeFlags::NONE.as_byte()];
+            packet.extend_from_slice(&ProtocolVersion::CURRENT.as_u16().to_le_bytes());
+            packet.extend_from_slice(&handshake.write_message(&payload).unwrap());
+            server.handle_message(&server_socket, client_addr, &packet).await.unwrap();
+
+            // The session the handshake installed knows who the client is
+            let sessions = server.sessions.read().await;
+            let session = sessions.values().next().unwrap();
+            let expected = match profile {
+                HandshakeProfile::Authenticated => Some(client_keypair.public_key()),
+                HandshakeProfile::Anonymous => None,
+            };
+            assert_eq!(session.crypto.peer_static_public_key(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_early_data_acknowledged_only_when_enabled() {
+        use nomad_protocol::core::ProtocolVersion;
+        use nomad_protocol::crypto::InitiatorHandshake;
+
+        for accept in [true, false] {
+            let server = EchoServer::new(EchoServerConfig {
+                accept_early_data: accept,
+                ..EchoServerConfig::default()
+            });
+            let server_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let client_socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let client_addr = client_socket.local_addr().unwrap();
+
+            let client_keypair = StaticKeypair::generate();
+            let mut handshake =
+                InitiatorHandshake::new(&client_keypair, server.public_key()).unwrap();
+            let mut payload = VersionRange::SUPPORTED.encode().to_vec();
+            payload.extend_from_slice(EchoState::STATE_TYPE_ID.as_bytes());
+            let noise_message = handshake
+                .write_message_with_early_data(&payload, &5u64.to_le_bytes())
+                .unwrap();
+            let mut packet = vec![msg_type::HANDSHAKE_INIT, HandshakeFlags::EARLY_DATA.as_byte()];
+            packet.extend_from_slice(&ProtocolVersion::CURRENT.as_u16().to_le_bytes());
+            packet.extend_from_slice(&noise_message);
+            server.handle_message(&server_socket, client_addr, &packet).await.unwrap();
+
+            // The handshake completes either way; only the response flag
+            // tells the client whether its early data was taken
+            let mut buf = [0u8; 1500];
+            let len = client_socket.recv(&mut buf).await.unwrap();
+            assert_eq!(buf[0], msg_type::HANDSHAKE_RESP);
+            let flags = HandshakeFlags::parse(buf[1], HandshakeValidation::Strict).unwrap();
+            assert_eq!(flags.has_early_data(), accept);
+            handshake.read_message(&buf[8..len]).unwrap();
+            assert_eq!(server.session_count().await, 1);
+        }
+    }
 }
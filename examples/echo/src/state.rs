@@ -27,6 +27,7 @@ impl EchoState {
     }
 
     /// Create echo state with a message.
+    #[cfg_attr(not(test), allow(dead_code))]
     pub fn with_message(message: impl Into<Vec<u8>>) -> Self {
         Self {
             message: message.into(),
@@ -35,6 +36,7 @@ impl EchoState {
     }
 
     /// Set the message content.
+    #[cfg_attr(not(test), allow(dead_code))]
     pub fn set_message(&mut self, message: impl Into<Vec<u8>>) {
         self.message = message.into();
         self.sequence += 1;
@@ -10,7 +10,8 @@ use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::{mpsc, oneshot, RwLock};
 
-use crate::core::SyncState;
+use crate::core::{CryptoError, SyncState};
+use crate::crypto::CryptoSession;
 
 /// Errors that can occur in the NOMAD client.
 #[derive(Debug, Error)]
@@ -42,6 +43,17 @@ pub enum ClientError {
     /// Operation timed out.
     #[error("operation timed out")]
     Timeout,
+
+    /// Retrying gave up (see `RetryPolicy`).
+    #[error("gave up after {attempts} attempt(s) ({reason:?}): {last}")]
+    GaveUp {
+        /// Why retrying stopped.
+        reason: super::GiveUpReason,
+        /// Number of attempts made.
+        attempts: u32,
+        /// The last error encountered.
+        last: Box<ClientError>,
+    },
 }
 
 /// Client configuration.
@@ -220,6 +232,12 @@ pub struct NomadClient<S: SyncState> {
     /// Channel for sending state updates.
     state_tx: mpsc::Sender<S>,
 
+    /// Channel for requesting an immediate rekey.
+    rekey_tx: mpsc::Sender<()>,
+
+    /// Crypto session, once the handshake has completed.
+    session: Arc<RwLock<Option<CryptoSession>>>,
+
     /// Shutdown signal.
     shutdown_tx: Option<oneshot::Sender<()>>,
 
@@ -236,12 +254,14 @@ impl<S: SyncState> NomadClient<S> {
         initial_state: S,
     ) -> Result<(Self, StateReceiver<S>), ClientError> {
         // Create channels for state communication
-        let (state_tx, _state_rx) = mpsc::channel::<S>(32);
+        let (state_tx, mut state_rx) = mpsc::channel::<S>(32);
         let (server_state_tx, server_state_rx) = mpsc::channel::<S>(32);
-        let (shutdown_tx, _shutdown_rx) = oneshot::channel();
+        let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
+        let (rekey_tx, mut rekey_rx) = mpsc::channel::<()>(1);
 
         let client_state = Arc::new(RwLock::new(ClientState::Connecting));
         let local_state = Arc::new(RwLock::new(initial_state));
+        let session = Arc::new(RwLock::new(None::<CryptoSession>));
 
         // TODO: Spawn connection task that:
         // 1. Creates UDP socket
@@ -256,18 +276,29 @@ impl<S: SyncState> NomadClient<S> {
         }
 
         // Spawn the background I/O task
-        let _io_state = client_state.clone();
+        let io_state = client_state.clone();
         let _io_local = local_state.clone();
         let _io_config = config.clone();
         let _io_server_tx = server_state_tx;
+        let io_session = session.clone();
 
         tokio::spawn(async move {
-            // TODO: Implement the actual I/O loop
+            // TODO: Implement frame I/O and sync
             // This will be implemented when lower layers are ready
-
-            // For now, this is a placeholder that keeps the client "alive"
             loop {
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                tokio::select! {
+                    _ = &mut shutdown_rx => break,
+                    Some(()) = rekey_rx.recv() => {
+                        if Self::handle_force_rekey(&io_session).await.is_err() {
+                            // Epoch limit reached: the session must end
+                            *io_state.write().await = ClientState::Closed;
+                            break;
+                        }
+                    }
+                    Some(_state) = state_rx.recv() => {
+                        // TODO: Feed the sync engine
+                    }
+                }
             }
         });
 
@@ -275,6 +306,8 @@ impl<S: SyncState> NomadClient<S> {
             state: client_state,
             local_state,
             state_tx,
+            rekey_tx,
+            session,
             shutdown_tx: Some(shutdown_tx),
             config,
         };
@@ -320,6 +353,46 @@ impl<S: SyncState> NomadClient<S> {
         }
     }
 
+    /// Rekey the session immediately, regardless of the rekey timers.
+    ///
+    /// Use this after transmitting sensitive data so that a later key
+    /// compromise cannot expose it. The rekey exchange costs one round trip
+    /// and is skipped (and the session terminated) if the epoch limit has
+    /// been reached; see `CryptoSession::force_rekey`.
+    ///
+    /// Requests are coalesced: if a forced rekey is already pending, this
+    /// returns immediately.
+    pub async fn force_rekey(&self) -> Result<(), ClientError> {
+        if !self.is_connected().await {
+            return Err(ClientError::Disconnected);
+        }
+
+        match self.rekey_tx.try_send(()) {
+            Ok(()) | Err(mpsc::error::TrySendError::Full(())) => Ok(()),
+            Err(mpsc::error::TrySendError::Closed(())) => Err(ClientError::Disconnected),
+        }
+    }
+
+    /// Advance the session to the next epoch on a `force_rekey` request.
+    ///
+    /// A no-op before the handshake has completed.
+    async fn handle_force_rekey(
+        session: &RwLock<Option<CryptoSession>>,
+    ) -> Result<(), CryptoError> {
+        let mut session = session.write().await;
+        let Some(session) = session.as_mut() else {
+            return Ok(());
+        };
+        session.force_rekey()?;
+        // TODO: Send the REKEY frame once the I/O loop owns the socket
+        Ok(())
+    }
+
+    /// Get the current key epoch, once the handshake has completed.
+    pub async fn epoch(&self) -> Option<u32> {
+        self.session.read().await.as_ref().map(CryptoSession::epoch)
+    }
+
     /// Check if the client is connected.
     pub async fn is_connected(&self) -> bool {
         matches!(*self.state.read().await, ClientState::Connected)
@@ -358,5 +431,62 @@ impl<S: SyncState> Drop for NomadClient<S> {
 
 #[cfg(test)]
 mod tests {
-    // TODO: Add tests once we have a concrete SyncState implementation
+    use super::*;
+    use crate::core::{ApplyError, DecodeError};
+    use crate::crypto::{Role, SessionId, SessionKey};
+
+    #[derive(Debug, Clone, Default)]
+    struct Counter(u64);
+
+    impl SyncState for Counter {
+        type Diff = u64;
+
+        const STATE_TYPE_ID: &'static str = "nomad.test.counter.v1";
+
+        fn diff_from(&self, _old: &Self) -> Self::Diff {
+            self.0
+        }
+
+        fn apply_diff(&mut self, diff: &Self::Diff) -> Result<(), ApplyError> {
+            self.0 = *diff;
+            Ok(())
+        }
+
+        fn encode_diff(diff: &Self::Diff) -> Vec<u8> {
+            diff.to_le_bytes().to_vec()
+        }
+
+        fn decode_diff(data: &[u8]) -> Result<Self::Diff, DecodeError> {
+            let bytes = data.try_into().map_err(|_| DecodeError::UnexpectedEof)?;
+            Ok(u64::from_le_bytes(bytes))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_force_rekey_advances_epoch() {
+        let (client, _rx) = NomadClient::connect(ClientConfig::default(), Counter(0))
+            .await
+            .unwrap();
+        assert_eq!(client.epoch().await, None);
+
+        // Install the session the handshake would have produced
+        *client.session.write().await = Some(CryptoSession::new(
+            SessionId::generate(),
+            Role::Initiator,
+            SessionKey::from_bytes([0x01; 32]),
+            SessionKey::from_bytes([0x02; 32]),
+            [0x42; 32],
+        ));
+        assert_eq!(client.epoch().await, Some(0));
+
+        client.force_rekey().await.unwrap();
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while client.epoch().await != Some(1) {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .expect("I/O task never handled the rekey request");
+        assert!(client.is_connected().await);
+    }
 }
@@ -3,7 +3,10 @@
 //! High-level API for NOMAD clients.
 
 mod bootstrap;
+#[allow(clippy::module_inception)]
 mod client;
+mod retry;
 
 pub use bootstrap::*;
 pub use client::*;
+pub use retry::*;
@@ -0,0 +1,318 @@
+//! Bounded retry for the client connect sequence.
+//!
+//! Wraps connect, handshake and initial sync in a single retry loop with
+//! exponential backoff. Only transient failures (timeouts, transient I/O)
+//! are retried; permanent ones (bad server key, authentication rejected)
+//! fail immediately, since retrying them can never succeed.
+
+use std::future::Future;
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+
+use crate::core::SyncState;
+
+use super::{ClientConfig, ClientError, NomadClient, StateReceiver};
+
+/// Default maximum number of connect attempts.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 5;
+
+/// Default delay before the first retry.
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Default upper bound on the delay between retries.
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Why the retry loop stopped trying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GiveUpReason {
+    /// The error is permanent; retrying cannot help.
+    Permanent,
+    /// The attempt budget was used up.
+    AttemptsExhausted,
+    /// The total time budget was used up.
+    DeadlineExceeded,
+}
+
+/// Retry policy for [`NomadClient::connect_reliable`].
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts (including the first).
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub initial_backoff: Duration,
+    /// Upper bound on the delay between retries.
+    pub max_backoff: Duration,
+    /// Backoff multiplier applied after each failed attempt.
+    pub backoff_multiplier: u32,
+    /// Total time budget across all attempts, if any.
+    pub deadline: Option<Duration>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            backoff_multiplier: 2,
+            deadline: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a policy with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of attempts.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Set the initial backoff.
+    pub fn initial_backoff(mut self, backoff: Duration) -> Self {
+        self.initial_backoff = backoff;
+        self
+    }
+
+    /// Set the maximum backoff.
+    pub fn max_backoff(mut self, backoff: Duration) -> Self {
+        self.max_backoff = backoff;
+        self
+    }
+
+    /// Set the total time budget.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Backoff before the retry following attempt `attempt` (1-based).
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self
+            .backoff_multiplier
+            .saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// Run `attempt` until it succeeds, fails permanently, or the budget runs out.
+    ///
+    /// # Errors
+    /// Returns `ClientError::GaveUp` wrapping the last error, classified by
+    /// [`GiveUpReason`].
+    pub async fn retry<T, F, Fut>(&self, mut attempt: F) -> Result<T, ClientError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, ClientError>>,
+    {
+        let start = Instant::now();
+        let mut attempts = 0;
+
+        loop {
+            attempts += 1;
+            let error = match attempt().await {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+
+            let reason = if !error.is_transient() {
+                Some(GiveUpReason::Permanent)
+            } else if attempts >= self.max_attempts {
+                Some(GiveUpReason::AttemptsExhausted)
+            } else {
+                None
+            };
+
+            // Don't sleep past the deadline
+            let backoff = self.backoff(attempts);
+            let reason = reason.or_else(|| {
+                self.deadline
+                    .filter(|&deadline| start.elapsed() + backoff >= deadline)
+                    .map(|_| GiveUpReason::DeadlineExceeded)
+            });
+
+            if let Some(reason) = reason {
+                return Err(ClientError::GaveUp {
+                    reason,
+                    attempts,
+                    last: Box::new(error),
+                });
+            }
+
+            tokio::time::sleep(backoff).await;
+        }
+    }
+}
+
+impl ClientError {
+    /// Check if this error is transient and worth retrying.
+    ///
+    /// Timeouts and transient I/O errors are; handshake failures (bad
+    /// server key, authentication rejected) and terminated sessions are not.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ClientError::Timeout
+            | ClientError::ConnectionFailed(_)
+            | ClientError::Disconnected => true,
+            ClientError::Io(e) => matches!(
+                e.kind(),
+                ErrorKind::TimedOut
+                    | ErrorKind::Interrupted
+                    | ErrorKind::WouldBlock
+                    | ErrorKind::ConnectionRefused
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::NetworkUnreachable
+                    | ErrorKind::HostUnreachable
+            ),
+            ClientError::HandshakeFailed(_)
+            | ClientError::SessionTerminated(_)
+            | ClientError::SyncError(_)
+            | ClientError::GaveUp { .. } => false,
+        }
+    }
+}
+
+impl<S: SyncState> NomadClient<S> {
+    /// Connect to a NOMAD server, retrying transient failures.
+    ///
+    /// Each attempt runs the full connect sequence (socket, handshake,
+    /// initial sync) bounded by `config.connect_timeout`. Attempts that time
+    /// out or hit transient I/O errors are retried with exponential backoff
+    /// per `policy`; permanent errors stop immediately.
+    ///
+    /// # Errors
+    /// Returns `ClientError::GaveUp` with the reason and the last error.
+    pub async fn connect_reliable(
+        config: ClientConfig,
+        initial_state: S,
+        policy: RetryPolicy,
+    ) -> Result<(Self, StateReceiver<S>), ClientError> {
+        policy
+            .retry(|| {
+                let config = config.clone();
+                let initial_state = initial_state.clone();
+                async move {
+                    let timeout = config.connect_timeout;
+                    tokio::time::timeout(timeout, Self::connect(config, initial_state))
+                        .await
+                        .map_err(|_| ClientError::Timeout)?
+                }
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{ApplyError, DecodeError};
+
+    #[derive(Debug, Clone, Default)]
+    struct Counter(u64);
+
+    impl SyncState for Counter {
+        type Diff = u64;
+
+        const STATE_TYPE_ID: &'static str = "nomad.test.counter.v1";
+
+        fn diff_from(&self, _old: &Self) -> Self::Diff {
+            self.0
+        }
+
+        fn apply_diff(&mut self, diff: &Self::Diff) -> Result<(), ApplyError> {
+            self.0 = *diff;
+            Ok(())
+        }
+
+        fn encode_diff(diff: &Self::Diff) -> Vec<u8> {
+            diff.to_le_bytes().to_vec()
+        }
+
+        fn decode_diff(data: &[u8]) -> Result<Self::Diff, DecodeError> {
+            let bytes = data.try_into().map_err(|_| DecodeError::UnexpectedEof)?;
+            Ok(u64::from_le_bytes(bytes))
+        }
+    }
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy::new()
+            .initial_backoff(Duration::from_millis(1))
+            .max_backoff(Duration::from_millis(4))
+    }
+
+    #[tokio::test]
+    async fn test_succeeds_on_third_attempt() {
+        let mut attempts = 0;
+        let (client, _rx) = fast_policy()
+            .retry(|| {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    if attempt < 3 {
+                        return Err(ClientError::Timeout);
+                    }
+                    NomadClient::connect(ClientConfig::default(), Counter(0)).await
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(attempts, 3);
+        assert!(client.is_connected().await);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_error_not_retried() {
+        let mut attempts = 0;
+        let result: Result<(), _> = fast_policy()
+            .retry(|| {
+                attempts += 1;
+                async { Err(ClientError::HandshakeFailed("bad server key".into())) }
+            })
+            .await;
+
+        assert_eq!(attempts, 1);
+        assert!(matches!(
+            result,
+            Err(ClientError::GaveUp {
+                reason: GiveUpReason::Permanent,
+                attempts: 1,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_attempts_exhausted() {
+        let result: Result<(), _> = fast_policy()
+            .max_attempts(4)
+            .retry(|| async { Err(ClientError::Timeout) })
+            .await;
+
+        match result {
+            Err(ClientError::GaveUp { reason, attempts, last }) => {
+                assert_eq!(reason, GiveUpReason::AttemptsExhausted);
+                assert_eq!(attempts, 4);
+                assert!(matches!(*last, ClientError::Timeout));
+            }
+            other => panic!("unexpected result: {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_backoff_capped() {
+        let policy = RetryPolicy::new()
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(350));
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(350));
+        assert_eq!(policy.backoff(40), Duration::from_millis(350));
+    }
+}
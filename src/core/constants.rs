@@ -58,6 +58,9 @@ pub const FLAG_ACK_ONLY: u8 = 0x01;
 /// Extension data follows payload.
 pub const FLAG_HAS_EXTENSION: u8 = 0x02;
 
+/// Payload is compressed (compression extension).
+pub const FLAG_COMPRESSED: u8 = 0x04;
+
 // =============================================================================
 // FRAME SIZES (2-TRANSPORT.md)
 // =============================================================================
@@ -74,6 +77,17 @@ pub const MIN_HANDSHAKE_INIT_SIZE: usize = 100;
 /// Minimum handshake response size.
 pub const MIN_HANDSHAKE_RESP_SIZE: usize = 56;
 
+/// Noise_IK init bytes outside the payload (e, encrypted s, payload tag).
+pub const HANDSHAKE_INIT_OVERHEAD: usize = 32 + 32 + 16 + 16;
+
+/// Maximum Noise handshake message size a responder accepts.
+///
+/// A Noise_IK init is [`HANDSHAKE_INIT_OVERHEAD`] bytes of keys and tags
+/// plus the encrypted payload
+/// (and any early data), so this leaves room for extensions and early data
+/// while keeping the whole message in one mobile-sized datagram.
+pub const MAX_HANDSHAKE_MESSAGE_SIZE: usize = RECOMMENDED_MAX_PAYLOAD;
+
 /// Recommended max payload for mobile networks.
 pub const RECOMMENDED_MAX_PAYLOAD: usize = 1200;
 
@@ -124,9 +138,16 @@ pub const REJECT_AFTER_TIME: Duration = Duration::from_secs(180);
 /// Soft limit on messages before rekey.
 pub const REKEY_AFTER_MESSAGES: u64 = 1 << 60;
 
+/// Default message-count lookahead for proactive rekeying.
+pub const REKEY_MESSAGE_MARGIN: u64 = 1 << 32;
+
 /// Hard limit on messages - MUST terminate session.
 pub const REJECT_AFTER_MESSAGES: u64 = u64::MAX;
 
+/// Send counters below the hard limit that only REKEY and CLOSE frames may
+/// use, so a session whose counter ran out can still rekey or close.
+pub const CONTROL_FRAME_HEADROOM: u64 = 16;
+
 /// Maximum epoch value.
 pub const MAX_EPOCH: u32 = u32::MAX;
 
@@ -142,6 +163,9 @@ pub const HANDSHAKE_MAX_RETRIES: u32 = 5;
 /// Handshake backoff multiplier.
 pub const HANDSHAKE_BACKOFF: u32 = 2;
 
+/// How long a responder keeps handshake state before abandoning it.
+pub const HANDSHAKE_DEADLINE: Duration = Duration::from_secs(5);
+
 // =============================================================================
 // ANTI-REPLAY (1-SECURITY.md)
 // =============================================================================
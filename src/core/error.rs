@@ -21,6 +21,10 @@ pub enum ApplyError {
     /// State corruption detected.
     #[error("state corruption detected")]
     StateCorruption,
+
+    /// The state type does not support this operation.
+    #[error("operation not supported by this state type")]
+    NotSupported,
 }
 
 /// Errors that can occur when decoding a diff.
@@ -46,6 +50,19 @@ pub enum CryptoError {
     #[error("handshake failed: {0}")]
     HandshakeFailed(String),
 
+    /// Handshake message exceeds `MAX_HANDSHAKE_MESSAGE_SIZE`.
+    #[error("handshake message too large: {size} bytes (max {max})")]
+    HandshakeMessageTooLarge {
+        /// Size of the rejected message.
+        size: usize,
+        /// Maximum accepted size.
+        max: usize,
+    },
+
+    /// Handshake did not complete before its deadline.
+    #[error("handshake timed out")]
+    HandshakeTimeout,
+
     /// AEAD encryption failed.
     #[error("AEAD encryption failed")]
     EncryptionFailed,
@@ -62,13 +79,34 @@ pub enum CryptoError {
     #[error("epoch exhausted - session must terminate")]
     EpochExhaustion,
 
+    /// Keys passed `REJECT_AFTER_TIME` - session must rekey or terminate.
+    #[error("session keys expired - session must rekey or terminate")]
+    SessionExpired,
+
     /// Replay detected.
     #[error("replay detected")]
     ReplayDetected,
 
+    /// Staged rekey keys are not for the epoch after the current one.
+    #[error("stale rekey: staged for epoch {staged}, expected {expected}")]
+    StaleRekey {
+        /// Epoch the staged keys were derived for.
+        staged: u32,
+        /// Epoch the session would advance to.
+        expected: u32,
+    },
+
     /// Key derivation failed.
     #[error("key derivation failed")]
     KeyDerivationFailed,
+
+    /// Persisted replay window state is malformed.
+    #[error("invalid replay window state: {0}")]
+    InvalidReplayState(String),
+
+    /// Session resumption ticket is malformed.
+    #[error("invalid resumption ticket: {0}")]
+    InvalidResumptionTicket(String),
 }
 
 /// Errors in the sync layer.
@@ -83,17 +121,53 @@ pub enum SyncError {
     Decode(#[from] DecodeError),
 }
 
+/// Coarse classification of a [`NomadError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// Handshake, AEAD, replay or key lifecycle failure.
+    Crypto,
+    /// Framing, socket or connection failure.
+    Transport,
+    /// State diff encoding or application failure.
+    Sync,
+    /// Version or extension negotiation failure.
+    Protocol,
+    /// Invalid local configuration.
+    Config,
+}
+
 /// Top-level NOMAD errors.
+///
+/// Every subsystem error converts into this with `?`. Variants for optional
+/// layers only exist when their feature is enabled, so core never requires
+/// them, and the enum is `#[non_exhaustive]` so enabling a feature does not
+/// break downstream matches.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum NomadError {
     /// Sync error.
     #[error("sync error: {0}")]
     Sync(#[from] SyncError),
 
+    /// Sync engine error.
+    #[cfg(feature = "sync")]
+    #[error("sync engine error: {0}")]
+    Engine(#[from] crate::sync::SyncError),
+
     /// Crypto error.
     #[error("crypto error: {0}")]
     Crypto(#[from] CryptoError),
 
+    /// Transport error.
+    #[cfg(feature = "transport")]
+    #[error("transport error: {0}")]
+    Transport(#[from] crate::transport::TransportError),
+
+    /// Extension negotiation error.
+    #[cfg(feature = "extensions")]
+    #[error("extension error: {0}")]
+    Extension(#[from] crate::extensions::NegotiationError),
+
     /// Configuration error.
     #[error("configuration error: {0}")]
     Config(String),
@@ -101,4 +175,114 @@ pub enum NomadError {
     /// I/O error.
     #[error("i/o error: {0}")]
     Io(#[from] std::io::Error),
+
+    /// The server found no unused session ID within its attempt budget.
+    #[cfg(feature = "server")]
+    #[error("no unused session ID after {0} attempts")]
+    SessionIdExhausted(usize),
+
+    /// No protocol version in the peer's offered range is supported.
+    #[error("unsupported protocol version range 0x{min:04x}..=0x{max:04x}")]
+    UnsupportedVersion {
+        /// Oldest version the peer offered.
+        min: u16,
+        /// Newest version the peer offered.
+        max: u16,
+    },
+}
+
+impl NomadError {
+    /// Get the coarse kind of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            NomadError::Sync(_) => ErrorKind::Sync,
+            #[cfg(feature = "sync")]
+            NomadError::Engine(_) => ErrorKind::Sync,
+            NomadError::Crypto(_) => ErrorKind::Crypto,
+            #[cfg(feature = "transport")]
+            NomadError::Transport(_) => ErrorKind::Transport,
+            #[cfg(feature = "extensions")]
+            NomadError::Extension(_) => ErrorKind::Protocol,
+            NomadError::Config(_) => ErrorKind::Config,
+            NomadError::Io(_) => ErrorKind::Transport,
+            #[cfg(feature = "server")]
+            NomadError::SessionIdExhausted(_) => ErrorKind::Transport,
+            NomadError::UnsupportedVersion { .. } => ErrorKind::Protocol,
+        }
+    }
+}
+
+impl From<ApplyError> for NomadError {
+    fn from(err: ApplyError) -> Self {
+        NomadError::Sync(err.into())
+    }
+}
+
+impl From<DecodeError> for NomadError {
+    fn from(err: DecodeError) -> Self {
+        NomadError::Sync(err.into())
+    }
+}
+
+#[cfg(feature = "transport")]
+impl From<crate::transport::FrameError> for NomadError {
+    fn from(err: crate::transport::FrameError) -> Self {
+        NomadError::Transport(err.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "transport")]
+    #[test]
+    fn test_frame_error_propagates_as_transport() {
+        use crate::transport::{parse_frame_header, TransportError};
+
+        fn parse(data: &[u8]) -> Result<(), NomadError> {
+            parse_frame_header(data)?;
+            Ok(())
+        }
+
+        let err = parse(&[0x03]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Transport);
+        assert!(matches!(
+            err,
+            NomadError::Transport(TransportError::Frame(_))
+        ));
+    }
+
+    #[test]
+    fn test_kinds() {
+        assert_eq!(NomadError::from(CryptoError::ReplayDetected).kind(), ErrorKind::Crypto);
+        assert_eq!(NomadError::from(DecodeError::UnexpectedEof).kind(), ErrorKind::Sync);
+        assert_eq!(
+            NomadError::UnsupportedVersion { min: 9, max: 9 }.kind(),
+            ErrorKind::Protocol
+        );
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_engine_error_propagates_as_sync() {
+        fn generate() -> Result<(), NomadError> {
+            Err(crate::sync::SyncError::NotInitialized)?;
+            Ok(())
+        }
+
+        let err = generate().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Sync);
+        assert!(matches!(
+            err,
+            NomadError::Engine(crate::sync::SyncError::NotInitialized)
+        ));
+    }
+
+    #[cfg(feature = "extensions")]
+    #[test]
+    fn test_negotiation_error_is_protocol() {
+        let err = NomadError::from(crate::extensions::NegotiationError::InvalidData);
+        assert_eq!(err.kind(), ErrorKind::Protocol);
+    }
 }
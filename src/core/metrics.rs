@@ -0,0 +1,216 @@
+//! Aggregate protocol metrics.
+//!
+//! [`Metrics`] holds lock-free counters and gauges that components update
+//! as they run (share one through an `Arc`), and renders them in the
+//! Prometheus text exposition format for scraping.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Counters and gauges for one endpoint.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Sessions currently established.
+    sessions_active: AtomicU64,
+    /// Handshakes that completed.
+    handshakes_succeeded: AtomicU64,
+    /// Handshakes that were rejected or failed.
+    handshakes_failed: AtomicU64,
+    /// Sum of reported SRTT values, in microseconds.
+    srtt_sum_us: AtomicU64,
+    /// Number of reported SRTT values.
+    srtt_samples: AtomicU64,
+    /// Frames sent, including retransmissions.
+    frames_sent: AtomicU64,
+    /// Frames retransmitted.
+    retransmits: AtomicU64,
+    /// Bytes received.
+    bytes_received: AtomicU64,
+    /// Bytes sent.
+    bytes_sent: AtomicU64,
+}
+
+impl Metrics {
+    /// Create a registry with everything at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of established sessions.
+    pub fn set_sessions_active(&self, count: usize) {
+        self.sessions_active.store(count as u64, Ordering::Relaxed);
+    }
+
+    /// Count a completed handshake.
+    pub fn handshake_succeeded(&self) {
+        self.handshakes_succeeded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a failed handshake.
+    pub fn handshake_failed(&self) {
+        self.handshakes_failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the current smoothed RTT of a connection.
+    pub fn record_srtt(&self, srtt: Duration) {
+        let micros = u64::try_from(srtt.as_micros()).unwrap_or(u64::MAX);
+        self.srtt_sum_us.fetch_add(micros, Ordering::Relaxed);
+        self.srtt_samples.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a sent frame.
+    ///
+    /// Call this where the datagram goes out, so every frame is counted
+    /// exactly once, retransmissions included.
+    pub fn frame_sent(&self) {
+        self.frames_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a retransmitted frame.
+    ///
+    /// The send itself is counted separately by [`frame_sent`](Self::frame_sent).
+    pub fn frame_retransmitted(&self) {
+        self.retransmits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count received bytes.
+    pub fn bytes_received(&self, bytes: usize) {
+        self.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Count sent bytes.
+    pub fn bytes_sent(&self, bytes: usize) {
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Mean of the recorded SRTT values, if any.
+    pub fn mean_srtt(&self) -> Option<Duration> {
+        let samples = self.srtt_samples.load(Ordering::Relaxed);
+        (samples > 0).then(|| Duration::from_micros(self.srtt_sum_us.load(Ordering::Relaxed) / samples))
+    }
+
+    /// Fraction of sent frames that were retransmissions.
+    pub fn retransmit_rate(&self) -> f64 {
+        let sent = self.frames_sent.load(Ordering::Relaxed);
+        if sent == 0 {
+            0.0
+        } else {
+            self.retransmits.load(Ordering::Relaxed) as f64 / sent as f64
+        }
+    }
+
+    /// Render all metrics in the Prometheus text exposition format.
+    pub fn encode_prometheus(&self) -> String {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let mut out = String::new();
+        // Each sample is a name suffix (labels, or `_sum`/`_count`) and a value
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, String)]| {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            for (suffix, value) in samples {
+                let _ = writeln!(out, "{name}{suffix} {value}");
+            }
+        };
+
+        metric(
+            "nomad_sessions_active",
+            "gauge",
+            "Established sessions.",
+            &[("", load(&self.sessions_active).to_string())],
+        );
+        metric(
+            "nomad_handshakes_total",
+            "counter",
+            "Handshakes by result.",
+            &[
+                ("{result=\"success\"}", load(&self.handshakes_succeeded).to_string()),
+                ("{result=\"failure\"}", load(&self.handshakes_failed).to_string()),
+            ],
+        );
+        // Summary without quantiles: rate(_sum) / rate(_count) gives the
+        // mean over any window
+        metric(
+            "nomad_srtt_seconds",
+            "summary",
+            "Reported smoothed round-trip times.",
+            &[
+                ("_sum", Duration::from_micros(load(&self.srtt_sum_us)).as_secs_f64().to_string()),
+                ("_count", load(&self.srtt_samples).to_string()),
+            ],
+        );
+        metric(
+            "nomad_frames_sent_total",
+            "counter",
+            "Frames sent, including retransmissions.",
+            &[("", load(&self.frames_sent).to_string())],
+        );
+        metric(
+            "nomad_retransmits_total",
+            "counter",
+            "Frames retransmitted.",
+            &[("", load(&self.retransmits).to_string())],
+        );
+        metric(
+            "nomad_retransmit_ratio",
+            "gauge",
+            "Fraction of sent frames that were retransmissions.",
+            &[("", self.retransmit_rate().to_string())],
+        );
+        metric(
+            "nomad_bytes_received_total",
+            "counter",
+            "Bytes received.",
+            &[("", load(&self.bytes_received).to_string())],
+        );
+        metric(
+            "nomad_bytes_sent_total",
+            "counter",
+            "Bytes sent.",
+            &[("", load(&self.bytes_sent).to_string())],
+        );
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_prometheus() {
+        let metrics = Metrics::new();
+        metrics.set_sessions_active(2);
+        metrics.handshake_succeeded();
+        metrics.handshake_failed();
+        metrics.record_srtt(Duration::from_millis(40));
+        metrics.record_srtt(Duration::from_millis(60));
+        for _ in 0..4 {
+            metrics.frame_sent();
+        }
+        metrics.frame_retransmitted();
+        metrics.bytes_received(100);
+        metrics.bytes_sent(250);
+
+        assert_eq!(metrics.mean_srtt(), Some(Duration::from_millis(50)));
+        assert_eq!(metrics.retransmit_rate(), 0.25);
+
+        let text = metrics.encode_prometheus();
+        for line in [
+            "# TYPE nomad_sessions_active gauge",
+            "nomad_sessions_active 2",
+            "nomad_handshakes_total{result=\"success\"} 1",
+            "nomad_handshakes_total{result=\"failure\"} 1",
+            "# TYPE nomad_srtt_seconds summary",
+            "nomad_srtt_seconds_sum 0.1",
+            "nomad_srtt_seconds_count 2",
+            "nomad_frames_sent_total 4",
+            "nomad_retransmits_total 1",
+            "nomad_retransmit_ratio 0.25",
+            "nomad_bytes_received_total 100",
+            "nomad_bytes_sent_total 250",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {line:?} in:\n{text}");
+        }
+    }
+}
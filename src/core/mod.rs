@@ -3,10 +3,18 @@
 //! This module provides the foundational traits and types for the NOMAD protocol.
 //! It has minimal dependencies and defines the core abstractions.
 
+mod clock;
 mod constants;
 mod error;
+#[cfg(feature = "metrics")]
+mod metrics;
 mod traits;
+mod version;
 
+pub use clock::*;
 pub use constants::*;
 pub use error::*;
+#[cfg(feature = "metrics")]
+pub use metrics::*;
 pub use traits::*;
+pub use version::*;
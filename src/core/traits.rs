@@ -66,6 +66,17 @@ pub trait SyncState: Clone + Send + Sync + 'static {
     /// MUST handle repeated application (idempotent).
     fn apply_diff(&mut self, diff: &Self::Diff) -> Result<(), ApplyError>;
 
+    /// Check the state's invariants after a peer update is applied.
+    ///
+    /// A diff that decodes and applies cleanly can still leave the state in
+    /// a configuration the application never produces (e.g. a negative
+    /// balance). The sync engine calls this after each update (see
+    /// `SyncEngine::with_validate`) and restores the previous state if it
+    /// fails. The default accepts every state.
+    fn validate(&self) -> Result<(), ApplyError> {
+        Ok(())
+    }
+
     /// Serialize diff for wire transmission.
     fn encode_diff(diff: &Self::Diff) -> Vec<u8>;
 
@@ -79,6 +90,88 @@ pub trait SyncState: Clone + Send + Sync + 'static {
         let _ = diff;
         false
     }
+
+    /// Drop no-op components from a diff before it is encoded.
+    ///
+    /// Useful when `diff_from` is naive (e.g. lists every field of a struct
+    /// whether or not it changed). The default is the identity.
+    ///
+    /// Compaction runs before `is_diff_empty`, so a diff whose components
+    /// are all no-ops should compact to one that `is_diff_empty` reports as
+    /// empty; it is then sent without a diff payload.
+    fn compact_diff(diff: &Self::Diff) -> Self::Diff {
+        diff.clone()
+    }
+
+    /// Merge a concurrently modified copy of the state into this one.
+    ///
+    /// The sync engine calls this instead of applying a diff blindly when
+    /// the peer computed the diff without having seen our latest local
+    /// changes (see `SyncEngine::with_merge`). State types that are CRDTs
+    /// opt in by overriding it; the default returns `NotSupported`.
+    ///
+    /// Implementations MUST be commutative, associative and idempotent, so
+    /// both peers converge regardless of the order merges happen in.
+    fn merge(&mut self, other: &Self) -> Result<(), ApplyError> {
+        let _ = other;
+        Err(ApplyError::NotSupported)
+    }
+
+    /// Serialize the whole state as an absolute snapshot.
+    ///
+    /// When a diff would be larger than the state itself (e.g. after a
+    /// near-total change), the sync engine sends this instead (see
+    /// `SyncEngine::with_snapshots`). Returns `None` if the state type
+    /// does not support snapshots, which is the default.
+    fn encode_snapshot(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Deserialize a snapshot produced by `encode_snapshot`.
+    fn decode_snapshot(data: &[u8]) -> Result<Self, DecodeError> {
+        let _ = data;
+        Err(DecodeError::InvalidEncoding(
+            "snapshots not supported".to_string(),
+        ))
+    }
+
+    /// Serialize the whole state for a checkpoint.
+    ///
+    /// Unlike `encode_snapshot`, which the sync engine only uses when it
+    /// beats a diff, this is the state's canonical full encoding, used to
+    /// bootstrap a peer that has no state yet. The default reuses
+    /// `encode_snapshot`; states that implement `Default` can delegate to
+    /// [`encode_diff_from_default`] instead. Returns `None` if unsupported.
+    fn encode_full(&self) -> Option<Vec<u8>> {
+        self.encode_snapshot()
+    }
+
+    /// Deserialize a state produced by `encode_full`.
+    ///
+    /// The default reuses `decode_snapshot`; pair [`decode_diff_onto_default`]
+    /// with [`encode_diff_from_default`].
+    fn decode_full(data: &[u8]) -> Result<Self, DecodeError> {
+        Self::decode_snapshot(data)
+    }
+}
+
+/// Full encoding of a state as its diff from `S::default()`.
+///
+/// Building block for `SyncState::encode_full`.
+pub fn encode_diff_from_default<S: SyncState + Default>(state: &S) -> Vec<u8> {
+    S::encode_diff(&state.diff_from(&S::default()))
+}
+
+/// Rebuild a state from [`encode_diff_from_default`] output.
+///
+/// Building block for `SyncState::decode_full`.
+pub fn decode_diff_onto_default<S: SyncState + Default>(data: &[u8]) -> Result<S, DecodeError> {
+    let diff = S::decode_diff(data)?;
+    let mut state = S::default();
+    state
+        .apply_diff(&diff)
+        .map_err(|e| DecodeError::InvalidEncoding(e.to_string()))?;
+    Ok(state)
 }
 
 /// Optional trait for states that support client-side prediction.
@@ -93,4 +186,14 @@ pub trait Predictable: SyncState {
 
     /// Reconcile with authoritative server state.
     fn reconcile(&mut self, authoritative: &Self);
+
+    /// Discard speculative changes, returning to a known state.
+    ///
+    /// The sync engine calls this (see `SyncEngine::with_rollback`) before
+    /// applying an authoritative update, then replays the predictions the
+    /// peer has not yet confirmed. The default clones `to`; override it if
+    /// the state can undo its predictions more cheaply.
+    fn rollback(&mut self, to: &Self) {
+        self.clone_from(to);
+    }
 }
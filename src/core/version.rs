@@ -0,0 +1,173 @@
+//! Protocol version negotiation.
+//!
+//! The initiator offers the range of versions it supports in the handshake
+//! payload; the responder picks the highest version both sides support, or
+//! rejects the handshake if the ranges don't overlap.
+//!
+//! Wire format of an offered range:
+//! ```text
+//! +0  Minimum version (2 bytes LE16)
+//! +2  Maximum version (2 bytes LE16)
+//! ```
+
+use std::fmt;
+
+use super::{DecodeError, NomadError, PROTOCOL_VERSION};
+
+/// Encoded size of a [`VersionRange`] in bytes.
+pub const VERSION_RANGE_SIZE: usize = 4;
+
+/// A NOMAD protocol version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProtocolVersion(u16);
+
+impl ProtocolVersion {
+    /// Version 1.0.
+    pub const V1: Self = Self(0x0001);
+
+    /// Version this implementation prefers.
+    pub const CURRENT: Self = Self(PROTOCOL_VERSION);
+
+    /// Oldest version this implementation speaks.
+    pub const MIN_SUPPORTED: Self = Self::V1;
+
+    /// Newest version this implementation speaks.
+    pub const MAX_SUPPORTED: Self = Self::CURRENT;
+
+    /// Create a version from its wire value.
+    pub const fn new(version: u16) -> Self {
+        Self(version)
+    }
+
+    /// Get the wire value.
+    pub const fn as_u16(self) -> u16 {
+        self.0
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:04x}", self.0)
+    }
+}
+
+/// An inclusive range of protocol versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionRange {
+    /// Oldest supported version.
+    pub min: ProtocolVersion,
+    /// Newest supported version.
+    pub max: ProtocolVersion,
+}
+
+impl VersionRange {
+    /// The range this implementation supports.
+    pub const SUPPORTED: Self = Self {
+        min: ProtocolVersion::MIN_SUPPORTED,
+        max: ProtocolVersion::MAX_SUPPORTED,
+    };
+
+    /// Create a range; the bounds are swapped if given out of order.
+    pub fn new(min: ProtocolVersion, max: ProtocolVersion) -> Self {
+        Self {
+            min: min.min(max),
+            max: min.max(max),
+        }
+    }
+
+    /// Check if `version` falls within the range.
+    pub fn contains(&self, version: ProtocolVersion) -> bool {
+        self.min <= version && version <= self.max
+    }
+
+    /// Pick the highest version in both this (local) range and `offered`.
+    ///
+    /// # Errors
+    /// Returns `NomadError::UnsupportedVersion` if the ranges don't overlap.
+    pub fn negotiate(&self, offered: VersionRange) -> Result<ProtocolVersion, NomadError> {
+        let chosen = self.max.min(offered.max);
+        if chosen < self.min || chosen < offered.min {
+            return Err(NomadError::UnsupportedVersion {
+                min: offered.min.as_u16(),
+                max: offered.max.as_u16(),
+            });
+        }
+        Ok(chosen)
+    }
+
+    /// Serialize to bytes.
+    pub fn encode(&self) -> [u8; VERSION_RANGE_SIZE] {
+        let mut buf = [0u8; VERSION_RANGE_SIZE];
+        buf[0..2].copy_from_slice(&self.min.as_u16().to_le_bytes());
+        buf[2..4].copy_from_slice(&self.max.as_u16().to_le_bytes());
+        buf
+    }
+
+    /// Parse from the start of `data`.
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        if data.len() < VERSION_RANGE_SIZE {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        let min = u16::from_le_bytes([data[0], data[1]]);
+        let max = u16::from_le_bytes([data[2], data[3]]);
+        if min > max {
+            return Err(DecodeError::InvalidEncoding(format!(
+                "version range {:#06x}..{:#06x} is empty",
+                min, max
+            )));
+        }
+        Ok(Self::new(ProtocolVersion(min), ProtocolVersion(max)))
+    }
+}
+
+impl Default for VersionRange {
+    fn default() -> Self {
+        Self::SUPPORTED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(min: u16, max: u16) -> VersionRange {
+        VersionRange::new(ProtocolVersion::new(min), ProtocolVersion::new(max))
+    }
+
+    #[test]
+    fn test_overlap_picks_highest_common() {
+        let server = range(1, 3);
+        assert_eq!(server.negotiate(range(2, 5)).unwrap(), ProtocolVersion::new(3));
+        assert_eq!(server.negotiate(range(1, 2)).unwrap(), ProtocolVersion::new(2));
+        assert_eq!(
+            VersionRange::SUPPORTED.negotiate(VersionRange::SUPPORTED).unwrap(),
+            ProtocolVersion::CURRENT
+        );
+    }
+
+    #[test]
+    fn test_client_too_new_rejected() {
+        let server = range(1, 2);
+        assert!(matches!(
+            server.negotiate(range(3, 4)),
+            Err(NomadError::UnsupportedVersion { min: 3, max: 4 })
+        ));
+    }
+
+    #[test]
+    fn test_client_too_old_rejected() {
+        let server = range(2, 3);
+        assert!(matches!(
+            server.negotiate(range(1, 1)),
+            Err(NomadError::UnsupportedVersion { min: 1, max: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_range_roundtrip() {
+        let offered = range(1, 7);
+        assert_eq!(VersionRange::decode(&offered.encode()).unwrap(), offered);
+        assert!(VersionRange::decode(&[1, 0, 0]).is_err());
+        assert!(VersionRange::decode(&[2, 0, 1, 0]).is_err());
+    }
+}
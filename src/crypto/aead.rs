@@ -1,12 +1,15 @@
 //! XChaCha20-Poly1305 AEAD encryption
 //!
 //! Per 1-SECURITY.md, all post-handshake frames use XChaCha20-Poly1305.
+//! [`AeadCipher`] abstracts the frame cipher so deployments with AES
+//! hardware can opt into AES-256-GCM ([`Aes256GcmCipher`]) instead.
 //! The AAD (Additional Authenticated Data) structure is exactly 16 bytes:
 //! - Frame type (1 byte)
 //! - Flags (1 byte)
 //! - Session ID (6 bytes)
 //! - Nonce counter (8 bytes, LE64)
 
+use aes_gcm::Aes256Gcm;
 use chacha20poly1305::{
     aead::{Aead, KeyInit},
     XChaCha20Poly1305, XNonce,
@@ -161,6 +164,155 @@ pub fn decrypt_in_place(
         .map_err(|_| CryptoError::DecryptionFailed)
 }
 
+/// Size of the AES-256-GCM nonce
+pub const GCM_NONCE_SIZE: usize = 12;
+
+/// An AEAD cipher suite for post-handshake frames.
+///
+/// Callers always build the 24-byte NOMAD nonce (see
+/// [`construct_nonce`](super::construct_nonce)); each suite adapts it to its
+/// own nonce size. The suite is a type parameter of
+/// [`CryptoSession`](super::CryptoSession), and its [`SUITE`](Self::SUITE)
+/// must also be passed to the handshake so both peers agree on it.
+pub trait AeadCipher {
+    /// Suite identifier, bound into the handshake transcript
+    const SUITE: CipherSuite;
+
+    /// Encrypt plaintext, returning ciphertext with the 16-byte tag appended
+    fn encrypt(
+        key: &SessionKey,
+        nonce: &[u8; AEAD_NONCE_SIZE],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, CryptoError>;
+
+    /// Decrypt ciphertext with its appended 16-byte tag
+    fn decrypt(
+        key: &SessionKey,
+        nonce: &[u8; AEAD_NONCE_SIZE],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, CryptoError>;
+}
+
+/// Frame cipher suites.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CipherSuite {
+    /// XChaCha20-Poly1305 (default)
+    XChaCha20Poly1305,
+    /// AES-256-GCM
+    Aes256Gcm,
+}
+
+impl CipherSuite {
+    /// Get the suite name
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::XChaCha20Poly1305 => "XChaCha20-Poly1305",
+            Self::Aes256Gcm => "AES-256-GCM",
+        }
+    }
+
+    /// Noise prologue binding the suite into the handshake transcript.
+    ///
+    /// The default suite uses an empty prologue, so its handshakes are
+    /// unchanged. Any other suite commits to its name, and a handshake
+    /// between peers configured for different suites fails instead of
+    /// silently settling on either one.
+    pub fn prologue(self) -> &'static [u8] {
+        match self {
+            Self::XChaCha20Poly1305 => b"",
+            Self::Aes256Gcm => b"nomad v1 suite AES-256-GCM",
+        }
+    }
+}
+
+/// XChaCha20-Poly1305, the default frame cipher.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct XChaCha20Poly1305Cipher;
+
+impl AeadCipher for XChaCha20Poly1305Cipher {
+    const SUITE: CipherSuite = CipherSuite::XChaCha20Poly1305;
+
+    fn encrypt(
+        key: &SessionKey,
+        nonce: &[u8; AEAD_NONCE_SIZE],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        encrypt(key, nonce, aad, plaintext)
+    }
+
+    fn decrypt(
+        key: &SessionKey,
+        nonce: &[u8; AEAD_NONCE_SIZE],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        decrypt(key, nonce, aad, ciphertext)
+    }
+}
+
+/// AES-256-GCM frame cipher.
+///
+/// GCM takes a 12-byte nonce, built from the 24-byte NOMAD nonce as
+/// `[epoch (4) | counter (8)]`. The direction byte is dropped: each
+/// direction already has its own key, so (key, epoch, counter) stays unique.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Aes256GcmCipher;
+
+impl Aes256GcmCipher {
+    /// Derive the 12-byte GCM nonce from the 24-byte NOMAD nonce
+    pub fn gcm_nonce(nonce: &[u8; AEAD_NONCE_SIZE]) -> [u8; GCM_NONCE_SIZE] {
+        let mut gcm = [0u8; GCM_NONCE_SIZE];
+        gcm[..4].copy_from_slice(&nonce[..4]);
+        gcm[4..].copy_from_slice(&nonce[AEAD_NONCE_SIZE - 8..]);
+        gcm
+    }
+}
+
+impl AeadCipher for Aes256GcmCipher {
+    const SUITE: CipherSuite = CipherSuite::Aes256Gcm;
+
+    fn encrypt(
+        key: &SessionKey,
+        nonce: &[u8; AEAD_NONCE_SIZE],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        let cipher = Aes256Gcm::new(key.as_bytes().into());
+        let gcm_nonce = Self::gcm_nonce(nonce);
+
+        cipher
+            .encrypt(
+                aes_gcm::Nonce::from_slice(&gcm_nonce),
+                aes_gcm::aead::Payload { msg: plaintext, aad },
+            )
+            .map_err(|_| CryptoError::EncryptionFailed)
+    }
+
+    fn decrypt(
+        key: &SessionKey,
+        nonce: &[u8; AEAD_NONCE_SIZE],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        if ciphertext.len() < AEAD_TAG_SIZE {
+            return Err(CryptoError::DecryptionFailed);
+        }
+
+        let cipher = Aes256Gcm::new(key.as_bytes().into());
+        let gcm_nonce = Self::gcm_nonce(nonce);
+
+        cipher
+            .decrypt(
+                aes_gcm::Nonce::from_slice(&gcm_nonce),
+                aes_gcm::aead::Payload { msg: ciphertext, aad },
+            )
+            .map_err(|_| CryptoError::DecryptionFailed)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -261,4 +413,36 @@ mod tests {
         let decrypted = decrypt(&key, &nonce, &aad, &ciphertext).unwrap();
         assert_eq!(decrypted, plaintext);
     }
+
+    #[test]
+    fn test_aes_gcm_roundtrip() {
+        let key = SessionKey::from_bytes([0x42; SESSION_KEY_SIZE]);
+        let nonce = [0x01; AEAD_NONCE_SIZE];
+        let aad = [0x02; AAD_SIZE];
+        let plaintext = b"Hello, NOMAD!";
+
+        let ciphertext = Aes256GcmCipher::encrypt(&key, &nonce, &aad, plaintext).unwrap();
+        assert_eq!(ciphertext.len(), plaintext.len() + AEAD_TAG_SIZE);
+        assert_eq!(
+            Aes256GcmCipher::decrypt(&key, &nonce, &aad, &ciphertext).unwrap(),
+            plaintext
+        );
+
+        // Ciphertext from one suite does not open under the other
+        assert!(XChaCha20Poly1305Cipher::decrypt(&key, &nonce, &aad, &ciphertext).is_err());
+        let xchacha = XChaCha20Poly1305Cipher::encrypt(&key, &nonce, &aad, plaintext).unwrap();
+        assert!(Aes256GcmCipher::decrypt(&key, &nonce, &aad, &xchacha).is_err());
+    }
+
+    #[test]
+    fn test_gcm_nonce_keeps_epoch_and_counter() {
+        let mut nonce = [0u8; AEAD_NONCE_SIZE];
+        nonce[..4].copy_from_slice(&7u32.to_le_bytes());
+        nonce[4] = 0x01;
+        nonce[16..].copy_from_slice(&42u64.to_le_bytes());
+
+        let gcm = Aes256GcmCipher::gcm_nonce(&nonce);
+        assert_eq!(&gcm[..4], &7u32.to_le_bytes());
+        assert_eq!(&gcm[4..], &42u64.to_le_bytes());
+    }
 }
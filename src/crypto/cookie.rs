@@ -0,0 +1,196 @@
+//! Stateless retry cookies (anti-DoS)
+//!
+//! A responder that creates handshake state for the first packet from any
+//! address can be exhausted by spoofed inits, and its larger response
+//! amplifies traffic toward the spoofed address. With retry enabled, the
+//! server answers an init that carries no cookie with a small `Retry` frame
+//! holding a cookie, and keeps no state. The client resends its init with
+//! the cookie echoed (`HandshakeFlags::COOKIE`); only an init whose cookie
+//! validates for its source address reaches `ResponderHandshake`.
+//!
+//! Cookie format:
+//! ```text
+//! +0  Issued At (8 bytes LE64, seconds since the Unix epoch)
+//! +8  MAC (16 bytes, keyed BLAKE2s over issued-at and client address)
+//! ```
+//!
+//! Only the server reads cookies, so the format is private to it; the
+//! secret should be rotated (by creating a new generator) periodically.
+
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use blake2::Blake2sMac;
+use blake2::digest::consts::U16;
+use blake2::digest::{FixedOutput, KeyInit, Mac};
+use rand::{rngs::OsRng, RngCore};
+use zeroize::Zeroize;
+
+/// Retry cookie size (issued-at + MAC)
+pub const RETRY_COOKIE_SIZE: usize = 8 + COOKIE_MAC_SIZE;
+
+// The transport layer sizes RETRY frames without depending on crypto
+#[cfg(feature = "transport")]
+const _: () = assert!(RETRY_COOKIE_SIZE == crate::transport::sizes::RETRY_COOKIE_SIZE);
+
+/// Default time a cookie stays valid
+pub const DEFAULT_COOKIE_LIFETIME: Duration = Duration::from_secs(30);
+
+/// Size of the truncated cookie MAC
+const COOKIE_MAC_SIZE: usize = 16;
+
+/// Size of the server's cookie secret
+const COOKIE_SECRET_SIZE: usize = 32;
+
+/// Issues and validates retry cookies bound to a client address.
+pub struct CookieGenerator {
+    /// Server secret keying the MAC
+    secret: [u8; COOKIE_SECRET_SIZE],
+    /// How long an issued cookie is accepted
+    lifetime: Duration,
+}
+
+impl CookieGenerator {
+    /// Create a generator with a fresh random secret
+    pub fn new(lifetime: Duration) -> Self {
+        let mut secret = [0u8; COOKIE_SECRET_SIZE];
+        OsRng.fill_bytes(&mut secret);
+        Self::with_secret(secret, lifetime)
+    }
+
+    /// Create a generator with a known secret (e.g. shared between servers
+    /// behind one address)
+    pub fn with_secret(secret: [u8; COOKIE_SECRET_SIZE], lifetime: Duration) -> Self {
+        Self { secret, lifetime }
+    }
+
+    /// Get the cookie lifetime
+    pub fn lifetime(&self) -> Duration {
+        self.lifetime
+    }
+
+    /// Issue a cookie for `addr`
+    pub fn issue(&self, addr: SocketAddr) -> [u8; RETRY_COOKIE_SIZE] {
+        self.issue_at(addr, SystemTime::now())
+    }
+
+    /// Issue a cookie for `addr` as of `now`
+    pub fn issue_at(&self, addr: SocketAddr, now: SystemTime) -> [u8; RETRY_COOKIE_SIZE] {
+        let issued_at = unix_secs(now).to_le_bytes();
+        let mut cookie = [0u8; RETRY_COOKIE_SIZE];
+        cookie[..8].copy_from_slice(&issued_at);
+        cookie[8..].copy_from_slice(&self.mac(&issued_at, addr).finalize_fixed());
+        cookie
+    }
+
+    /// Check a cookie echoed by `addr`
+    pub fn validate(&self, cookie: &[u8], addr: SocketAddr) -> bool {
+        self.validate_at(cookie, addr, SystemTime::now())
+    }
+
+    /// Check a cookie echoed by `addr` as of `now`
+    ///
+    /// Fails for a malformed cookie, a MAC that does not match `addr`, or a
+    /// cookie older than the lifetime (or issued in the future).
+    pub fn validate_at(&self, cookie: &[u8], addr: SocketAddr, now: SystemTime) -> bool {
+        if cookie.len() != RETRY_COOKIE_SIZE {
+            return false;
+        }
+        let issued_at: [u8; 8] = cookie[..8].try_into().expect("length checked above");
+        if self.mac(&issued_at, addr).verify_slice(&cookie[8..]).is_err() {
+            return false;
+        }
+
+        let age = unix_secs(now).checked_sub(u64::from_le_bytes(issued_at));
+        age.is_some_and(|age| age <= self.lifetime.as_secs())
+    }
+
+    /// MAC state over the issued-at time and client address
+    fn mac(&self, issued_at: &[u8; 8], addr: SocketAddr) -> Blake2sMac<U16> {
+        let mut mac = <Blake2sMac<U16> as KeyInit>::new_from_slice(&self.secret)
+            .expect("cookie secret is a valid BLAKE2s key");
+        Mac::update(&mut mac, issued_at);
+        match addr {
+            SocketAddr::V4(v4) => {
+                Mac::update(&mut mac, &[4]);
+                Mac::update(&mut mac, &v4.ip().octets());
+            }
+            SocketAddr::V6(v6) => {
+                Mac::update(&mut mac, &[6]);
+                Mac::update(&mut mac, &v6.ip().octets());
+            }
+        }
+        Mac::update(&mut mac, &addr.port().to_le_bytes());
+        mac
+    }
+}
+
+impl Drop for CookieGenerator {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+impl std::fmt::Debug for CookieGenerator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CookieGenerator")
+            .field("lifetime", &self.lifetime)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Seconds since the Unix epoch (0 for times before it)
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_cookie_validates_for_originating_address() {
+        let generator = CookieGenerator::new(DEFAULT_COOKIE_LIFETIME);
+        let client = addr("203.0.113.7:40000");
+        let cookie = generator.issue(client);
+        assert!(generator.validate(&cookie, client));
+
+        let v6 = addr("[2001:db8::1]:40000");
+        assert!(generator.validate(&generator.issue(v6), v6));
+    }
+
+    #[test]
+    fn test_cookie_fails_for_other_address() {
+        let generator = CookieGenerator::new(DEFAULT_COOKIE_LIFETIME);
+        let cookie = generator.issue(addr("203.0.113.7:40000"));
+        assert!(!generator.validate(&cookie, addr("203.0.113.8:40000")));
+        assert!(!generator.validate(&cookie, addr("203.0.113.7:40001")));
+
+        // Another server secret, or a tampered cookie, fails too
+        let other = CookieGenerator::new(DEFAULT_COOKIE_LIFETIME);
+        assert!(!other.validate(&cookie, addr("203.0.113.7:40000")));
+        let mut tampered = cookie;
+        tampered[0] ^= 1;
+        assert!(!generator.validate(&tampered, addr("203.0.113.7:40000")));
+        assert!(!generator.validate(&cookie[..10], addr("203.0.113.7:40000")));
+    }
+
+    #[test]
+    fn test_cookie_expires_after_lifetime() {
+        let generator = CookieGenerator::with_secret([7; 32], Duration::from_secs(10));
+        let client = addr("198.51.100.1:5000");
+        let issued = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let cookie = generator.issue_at(client, issued);
+
+        assert!(generator.validate_at(&cookie, client, issued));
+        assert!(generator.validate_at(&cookie, client, issued + Duration::from_secs(10)));
+        assert!(!generator.validate_at(&cookie, client, issued + Duration::from_secs(11)));
+        // Issued in the future
+        assert!(!generator.validate_at(&cookie, client, issued - Duration::from_secs(1)));
+    }
+}
@@ -2,12 +2,14 @@
 //!
 //! Implements the cryptographic primitives for NOMAD:
 //! - Noise_IK handshake
-//! - XChaCha20-Poly1305 AEAD
+//! - XChaCha20-Poly1305 AEAD (AES-256-GCM as an alternative suite)
 //! - Nonce construction
 //! - Anti-replay protection
 //! - Rekeying
+//! - Stateless retry cookies for handshake anti-DoS
 
 mod aead;
+mod cookie;
 mod keys;
 mod noise;
 mod nonce;
@@ -15,6 +17,7 @@ mod rekey;
 mod session;
 
 pub use aead::*;
+pub use cookie::*;
 pub use keys::*;
 pub use noise::*;
 pub use nonce::*;
@@ -11,18 +11,85 @@
 //! ```
 //!
 //! After handshake, both parties derive session keys using HKDF.
+//!
+//! # Anonymous profile
+//!
+//! [`HandshakeProfile::Anonymous`] swaps in Noise_NK for clients that have no
+//! identity (e.g. public read-only feeds):
+//!
+//! ```text
+//! Noise_NK(rs):
+//!   <- s                    # Responder's static key known to Initiator
+//!   ...
+//!   -> e, es                # Initiator sends ephemeral only
+//!   <- e, ee                # Responder sends ephemeral, completes DH
+//! ```
+//!
+//! This is a distinct security profile, not a weaker setting of the same
+//! one. The server is still authenticated and every session gets forward
+//! secrecy from `ee`, but there is no `ss`/`se` DH with a client static key:
+//! - The server learns nothing about who the client is, and cannot tell a
+//!   reconnecting client apart from any other.
+//! - Post-compromise security does not extend to client identity. In
+//!   Noise_IK, an attacker who stole one session's keys must also steal the
+//!   client's static key to open a new session as that client; in Noise_NK
+//!   there is nothing to steal, so any per-client state (authorization,
+//!   rate limits, resumption) must not be keyed on an anonymous session.
+//! - Rekeying is unaffected: it derives from the handshake hash in both
+//!   profiles.
+//!
+//! The pattern is part of the handshake hash, so an initiator and responder
+//! using different profiles fail the handshake rather than silently
+//! downgrading.
+//!
+//! # Early data (0-RTT)
+//!
+//! The first handshake message already carries an encrypted payload, so a
+//! client can append application data to it
+//! ([`InitiatorHandshake::write_message_with_early_data`]) and the server
+//! can act on it before replying. The init payload is then framed as:
+//!
+//! ```text
+//! +0  Payload Length (2 bytes LE16)
+//! +2  Payload (state type ID, extensions, ...)
+//! +n  Early Data (rest of the message)
+//! ```
+//!
+//! and the frame sets `HandshakeFlags::EARLY_DATA` so the server knows to
+//! unframe it. Servers opt in with [`ResponderHandshake::with_early_data`];
+//! otherwise early data is dropped and only the payload is used.
+//!
+//! Early data is weaker than post-handshake data:
+//! - **It can be replayed.** It is encrypted only under `es`/`ss`, with no
+//!   server contribution, so an attacker can resend a captured init and the
+//!   server will decrypt it again. Only accept early data that is
+//!   idempotent or otherwise safe to apply twice, or deduplicate it at the
+//!   application layer.
+//! - It has no forward secrecy against compromise of the server's static
+//!   key.
+//! - The client cannot tell whether it was accepted until the server
+//!   replies; if the response does not set `EARLY_DATA`, resend the data
+//!   after the handshake.
+//! - It shares the init's `MAX_HANDSHAKE_MESSAGE_SIZE` budget with the
+//!   payload; a responder rejects anything larger.
 
 use std::sync::LazyLock;
+use std::time::{Duration, Instant};
 
-use crate::core::{CryptoError, HASH_SIZE, PUBLIC_KEY_SIZE};
+use crate::core::{
+    CryptoError, HANDSHAKE_DEADLINE, HASH_SIZE, MAX_HANDSHAKE_MESSAGE_SIZE, PUBLIC_KEY_SIZE,
+};
 use snow::{params::NoiseParams, Builder, HandshakeState};
 use zeroize::Zeroize;
 
-use super::{SessionKey, StaticKeypair, SESSION_KEY_SIZE};
+use super::{CipherSuite, SessionKey, StaticKeypair, SESSION_KEY_SIZE};
 
 /// Noise protocol pattern for NOMAD
 const NOISE_PATTERN: &str = "Noise_IK_25519_ChaChaPoly_BLAKE2s";
 
+/// Noise protocol pattern for anonymous clients
+const NOISE_PATTERN_ANONYMOUS: &str = "Noise_NK_25519_ChaChaPoly_BLAKE2s";
+
 /// Lazily-parsed Noise parameters (validated once at first use)
 static NOISE_PARAMS: LazyLock<NoiseParams> = LazyLock::new(|| {
     NOISE_PATTERN
@@ -30,15 +97,85 @@ static NOISE_PARAMS: LazyLock<NoiseParams> = LazyLock::new(|| {
         .expect("NOISE_PATTERN is a valid Noise protocol pattern")
 });
 
+/// Lazily-parsed Noise parameters for the anonymous profile
+static NOISE_PARAMS_ANONYMOUS: LazyLock<NoiseParams> = LazyLock::new(|| {
+    NOISE_PATTERN_ANONYMOUS
+        .parse()
+        .expect("NOISE_PATTERN_ANONYMOUS is a valid Noise protocol pattern")
+});
+
+/// Handshake security profile.
+///
+/// See the [module documentation](self) for what the anonymous profile
+/// gives up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HandshakeProfile {
+    /// Noise_IK: mutual authentication with a client static key.
+    #[default]
+    Authenticated,
+    /// Noise_NK: server authenticated, client anonymous (ephemeral key only).
+    Anonymous,
+}
+
+impl HandshakeProfile {
+    /// Get the Noise protocol pattern string.
+    pub fn pattern(&self) -> &'static str {
+        match self {
+            HandshakeProfile::Authenticated => NOISE_PATTERN,
+            HandshakeProfile::Anonymous => NOISE_PATTERN_ANONYMOUS,
+        }
+    }
+
+    /// Whether the handshake authenticates the initiator's identity.
+    pub fn authenticates_initiator(&self) -> bool {
+        matches!(self, HandshakeProfile::Authenticated)
+    }
+
+    /// Get the parsed Noise parameters.
+    fn params(&self) -> NoiseParams {
+        match self {
+            HandshakeProfile::Authenticated => NOISE_PARAMS.clone(),
+            HandshakeProfile::Anonymous => NOISE_PARAMS_ANONYMOUS.clone(),
+        }
+    }
+}
+
+/// Size of the payload length prefix in an init carrying early data
+pub const EARLY_DATA_LENGTH_SIZE: usize = 2;
+
 /// Result of a completed handshake
 pub struct HandshakeResult {
     /// The handshake hash (used for key derivation)
     pub handshake_hash: [u8; HASH_SIZE],
+    /// Early data the responder accepted, if any
+    early_data: Option<Vec<u8>>,
+    /// Frame cipher suite both sides committed to
+    cipher_suite: CipherSuite,
+}
+
+impl HandshakeResult {
+    /// Get the frame cipher suite the handshake was bound to
+    ///
+    /// Build the session with the matching cipher, e.g. `CryptoSession`
+    /// over `Aes256GcmCipher` for [`CipherSuite::Aes256Gcm`].
+    pub fn cipher_suite(&self) -> CipherSuite {
+        self.cipher_suite
+    }
+
+    /// Get the accepted early data
+    ///
+    /// Only a responder that enabled early data sees it; see the
+    /// [module documentation](self) for the replay caveats.
+    pub fn early_data(&self) -> Option<&[u8]> {
+        self.early_data.as_deref()
+    }
 }
 
 /// Handshake state machine for the initiator (client).
 pub struct InitiatorHandshake {
     state: HandshakeState,
+    /// Frame cipher suite bound into the prologue
+    suite: CipherSuite,
 }
 
 impl InitiatorHandshake {
@@ -50,19 +187,59 @@ impl InitiatorHandshake {
     pub fn new(
         local_keypair: &StaticKeypair,
         remote_public: &[u8; PUBLIC_KEY_SIZE],
+    ) -> Result<Self, CryptoError> {
+        Self::with_suite(local_keypair, remote_public, CipherSuite::XChaCha20Poly1305)
+    }
+
+    /// Create an initiator handshake committed to a frame cipher suite.
+    ///
+    /// The suite is bound into the transcript via the Noise prologue, so the
+    /// handshake fails unless the responder uses the same suite.
+    ///
+    /// # Arguments
+    /// * `local_keypair` - The initiator's static keypair
+    /// * `remote_public` - The responder's known static public key
+    /// * `suite` - The frame cipher the session will use
+    pub fn with_suite(
+        local_keypair: &StaticKeypair,
+        remote_public: &[u8; PUBLIC_KEY_SIZE],
+        suite: CipherSuite,
     ) -> Result<Self, CryptoError> {
         let builder = Builder::new(NOISE_PARAMS.clone());
         let state = builder
+            .prologue(suite.prologue())
             .local_private_key(local_keypair.private_key())
             .remote_public_key(remote_public)
             .build_initiator()
             .map_err(|e| CryptoError::HandshakeFailed(e.to_string()))?;
 
-        Ok(Self { state })
+        Ok(Self { state, suite })
+    }
+
+    /// Create an anonymous initiator handshake (Noise_NK).
+    ///
+    /// The initiator uses only an ephemeral key; the responder must use
+    /// [`HandshakeProfile::Anonymous`] as well.
+    ///
+    /// # Arguments
+    /// * `remote_public` - The responder's known static public key
+    pub fn anonymous(remote_public: &[u8; PUBLIC_KEY_SIZE]) -> Result<Self, CryptoError> {
+        let builder = Builder::new(HandshakeProfile::Anonymous.params());
+        let state = builder
+            .remote_public_key(remote_public)
+            .build_initiator()
+            .map_err(|e| CryptoError::HandshakeFailed(e.to_string()))?;
+
+        Ok(Self {
+            state,
+            suite: CipherSuite::XChaCha20Poly1305,
+        })
     }
 
     /// Generate the first handshake message (-> e, es, s, ss).
     ///
+    /// With the anonymous profile the message is (-> e, es).
+    ///
     /// # Arguments
     /// * `payload` - Optional payload to include (state type ID, extensions)
     ///
@@ -75,9 +252,32 @@ impl InitiatorHandshake {
             .write_message(payload, &mut buf)
             .map_err(|e| CryptoError::HandshakeFailed(e.to_string()))?;
         buf.truncate(len);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(role = "initiator", len, "handshake init written");
         Ok(buf)
     }
 
+    /// Generate the first handshake message with 0-RTT early data.
+    ///
+    /// Frames `payload` and `early_data` as described in the
+    /// [module documentation](self); send the message with
+    /// `HandshakeFlags::EARLY_DATA` set. Early data may be replayed by an
+    /// attacker, so only send what is safe to apply more than once.
+    pub fn write_message_with_early_data(
+        &mut self,
+        payload: &[u8],
+        early_data: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        let payload_len = u16::try_from(payload.len())
+            .map_err(|_| CryptoError::HandshakeFailed("handshake payload too long".into()))?;
+        let mut framed =
+            Vec::with_capacity(EARLY_DATA_LENGTH_SIZE + payload.len() + early_data.len());
+        framed.extend_from_slice(&payload_len.to_le_bytes());
+        framed.extend_from_slice(payload);
+        framed.extend_from_slice(early_data);
+        self.write_message(&framed)
+    }
+
     /// Process the handshake response (<- e, ee, se).
     ///
     /// # Arguments
@@ -103,14 +303,38 @@ impl InitiatorHandshake {
             .state
             .into_transport_mode()
             .map_err(|e| CryptoError::HandshakeFailed(e.to_string()))?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(role = "initiator", "handshake complete");
 
-        Ok((payload, HandshakeResult { handshake_hash }))
+        Ok((
+            payload,
+            HandshakeResult {
+                handshake_hash,
+                early_data: None,
+                cipher_suite: self.suite,
+            },
+        ))
     }
 }
 
 /// Handshake state machine for the responder (server).
+///
+/// Initiator messages are capped at `MAX_HANDSHAKE_MESSAGE_SIZE`, and the
+/// handshake must finish within its timeout (`HANDSHAKE_DEADLINE` by
+/// default, see [`with_timeout`](Self::with_timeout)); servers holding
+/// pending handshakes can evict them once [`is_expired`](Self::is_expired).
 pub struct ResponderHandshake {
     state: HandshakeState,
+    /// Whether to accept 0-RTT early data
+    accept_early_data: bool,
+    /// Early data from the initiator, held until the handshake completes
+    early_data: Option<Vec<u8>>,
+    /// When the handshake state was created
+    started: Instant,
+    /// How long the handshake may take
+    timeout: Duration,
+    /// Frame cipher suite bound into the prologue
+    suite: CipherSuite,
 }
 
 impl ResponderHandshake {
@@ -119,13 +343,84 @@ impl ResponderHandshake {
     /// # Arguments
     /// * `local_keypair` - The responder's static keypair
     pub fn new(local_keypair: &StaticKeypair) -> Result<Self, CryptoError> {
-        let builder = Builder::new(NOISE_PARAMS.clone());
+        Self::with_profile(local_keypair, HandshakeProfile::Authenticated)
+    }
+
+    /// Create a responder handshake for the given security profile.
+    ///
+    /// # Arguments
+    /// * `local_keypair` - The responder's static keypair
+    /// * `profile` - Must match the profile the initiator uses
+    pub fn with_profile(
+        local_keypair: &StaticKeypair,
+        profile: HandshakeProfile,
+    ) -> Result<Self, CryptoError> {
+        Self::with_suite(local_keypair, profile, CipherSuite::XChaCha20Poly1305)
+    }
+
+    /// Create a responder handshake committed to a frame cipher suite.
+    ///
+    /// # Arguments
+    /// * `local_keypair` - The responder's static keypair
+    /// * `profile` - Must match the profile the initiator uses
+    /// * `suite` - Must match the suite the initiator uses
+    pub fn with_suite(
+        local_keypair: &StaticKeypair,
+        profile: HandshakeProfile,
+        suite: CipherSuite,
+    ) -> Result<Self, CryptoError> {
+        let builder = Builder::new(profile.params());
         let state = builder
+            .prologue(suite.prologue())
             .local_private_key(local_keypair.private_key())
             .build_responder()
             .map_err(|e| CryptoError::HandshakeFailed(e.to_string()))?;
 
-        Ok(Self { state })
+        Ok(Self {
+            state,
+            accept_early_data: false,
+            early_data: None,
+            started: Instant::now(),
+            timeout: HANDSHAKE_DEADLINE,
+            suite,
+        })
+    }
+
+    /// Enable or disable accepting 0-RTT early data (disabled by default).
+    ///
+    /// See the [module documentation](self) for the replay caveats.
+    pub fn with_early_data(mut self, accept: bool) -> Self {
+        self.accept_early_data = accept;
+        self
+    }
+
+    /// Set how long the handshake may take (default `HANDSHAKE_DEADLINE`).
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// When the handshake expires.
+    pub fn deadline(&self) -> Instant {
+        self.started + self.timeout
+    }
+
+    /// Check whether the deadline has passed.
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(Instant::now())
+    }
+
+    /// Check whether the deadline has passed at `now`.
+    pub fn is_expired_at(&self, now: Instant) -> bool {
+        now >= self.deadline()
+    }
+
+    /// Fail with `HandshakeTimeout` once the deadline has passed.
+    fn check_deadline(&self) -> Result<(), CryptoError> {
+        if self.is_expired() {
+            return Err(CryptoError::HandshakeTimeout);
+        }
+        Ok(())
     }
 
     /// Process the initiator's handshake message (-> e, es, s, ss).
@@ -135,26 +430,94 @@ impl ResponderHandshake {
     ///
     /// # Returns
     /// The payload from the initiator and the remote static public key
+    ///
+    /// # Errors
+    /// Fails for anonymous initiators, which have no static key; use
+    /// [`read_initiator_message`](Self::read_initiator_message) to accept them.
     pub fn read_message(&mut self, message: &[u8]) -> Result<(Vec<u8>, [u8; PUBLIC_KEY_SIZE]), CryptoError> {
-        let mut payload = vec![0u8; 65535];
+        let (payload, remote_public) = self.read_initiator_message(message)?;
+        let remote_public = remote_public
+            .ok_or_else(|| CryptoError::HandshakeFailed("no remote static key".into()))?;
+
+        Ok((payload, remote_public))
+    }
+
+    /// Process the initiator's handshake message under either profile.
+    ///
+    /// # Returns
+    /// The payload from the initiator and its static public key, or `None`
+    /// for an anonymous initiator.
+    ///
+    /// # Errors
+    /// Returns `HandshakeMessageTooLarge` for messages over
+    /// `MAX_HANDSHAKE_MESSAGE_SIZE` (before any DH work), and
+    /// `HandshakeTimeout` once the deadline has passed.
+    pub fn read_initiator_message(
+        &mut self,
+        message: &[u8],
+    ) -> Result<(Vec<u8>, Option<[u8; PUBLIC_KEY_SIZE]>), CryptoError> {
+        if message.len() > MAX_HANDSHAKE_MESSAGE_SIZE {
+            return Err(CryptoError::HandshakeMessageTooLarge {
+                size: message.len(),
+                max: MAX_HANDSHAKE_MESSAGE_SIZE,
+            });
+        }
+        self.check_deadline()?;
+
+        // The payload is never longer than the message carrying it
+        let mut buf = [0u8; MAX_HANDSHAKE_MESSAGE_SIZE];
         let len = self
             .state
-            .read_message(message, &mut payload)
+            .read_message(message, &mut buf)
             .map_err(|e| CryptoError::HandshakeFailed(e.to_string()))?;
-        payload.truncate(len);
+        let payload = buf[..len].to_vec();
 
-        // Get the remote static public key
-        let remote_static = self
-            .state
-            .get_remote_static()
-            .ok_or_else(|| CryptoError::HandshakeFailed("no remote static key".into()))?;
+        // Get the remote static public key, if the profile has one
+        let remote_public = self.state.get_remote_static().map(|remote_static| {
+            let mut remote_public = [0u8; PUBLIC_KEY_SIZE];
+            remote_public.copy_from_slice(remote_static);
+            remote_public
+        });
+        #[cfg(feature = "tracing")]
+        tracing::debug!(role = "responder", anonymous = remote_public.is_none(), "handshake init read");
 
-        let mut remote_public = [0u8; PUBLIC_KEY_SIZE];
-        remote_public.copy_from_slice(remote_static);
+        Ok((payload, remote_public))
+    }
+
+    /// Process an initiator message that carries early data.
+    ///
+    /// Use this instead of
+    /// [`read_initiator_message`](Self::read_initiator_message) when the
+    /// frame sets `HandshakeFlags::EARLY_DATA`. Returns the payload with the
+    /// early data split off; if early data is enabled it is available from
+    /// [`early_data`](Self::early_data) now and from the
+    /// [`HandshakeResult`], otherwise it is dropped.
+    pub fn read_initiator_message_with_early_data(
+        &mut self,
+        message: &[u8],
+    ) -> Result<(Vec<u8>, Option<[u8; PUBLIC_KEY_SIZE]>), CryptoError> {
+        let (framed, remote_public) = self.read_initiator_message(message)?;
+        if framed.len() < EARLY_DATA_LENGTH_SIZE {
+            return Err(CryptoError::HandshakeFailed("early data frame too short".into()));
+        }
+        let payload_len = u16::from_le_bytes([framed[0], framed[1]]) as usize;
+        let payload_end = EARLY_DATA_LENGTH_SIZE + payload_len;
+        if framed.len() < payload_end {
+            return Err(CryptoError::HandshakeFailed("early data frame truncated".into()));
+        }
 
+        let payload = framed[EARLY_DATA_LENGTH_SIZE..payload_end].to_vec();
+        if self.accept_early_data {
+            self.early_data = Some(framed[payload_end..].to_vec());
+        }
         Ok((payload, remote_public))
     }
 
+    /// Get the accepted early data, if any
+    pub fn early_data(&self) -> Option<&[u8]> {
+        self.early_data.as_deref()
+    }
+
     /// Generate the handshake response (<- e, ee, se).
     ///
     /// # Arguments
@@ -162,13 +525,20 @@ impl ResponderHandshake {
     ///
     /// # Returns
     /// The handshake response bytes and the handshake result
+    ///
+    /// # Errors
+    /// Returns `HandshakeTimeout` once the deadline has passed, and
+    /// `HandshakeFailed` if the response would exceed
+    /// `MAX_HANDSHAKE_MESSAGE_SIZE`.
     pub fn write_message(mut self, payload: &[u8]) -> Result<(Vec<u8>, HandshakeResult), CryptoError> {
-        let mut buf = vec![0u8; 65535];
+        self.check_deadline()?;
+
+        let mut buf = [0u8; MAX_HANDSHAKE_MESSAGE_SIZE];
         let len = self
             .state
             .write_message(payload, &mut buf)
             .map_err(|e| CryptoError::HandshakeFailed(e.to_string()))?;
-        buf.truncate(len);
+        let buf = buf[..len].to_vec();
 
         // Get the handshake hash BEFORE transitioning to transport mode
         let hash_slice = self.state.get_handshake_hash();
@@ -180,8 +550,17 @@ impl ResponderHandshake {
             .state
             .into_transport_mode()
             .map_err(|e| CryptoError::HandshakeFailed(e.to_string()))?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(role = "responder", early_data = self.early_data.is_some(), "handshake complete");
 
-        Ok((buf, HandshakeResult { handshake_hash }))
+        Ok((
+            buf,
+            HandshakeResult {
+                handshake_hash,
+                early_data: self.early_data,
+                cipher_suite: self.suite,
+            },
+        ))
     }
 }
 
@@ -209,6 +588,61 @@ impl SessionKeys {
     ///
     /// Uses BLAKE2s-based HKDF with the handshake hash as input.
     pub fn derive(result: &HandshakeResult) -> Result<Self, CryptoError> {
+        Self::derive_bound(result, None)
+    }
+
+    /// Derive session keys bound to the negotiated extension set.
+    ///
+    /// A BLAKE2s hash of the canonical (type-sorted) extension encoding is
+    /// mixed into the key derivation, so if an on-path attacker strips or
+    /// alters extensions in the handshake payloads, the two sides end up
+    /// with different keys and the first data frame fails to decrypt.
+    #[cfg(feature = "extensions")]
+    pub fn derive_with_extensions(
+        result: &HandshakeResult,
+        extensions: &crate::extensions::ExtensionSet,
+    ) -> Result<Self, CryptoError> {
+        let binding = Binding::new().extensions(extensions).finish();
+        Self::derive_bound(result, Some(&binding))
+    }
+
+    /// Derive session keys bound to the state type being synchronized.
+    ///
+    /// Each side mixes the [`STATE_TYPE_ID`](crate::core::SyncState::STATE_TYPE_ID)
+    /// it expects into the key derivation, so a peer advertising a different
+    /// state type ends up with different keys and cannot sync into this one:
+    /// its first data frame fails to decrypt.
+    pub fn derive_for_state_type(
+        result: &HandshakeResult,
+        state_type_id: &str,
+    ) -> Result<Self, CryptoError> {
+        let binding = Binding::new().state_type(state_type_id).finish();
+        Self::derive_bound(result, Some(&binding))
+    }
+
+    /// Derive session keys bound to both the state type and the extensions.
+    ///
+    /// Combines [`derive_for_state_type`](Self::derive_for_state_type) and
+    /// [`derive_with_extensions`](Self::derive_with_extensions): both are
+    /// hashed into a single binding, so a mismatch in either one gives the
+    /// two sides different keys.
+    #[cfg(feature = "extensions")]
+    pub fn derive_for_session(
+        result: &HandshakeResult,
+        state_type_id: &str,
+        extensions: &crate::extensions::ExtensionSet,
+    ) -> Result<Self, CryptoError> {
+        let binding = Binding::new()
+            .state_type(state_type_id)
+            .extensions(extensions)
+            .finish();
+        Self::derive_bound(result, Some(&binding))
+    }
+
+    fn derive_bound(
+        result: &HandshakeResult,
+        binding: Option<&[u8; HASH_SIZE]>,
+    ) -> Result<Self, CryptoError> {
         use blake2::{Blake2s256, Digest};
 
         let handshake_hash = &result.handshake_hash;
@@ -218,10 +652,13 @@ impl SessionKeys {
         let label = b"nomad v1 session keys";
 
         // Simple HKDF-Expand using BLAKE2s
-        // PRK = handshake_hash, info = label
+        // PRK = handshake_hash, info = label || binding
         let mut hasher1 = Blake2s256::new();
         hasher1.update(handshake_hash);
         hasher1.update(label);
+        if let Some(binding) = binding {
+            hasher1.update(binding);
+        }
         hasher1.update([0x01]); // Counter byte
         let output1 = hasher1.finalize();
 
@@ -229,6 +666,9 @@ impl SessionKeys {
         hasher2.update(handshake_hash);
         hasher2.update(output1);
         hasher2.update(label);
+        if let Some(binding) = binding {
+            hasher2.update(binding);
+        }
         hasher2.update([0x02]); // Counter byte
         let output2 = hasher2.finalize();
 
@@ -252,6 +692,54 @@ impl SessionKeys {
     }
 }
 
+/// Session parameters mixed into the key derivation.
+///
+/// Each part is labelled and length-delimited, so different combinations
+/// never hash the same input.
+struct Binding(blake2::Blake2s256);
+
+impl Binding {
+    fn new() -> Self {
+        use blake2::Digest;
+
+        let mut hasher = blake2::Blake2s256::new();
+        hasher.update(b"nomad v1 binding");
+        Self(hasher)
+    }
+
+    /// Bind the state type ID.
+    fn state_type(mut self, state_type_id: &str) -> Self {
+        use blake2::Digest;
+
+        self.0.update(b"state type");
+        self.0.update((state_type_id.len() as u64).to_le_bytes());
+        self.0.update(state_type_id.as_bytes());
+        self
+    }
+
+    /// Bind the extension set, in canonical (type-sorted) order.
+    #[cfg(feature = "extensions")]
+    fn extensions(mut self, extensions: &crate::extensions::ExtensionSet) -> Self {
+        use blake2::Digest;
+
+        let mut sorted: Vec<_> = extensions.iter().collect();
+        sorted.sort_by_key(|ext| ext.ext_type);
+
+        self.0.update(b"extensions");
+        self.0.update((sorted.len() as u64).to_le_bytes());
+        for ext in sorted {
+            self.0.update(ext.encode());
+        }
+        self
+    }
+
+    fn finish(self) -> [u8; HASH_SIZE] {
+        use blake2::Digest;
+
+        self.0.finalize().into()
+    }
+}
+
 /// Role in the handshake (affects which key is used for send/receive)
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Role {
@@ -283,6 +771,76 @@ impl SessionKeys {
 mod tests {
     use super::*;
 
+    /// Run a handshake whose init carries `early_data`, returning the
+    /// payload and early data the responder saw, and the initiator result
+    fn early_data_handshake(
+        accept: bool,
+        early_data: &[u8],
+    ) -> (Vec<u8>, Option<Vec<u8>>, HandshakeResult) {
+        let initiator_keypair = StaticKeypair::generate();
+        let responder_keypair = StaticKeypair::generate();
+        let mut initiator =
+            InitiatorHandshake::new(&initiator_keypair, responder_keypair.public_key()).unwrap();
+        let mut responder = ResponderHandshake::new(&responder_keypair)
+            .unwrap()
+            .with_early_data(accept);
+
+        let init = initiator
+            .write_message_with_early_data(b"nomad.counter.v1", early_data)
+            .unwrap();
+        let (payload, remote_public) =
+            responder.read_initiator_message_with_early_data(&init).unwrap();
+        assert_eq!(remote_public.as_ref(), Some(initiator_keypair.public_key()));
+
+        let (response, responder_result) = responder.write_message(b"OK").unwrap();
+        let seen = responder_result.early_data().map(<[u8]>::to_vec);
+        let (_, initiator_result) = initiator.read_message(&response).unwrap();
+        assert_eq!(initiator_result.handshake_hash, responder_result.handshake_hash);
+        (payload, seen, initiator_result)
+    }
+
+    #[test]
+    fn test_early_data_applied_before_handshake_completes() {
+        let initiator_keypair = StaticKeypair::generate();
+        let responder_keypair = StaticKeypair::generate();
+        let mut initiator =
+            InitiatorHandshake::new(&initiator_keypair, responder_keypair.public_key()).unwrap();
+        let mut responder = ResponderHandshake::new(&responder_keypair)
+            .unwrap()
+            .with_early_data(true);
+
+        // Client sends "increment by 5" in its first flight
+        let increment = 5u64.to_le_bytes();
+        let init = initiator
+            .write_message_with_early_data(b"nomad.counter.v1", &increment)
+            .unwrap();
+
+        // Server applies it before sending its response
+        let mut counter = 0u64;
+        let (payload, _) = responder.read_initiator_message_with_early_data(&init).unwrap();
+        assert_eq!(payload, b"nomad.counter.v1");
+        let early = responder.early_data().unwrap();
+        counter += u64::from_le_bytes(early.try_into().unwrap());
+        assert_eq!(counter, 5);
+
+        // The response already reflects the increment
+        let (response, responder_result) = responder.write_message(&counter.to_le_bytes()).unwrap();
+        assert_eq!(responder_result.early_data(), Some(&increment[..]));
+        let (reply, initiator_result) = initiator.read_message(&response).unwrap();
+        assert_eq!(reply, 5u64.to_le_bytes());
+        assert!(initiator_result.early_data().is_none());
+    }
+
+    #[test]
+    fn test_early_data_rejected_when_disabled() {
+        let (payload, seen, _) = early_data_handshake(false, &7u64.to_le_bytes());
+        assert_eq!(payload, b"nomad.counter.v1");
+        assert_eq!(seen, None);
+
+        let (_, seen, _) = early_data_handshake(true, &7u64.to_le_bytes());
+        assert_eq!(seen, Some(7u64.to_le_bytes().to_vec()));
+    }
+
     #[test]
     fn test_handshake_roundtrip() {
         // Generate keypairs
@@ -333,6 +891,237 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "extensions")]
+    #[test]
+    fn test_extension_binding_mismatch_fails_first_frame() {
+        use crate::crypto::{CryptoSession, SessionId};
+        use crate::extensions::{Extension, ExtensionSet, ext_type};
+
+        let initiator_keypair = StaticKeypair::generate();
+        let responder_keypair = StaticKeypair::generate();
+
+        let mut initiator = InitiatorHandshake::new(
+            &initiator_keypair,
+            responder_keypair.public_key(),
+        ).unwrap();
+        let mut responder = ResponderHandshake::new(&responder_keypair).unwrap();
+
+        let init_message = initiator.write_message(b"").unwrap();
+        responder.read_message(&init_message).unwrap();
+        let (resp_message, responder_result) = responder.write_message(b"").unwrap();
+        let (_, initiator_result) = initiator.read_message(&resp_message).unwrap();
+
+        let mut agreed = ExtensionSet::new();
+        agreed.add_compression(3);
+        agreed.add(Extension::new(ext_type::BATCHING, vec![]));
+
+        // Same set in a different insertion order binds to the same keys
+        let mut reordered = ExtensionSet::new();
+        reordered.add(Extension::new(ext_type::BATCHING, vec![]));
+        reordered.add_compression(3);
+        let a = SessionKeys::derive_with_extensions(&initiator_result, &agreed).unwrap();
+        let b = SessionKeys::derive_with_extensions(&responder_result, &reordered).unwrap();
+        assert_eq!(a.initiator_key.as_bytes(), b.initiator_key.as_bytes());
+
+        // Binding differs from the unbound derivation
+        let unbound = SessionKeys::derive(&initiator_result).unwrap();
+        assert_ne!(a.initiator_key.as_bytes(), unbound.initiator_key.as_bytes());
+
+        // Responder's view was tampered with: compression stripped
+        let mut tampered = ExtensionSet::new();
+        tampered.add(Extension::new(ext_type::BATCHING, vec![]));
+
+        let initiator_keys =
+            SessionKeys::derive_with_extensions(&initiator_result, &agreed).unwrap();
+        let responder_keys =
+            SessionKeys::derive_with_extensions(&responder_result, &tampered).unwrap();
+
+        let session_id = SessionId::generate();
+        let mut client = CryptoSession::new(
+            session_id,
+            Role::Initiator,
+            initiator_keys.initiator_key,
+            initiator_keys.responder_key,
+            initiator_keys.handshake_hash,
+        );
+        let mut server = CryptoSession::new(
+            session_id,
+            Role::Responder,
+            responder_keys.responder_key,
+            responder_keys.initiator_key,
+            responder_keys.handshake_hash,
+        );
+
+        let (counter, ciphertext) = client.encrypt_frame(0x03, 0, b"hello").unwrap();
+        assert!(matches!(
+            server.decrypt_frame(0x03, 0, counter, &ciphertext),
+            Err(CryptoError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_state_type_binding_mismatch_fails_first_frame() {
+        use crate::crypto::{CryptoSession, SessionId};
+
+        let initiator_keypair = StaticKeypair::generate();
+        let responder_keypair = StaticKeypair::generate();
+
+        let mut initiator =
+            InitiatorHandshake::new(&initiator_keypair, responder_keypair.public_key()).unwrap();
+        let mut responder = ResponderHandshake::new(&responder_keypair).unwrap();
+
+        let init_message = initiator.write_message(b"").unwrap();
+        responder.read_message(&init_message).unwrap();
+        let (resp_message, responder_result) = responder.write_message(b"").unwrap();
+        let (_, initiator_result) = initiator.read_message(&resp_message).unwrap();
+
+        // Matching IDs agree, and differ from the unbound derivation
+        let a = SessionKeys::derive_for_state_type(&initiator_result, "nomad.test.a.v1").unwrap();
+        let b = SessionKeys::derive_for_state_type(&responder_result, "nomad.test.a.v1").unwrap();
+        assert_eq!(a.initiator_key.as_bytes(), b.initiator_key.as_bytes());
+        let unbound = SessionKeys::derive(&initiator_result).unwrap();
+        assert_ne!(a.initiator_key.as_bytes(), unbound.initiator_key.as_bytes());
+
+        // Client wants to sync a different state type than the server serves
+        let initiator_keys =
+            SessionKeys::derive_for_state_type(&initiator_result, "nomad.test.b.v1").unwrap();
+        let responder_keys =
+            SessionKeys::derive_for_state_type(&responder_result, "nomad.test.a.v1").unwrap();
+
+        let session_id = SessionId::generate();
+        let mut client = CryptoSession::new(
+            session_id,
+            Role::Initiator,
+            initiator_keys.initiator_key,
+            initiator_keys.responder_key,
+            initiator_keys.handshake_hash,
+        );
+        let mut server = CryptoSession::new(
+            session_id,
+            Role::Responder,
+            responder_keys.responder_key,
+            responder_keys.initiator_key,
+            responder_keys.handshake_hash,
+        );
+
+        let (counter, ciphertext) = client.encrypt_frame(0x03, 0, b"hello").unwrap();
+        assert!(matches!(
+            server.decrypt_frame(0x03, 0, counter, &ciphertext),
+            Err(CryptoError::DecryptionFailed)
+        ));
+    }
+
+    #[cfg(feature = "extensions")]
+    #[test]
+    fn test_session_binding_covers_state_type_and_extensions() {
+        use crate::extensions::ExtensionSet;
+
+        let initiator_keypair = StaticKeypair::generate();
+        let responder_keypair = StaticKeypair::generate();
+
+        let mut initiator =
+            InitiatorHandshake::new(&initiator_keypair, responder_keypair.public_key()).unwrap();
+        let mut responder = ResponderHandshake::new(&responder_keypair).unwrap();
+
+        let init_message = initiator.write_message(b"").unwrap();
+        responder.read_message(&init_message).unwrap();
+        let (resp_message, responder_result) = responder.write_message(b"").unwrap();
+        let (_, result) = initiator.read_message(&resp_message).unwrap();
+
+        let mut agreed = ExtensionSet::new();
+        agreed.add_compression(3);
+        let key = |state_type_id: &str, extensions: &ExtensionSet| {
+            *SessionKeys::derive_for_session(&result, state_type_id, extensions)
+                .unwrap()
+                .initiator_key
+                .as_bytes()
+        };
+
+        // Both sides agree on everything
+        let bound = key("nomad.test.a.v1", &agreed);
+        let server =
+            SessionKeys::derive_for_session(&responder_result, "nomad.test.a.v1", &agreed).unwrap();
+        assert_eq!(server.initiator_key.as_bytes(), &bound);
+
+        // A mismatch in either part changes the keys
+        assert_ne!(key("nomad.test.b.v1", &agreed), bound);
+        assert_ne!(key("nomad.test.a.v1", &ExtensionSet::new()), bound);
+
+        // Binding both differs from binding either one alone
+        let state_only = SessionKeys::derive_for_state_type(&result, "nomad.test.a.v1").unwrap();
+        let extensions_only = SessionKeys::derive_with_extensions(&result, &agreed).unwrap();
+        assert_ne!(state_only.initiator_key.as_bytes(), &bound);
+        assert_ne!(extensions_only.initiator_key.as_bytes(), &bound);
+    }
+
+    #[test]
+    fn test_init_overhead_matches_message_cap() {
+        use crate::core::HANDSHAKE_INIT_OVERHEAD;
+
+        let initiator_keypair = StaticKeypair::generate();
+        let responder_keypair = StaticKeypair::generate();
+        let mut initiator =
+            InitiatorHandshake::new(&initiator_keypair, responder_keypair.public_key()).unwrap();
+        let mut responder = ResponderHandshake::new(&responder_keypair).unwrap();
+
+        // The largest payload the overhead allows fills the cap exactly
+        let payload = [0u8; MAX_HANDSHAKE_MESSAGE_SIZE - HANDSHAKE_INIT_OVERHEAD];
+        let init = initiator.write_message(&payload).unwrap();
+        assert_eq!(init.len(), MAX_HANDSHAKE_MESSAGE_SIZE);
+        assert!(responder.read_initiator_message(&init).is_ok());
+    }
+
+    #[test]
+    fn test_oversized_handshake_message_rejected() {
+        let initiator_keypair = StaticKeypair::generate();
+        let responder_keypair = StaticKeypair::generate();
+        let mut initiator =
+            InitiatorHandshake::new(&initiator_keypair, responder_keypair.public_key()).unwrap();
+        let mut responder = ResponderHandshake::new(&responder_keypair).unwrap();
+
+        let init = initiator
+            .write_message(&[0u8; MAX_HANDSHAKE_MESSAGE_SIZE])
+            .unwrap();
+        assert!(matches!(
+            responder.read_initiator_message(&init),
+            Err(CryptoError::HandshakeMessageTooLarge { size, max: MAX_HANDSHAKE_MESSAGE_SIZE })
+                if size == init.len()
+        ));
+
+        // A normal-sized init still completes on a fresh responder
+        let mut initiator =
+            InitiatorHandshake::new(&initiator_keypair, responder_keypair.public_key()).unwrap();
+        let mut responder = ResponderHandshake::new(&responder_keypair).unwrap();
+        let init = initiator.write_message(b"nomad.echo.v1").unwrap();
+        let (payload, _) = responder.read_message(&init).unwrap();
+        assert_eq!(payload, b"nomad.echo.v1");
+        let (response, responder_result) = responder.write_message(b"OK").unwrap();
+        let (_, initiator_result) = initiator.read_message(&response).unwrap();
+        assert_eq!(initiator_result.handshake_hash, responder_result.handshake_hash);
+    }
+
+    #[test]
+    fn test_responder_handshake_deadline() {
+        let initiator_keypair = StaticKeypair::generate();
+        let responder_keypair = StaticKeypair::generate();
+        let mut initiator =
+            InitiatorHandshake::new(&initiator_keypair, responder_keypair.public_key()).unwrap();
+        let init = initiator.write_message(b"").unwrap();
+
+        let responder = ResponderHandshake::new(&responder_keypair).unwrap();
+        let deadline = responder.deadline();
+        assert!(!responder.is_expired_at(deadline - Duration::from_millis(1)));
+        assert!(responder.is_expired_at(deadline));
+
+        let mut responder = responder.with_timeout(Duration::ZERO);
+        assert!(responder.is_expired());
+        assert!(matches!(
+            responder.read_initiator_message(&init),
+            Err(CryptoError::HandshakeTimeout)
+        ));
+        assert!(matches!(responder.write_message(b""), Err(CryptoError::HandshakeTimeout)));
+    }
+
     #[test]
     fn test_handshake_wrong_key_fails() {
         let initiator_keypair = StaticKeypair::generate();
@@ -391,4 +1180,139 @@ mod tests {
             responder_keys.initiator_key.as_bytes()
         );
     }
+
+    #[test]
+    fn test_anonymous_handshake() {
+        let responder_keypair = StaticKeypair::generate();
+        let profile = HandshakeProfile::Anonymous;
+        assert!(!profile.authenticates_initiator());
+        assert_eq!(profile.pattern(), "Noise_NK_25519_ChaChaPoly_BLAKE2s");
+
+        let mut initiator = InitiatorHandshake::anonymous(responder_keypair.public_key()).unwrap();
+        let mut responder = ResponderHandshake::with_profile(&responder_keypair, profile).unwrap();
+
+        let init_message = initiator.write_message(b"nomad.feed.v1").unwrap();
+
+        // No client identity: the strict accessor refuses, the profile-aware one yields None
+        let mut strict = ResponderHandshake::with_profile(&responder_keypair, profile).unwrap();
+        assert!(strict.read_message(&init_message).is_err());
+        let (payload, remote_public) = responder.read_initiator_message(&init_message).unwrap();
+        assert_eq!(payload, b"nomad.feed.v1");
+        assert_eq!(remote_public, None);
+
+        let (resp_message, responder_result) = responder.write_message(b"OK").unwrap();
+        let (_, initiator_result) = initiator.read_message(&resp_message).unwrap();
+        assert_eq!(initiator_result.handshake_hash, responder_result.handshake_hash);
+        assert_eq!(initiator_result.cipher_suite(), CipherSuite::XChaCha20Poly1305);
+
+        let initiator_keys = SessionKeys::derive(&initiator_result).unwrap();
+        let responder_keys = SessionKeys::derive(&responder_result).unwrap();
+        assert_eq!(
+            initiator_keys.send_key(Role::Initiator).as_bytes(),
+            responder_keys.recv_key(Role::Responder).as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_profile_mismatch_fails() {
+        let initiator_keypair = StaticKeypair::generate();
+        let responder_keypair = StaticKeypair::generate();
+
+        // Authenticated client against an anonymous-only server
+        let mut initiator = InitiatorHandshake::new(
+            &initiator_keypair,
+            responder_keypair.public_key(),
+        ).unwrap();
+        let mut responder =
+            ResponderHandshake::with_profile(&responder_keypair, HandshakeProfile::Anonymous).unwrap();
+        let init_message = initiator.write_message(b"").unwrap();
+        let accepted = responder
+            .read_initiator_message(&init_message)
+            .and_then(|_| responder.write_message(b""))
+            .and_then(|(resp, _)| initiator.read_message(&resp));
+        assert!(accepted.is_err());
+
+        // Anonymous client against an authenticated server
+        let mut initiator = InitiatorHandshake::anonymous(responder_keypair.public_key()).unwrap();
+        let mut responder = ResponderHandshake::new(&responder_keypair).unwrap();
+        let init_message = initiator.write_message(b"").unwrap();
+        assert!(responder.read_message(&init_message).is_err());
+    }
+
+    #[test]
+    fn test_aes_gcm_suite_roundtrip() {
+        use crate::crypto::{Aes256GcmCipher, CryptoSession, SessionId};
+
+        let initiator_keypair = StaticKeypair::generate();
+        let responder_keypair = StaticKeypair::generate();
+        let mut initiator = InitiatorHandshake::with_suite(
+            &initiator_keypair,
+            responder_keypair.public_key(),
+            CipherSuite::Aes256Gcm,
+        )
+        .unwrap();
+        let mut responder = ResponderHandshake::with_suite(
+            &responder_keypair,
+            HandshakeProfile::Authenticated,
+            CipherSuite::Aes256Gcm,
+        )
+        .unwrap();
+
+        let init_message = initiator.write_message(b"").unwrap();
+        responder.read_message(&init_message).unwrap();
+        let (resp_message, responder_result) = responder.write_message(b"").unwrap();
+        let (_, initiator_result) = initiator.read_message(&resp_message).unwrap();
+        assert_eq!(initiator_result.cipher_suite(), CipherSuite::Aes256Gcm);
+        assert_eq!(responder_result.cipher_suite(), CipherSuite::Aes256Gcm);
+
+        let session = |result: &HandshakeResult, role: Role| {
+            let keys = SessionKeys::derive(result).unwrap();
+            CryptoSession::<Aes256GcmCipher>::with_cipher(
+                SessionId::from_bytes([1; 6]),
+                role,
+                keys.send_key(role).clone(),
+                keys.recv_key(role).clone(),
+                result.handshake_hash,
+            )
+        };
+        let mut client = session(&initiator_result, Role::Initiator);
+        let mut server = session(&responder_result, Role::Responder);
+        assert_eq!(client.connection_info().frame_aead, "AES-256-GCM");
+
+        let (nonce, ciphertext) = client.encrypt_frame(0x03, 0, b"hello").unwrap();
+        assert_eq!(server.decrypt_frame(0x03, 0, nonce, &ciphertext).unwrap(), b"hello");
+        let (nonce, ciphertext) = server.encrypt_frame(0x03, 0, b"world").unwrap();
+        assert_eq!(client.decrypt_frame(0x03, 0, nonce, &ciphertext).unwrap(), b"world");
+    }
+
+    #[test]
+    fn test_cipher_suite_mismatch_fails() {
+        let initiator_keypair = StaticKeypair::generate();
+        let responder_keypair = StaticKeypair::generate();
+
+        // A client committed to AES-256-GCM cannot be downgraded to the default
+        let mut initiator = InitiatorHandshake::with_suite(
+            &initiator_keypair,
+            responder_keypair.public_key(),
+            CipherSuite::Aes256Gcm,
+        )
+        .unwrap();
+        let mut responder = ResponderHandshake::new(&responder_keypair).unwrap();
+        let init_message = initiator.write_message(b"").unwrap();
+        assert!(responder.read_message(&init_message).is_err());
+
+        // Nor the other way around
+        let mut initiator = InitiatorHandshake::new(
+            &initiator_keypair,
+            responder_keypair.public_key(),
+        ).unwrap();
+        let mut responder = ResponderHandshake::with_suite(
+            &responder_keypair,
+            HandshakeProfile::Authenticated,
+            CipherSuite::Aes256Gcm,
+        )
+        .unwrap();
+        let init_message = initiator.write_message(b"").unwrap();
+        assert!(responder.read_message(&init_message).is_err());
+    }
 }
@@ -7,12 +7,13 @@
 //! - `REJECT_AFTER_MESSAGES` (2^64-1): MUST terminate session
 //! - `OLD_KEY_RETENTION` (5s): Keep old keys for late packets
 
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use blake2::{Blake2s256, Digest};
 use crate::core::{
-    CryptoError, MAX_EPOCH, OLD_KEY_RETENTION, REJECT_AFTER_MESSAGES, REJECT_AFTER_TIME,
-    REKEY_AFTER_MESSAGES, REKEY_AFTER_TIME,
+    system_clock, CryptoError, SharedClock, CONTROL_FRAME_HEADROOM, MAX_EPOCH, OLD_KEY_RETENTION,
+    REJECT_AFTER_MESSAGES, REJECT_AFTER_TIME, REKEY_AFTER_MESSAGES, REKEY_AFTER_TIME,
+    REKEY_MESSAGE_MARGIN,
 };
 use zeroize::Zeroize;
 
@@ -29,6 +30,14 @@ pub struct RekeyState {
     send_count: u64,
     /// Number of messages received in current epoch
     recv_count: u64,
+    /// Message-count lookahead for `needs_rekey_soon`
+    message_margin: u64,
+    /// Hard limit on the send counter (`REJECT_AFTER_MESSAGES` by default)
+    counter_limit: u64,
+    /// Last epoch a rekey may reach (`MAX_EPOCH` by default)
+    epoch_limit: u32,
+    /// Time source
+    clock: SharedClock,
 }
 
 impl RekeyState {
@@ -39,9 +48,38 @@ impl RekeyState {
             epoch_start: Instant::now(),
             send_count: 0,
             recv_count: 0,
+            message_margin: REKEY_MESSAGE_MARGIN,
+            counter_limit: REJECT_AFTER_MESSAGES,
+            epoch_limit: MAX_EPOCH,
+            clock: system_clock(),
         }
     }
 
+    /// Use a custom time source; the current epoch restarts at its `now`.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.epoch_start = clock.now();
+        self.clock = clock;
+        self
+    }
+
+    /// Set the message-count lookahead for [`needs_rekey_soon`](Self::needs_rekey_soon).
+    pub fn set_message_margin(&mut self, margin: u64) {
+        self.message_margin = margin;
+    }
+
+    /// Lower the send counter's hard limit, e.g. to an AEAD usage limit.
+    ///
+    /// The limit applies to every epoch.
+    pub fn set_counter_limit(&mut self, limit: u64) {
+        self.counter_limit = limit;
+    }
+
+    /// Lower the last epoch a rekey may reach, capping how long a session
+    /// can live before it needs a fresh handshake.
+    pub fn set_epoch_limit(&mut self, limit: u32) {
+        self.epoch_limit = limit;
+    }
+
     /// Get the current epoch.
     pub fn epoch(&self) -> u32 {
         self.epoch
@@ -64,7 +102,7 @@ impl RekeyState {
     /// # Errors
     /// Returns `CounterExhaustion` if the counter has reached the hard limit.
     pub fn increment_send(&mut self) -> Result<u64, CryptoError> {
-        if self.send_count == REJECT_AFTER_MESSAGES {
+        if self.send_count >= self.counter_limit {
             return Err(CryptoError::CounterExhaustion);
         }
         let counter = self.send_count;
@@ -72,6 +110,14 @@ impl RekeyState {
         Ok(counter)
     }
 
+    /// Check if only the counters reserved for control frames are left.
+    ///
+    /// The last `CONTROL_FRAME_HEADROOM` counters below the hard limit are
+    /// kept for the REKEY exchange or a CLOSE.
+    pub fn counter_reserved(&self) -> bool {
+        self.send_count >= self.counter_limit.saturating_sub(CONTROL_FRAME_HEADROOM)
+    }
+
     /// Record a received message counter.
     ///
     /// Note: Actual replay detection is handled by the replay window.
@@ -83,19 +129,64 @@ impl RekeyState {
 
     /// Check if we should initiate a rekey (soft limit reached).
     pub fn should_rekey(&self) -> bool {
-        let time_exceeded = self.epoch_start.elapsed() >= REKEY_AFTER_TIME;
+        let time_exceeded = self.epoch_age() >= REKEY_AFTER_TIME;
         let messages_exceeded = self.send_count >= REKEY_AFTER_MESSAGES;
         time_exceeded || messages_exceeded
     }
 
+    /// Check if the soft limit is close enough to start a rekey now.
+    ///
+    /// True within `margin` of `REKEY_AFTER_TIME`, or within the message
+    /// margin of `REKEY_AFTER_MESSAGES`. A rekey takes a round trip, so
+    /// starting early keeps a lossy path clear of `REJECT_AFTER_TIME`.
+    pub fn needs_rekey_soon(&self, margin: Duration) -> bool {
+        let time_close = self.epoch_age().saturating_add(margin) >= REKEY_AFTER_TIME;
+        let messages_close =
+            self.send_count.saturating_add(self.message_margin) >= REKEY_AFTER_MESSAGES;
+        time_close || messages_close
+    }
+
     /// Check if the current keys are expired (hard limit reached).
     pub fn keys_expired(&self) -> bool {
-        self.epoch_start.elapsed() >= REJECT_AFTER_TIME
+        self.epoch_age() >= REJECT_AFTER_TIME
     }
 
     /// Check if we can perform another rekey (epoch limit).
     pub fn can_rekey(&self) -> bool {
-        self.epoch < MAX_EPOCH
+        self.epoch < self.epoch_limit
+    }
+
+    /// Time since the current epoch started.
+    pub fn epoch_age(&self) -> Duration {
+        self.clock.now().saturating_duration_since(self.epoch_start)
+    }
+
+    /// Rebuild state exported from another session (for resumption).
+    #[cfg(feature = "session-resumption")]
+    pub(crate) fn restore(epoch: u32, epoch_age: Duration, send_count: u64, recv_count: u64) -> Self {
+        let now = Instant::now();
+        Self {
+            epoch,
+            epoch_start: now.checked_sub(epoch_age).unwrap_or(now),
+            send_count,
+            recv_count,
+            message_margin: REKEY_MESSAGE_MARGIN,
+            counter_limit: REJECT_AFTER_MESSAGES,
+            epoch_limit: MAX_EPOCH,
+            clock: system_clock(),
+        }
+    }
+
+    /// Force the epoch to a specific value (test helper).
+    #[cfg(test)]
+    pub(crate) fn set_epoch(&mut self, epoch: u32) {
+        self.epoch = epoch;
+    }
+
+    /// Force the send counter to a specific value (test helper).
+    #[cfg(test)]
+    pub(crate) fn set_send_count(&mut self, count: u64) {
+        self.send_count = count;
     }
 
     /// Advance to the next epoch.
@@ -105,11 +196,11 @@ impl RekeyState {
     /// # Errors
     /// Returns `EpochExhaustion` if the epoch counter has reached the limit.
     pub fn advance_epoch(&mut self) -> Result<(), CryptoError> {
-        if self.epoch == MAX_EPOCH {
+        if self.epoch >= self.epoch_limit {
             return Err(CryptoError::EpochExhaustion);
         }
         self.epoch += 1;
-        self.epoch_start = Instant::now();
+        self.epoch_start = self.clock.now();
         self.send_count = 0;
         self.recv_count = 0;
         Ok(())
@@ -130,6 +221,8 @@ pub struct OldKeyRetention {
     responder_key: Option<SessionKey>,
     /// When the old keys were retained
     retained_at: Option<Instant>,
+    /// Time source
+    clock: SharedClock,
 }
 
 impl OldKeyRetention {
@@ -139,14 +232,21 @@ impl OldKeyRetention {
             initiator_key: None,
             responder_key: None,
             retained_at: None,
+            clock: system_clock(),
         }
     }
 
+    /// Use a custom time source instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
     /// Retain the current keys as old keys.
     pub fn retain(&mut self, initiator_key: SessionKey, responder_key: SessionKey) {
         self.initiator_key = Some(initiator_key);
         self.responder_key = Some(responder_key);
-        self.retained_at = Some(Instant::now());
+        self.retained_at = Some(self.clock.now());
     }
 
     /// Get the old initiator key if still within retention window.
@@ -169,8 +269,9 @@ impl OldKeyRetention {
 
     /// Check if we're within the retention window.
     pub fn within_retention_window(&self) -> bool {
+        let now = self.clock.now();
         self.retained_at
-            .is_some_and(|t| t.elapsed() < OLD_KEY_RETENTION)
+            .is_some_and(|t| now.saturating_duration_since(t) < OLD_KEY_RETENTION)
     }
 
     /// Clear old keys (call after retention window expires or explicitly).
@@ -182,8 +283,9 @@ impl OldKeyRetention {
 
     /// Check if old keys should be cleared due to expired retention.
     pub fn should_clear(&self) -> bool {
+        let now = self.clock.now();
         self.retained_at
-            .is_some_and(|t| t.elapsed() >= OLD_KEY_RETENTION)
+            .is_some_and(|t| now.saturating_duration_since(t) >= OLD_KEY_RETENTION)
     }
 
     /// Clear old keys if retention has expired.
@@ -254,6 +356,7 @@ pub fn derive_rekey_keys(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::TestClock;
 
     #[test]
     fn test_rekey_state_new() {
@@ -321,6 +424,57 @@ mod tests {
         assert!(retention.old_responder_key().is_some());
     }
 
+    #[test]
+    fn test_needs_rekey_soon_time_lookahead() {
+        let clock = TestClock::new();
+        let state = RekeyState::new().with_clock(clock.shared());
+        let margin = Duration::from_secs(10);
+
+        clock.advance(REKEY_AFTER_TIME - margin - Duration::from_secs(1));
+        assert!(!state.needs_rekey_soon(margin));
+
+        // Inside the margin: the lookahead fires before the soft limit
+        clock.advance(Duration::from_secs(1));
+        assert!(state.needs_rekey_soon(margin));
+        assert!(!state.should_rekey());
+
+        clock.advance(margin);
+        assert!(state.should_rekey());
+    }
+
+    #[test]
+    fn test_needs_rekey_soon_message_lookahead() {
+        let mut state = RekeyState::new();
+        state.set_message_margin(100);
+
+        state.set_send_count(REKEY_AFTER_MESSAGES - 101);
+        assert!(!state.needs_rekey_soon(Duration::ZERO));
+
+        state.set_send_count(REKEY_AFTER_MESSAGES - 100);
+        assert!(state.needs_rekey_soon(Duration::ZERO));
+        assert!(!state.should_rekey());
+    }
+
+    #[test]
+    fn test_old_key_retention_expires_on_test_clock() {
+        let clock = TestClock::new();
+        let mut retention = OldKeyRetention::new().with_clock(clock.shared());
+
+        retention.retain(
+            SessionKey::from_bytes([0x01; SESSION_KEY_SIZE]),
+            SessionKey::from_bytes([0x02; SESSION_KEY_SIZE]),
+        );
+
+        clock.advance(OLD_KEY_RETENTION - Duration::from_millis(1));
+        assert!(retention.old_initiator_key().is_some());
+        assert!(!retention.should_clear());
+
+        clock.advance(Duration::from_millis(1));
+        assert!(retention.old_initiator_key().is_none());
+        retention.clear_if_expired();
+        assert!(!retention.should_clear());
+    }
+
     #[test]
     fn test_derive_rekey_keys() {
         let handshake_hash = [0x42u8; 32];
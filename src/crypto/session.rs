@@ -7,15 +7,105 @@
 //! - Anti-replay protection via sliding window
 //! - Epoch/counter tracking
 
-use crate::core::{CryptoError, HASH_SIZE, REPLAY_WINDOW_SIZE};
+use std::marker::PhantomData;
+use std::time::Duration;
+
+#[cfg(feature = "session-resumption")]
+use zeroize::{Zeroize, Zeroizing};
+
+#[cfg(feature = "session-resumption")]
+use crate::core::SESSION_ID_SIZE;
+use crate::core::{
+    CryptoError, SharedClock, FRAME_TYPE_CLOSE, FRAME_TYPE_REKEY, HASH_SIZE, PROTOCOL_VERSION,
+    PUBLIC_KEY_SIZE, RECOMMENDED_MAX_PAYLOAD, REPLAY_WINDOW_SIZE,
+};
+#[cfg(feature = "extensions")]
+use crate::extensions::ExtensionSet;
+
+#[cfg(feature = "session-resumption")]
+use super::aead::SESSION_KEY_SIZE;
 
 use super::{
-    aead::{construct_aad, decrypt, encrypt, SessionKey},
+    aead::{construct_aad, AeadCipher, SessionKey, XChaCha20Poly1305Cipher},
     nonce::{construct_nonce, Direction},
     rekey::{OldKeyRetention, RekeyState},
-    Role, SessionId,
+    HandshakeProfile, Role, SessionId,
 };
 
+/// Default AEAD used for DATA frames (see 1-SECURITY.md).
+pub const FRAME_AEAD: &str = "XChaCha20-Poly1305";
+
+/// Number of 64-bit words in the replay bitmap.
+const REPLAY_BITMAP_WORDS: usize = REPLAY_WINDOW_SIZE / 64;
+
+/// Exported anti-replay state, for persisting across session resumption.
+///
+/// Produced by [`ReplayWindow::export`] and restored with
+/// [`ReplayWindow::import`]. Fields are plain data so callers can store
+/// them however they like; [`to_bytes`](Self::to_bytes) provides a compact
+/// binary encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayWindowState {
+    /// Seen-nonce bitmap; bit `i` marks `highest - i` as seen
+    pub bitmap: Vec<u64>,
+    /// Highest nonce seen so far
+    pub highest: u64,
+    /// Whether any nonce has been seen
+    pub initialized: bool,
+}
+
+impl ReplayWindowState {
+    /// Encoded size: initialized (1) + highest (8) + bitmap.
+    pub const ENCODED_SIZE: usize = 1 + 8 + REPLAY_BITMAP_WORDS * 8;
+
+    /// Encode as `[initialized:1][highest:8 LE][bitmap words: LE64...]`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(1 + 8 + self.bitmap.len() * 8);
+        buf.push(self.initialized as u8);
+        buf.extend_from_slice(&self.highest.to_le_bytes());
+        for word in &self.bitmap {
+            buf.extend_from_slice(&word.to_le_bytes());
+        }
+        buf
+    }
+
+    /// Decode from the format produced by [`to_bytes`](Self::to_bytes).
+    ///
+    /// # Errors
+    /// Returns `InvalidReplayState` if the length or flag byte is invalid.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, CryptoError> {
+        if data.len() != Self::ENCODED_SIZE {
+            return Err(CryptoError::InvalidReplayState(format!(
+                "expected {} bytes, got {}",
+                Self::ENCODED_SIZE,
+                data.len()
+            )));
+        }
+
+        let initialized = match data[0] {
+            0 => false,
+            1 => true,
+            other => {
+                return Err(CryptoError::InvalidReplayState(format!(
+                    "invalid initialized flag: 0x{:02x}",
+                    other
+                )))
+            }
+        };
+        let highest = u64::from_le_bytes(data[1..9].try_into().expect("length checked above"));
+        let bitmap = data[9..]
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().expect("chunk is 8 bytes")))
+            .collect();
+
+        Ok(Self {
+            bitmap,
+            highest,
+            initialized,
+        })
+    }
+}
+
 /// Anti-replay sliding window.
 ///
 /// Per 1-SECURITY.md:
@@ -25,7 +115,7 @@ use super::{
 /// - Above highest: Update window
 pub struct ReplayWindow {
     /// Bitmap for tracking seen nonces
-    bitmap: [u64; REPLAY_WINDOW_SIZE / 64],
+    bitmap: [u64; REPLAY_BITMAP_WORDS],
     /// Highest nonce seen so far
     highest: u64,
     /// Whether we've seen any packets yet
@@ -36,12 +126,64 @@ impl ReplayWindow {
     /// Create a new replay window.
     pub fn new() -> Self {
         Self {
-            bitmap: [0; REPLAY_WINDOW_SIZE / 64],
+            bitmap: [0; REPLAY_BITMAP_WORDS],
             highest: 0,
             initialized: false,
         }
     }
 
+    /// Get the highest nonce seen, or `None` if no nonce has been seen.
+    pub fn highest_seen(&self) -> Option<u64> {
+        self.initialized.then_some(self.highest)
+    }
+
+    /// Export the window state for persistence.
+    pub fn export(&self) -> ReplayWindowState {
+        ReplayWindowState {
+            bitmap: self.bitmap.to_vec(),
+            highest: self.highest,
+            initialized: self.initialized,
+        }
+    }
+
+    /// Restore a window from exported state.
+    ///
+    /// The bitmap is restored exactly, so nonces seen before the export are
+    /// still rejected afterwards.
+    ///
+    /// # Errors
+    /// Returns `InvalidReplayState` if the bitmap length is not
+    /// `REPLAY_WINDOW_SIZE / 64`, or if the state is internally inconsistent.
+    pub fn import(state: ReplayWindowState) -> Result<Self, CryptoError> {
+        let bitmap: [u64; REPLAY_BITMAP_WORDS] =
+            state.bitmap.as_slice().try_into().map_err(|_| {
+                CryptoError::InvalidReplayState(format!(
+                    "bitmap has {} words, expected {}",
+                    state.bitmap.len(),
+                    REPLAY_BITMAP_WORDS
+                ))
+            })?;
+
+        if state.initialized {
+            // The highest nonce is always marked as seen
+            if bitmap[0] & 1 == 0 {
+                return Err(CryptoError::InvalidReplayState(
+                    "highest nonce not marked as seen".into(),
+                ));
+            }
+        } else if state.highest != 0 || bitmap.iter().any(|&word| word != 0) {
+            return Err(CryptoError::InvalidReplayState(
+                "uninitialized window has non-empty state".into(),
+            ));
+        }
+
+        Ok(Self {
+            bitmap,
+            highest: state.highest,
+            initialized: state.initialized,
+        })
+    }
+
     /// Check if a nonce is a replay (without updating).
     pub fn is_replay(&self, nonce: u64) -> bool {
         if !self.initialized {
@@ -141,7 +283,7 @@ impl ReplayWindow {
     fn shift_window(&mut self, shift: u64) {
         if shift >= REPLAY_WINDOW_SIZE as u64 {
             // Complete reset - all previous nonces fall outside the window
-            self.bitmap = [0; REPLAY_WINDOW_SIZE / 64];
+            self.bitmap = [0; REPLAY_BITMAP_WORDS];
             return;
         }
 
@@ -174,7 +316,7 @@ impl ReplayWindow {
 
     /// Reset the window (e.g., after rekey).
     pub fn reset(&mut self) {
-        self.bitmap = [0; REPLAY_WINDOW_SIZE / 64];
+        self.bitmap = [0; REPLAY_BITMAP_WORDS];
         self.highest = 0;
         self.initialized = false;
     }
@@ -186,11 +328,59 @@ impl Default for ReplayWindow {
     }
 }
 
+/// Keys for the next epoch, staged by [`CryptoSession::begin_rekey`].
+pub struct PendingRekey {
+    /// Epoch the staged keys belong to
+    epoch: u32,
+    /// New initiator key
+    initiator_key: SessionKey,
+    /// New responder key
+    responder_key: SessionKey,
+}
+
+impl PendingRekey {
+    /// Get the epoch these keys belong to.
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+}
+
+/// Everything negotiated for a connection, in one place.
+///
+/// Produced by [`CryptoSession::connection_info`] for logging and UI.
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    /// Session ID, assigned by the responder in the handshake response.
+    pub session_id: SessionId,
+    /// Our role in the handshake.
+    pub role: Role,
+    /// Protocol version from the handshake header (`PROTOCOL_VERSION`
+    /// unless set with [`CryptoSession::with_protocol_version`]).
+    pub protocol_version: u16,
+    /// Handshake profile; determines the Noise pattern.
+    pub profile: HandshakeProfile,
+    /// Noise protocol name, from the profile.
+    pub handshake_pattern: &'static str,
+    /// AEAD for DATA frames ([`FRAME_AEAD`] unless another suite was chosen).
+    pub frame_aead: &'static str,
+    /// Maximum sync payload per frame, derived from the path MTU.
+    pub max_payload: usize,
+    /// Current key epoch.
+    pub epoch: u32,
+    /// Peer's authenticated static public key, from the Noise handshake.
+    /// `None` for an anonymous initiator, or if not recorded.
+    pub peer_public_key: Option<[u8; PUBLIC_KEY_SIZE]>,
+    /// Extensions agreed in negotiation, with their parameters.
+    #[cfg(feature = "extensions")]
+    pub extensions: ExtensionSet,
+}
+
 /// A complete crypto session for secure communication.
 ///
 /// Combines key management, nonce construction, AEAD, and anti-replay
-/// into a single interface.
-pub struct CryptoSession {
+/// into a single interface. The frame cipher is the type parameter `C`,
+/// XChaCha20-Poly1305 by default.
+pub struct CryptoSession<C: AeadCipher = XChaCha20Poly1305Cipher> {
     /// Session ID
     session_id: SessionId,
     /// Our role (initiator or responder)
@@ -203,10 +393,29 @@ pub struct CryptoSession {
     rekey_state: RekeyState,
     /// Replay window for incoming packets
     replay_window: ReplayWindow,
+    /// Replay window for the previous epoch, used with the old keys
+    old_replay_window: ReplayWindow,
     /// Old key retention for late packets during rekey
     old_keys: OldKeyRetention,
     /// Handshake hash for key derivation
     handshake_hash: [u8; HASH_SIZE],
+    /// Handshake profile used to establish the session
+    profile: HandshakeProfile,
+    /// Protocol version agreed in the handshake
+    protocol_version: u16,
+    /// Maximum sync payload per frame
+    max_payload: usize,
+    /// Peer's static public key, if authenticated
+    peer_public_key: Option<[u8; PUBLIC_KEY_SIZE]>,
+    /// Negotiated extensions
+    #[cfg(feature = "extensions")]
+    extensions: ExtensionSet,
+    /// Frame cipher suite
+    cipher: PhantomData<fn() -> C>,
+    /// Highest (epoch, counter) sent, to catch nonce reuse in debug
+    /// builds; compiled out of release builds
+    #[cfg(debug_assertions)]
+    last_sent_nonce: Option<(u32, u64)>,
 }
 
 impl CryptoSession {
@@ -217,6 +426,22 @@ impl CryptoSession {
         send_key: SessionKey,
         recv_key: SessionKey,
         handshake_hash: [u8; HASH_SIZE],
+    ) -> Self {
+        Self::with_cipher(session_id, role, send_key, recv_key, handshake_hash)
+    }
+}
+
+impl<C: AeadCipher> CryptoSession<C> {
+    /// Create a new crypto session using the frame cipher `C`.
+    ///
+    /// The handshake must have been run with `C::SUITE` so the suite is
+    /// bound into its transcript.
+    pub fn with_cipher(
+        session_id: SessionId,
+        role: Role,
+        send_key: SessionKey,
+        recv_key: SessionKey,
+        handshake_hash: [u8; HASH_SIZE],
     ) -> Self {
         Self {
             session_id,
@@ -225,8 +450,75 @@ impl CryptoSession {
             recv_key,
             rekey_state: RekeyState::new(),
             replay_window: ReplayWindow::new(),
+            old_replay_window: ReplayWindow::new(),
             old_keys: OldKeyRetention::new(),
             handshake_hash,
+            profile: HandshakeProfile::default(),
+            protocol_version: PROTOCOL_VERSION,
+            max_payload: RECOMMENDED_MAX_PAYLOAD,
+            peer_public_key: None,
+            #[cfg(feature = "extensions")]
+            extensions: ExtensionSet::new(),
+            cipher: PhantomData,
+            #[cfg(debug_assertions)]
+            last_sent_nonce: None,
+        }
+    }
+
+    /// Record the handshake profile the session was established with.
+    pub fn with_profile(mut self, profile: HandshakeProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Record the protocol version from the handshake header.
+    pub fn with_protocol_version(mut self, version: u16) -> Self {
+        self.protocol_version = version;
+        self
+    }
+
+    /// Record the maximum sync payload per frame.
+    pub fn with_max_payload(mut self, max_payload: usize) -> Self {
+        self.max_payload = max_payload;
+        self
+    }
+
+    /// Record the peer's authenticated static public key.
+    pub fn with_peer_public_key(mut self, key: [u8; PUBLIC_KEY_SIZE]) -> Self {
+        self.peer_public_key = Some(key);
+        self
+    }
+
+    /// Use a custom time source for rekey and old-key timers.
+    ///
+    /// The current epoch restarts at the clock's `now`.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.rekey_state = std::mem::take(&mut self.rekey_state).with_clock(clock.clone());
+        self.old_keys = std::mem::take(&mut self.old_keys).with_clock(clock);
+        self
+    }
+
+    /// Record the negotiated extensions.
+    #[cfg(feature = "extensions")]
+    pub fn with_extensions(mut self, extensions: ExtensionSet) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// Get everything negotiated for this session as one struct.
+    pub fn connection_info(&self) -> ConnectionInfo {
+        ConnectionInfo {
+            session_id: self.session_id,
+            role: self.role,
+            protocol_version: self.protocol_version,
+            profile: self.profile,
+            handshake_pattern: self.profile.pattern(),
+            frame_aead: C::SUITE.name(),
+            max_payload: self.max_payload,
+            epoch: self.epoch(),
+            peer_public_key: self.peer_public_key,
+            #[cfg(feature = "extensions")]
+            extensions: self.extensions.clone(),
         }
     }
 
@@ -235,11 +527,25 @@ impl CryptoSession {
         &self.session_id
     }
 
+    /// Get the protocol version agreed in the handshake.
+    pub fn protocol_version(&self) -> u16 {
+        self.protocol_version
+    }
+
     /// Get the current role.
     pub fn role(&self) -> Role {
         self.role
     }
 
+    /// Get the peer's authenticated static public key, for authorizing
+    /// the session against an allow-list.
+    ///
+    /// `None` if the peer did not authenticate (an anonymous initiator) or
+    /// the key was never recorded with [`with_peer_public_key`](Self::with_peer_public_key).
+    pub fn peer_static_public_key(&self) -> Option<&[u8; PUBLIC_KEY_SIZE]> {
+        self.peer_public_key.as_ref()
+    }
+
     /// Get the current epoch.
     pub fn epoch(&self) -> u32 {
         self.rekey_state.epoch()
@@ -250,11 +556,43 @@ impl CryptoSession {
         self.rekey_state.should_rekey()
     }
 
+    /// Check if a rekey should start now to finish before the soft limit.
+    ///
+    /// See [`RekeyState::needs_rekey_soon`]; the message-count margin is
+    /// set with [`set_rekey_message_margin`](Self::set_rekey_message_margin).
+    pub fn needs_rekey_soon(&self, margin: Duration) -> bool {
+        self.rekey_state.needs_rekey_soon(margin)
+    }
+
+    /// Set the message-count lookahead used by [`needs_rekey_soon`](Self::needs_rekey_soon).
+    pub fn set_rekey_message_margin(&mut self, margin: u64) {
+        self.rekey_state.set_message_margin(margin);
+    }
+
+    /// Lower the send counter's hard limit below `REJECT_AFTER_MESSAGES`.
+    ///
+    /// See [`RekeyState::set_counter_limit`].
+    pub fn set_send_counter_limit(&mut self, limit: u64) {
+        self.rekey_state.set_counter_limit(limit);
+    }
+
+    /// Lower the last epoch a rekey may reach below `MAX_EPOCH`.
+    ///
+    /// See [`RekeyState::set_epoch_limit`].
+    pub fn set_epoch_limit(&mut self, limit: u32) {
+        self.rekey_state.set_epoch_limit(limit);
+    }
+
     /// Check if keys are expired (session must terminate).
     pub fn keys_expired(&self) -> bool {
         self.rekey_state.keys_expired()
     }
 
+    /// Check if another rekey is possible (epoch limit not reached).
+    pub fn can_rekey(&self) -> bool {
+        self.rekey_state.can_rekey()
+    }
+
     /// Get the direction for sending based on our role.
     fn send_direction(&self) -> Direction {
         match self.role {
@@ -271,21 +609,74 @@ impl CryptoSession {
     /// Encrypt a frame for sending.
     ///
     /// Returns (nonce_counter, ciphertext).
+    ///
+    /// # Errors
+    /// Returns `SessionExpired` once the keys have passed
+    /// `REJECT_AFTER_TIME`; the session must rekey or terminate.
+    ///
+    /// Returns `CounterExhaustion` once the send counter is within
+    /// `CONTROL_FRAME_HEADROOM` of its hard limit, unless the frame is a
+    /// REKEY or CLOSE; those may use the reserved counters. The caller can
+    /// then rekey and retry, or close the session at the epoch limit.
+    ///
+    /// # Panics
+    /// In debug builds, panics if the (epoch, direction, counter) nonce was
+    /// already used by this session, which would mean a counter or rekey
+    /// bug. The check is compiled out of release builds.
     pub fn encrypt_frame(
         &mut self,
         frame_type: u8,
         flags: u8,
         plaintext: &[u8],
     ) -> Result<(u64, Vec<u8>), CryptoError> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!(
+            "encrypt_frame",
+            session_id = ?self.session_id,
+            epoch = self.rekey_state.epoch(),
+            frame_type,
+            nonce = tracing::field::Empty,
+        )
+        .entered();
+
+        if self.rekey_state.keys_expired() {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("refusing to encrypt: session keys expired");
+            return Err(CryptoError::SessionExpired);
+        }
+
+        // Keep the last counters for the REKEY exchange or a CLOSE
+        let control = frame_type == FRAME_TYPE_REKEY || frame_type == FRAME_TYPE_CLOSE;
+        if !control && self.rekey_state.counter_reserved() {
+            return Err(CryptoError::CounterExhaustion);
+        }
+
         // Get counter and construct nonce
         let counter = self.rekey_state.increment_send()?;
+        #[cfg(feature = "tracing")]
+        span.record("nonce", counter);
         let nonce = construct_nonce(self.rekey_state.epoch(), self.send_direction(), counter);
 
+        #[cfg(debug_assertions)]
+        {
+            // Counters only move forward within an epoch and epochs only
+            // move forward, so each nonce must sort after the last one
+            let current = (self.rekey_state.epoch(), counter);
+            debug_assert!(
+                self.last_sent_nonce.is_none_or(|last| current > last),
+                "nonce reuse: epoch {}, direction {}, counter {}",
+                current.0,
+                self.send_direction().as_byte(),
+                current.1
+            );
+            self.last_sent_nonce = Some(current);
+        }
+
         // Construct AAD
         let aad = construct_aad(frame_type, flags, self.session_id.as_bytes(), counter);
 
         // Encrypt
-        let ciphertext = encrypt(&self.send_key, &nonce, &aad, plaintext)?;
+        let ciphertext = C::encrypt(&self.send_key, &nonce, &aad, plaintext)?;
 
         Ok((counter, ciphertext))
     }
@@ -293,6 +684,10 @@ impl CryptoSession {
     /// Decrypt a received frame.
     ///
     /// Performs replay check BEFORE decryption per spec.
+    ///
+    /// # Errors
+    /// Returns `SessionExpired` once the keys have passed
+    /// `REJECT_AFTER_TIME`; the session must rekey or terminate.
     pub fn decrypt_frame(
         &mut self,
         frame_type: u8,
@@ -300,17 +695,56 @@ impl CryptoSession {
         nonce_counter: u64,
         ciphertext: &[u8],
     ) -> Result<Vec<u8>, CryptoError> {
-        // 1. Replay check FIRST (cheap, prevents DoS)
-        if self.replay_window.is_replay(nonce_counter) {
-            return Err(CryptoError::ReplayDetected);
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!(
+            "decrypt_frame",
+            session_id = ?self.session_id,
+            epoch = self.rekey_state.epoch(),
+            frame_type,
+            nonce = nonce_counter,
+            outcome = tracing::field::Empty,
+        )
+        .entered();
+
+        let result = self.open_frame(frame_type, flags, nonce_counter, ciphertext);
+
+        #[cfg(feature = "tracing")]
+        match &result {
+            Ok(_) => {
+                span.record("outcome", "ok");
+            }
+            Err(err) => {
+                span.record("outcome", tracing::field::display(err));
+                tracing::debug!(error = %err, "frame rejected");
+            }
         }
 
+        result
+    }
+
+    /// Replay-check and decrypt a frame under the current or old keys.
+    fn open_frame(
+        &mut self,
+        frame_type: u8,
+        flags: u8,
+        nonce_counter: u64,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        if self.rekey_state.keys_expired() {
+            return Err(CryptoError::SessionExpired);
+        }
+
+        // 1. Replay check FIRST (cheap, prevents DoS)
+        let current_replay = self.replay_window.is_replay(nonce_counter);
+
         // Construct nonce and AAD
         let nonce = construct_nonce(self.rekey_state.epoch(), self.recv_direction(), nonce_counter);
         let aad = construct_aad(frame_type, flags, self.session_id.as_bytes(), nonce_counter);
 
         // 2. Try current keys first
-        if let Ok(plaintext) = decrypt(&self.recv_key, &nonce, &aad, ciphertext) {
+        if !current_replay
+            && let Ok(plaintext) = C::decrypt(&self.recv_key, &nonce, &aad, ciphertext)
+        {
             // 3. Update replay window only after successful verification
             let _ = self.replay_window.check_and_update(nonce_counter);
             self.rekey_state.record_recv(nonce_counter);
@@ -320,18 +754,26 @@ impl CryptoSession {
         // 4. Try old keys if within retention window
         self.old_keys.clear_if_expired();
         if let Some(old_recv_key) = self.get_old_recv_key() {
+            // Old epoch has its own counter space, and its own replay window
+            if self.old_replay_window.is_replay(nonce_counter) {
+                return Err(CryptoError::ReplayDetected);
+            }
+
             // Try with previous epoch's nonce
             let old_epoch = self.rekey_state.epoch().saturating_sub(1);
             let old_nonce = construct_nonce(old_epoch, self.recv_direction(), nonce_counter);
 
-            if let Ok(plaintext) = decrypt(old_recv_key, &old_nonce, &aad, ciphertext) {
-                // Note: Don't update replay window for old epoch packets
-                // (they have their own counter space)
+            if let Ok(plaintext) = C::decrypt(old_recv_key, &old_nonce, &aad, ciphertext) {
+                let _ = self.old_replay_window.check_and_update(nonce_counter);
                 return Ok(plaintext);
             }
         }
 
-        Err(CryptoError::DecryptionFailed)
+        if current_replay {
+            Err(CryptoError::ReplayDetected)
+        } else {
+            Err(CryptoError::DecryptionFailed)
+        }
     }
 
     /// Get the old receive key based on role.
@@ -344,38 +786,383 @@ impl CryptoSession {
 
     /// Perform a rekey operation.
     ///
-    /// Advances the epoch and derives new keys.
+    /// Advances the epoch and derives new keys. Equivalent to
+    /// [`begin_rekey`](Self::begin_rekey) followed immediately by
+    /// [`complete_rekey`](Self::complete_rekey).
     pub fn rekey(&mut self) -> Result<(), CryptoError> {
+        let pending = self.begin_rekey()?;
+        self.complete_rekey(pending)
+    }
+
+    /// Stage a rekey without switching keys yet.
+    ///
+    /// Derives the next epoch's keys but leaves the current keys and epoch
+    /// in place, so frames can keep flowing under the current epoch until
+    /// the caller decides to switch. Typically the responder stages the
+    /// rekey, sends its REKEY reply under the current keys, then completes;
+    /// the initiator completes as soon as it receives that reply.
+    ///
+    /// Staging consumes no randomness: the new keys are derived from the
+    /// handshake hash and the epoch, so both peers arrive at the same keys.
+    ///
+    /// # Errors
+    /// Returns `EpochExhaustion` if the epoch limit has been reached.
+    pub fn begin_rekey(&self) -> Result<PendingRekey, CryptoError> {
         use super::rekey::derive_rekey_keys;
 
-        // Retain current keys
-        self.old_keys
-            .retain(self.send_key.clone(), self.recv_key.clone());
+        if !self.can_rekey() {
+            return Err(CryptoError::EpochExhaustion);
+        }
+
+        let epoch = self.rekey_state.epoch() + 1;
+        let (initiator_key, responder_key) = derive_rekey_keys(&self.handshake_hash, epoch)?;
+
+        Ok(PendingRekey {
+            epoch,
+            initiator_key,
+            responder_key,
+        })
+    }
+
+    /// Switch to the keys staged by [`begin_rekey`](Self::begin_rekey).
+    ///
+    /// The current keys are retained for `OLD_KEY_RETENTION`, so frames
+    /// already in flight under the previous epoch still decrypt.
+    ///
+    /// # Errors
+    /// Returns `StaleRekey` if `pending` was not staged for the next epoch
+    /// (e.g. the session rekeyed since), leaving the session unchanged, and
+    /// `EpochExhaustion` if the epoch limit has been reached.
+    pub fn complete_rekey(&mut self, pending: PendingRekey) -> Result<(), CryptoError> {
+        let expected = self.rekey_state.epoch().wrapping_add(1);
+        if pending.epoch != expected {
+            return Err(CryptoError::StaleRekey {
+                staged: pending.epoch,
+                expected,
+            });
+        }
 
         // Advance epoch
         self.rekey_state.advance_epoch()?;
 
-        // Derive new keys
-        let (new_initiator_key, new_responder_key) =
-            derive_rekey_keys(&self.handshake_hash, self.rekey_state.epoch())?;
+        // Retain previous keys, ordered by role
+        let (old_initiator_key, old_responder_key) = match self.role {
+            Role::Initiator => (self.send_key.clone(), self.recv_key.clone()),
+            Role::Responder => (self.recv_key.clone(), self.send_key.clone()),
+        };
+        self.old_keys.retain(old_initiator_key, old_responder_key);
 
         // Update keys based on role
         match self.role {
             Role::Initiator => {
-                self.send_key = new_initiator_key;
-                self.recv_key = new_responder_key;
+                self.send_key = pending.initiator_key;
+                self.recv_key = pending.responder_key;
             }
             Role::Responder => {
-                self.send_key = new_responder_key;
-                self.recv_key = new_initiator_key;
+                self.send_key = pending.responder_key;
+                self.recv_key = pending.initiator_key;
             }
         }
 
-        // Reset replay window for new epoch
-        self.replay_window.reset();
+        // Start a fresh replay window for the new epoch. The outgoing window
+        // moves with the old keys, so frames already accepted under the
+        // previous epoch can't be replayed through the old-key fallback.
+        self.old_replay_window = std::mem::take(&mut self.replay_window);
 
         Ok(())
     }
+
+    /// Rekey immediately, regardless of [`should_rekey`](Self::should_rekey).
+    ///
+    /// Bounds exposure if only the current traffic keys leak: frames sent
+    /// before the rekey were encrypted under the previous epoch's keys.
+    /// It is not forward secrecy against a full session compromise. Every
+    /// epoch's keys derive from the retained handshake hash and the epoch
+    /// number, and `export_state` (session resumption) exports that hash,
+    /// so anyone holding it can decrypt earlier epochs. Only a fresh
+    /// handshake gives that guarantee.
+    ///
+    /// The caller is responsible for sending the REKEY frame to the peer.
+    /// The exchange costs one round trip, during which late frames from the
+    /// previous epoch are still accepted via the old key retention window.
+    ///
+    /// # Errors
+    /// Returns `EpochExhaustion` if the epoch limit has been reached. The
+    /// session keys are left untouched in that case.
+    pub fn force_rekey(&mut self) -> Result<(), CryptoError> {
+        if !self.can_rekey() {
+            return Err(CryptoError::EpochExhaustion);
+        }
+        self.rekey()
+    }
+}
+
+/// Live session state for resuming without a new handshake.
+///
+/// Produced by [`CryptoSession::export_state`] and consumed by
+/// [`CryptoSession::import_state`], e.g. to survive a mobile app being
+/// backgrounded and killed.
+///
+/// # Security
+///
+/// A ticket contains the current session keys in the clear. Anyone who
+/// obtains it can decrypt and forge traffic for the session until the next
+/// rekey. Store it encrypted at rest (e.g. in the platform keychain), and
+/// import each ticket at most once: resuming twice from the same ticket
+/// reuses nonces, which breaks the AEAD. Key material is zeroized on drop.
+///
+/// Keys retained from the previous epoch are not exported, so late frames
+/// from before the last rekey are rejected after resumption. Negotiated
+/// extensions are not included either; reapply them with
+/// [`CryptoSession::with_extensions`].
+#[cfg(feature = "session-resumption")]
+pub struct SessionResumptionTicket {
+    session_id: SessionId,
+    role: Role,
+    profile: HandshakeProfile,
+    protocol_version: u16,
+    max_payload: u32,
+    peer_public_key: Option<[u8; PUBLIC_KEY_SIZE]>,
+    epoch: u32,
+    epoch_age: Duration,
+    send_count: u64,
+    recv_count: u64,
+    send_key: SessionKey,
+    recv_key: SessionKey,
+    handshake_hash: [u8; HASH_SIZE],
+    replay_window: ReplayWindowState,
+}
+
+#[cfg(feature = "session-resumption")]
+impl SessionResumptionTicket {
+    /// Ticket format version.
+    const FORMAT_VERSION: u8 = 1;
+
+    /// Encoded size in bytes.
+    pub const ENCODED_SIZE: usize = 1 // format version
+        + SESSION_ID_SIZE
+        + 1 // role
+        + 1 // profile
+        + 2 // protocol version
+        + 4 // max payload
+        + 4 // epoch
+        + 8 // epoch age (ms)
+        + 8 // send count
+        + 8 // recv count
+        + 2 * SESSION_KEY_SIZE
+        + HASH_SIZE
+        + 1 + PUBLIC_KEY_SIZE // peer key flag + key
+        + ReplayWindowState::ENCODED_SIZE;
+
+    /// Get the session ID.
+    pub fn session_id(&self) -> &SessionId {
+        &self.session_id
+    }
+
+    /// Get the key epoch at export.
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+
+    /// Encode the ticket for storage.
+    ///
+    /// The returned buffer is zeroized on drop.
+    pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let mut buf = Zeroizing::new(Vec::with_capacity(Self::ENCODED_SIZE));
+        buf.push(Self::FORMAT_VERSION);
+        buf.extend_from_slice(&self.session_id.0);
+        buf.push(match self.role {
+            Role::Initiator => 0,
+            Role::Responder => 1,
+        });
+        buf.push(match self.profile {
+            HandshakeProfile::Authenticated => 0,
+            HandshakeProfile::Anonymous => 1,
+        });
+        buf.extend_from_slice(&self.protocol_version.to_le_bytes());
+        buf.extend_from_slice(&self.max_payload.to_le_bytes());
+        buf.extend_from_slice(&self.epoch.to_le_bytes());
+        buf.extend_from_slice(&(self.epoch_age.as_millis() as u64).to_le_bytes());
+        buf.extend_from_slice(&self.send_count.to_le_bytes());
+        buf.extend_from_slice(&self.recv_count.to_le_bytes());
+        buf.extend_from_slice(self.send_key.as_bytes());
+        buf.extend_from_slice(self.recv_key.as_bytes());
+        buf.extend_from_slice(&self.handshake_hash);
+        buf.push(self.peer_public_key.is_some() as u8);
+        buf.extend_from_slice(&self.peer_public_key.unwrap_or([0; PUBLIC_KEY_SIZE]));
+        buf.extend_from_slice(&self.replay_window.to_bytes());
+        buf
+    }
+
+    /// Decode a ticket produced by [`to_bytes`](Self::to_bytes).
+    ///
+    /// # Errors
+    /// Returns `InvalidResumptionTicket` if the length, version or any
+    /// field is invalid.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, CryptoError> {
+        let invalid = |msg: String| CryptoError::InvalidResumptionTicket(msg);
+        if data.len() != Self::ENCODED_SIZE {
+            return Err(invalid(format!(
+                "expected {} bytes, got {}",
+                Self::ENCODED_SIZE,
+                data.len()
+            )));
+        }
+        if data[0] != Self::FORMAT_VERSION {
+            return Err(invalid(format!("unknown format version {}", data[0])));
+        }
+
+        let mut reader = TicketReader { data, offset: 1 };
+        let session_id = SessionId(reader.array());
+        let role = match reader.array::<1>()[0] {
+            0 => Role::Initiator,
+            1 => Role::Responder,
+            other => return Err(invalid(format!("invalid role 0x{:02x}", other))),
+        };
+        let profile = match reader.array::<1>()[0] {
+            0 => HandshakeProfile::Authenticated,
+            1 => HandshakeProfile::Anonymous,
+            other => return Err(invalid(format!("invalid profile 0x{:02x}", other))),
+        };
+        let protocol_version = u16::from_le_bytes(reader.array());
+        let max_payload = u32::from_le_bytes(reader.array());
+        let epoch = u32::from_le_bytes(reader.array());
+        let epoch_age = Duration::from_millis(u64::from_le_bytes(reader.array()));
+        let send_count = u64::from_le_bytes(reader.array());
+        let recv_count = u64::from_le_bytes(reader.array());
+        let send_key = SessionKey::from_bytes(reader.array());
+        let recv_key = SessionKey::from_bytes(reader.array());
+        let handshake_hash = reader.array();
+        let peer_public_key = match reader.array::<1>()[0] {
+            0 => {
+                reader.array::<PUBLIC_KEY_SIZE>();
+                None
+            }
+            1 => Some(reader.array()),
+            other => return Err(invalid(format!("invalid peer key flag 0x{:02x}", other))),
+        };
+        let replay_window = ReplayWindowState::from_bytes(&data[reader.offset..])?;
+
+        Ok(Self {
+            session_id,
+            role,
+            profile,
+            protocol_version,
+            max_payload,
+            peer_public_key,
+            epoch,
+            epoch_age,
+            send_count,
+            recv_count,
+            send_key,
+            recv_key,
+            handshake_hash,
+            replay_window,
+        })
+    }
+}
+
+#[cfg(feature = "session-resumption")]
+impl Drop for SessionResumptionTicket {
+    fn drop(&mut self) {
+        // The session keys zeroize themselves
+        self.handshake_hash.zeroize();
+    }
+}
+
+#[cfg(feature = "session-resumption")]
+impl std::fmt::Debug for SessionResumptionTicket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SessionResumptionTicket")
+            .field("session_id", &self.session_id)
+            .field("role", &self.role)
+            .field("epoch", &self.epoch)
+            .field("send_count", &self.send_count)
+            .field("keys", &"[redacted]")
+            .finish()
+    }
+}
+
+/// Sequential fixed-size reads over a length-checked ticket.
+#[cfg(feature = "session-resumption")]
+struct TicketReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+#[cfg(feature = "session-resumption")]
+impl TicketReader<'_> {
+    fn array<const N: usize>(&mut self) -> [u8; N] {
+        let bytes = self.data[self.offset..self.offset + N]
+            .try_into()
+            .expect("ticket length checked before reading");
+        self.offset += N;
+        bytes
+    }
+}
+
+#[cfg(feature = "session-resumption")]
+impl CryptoSession {
+    /// Export the live session for later resumption.
+    ///
+    /// Consumes the session: sending after export and then resuming from
+    /// the ticket would reuse nonces. See [`SessionResumptionTicket`] for
+    /// how to handle the ticket safely.
+    pub fn export_state(self) -> SessionResumptionTicket {
+        SessionResumptionTicket {
+            session_id: self.session_id,
+            role: self.role,
+            profile: self.profile,
+            protocol_version: self.protocol_version,
+            max_payload: self.max_payload as u32,
+            peer_public_key: self.peer_public_key,
+            epoch: self.rekey_state.epoch(),
+            epoch_age: self.rekey_state.epoch_age(),
+            send_count: self.rekey_state.send_count(),
+            recv_count: self.rekey_state.recv_count(),
+            send_key: self.send_key.clone(),
+            recv_key: self.recv_key.clone(),
+            handshake_hash: self.handshake_hash,
+            replay_window: self.replay_window.export(),
+        }
+    }
+
+    /// Resume a session from an exported ticket.
+    ///
+    /// Counters and the replay window continue exactly where the exported
+    /// session left off.
+    ///
+    /// # Errors
+    /// Returns `InvalidReplayState` if the ticket's replay window is
+    /// inconsistent.
+    pub fn import_state(ticket: SessionResumptionTicket) -> Result<Self, CryptoError> {
+        let replay_window = ReplayWindow::import(ticket.replay_window.clone())?;
+        Ok(Self {
+            session_id: ticket.session_id,
+            role: ticket.role,
+            send_key: ticket.send_key.clone(),
+            recv_key: ticket.recv_key.clone(),
+            rekey_state: RekeyState::restore(
+                ticket.epoch,
+                ticket.epoch_age,
+                ticket.send_count,
+                ticket.recv_count,
+            ),
+            replay_window,
+            old_replay_window: ReplayWindow::new(),
+            old_keys: OldKeyRetention::new(),
+            handshake_hash: ticket.handshake_hash,
+            profile: ticket.profile,
+            protocol_version: ticket.protocol_version,
+            max_payload: ticket.max_payload as usize,
+            peer_public_key: ticket.peer_public_key,
+            #[cfg(feature = "extensions")]
+            extensions: ExtensionSet::new(),
+            cipher: PhantomData,
+            #[cfg(debug_assertions)]
+            last_sent_nonce: None,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -443,6 +1230,75 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_replay_window_highest_seen() {
+        let mut window = ReplayWindow::new();
+        assert_eq!(window.highest_seen(), None);
+
+        window.check_and_update(0).unwrap();
+        assert_eq!(window.highest_seen(), Some(0));
+
+        window.check_and_update(42).unwrap();
+        window.check_and_update(10).unwrap();
+        assert_eq!(window.highest_seen(), Some(42));
+    }
+
+    #[test]
+    fn test_replay_window_export_import_roundtrip() {
+        let mut window = ReplayWindow::new();
+        for nonce in [1, 5, 70, 200, 199, 3000] {
+            window.check_and_update(nonce).unwrap();
+        }
+
+        let state = window.export();
+        let restored = ReplayWindow::import(state.clone()).unwrap();
+        assert_eq!(restored.export(), state);
+
+        // Byte encoding round-trips too
+        let decoded = ReplayWindowState::from_bytes(&state.to_bytes()).unwrap();
+        assert_eq!(decoded, state);
+
+        // Uninitialized window round-trips
+        let empty = ReplayWindow::import(ReplayWindow::new().export()).unwrap();
+        assert_eq!(empty.highest_seen(), None);
+    }
+
+    #[test]
+    fn test_replay_window_import_rejects_seen_nonce() {
+        let mut window = ReplayWindow::new();
+        for nonce in 100..110 {
+            window.check_and_update(nonce).unwrap();
+        }
+
+        let mut restored = ReplayWindow::import(window.export()).unwrap();
+        assert!(restored.check_and_update(105).is_err());
+        assert!(restored.check_and_update(109).is_err());
+        assert!(restored.check_and_update(99).is_ok());
+        assert!(restored.check_and_update(110).is_ok());
+    }
+
+    #[test]
+    fn test_replay_window_import_malformed() {
+        let mut state = ReplayWindow::new().export();
+        state.bitmap.pop();
+        assert!(matches!(
+            ReplayWindow::import(state),
+            Err(CryptoError::InvalidReplayState(_))
+        ));
+
+        let mut state = ReplayWindow::new().export();
+        state.highest = 7;
+        assert!(matches!(
+            ReplayWindow::import(state),
+            Err(CryptoError::InvalidReplayState(_))
+        ));
+
+        assert!(matches!(
+            ReplayWindowState::from_bytes(&[0u8; 10]),
+            Err(CryptoError::InvalidReplayState(_))
+        ));
+    }
+
     #[test]
     fn test_crypto_session_roundtrip() {
         let session_id = SessionId::generate();
@@ -556,4 +1412,492 @@ mod tests {
             .decrypt_frame(0x04, 0x00, counter, &ciphertext)
             .is_err());
     }
+
+    fn session_pair() -> (CryptoSession, CryptoSession) {
+        let session_id = SessionId::generate();
+        let initiator_key = SessionKey::from_bytes([0x01; 32]);
+        let responder_key = SessionKey::from_bytes([0x02; 32]);
+        let handshake_hash = [0x42; 32];
+
+        let initiator = CryptoSession::new(
+            session_id,
+            Role::Initiator,
+            initiator_key.clone(),
+            responder_key.clone(),
+            handshake_hash,
+        );
+        let responder = CryptoSession::new(
+            session_id,
+            Role::Responder,
+            responder_key,
+            initiator_key,
+            handshake_hash,
+        );
+        (initiator, responder)
+    }
+
+    #[test]
+    fn test_expired_session_rejects_frames() {
+        let clock = crate::core::TestClock::new();
+        let (initiator, responder) = session_pair();
+        let mut initiator = initiator.with_clock(clock.shared());
+        let mut responder = responder.with_clock(clock.shared());
+
+        let (counter, ciphertext) = initiator.encrypt_frame(0x03, 0, b"late").unwrap();
+        clock.advance(crate::core::REJECT_AFTER_TIME);
+        assert!(initiator.keys_expired());
+
+        assert!(matches!(
+            initiator.encrypt_frame(0x03, 0, b"hello"),
+            Err(CryptoError::SessionExpired)
+        ));
+        assert!(matches!(
+            responder.decrypt_frame(0x03, 0, counter, &ciphertext),
+            Err(CryptoError::SessionExpired)
+        ));
+
+        // A rekey starts a fresh epoch with usable keys
+        initiator.rekey().unwrap();
+        responder.rekey().unwrap();
+        let (counter, ciphertext) = initiator.encrypt_frame(0x03, 0, b"hello").unwrap();
+        assert_eq!(
+            responder.decrypt_frame(0x03, 0, counter, &ciphertext).unwrap(),
+            b"hello"
+        );
+    }
+
+    #[test]
+    fn test_needs_rekey_soon_before_should_rekey() {
+        let clock = crate::core::TestClock::new();
+        let (initiator, _) = session_pair();
+        let initiator = initiator.with_clock(clock.shared());
+        let margin = Duration::from_secs(5);
+
+        assert!(!initiator.needs_rekey_soon(margin));
+        clock.advance(crate::core::REKEY_AFTER_TIME - margin);
+        assert!(initiator.needs_rekey_soon(margin));
+        assert!(!initiator.should_rekey());
+
+        clock.advance(margin);
+        assert!(initiator.should_rekey());
+    }
+
+    #[test]
+    fn test_force_rekey_advances_epoch() {
+        let (mut initiator, mut responder) = session_pair();
+        assert!(!initiator.should_rekey());
+
+        initiator.force_rekey().unwrap();
+        responder.force_rekey().unwrap();
+        assert_eq!(initiator.epoch(), 1);
+        assert_eq!(responder.epoch(), 1);
+
+        // Both directions still work under the new keys
+        let (counter, ciphertext) = initiator.encrypt_frame(0x03, 0x00, b"ping").unwrap();
+        let decrypted = responder
+            .decrypt_frame(0x03, 0x00, counter, &ciphertext)
+            .unwrap();
+        assert_eq!(decrypted, b"ping");
+
+        let (counter, ciphertext) = responder.encrypt_frame(0x03, 0x00, b"pong").unwrap();
+        let decrypted = initiator
+            .decrypt_frame(0x03, 0x00, counter, &ciphertext)
+            .unwrap();
+        assert_eq!(decrypted, b"pong");
+    }
+
+    #[test]
+    fn test_complete_rekey_rejects_stale_keys() {
+        let (mut initiator, _responder) = session_pair();
+
+        let stale = initiator.begin_rekey().unwrap();
+        initiator.force_rekey().unwrap();
+        assert!(matches!(
+            initiator.complete_rekey(stale),
+            Err(CryptoError::StaleRekey { staged: 1, expected: 2 })
+        ));
+        assert_eq!(initiator.epoch(), 1);
+    }
+
+    #[test]
+    fn test_staged_rekey_old_epoch_frame_decrypts() {
+        let (mut initiator, mut responder) = session_pair();
+
+        // Initiator sends a data frame under epoch 0
+        let (old_counter, old_ciphertext) =
+            initiator.encrypt_frame(0x03, 0x00, b"in flight").unwrap();
+
+        // Responder stages the rekey and replies under the current epoch
+        let pending = responder.begin_rekey().unwrap();
+        assert_eq!(pending.epoch(), 1);
+        assert_eq!(responder.epoch(), 0);
+        let (reply_counter, reply_ciphertext) =
+            responder.encrypt_frame(0x04, 0x00, b"rekey").unwrap();
+        responder.complete_rekey(pending).unwrap();
+        assert_eq!(responder.epoch(), 1);
+
+        // Initiator receives the reply under epoch 0, then switches
+        let reply = initiator
+            .decrypt_frame(0x04, 0x00, reply_counter, &reply_ciphertext)
+            .unwrap();
+        assert_eq!(reply, b"rekey");
+        let pending = initiator.begin_rekey().unwrap();
+        initiator.complete_rekey(pending).unwrap();
+        assert_eq!(initiator.epoch(), 1);
+
+        // The epoch 0 frame arrives late and decrypts via the old keys
+        let late = responder
+            .decrypt_frame(0x03, 0x00, old_counter, &old_ciphertext)
+            .unwrap();
+        assert_eq!(late, b"in flight");
+
+        // New epoch traffic works in both directions
+        let (counter, ciphertext) = initiator.encrypt_frame(0x03, 0x00, b"new").unwrap();
+        assert_eq!(
+            responder
+                .decrypt_frame(0x03, 0x00, counter, &ciphertext)
+                .unwrap(),
+            b"new"
+        );
+        let (counter, ciphertext) = responder.encrypt_frame(0x03, 0x00, b"back").unwrap();
+        assert_eq!(
+            initiator
+                .decrypt_frame(0x03, 0x00, counter, &ciphertext)
+                .unwrap(),
+            b"back"
+        );
+    }
+
+    #[test]
+    fn test_old_epoch_replay_rejected() {
+        let (mut initiator, mut responder) = session_pair();
+
+        // Two frames under epoch 0; the first is delivered before the rekey
+        let (seen_counter, seen_ciphertext) =
+            initiator.encrypt_frame(0x03, 0x00, b"seen").unwrap();
+        let (late_counter, late_ciphertext) =
+            initiator.encrypt_frame(0x03, 0x00, b"late").unwrap();
+        responder
+            .decrypt_frame(0x03, 0x00, seen_counter, &seen_ciphertext)
+            .unwrap();
+
+        initiator.rekey().unwrap();
+        responder.rekey().unwrap();
+
+        // Late old-epoch frame is accepted once via the old keys
+        assert_eq!(
+            responder
+                .decrypt_frame(0x03, 0x00, late_counter, &late_ciphertext)
+                .unwrap(),
+            b"late"
+        );
+        assert!(matches!(
+            responder.decrypt_frame(0x03, 0x00, late_counter, &late_ciphertext),
+            Err(CryptoError::ReplayDetected)
+        ));
+
+        // A frame accepted before the rekey can't be replayed afterwards
+        assert!(matches!(
+            responder.decrypt_frame(0x03, 0x00, seen_counter, &seen_ciphertext),
+            Err(CryptoError::ReplayDetected)
+        ));
+
+        // New epoch counters are unaffected by the old window
+        let (counter, ciphertext) = initiator.encrypt_frame(0x03, 0x00, b"new").unwrap();
+        assert_eq!(counter, 0);
+        assert!(responder
+            .decrypt_frame(0x03, 0x00, counter, &ciphertext)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_force_rekey_at_epoch_limit() {
+        let (mut initiator, mut responder) = session_pair();
+        initiator.rekey_state.set_epoch(crate::core::MAX_EPOCH);
+        responder.rekey_state.set_epoch(crate::core::MAX_EPOCH);
+        assert!(!initiator.can_rekey());
+
+        assert!(matches!(
+            initiator.force_rekey(),
+            Err(CryptoError::EpochExhaustion)
+        ));
+        assert_eq!(initiator.epoch(), crate::core::MAX_EPOCH);
+
+        // Existing keys remain usable after the failed rekey
+        let (counter, ciphertext) = initiator.encrypt_frame(0x03, 0x00, b"still here").unwrap();
+        let decrypted = responder
+            .decrypt_frame(0x03, 0x00, counter, &ciphertext)
+            .unwrap();
+        assert_eq!(decrypted, b"still here");
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "nonce reuse")]
+    fn test_nonce_reuse_detected_in_debug() {
+        let (mut initiator, _responder) = session_pair();
+        initiator.encrypt_frame(0x03, 0x00, b"first").unwrap();
+
+        // Simulate a counter bug rewinding the send counter
+        initiator.rekey_state.set_send_count(0);
+        let _ = initiator.encrypt_frame(0x03, 0x00, b"second");
+    }
+
+    #[test]
+    fn test_counter_headroom_reserved_for_rekey() {
+        use crate::core::{CONTROL_FRAME_HEADROOM, REJECT_AFTER_MESSAGES};
+
+        let (mut initiator, mut responder) = session_pair();
+        initiator
+            .rekey_state
+            .set_send_count(REJECT_AFTER_MESSAGES - CONTROL_FRAME_HEADROOM);
+
+        // DATA stops at the headroom, without touching the keys
+        assert!(matches!(
+            initiator.encrypt_frame(0x03, 0x00, b"blocked"),
+            Err(CryptoError::CounterExhaustion)
+        ));
+        assert_eq!(initiator.epoch(), 0);
+
+        // The REKEY exchange still goes out on a reserved counter
+        let pending = initiator.begin_rekey().unwrap();
+        let (counter, ciphertext) = initiator.encrypt_frame(0x04, 0x00, b"rekey").unwrap();
+        responder.decrypt_frame(0x04, 0x00, counter, &ciphertext).unwrap();
+        initiator.complete_rekey(pending).unwrap();
+        responder.rekey().unwrap();
+
+        // The counter restarts under the new epoch and the retry succeeds
+        let (counter, ciphertext) = initiator.encrypt_frame(0x03, 0x00, b"retry").unwrap();
+        assert_eq!(counter, 0);
+        assert_eq!(
+            responder.decrypt_frame(0x03, 0x00, counter, &ciphertext).unwrap(),
+            b"retry"
+        );
+    }
+
+    #[test]
+    fn test_lowered_limits() {
+        use crate::core::CONTROL_FRAME_HEADROOM;
+
+        let (mut initiator, _responder) = session_pair();
+        initiator.set_send_counter_limit(CONTROL_FRAME_HEADROOM + 1);
+        initiator.set_epoch_limit(0);
+
+        initiator.encrypt_frame(0x03, 0x00, b"last").unwrap();
+        assert!(matches!(
+            initiator.encrypt_frame(0x03, 0x00, b"over"),
+            Err(CryptoError::CounterExhaustion)
+        ));
+
+        // No epoch left: only a CLOSE remains
+        assert!(!initiator.can_rekey());
+        assert!(matches!(initiator.force_rekey(), Err(CryptoError::EpochExhaustion)));
+        assert!(initiator.encrypt_frame(0x05, 0x00, b"close").is_ok());
+    }
+
+    #[test]
+    fn test_epoch_limit_must_close() {
+        let (mut initiator, _responder) = session_pair();
+        initiator.rekey_state.set_epoch(crate::core::MAX_EPOCH);
+        initiator.rekey_state.set_send_count(crate::core::REKEY_AFTER_MESSAGES);
+
+        // Soft limit reached with no epoch left: the keys still encrypt,
+        // so the session can send its CLOSE
+        assert!(initiator.should_rekey() && !initiator.can_rekey());
+        assert!(initiator.encrypt_frame(0x05, 0x00, b"close").is_ok());
+    }
+
+    #[cfg(feature = "session-resumption")]
+    #[test]
+    fn test_resume_from_exported_ticket() {
+        let (mut initiator, mut responder) = session_pair();
+
+        // Mid-stream: a few frames each way
+        let mut sent = Vec::new();
+        for i in 0..3u8 {
+            let (counter, ct) = initiator.encrypt_frame(0x03, 0x00, &[i]).unwrap();
+            responder.decrypt_frame(0x03, 0x00, counter, &ct).unwrap();
+            sent.push((counter, ct));
+        }
+        let (counter, ct) = responder.encrypt_frame(0x03, 0x00, b"reply").unwrap();
+        initiator.decrypt_frame(0x03, 0x00, counter, &ct).unwrap();
+
+        // The responder process dies and resumes from a stored ticket
+        let stored = responder.export_state().to_bytes();
+        let ticket = SessionResumptionTicket::from_bytes(&stored).unwrap();
+        let mut responder = CryptoSession::import_state(ticket).unwrap();
+
+        // Counters continue where they left off
+        let (counter, ct) = responder.encrypt_frame(0x03, 0x00, b"resumed").unwrap();
+        assert_eq!(counter, 1);
+        assert_eq!(initiator.decrypt_frame(0x03, 0x00, counter, &ct).unwrap(), b"resumed");
+
+        let (counter, ct) = initiator.encrypt_frame(0x03, 0x00, b"next").unwrap();
+        assert_eq!(counter, 3);
+        assert_eq!(responder.decrypt_frame(0x03, 0x00, counter, &ct).unwrap(), b"next");
+
+        // Frames accepted before the export are still replays
+        for (counter, ct) in &sent {
+            assert!(matches!(
+                responder.decrypt_frame(0x03, 0x00, *counter, ct),
+                Err(CryptoError::ReplayDetected)
+            ));
+        }
+    }
+
+    #[cfg(feature = "session-resumption")]
+    #[test]
+    fn test_resumption_ticket_rejects_malformed() {
+        let (initiator, _) = session_pair();
+        let bytes = initiator.export_state().to_bytes();
+        assert_eq!(bytes.len(), SessionResumptionTicket::ENCODED_SIZE);
+
+        assert!(SessionResumptionTicket::from_bytes(&bytes[1..]).is_err());
+        let mut bad = bytes.to_vec();
+        bad[0] = 0xFF;
+        assert!(matches!(
+            SessionResumptionTicket::from_bytes(&bad),
+            Err(CryptoError::InvalidResumptionTicket(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "extensions")]
+    fn test_connection_info_after_handshake() {
+        use crate::crypto::{InitiatorHandshake, ResponderHandshake, SessionKeys, StaticKeypair};
+        use crate::extensions::{ext_type, negotiate, supported_extensions, Extension};
+
+        let client_keypair = StaticKeypair::generate();
+        let server_keypair = StaticKeypair::generate();
+
+        let mut initiator =
+            InitiatorHandshake::new(&client_keypair, server_keypair.public_key()).unwrap();
+        let mut responder = ResponderHandshake::new(&server_keypair).unwrap();
+        let init = initiator.write_message(b"").unwrap();
+        let (_, client_public) = responder.read_message(&init).unwrap();
+        let (resp, server_result) = responder.write_message(b"").unwrap();
+        let (_, _client_result) = initiator.read_message(&resp).unwrap();
+
+        // Client offers compression at level 9 plus an extension we don't support
+        let mut offered = crate::extensions::ExtensionSet::new();
+        offered.add_compression(9);
+        offered.add(Extension::new(ext_type::SCROLLBACK, vec![0x10]));
+        let agreed = negotiate(&offered, &supported_extensions());
+
+        let keys = SessionKeys::derive(&server_result).unwrap();
+        let session = CryptoSession::new(
+            SessionId::generate(),
+            Role::Responder,
+            keys.responder_key,
+            keys.initiator_key,
+            keys.handshake_hash,
+        )
+        .with_peer_public_key(client_public)
+        .with_extensions(agreed);
+
+        let info = session.connection_info();
+        assert_eq!(info.protocol_version, PROTOCOL_VERSION);
+        assert_eq!(info.handshake_pattern, "Noise_IK_25519_ChaChaPoly_BLAKE2s");
+        assert_eq!(info.frame_aead, "XChaCha20-Poly1305");
+        assert_eq!(info.max_payload, RECOMMENDED_MAX_PAYLOAD);
+        assert_eq!(info.epoch, 0);
+        assert_eq!(info.peer_public_key.as_ref(), Some(client_keypair.public_key()));
+        assert_eq!(info.extensions.len(), 1);
+        assert_eq!(info.extensions.compression_level(), Some(3));
+        assert!(!info.extensions.has(ext_type::SCROLLBACK));
+    }
+
+    #[test]
+    fn test_peer_static_public_key_after_handshake() {
+        use crate::crypto::{InitiatorHandshake, ResponderHandshake, SessionKeys, StaticKeypair};
+
+        let client_keypair = StaticKeypair::generate();
+        let server_keypair = StaticKeypair::generate();
+
+        let mut initiator =
+            InitiatorHandshake::new(&client_keypair, server_keypair.public_key()).unwrap();
+        let mut responder = ResponderHandshake::new(&server_keypair).unwrap();
+        let init = initiator.write_message(b"").unwrap();
+        let (_, client_public) = responder.read_message(&init).unwrap();
+        let (resp, server_result) = responder.write_message(b"").unwrap();
+        initiator.read_message(&resp).unwrap();
+
+        let keys = SessionKeys::derive(&server_result).unwrap();
+        let bare = CryptoSession::new(
+            SessionId::generate(),
+            Role::Responder,
+            keys.responder_key,
+            keys.initiator_key,
+            keys.handshake_hash,
+        );
+        assert_eq!(bare.peer_static_public_key(), None);
+
+        let session = bare.with_peer_public_key(client_public);
+        assert_eq!(session.peer_static_public_key(), Some(client_keypair.public_key()));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_decrypt_failure_traced() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        type Fields = Vec<(String, String)>;
+
+        struct Collect<'a>(&'a mut Fields);
+
+        impl Visit for Collect<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0.push((field.name().to_string(), format!("{value:?}")));
+            }
+        }
+
+        /// Records every span with its fields.
+        #[derive(Clone, Default)]
+        struct Capture {
+            spans: Arc<Mutex<Vec<(&'static str, Fields)>>>,
+        }
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+                let mut fields = Fields::new();
+                attrs.record(&mut Collect(&mut fields));
+                let mut spans = self.spans.lock().unwrap();
+                spans.push((attrs.metadata().name(), fields));
+                Id::from_u64(spans.len() as u64)
+            }
+            fn record(&self, span: &Id, values: &Record<'_>) {
+                let mut spans = self.spans.lock().unwrap();
+                let (_, fields) = &mut spans[span.into_u64() as usize - 1];
+                values.record(&mut Collect(fields));
+            }
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event<'_>) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let (mut initiator, mut responder) = session_pair();
+        let (counter, mut ciphertext) = initiator.encrypt_frame(0x03, 0, b"hello").unwrap();
+        ciphertext[0] ^= 0xFF;
+
+        let capture = Capture::default();
+        let result = tracing::subscriber::with_default(capture.clone(), || {
+            responder.decrypt_frame(0x03, 0, counter, &ciphertext)
+        });
+        assert!(matches!(result, Err(CryptoError::DecryptionFailed)));
+
+        let spans = capture.spans.lock().unwrap();
+        let (_, fields) = spans.iter().find(|(name, _)| *name == "decrypt_frame").unwrap();
+        let field = |name: &str| fields.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+        assert_eq!(field("session_id"), Some(format!("{:?}", responder.session_id()).as_str()));
+        assert_eq!(field("epoch"), Some("0"));
+        assert_eq!(field("nonce"), Some(counter.to_string().as_str()));
+        assert_eq!(field("outcome"), Some(CryptoError::DecryptionFailed.to_string().as_str()));
+    }
 }
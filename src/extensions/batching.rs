@@ -0,0 +1,299 @@
+//! Batching extension
+//!
+//! Packs several sync messages into one frame payload below the MTU and
+//! unpacks them in order (extension [`ext_type::BATCHING`]).
+//!
+//! Wire format (repeated until the end of the payload):
+//! ```text
+//! +0  Message Length (2 bytes LE16)
+//! +2  Sync Message (Message Length bytes, see `SyncMessage`)
+//! ```
+//!
+//! With coalescing enabled ([`BatchEncoder::with_coalesce`]), a message
+//! drops the earlier ones it supersedes, so a batch collected over several
+//! rapid changes carries only their net effect. The sync engine diffs
+//! every message against the acked snapshot, so a later message with the
+//! same base already contains everything an earlier one did.
+//!
+//! [`ext_type::BATCHING`]: super::ext_type::BATCHING
+
+use crate::core::RECOMMENDED_MAX_PAYLOAD;
+use crate::sync::SyncMessage;
+
+use super::NegotiationError;
+
+/// Per-message length prefix size in bytes.
+pub const BATCH_LENGTH_PREFIX_SIZE: usize = 2;
+
+/// Check whether `newer` makes `older` redundant within one batch.
+///
+/// Fragments are never dropped (each carries part of one diff). Otherwise
+/// `newer` must be at least as recent in both version and ack, and either
+/// `older` carries no state, `newer` is a full snapshot, or both are diffs
+/// from the same base.
+fn supersedes(newer: &SyncMessage, older: &SyncMessage) -> bool {
+    if newer.fragment.is_some() || older.fragment.is_some() {
+        return false;
+    }
+    if newer.sender_state_num < older.sender_state_num
+        || newer.acked_state_num < older.acked_state_num
+    {
+        return false;
+    }
+    older.is_ack_only()
+        || newer.snapshot
+        || (!newer.is_ack_only() && newer.base_state_num == older.base_state_num)
+}
+
+/// Packs sync messages into a single frame payload.
+#[derive(Debug, Clone)]
+pub struct BatchEncoder {
+    /// Maximum size of the packed payload
+    max_payload: usize,
+    /// Whether newer messages replace the ones they supersede
+    coalesce: bool,
+    /// Packed messages so far
+    messages: Vec<SyncMessage>,
+    /// Packed size so far, including length prefixes
+    size: usize,
+}
+
+impl BatchEncoder {
+    /// Create an encoder with the given per-frame budget.
+    pub fn new(max_payload: usize) -> Self {
+        Self {
+            max_payload,
+            coalesce: false,
+            messages: Vec::new(),
+            size: 0,
+        }
+    }
+
+    /// Enable or disable coalescing of superseded messages (off by default).
+    ///
+    /// See the [module documentation](self).
+    pub fn with_coalesce(mut self, coalesce: bool) -> Self {
+        self.coalesce = coalesce;
+        self
+    }
+
+    /// Get the per-frame budget.
+    pub fn max_payload(&self) -> usize {
+        self.max_payload
+    }
+
+    /// Number of messages packed so far.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Check if no messages have been packed.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Add a message to the batch.
+    ///
+    /// When coalescing, messages it supersedes are removed first (and no
+    /// longer count against the budget). Returns `false` (and leaves the
+    /// batch unchanged) if adding it would exceed `max_payload`; the caller
+    /// should `finish` this batch and start a new one.
+    pub fn push(&mut self, msg: &SyncMessage) -> bool {
+        let entry_size = |m: &SyncMessage| BATCH_LENGTH_PREFIX_SIZE + m.wire_size();
+        let size = msg.wire_size();
+        let superseded: usize = if self.coalesce {
+            self.messages
+                .iter()
+                .filter(|older| supersedes(msg, older))
+                .map(entry_size)
+                .sum()
+        } else {
+            0
+        };
+        if size > u16::MAX as usize
+            || self.size - superseded + BATCH_LENGTH_PREFIX_SIZE + size > self.max_payload
+        {
+            return false;
+        }
+
+        if self.coalesce {
+            self.messages.retain(|older| !supersedes(msg, older));
+        }
+        self.size = self.size - superseded + entry_size(msg);
+        self.messages.push(msg.clone());
+        true
+    }
+
+    /// Consume the encoder and return the packed payload.
+    pub fn finish(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.size);
+        for msg in &self.messages {
+            buf.extend_from_slice(&(msg.wire_size() as u16).to_le_bytes());
+            buf.extend_from_slice(&msg.encode());
+        }
+        buf
+    }
+}
+
+impl Default for BatchEncoder {
+    fn default() -> Self {
+        Self::new(RECOMMENDED_MAX_PAYLOAD)
+    }
+}
+
+/// Unpacks a batched frame payload.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BatchDecoder;
+
+impl BatchDecoder {
+    /// Split a batched payload back into sync messages, in order.
+    pub fn decode(data: &[u8]) -> Result<Vec<SyncMessage>, NegotiationError> {
+        let mut messages = Vec::new();
+        let mut offset = 0;
+
+        while offset < data.len() {
+            let remaining = &data[offset..];
+            if remaining.len() < BATCH_LENGTH_PREFIX_SIZE {
+                return Err(NegotiationError::TooShort {
+                    expected: BATCH_LENGTH_PREFIX_SIZE,
+                    actual: remaining.len(),
+                });
+            }
+
+            let len = u16::from_le_bytes([remaining[0], remaining[1]]) as usize;
+            let end = BATCH_LENGTH_PREFIX_SIZE + len;
+            if remaining.len() < end {
+                return Err(NegotiationError::TooShort {
+                    expected: end,
+                    actual: remaining.len(),
+                });
+            }
+
+            let (message, consumed) =
+                SyncMessage::decode_with_length(&remaining[BATCH_LENGTH_PREFIX_SIZE..end])
+                    .map_err(|_| NegotiationError::InvalidData)?;
+            if consumed != len {
+                return Err(NegotiationError::InvalidData);
+            }
+
+            messages.push(message);
+            offset += end;
+        }
+
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(version: u64, diff_len: usize) -> SyncMessage {
+        SyncMessage::new(version, 1, 0, vec![version as u8; diff_len])
+    }
+
+    #[test]
+    fn test_pack_three_messages() {
+        let messages = vec![msg(1, 10), msg(2, 0), msg(3, 40)];
+        let mut encoder = BatchEncoder::default();
+        for m in &messages {
+            assert!(encoder.push(m));
+        }
+        assert_eq!(encoder.len(), 3);
+
+        let payload = encoder.finish();
+        assert_eq!(BatchDecoder::decode(&payload).unwrap(), messages);
+    }
+
+    #[test]
+    fn test_push_over_mtu_rejected() {
+        // Each entry is 2 + 28 + 50 = 80 bytes; only two fit in 200
+        let mut encoder = BatchEncoder::new(200);
+        assert!(encoder.push(&msg(1, 50)));
+        assert!(encoder.push(&msg(2, 50)));
+        assert!(!encoder.push(&msg(3, 50)));
+        assert_eq!(encoder.len(), 2);
+
+        let payload = encoder.finish();
+        assert!(payload.len() <= 200);
+        assert_eq!(BatchDecoder::decode(&payload).unwrap(), vec![msg(1, 50), msg(2, 50)]);
+    }
+
+    #[test]
+    fn test_coalesce_rapid_increments() {
+        use crate::sync::SyncEngine;
+
+        fn encode(delta: &i64) -> Vec<u8> {
+            delta.to_le_bytes().to_vec()
+        }
+        fn decode(data: &[u8]) -> Result<i64, String> {
+            Ok(i64::from_le_bytes(data.try_into().map_err(|_| "bad delta")?))
+        }
+        fn compute(old: &i64, new: &i64) -> i64 {
+            new - old
+        }
+        fn apply(state: &mut i64, delta: &i64) -> Result<(), String> {
+            *state += delta;
+            Ok(())
+        }
+
+        let batch = |coalesce: bool| {
+            let mut engine = SyncEngine::new(encode, decode, compute, apply, |d| *d == 0);
+            engine.init(0i64);
+            let mut encoder = BatchEncoder::default().with_coalesce(coalesce);
+            for value in 1..=3 {
+                engine.update_state(value);
+                assert!(encoder.push(&engine.generate_message().unwrap().unwrap()));
+            }
+            BatchDecoder::decode(&encoder.finish()).unwrap()
+        };
+
+        // Without coalescing every change goes out as its own sub-message
+        assert_eq!(batch(false).len(), 3);
+
+        // Coalesced, only the net +3 from the acked snapshot is sent
+        let messages = batch(true);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].sender_state_num, 3);
+        assert_eq!(decode(&messages[0].diff).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_coalesce_keeps_fragments_and_other_bases() {
+        let mut encoder = BatchEncoder::default().with_coalesce(true);
+        let fragment = SyncMessage::new(1, 0, 0, vec![1; 4]).with_fragment(0, 2);
+        let other_base = SyncMessage::new(2, 0, 0, vec![2; 4]);
+        assert!(encoder.push(&fragment));
+        assert!(encoder.push(&other_base));
+        assert!(encoder.push(&SyncMessage::new(3, 0, 1, vec![3; 4])));
+        // Ack-only messages are superseded by anything newer
+        assert!(encoder.push(&SyncMessage::new(3, 2, 0, Vec::new())));
+        // Same base as version 3: replaces it and the ack
+        let latest = SyncMessage::new(4, 2, 1, vec![4; 4]);
+        assert!(encoder.push(&latest));
+        assert_eq!(
+            BatchDecoder::decode(&encoder.finish()).unwrap(),
+            vec![fragment, other_base, latest]
+        );
+    }
+
+    #[test]
+    fn test_empty_batch_roundtrip() {
+        let encoder = BatchEncoder::default();
+        assert!(encoder.is_empty());
+        let payload = encoder.finish();
+        assert!(payload.is_empty());
+        assert!(BatchDecoder::decode(&payload).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        let mut encoder = BatchEncoder::default();
+        encoder.push(&msg(1, 10));
+        let payload = encoder.finish();
+        assert!(matches!(
+            BatchDecoder::decode(&payload[..payload.len() - 1]),
+            Err(NegotiationError::TooShort { .. })
+        ));
+    }
+}
@@ -0,0 +1,844 @@
+//! Checkpoint extension
+//!
+//! Full-state snapshots for initial sync and recovery (extension
+//! [`ext_type::CHECKPOINT`]). A client with no state sends a
+//! [`CheckpointRequest`]; the peer answers with a [`Checkpoint`] of its
+//! state at some version, and incremental sync messages continue from
+//! there (see `SyncEngine::init_at_peer_version`).
+//!
+//! Checkpoint wire format:
+//! ```text
+//! +0   Checkpoint ID (8 bytes LE64)
+//! +8   State Num (8 bytes LE64)
+//! +16  Base ID (8 bytes LE64, 0 unless INCREMENTAL)
+//! +24  Flags (1 byte, see `checkpoint_flags`)
+//! +25  Payload Length (4 bytes LE32)
+//! +29  Payload (variable)
+//! +N   Signature (64 bytes, only if SIGNED)
+//! ```
+//!
+//! The payload is the state's `SyncState::encode_full` output, or for an
+//! incremental checkpoint its encoded diff from the base checkpoint's
+//! state; zstd compressed when [`checkpoint_flags::COMPRESSED`] is set. A signed
+//! checkpoint carries an Ed25519 signature over everything before it
+//! (header, payload length and payload), so a recovery snapshot from
+//! untrusted storage can be checked before it is restored.
+//!
+//! [`ext_type::CHECKPOINT`]: super::ext_type::CHECKPOINT
+
+use ed25519_dalek::Signer;
+use thiserror::Error;
+
+use crate::core::{ApplyError, DecodeError, SyncState};
+
+use super::{maybe_compress, maybe_decompress, CompressionError};
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+/// Checkpoint header size in bytes, including the payload length.
+pub const CHECKPOINT_HEADER_SIZE: usize = 29;
+
+/// Ed25519 signature size in bytes.
+pub const CHECKPOINT_SIGNATURE_SIZE: usize = 64;
+
+/// Checkpoint flag bits
+pub mod checkpoint_flags {
+    /// Payload is zstd compressed
+    pub const COMPRESSED: u8 = 0x01;
+    /// An Ed25519 signature follows the payload
+    pub const SIGNED: u8 = 0x02;
+    /// Payload is relative to the checkpoint named by `base_id`
+    pub const INCREMENTAL: u8 = 0x04;
+}
+
+/// Errors from building or restoring checkpoints.
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+    /// Input buffer is shorter than required.
+    #[error("checkpoint too short: expected {expected} bytes, got {actual}")]
+    TooShort {
+        /// Minimum bytes required.
+        expected: usize,
+        /// Actual bytes available.
+        actual: usize,
+    },
+
+    /// Checkpoint data is malformed.
+    #[error("invalid checkpoint: {0}")]
+    InvalidFormat(String),
+
+    /// The SIGNED flag is set but the trailing signature has the wrong size.
+    #[error("invalid signature length: expected {expected} bytes, got {actual}")]
+    InvalidSignatureLength {
+        /// Signature size the flag requires.
+        expected: usize,
+        /// Bytes present after the payload.
+        actual: usize,
+    },
+
+    /// The state type has no full encoding (`SyncState::encode_full`).
+    #[error("state type does not support checkpoints")]
+    Unsupported,
+
+    /// Checkpoint is incremental and needs its base to be restored.
+    #[error("checkpoint is incremental from {base_id}")]
+    Incremental {
+        /// ID of the checkpoint this one is relative to.
+        base_id: u64,
+    },
+
+    /// Checkpoint is not incremental, so there is nothing to apply.
+    #[error("checkpoint is not incremental")]
+    NotIncremental,
+
+    /// The base given for an incremental checkpoint is the wrong one.
+    #[error("base mismatch: expected checkpoint {expected}, got {actual}")]
+    BaseMismatch {
+        /// Base ID recorded in the incremental checkpoint.
+        expected: u64,
+        /// ID of the checkpoint that was provided.
+        actual: u64,
+    },
+
+    /// Applying the incremental diff to the base state failed.
+    #[error("checkpoint diff apply failed: {0}")]
+    Apply(#[from] ApplyError),
+
+    /// Payload decompression failed.
+    #[error("checkpoint decompression failed: {0}")]
+    Compression(#[from] CompressionError),
+
+    /// Payload did not decode as the requested state type.
+    #[error("checkpoint state decode failed: {0}")]
+    Decode(#[from] DecodeError),
+}
+
+/// Checkpoint header: identity and version of the snapshotted state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckpointHeader {
+    /// Identifier of this checkpoint
+    pub checkpoint_id: u64,
+    /// Sync version of the state in the checkpoint
+    pub state_num: u64,
+    /// Checkpoint this one is relative to (0 for a full checkpoint)
+    pub base_id: u64,
+    /// Flag bits (see [`checkpoint_flags`])
+    pub flags: u8,
+}
+
+impl CheckpointHeader {
+    /// Header for a full checkpoint
+    pub fn full(checkpoint_id: u64, state_num: u64) -> Self {
+        Self {
+            checkpoint_id,
+            state_num,
+            base_id: 0,
+            flags: 0,
+        }
+    }
+
+    /// Header for a checkpoint relative to `base_id`
+    pub fn incremental(checkpoint_id: u64, state_num: u64, base_id: u64) -> Self {
+        Self {
+            checkpoint_id,
+            state_num,
+            base_id,
+            flags: checkpoint_flags::INCREMENTAL,
+        }
+    }
+
+    /// Check if the payload is compressed
+    pub fn is_compressed(&self) -> bool {
+        self.flags & checkpoint_flags::COMPRESSED != 0
+    }
+
+    /// Check if this checkpoint is relative to a base
+    pub fn is_incremental(&self) -> bool {
+        self.flags & checkpoint_flags::INCREMENTAL != 0
+    }
+
+    /// Check if a signature follows the payload
+    pub fn is_signed(&self) -> bool {
+        self.flags & checkpoint_flags::SIGNED != 0
+    }
+}
+
+/// A snapshot of a peer's full state at a sync version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Checkpoint header
+    pub header: CheckpointHeader,
+    /// Encoded state, compressed if the header says so
+    pub payload: Vec<u8>,
+    /// Ed25519 signature, present when the header says so
+    pub signature: Option<[u8; CHECKPOINT_SIGNATURE_SIZE]>,
+}
+
+impl Checkpoint {
+    /// Snapshot `state` as of sync version `state_num`
+    ///
+    /// Fails with [`CheckpointError::Unsupported`] if the state type has no
+    /// full encoding.
+    pub fn from_state<S: SyncState>(
+        state: &S,
+        checkpoint_id: u64,
+        state_num: u64,
+    ) -> Result<Self, CheckpointError> {
+        let payload = state.encode_full().ok_or(CheckpointError::Unsupported)?;
+        Ok(Self {
+            header: CheckpointHeader::full(checkpoint_id, state_num),
+            payload,
+            signature: None,
+        })
+    }
+
+    /// Snapshot `current_state` as a diff from the state in `base`
+    ///
+    /// Only the diff is stored, so the result is small when little changed
+    /// since the base. `base` must be a full checkpoint of the same state
+    /// type; restore with [`apply_incremental`](Self::apply_incremental).
+    pub fn incremental_from<S: SyncState>(
+        base: &Checkpoint,
+        current_state: &S,
+        checkpoint_id: u64,
+        state_num: u64,
+    ) -> Result<Self, CheckpointError> {
+        let base_state: S = base.into_state()?;
+        let diff = current_state.diff_from(&base_state);
+        Ok(Self {
+            header: CheckpointHeader::incremental(
+                checkpoint_id,
+                state_num,
+                base.header.checkpoint_id,
+            ),
+            payload: S::encode_diff(&diff),
+            signature: None,
+        })
+    }
+
+    /// Rebuild the full checkpoint this incremental one describes
+    ///
+    /// `base` must be the checkpoint named by the header's `base_id`. The
+    /// result is an uncompressed, unsigned full checkpoint with this
+    /// checkpoint's ID and state version.
+    pub fn apply_incremental<S: SyncState>(
+        &self,
+        base: &Checkpoint,
+    ) -> Result<Checkpoint, CheckpointError> {
+        if !self.header.is_incremental() {
+            return Err(CheckpointError::NotIncremental);
+        }
+        if base.header.checkpoint_id != self.header.base_id {
+            return Err(CheckpointError::BaseMismatch {
+                expected: self.header.base_id,
+                actual: base.header.checkpoint_id,
+            });
+        }
+
+        let mut state: S = base.into_state()?;
+        let diff = S::decode_diff(&self.raw_payload()?)?;
+        state.apply_diff(&diff)?;
+        Checkpoint::from_state(&state, self.header.checkpoint_id, self.header.state_num)
+    }
+
+    /// Compress the payload at the given zstd level
+    ///
+    /// Left uncompressed if compression does not pay off (see
+    /// [`maybe_compress`]) or the payload is already compressed. Compressing
+    /// changes the signed bytes, so any signature is dropped: sign last.
+    pub fn compressed(mut self, level: u8) -> Self {
+        if self.header.is_compressed() {
+            return self;
+        }
+        let (compressed, payload) = maybe_compress(&self.payload, level);
+        if compressed {
+            self.header.flags |= checkpoint_flags::COMPRESSED;
+            self.payload = payload;
+            self.clear_signature();
+        }
+        self
+    }
+
+    /// Sign the checkpoint with an Ed25519 key
+    ///
+    /// Sets the SIGNED flag and signs the header and payload. Replaces any
+    /// previous signature.
+    pub fn sign(&mut self, signing_key: &SigningKey) {
+        self.header.flags |= checkpoint_flags::SIGNED;
+        let signature = signing_key.sign(&self.signed_bytes());
+        self.signature = Some(signature.to_bytes());
+    }
+
+    /// Check the signature against the signer's public key
+    ///
+    /// Returns `false` for unsigned checkpoints.
+    pub fn verify(&self, public_key: &VerifyingKey) -> bool {
+        let Some(signature) = self.signature.filter(|_| self.header.is_signed()) else {
+            return false;
+        };
+        let signature = ed25519_dalek::Signature::from_bytes(&signature);
+        public_key.verify_strict(&self.signed_bytes(), &signature).is_ok()
+    }
+
+    /// Remove the signature and the SIGNED flag
+    fn clear_signature(&mut self) {
+        self.header.flags &= !checkpoint_flags::SIGNED;
+        self.signature = None;
+    }
+
+    /// Bytes covered by the signature: everything before it on the wire
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(CHECKPOINT_HEADER_SIZE + self.payload.len());
+        buf.extend_from_slice(&self.header.checkpoint_id.to_le_bytes());
+        buf.extend_from_slice(&self.header.state_num.to_le_bytes());
+        buf.extend_from_slice(&self.header.base_id.to_le_bytes());
+        buf.push(self.header.flags);
+        buf.extend_from_slice(&(self.payload.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&self.payload);
+        buf
+    }
+
+    /// Encoded state with any compression undone
+    fn raw_payload(&self) -> Result<Vec<u8>, CheckpointError> {
+        Ok(maybe_decompress(self.header.is_compressed(), &self.payload)?)
+    }
+
+    /// Restore the full state from this checkpoint
+    ///
+    /// Incremental checkpoints need their base and fail with
+    /// [`CheckpointError::Incremental`].
+    pub fn into_state<S: SyncState>(&self) -> Result<S, CheckpointError> {
+        if self.header.is_incremental() {
+            return Err(CheckpointError::Incremental {
+                base_id: self.header.base_id,
+            });
+        }
+        Ok(S::decode_full(&self.raw_payload()?)?)
+    }
+
+    /// Total wire size
+    pub fn wire_size(&self) -> usize {
+        let signature_size = if self.signature.is_some() {
+            CHECKPOINT_SIGNATURE_SIZE
+        } else {
+            0
+        };
+        CHECKPOINT_HEADER_SIZE + self.payload.len() + signature_size
+    }
+
+    /// Encode to wire format
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = self.signed_bytes();
+        if let Some(signature) = self.signature {
+            buf.extend_from_slice(&signature);
+        }
+        buf
+    }
+
+    /// Decode from wire format
+    pub fn decode(data: &[u8]) -> Result<Self, CheckpointError> {
+        if data.len() < CHECKPOINT_HEADER_SIZE {
+            return Err(CheckpointError::TooShort {
+                expected: CHECKPOINT_HEADER_SIZE,
+                actual: data.len(),
+            });
+        }
+
+        let header = CheckpointHeader {
+            checkpoint_id: u64::from_le_bytes(data[0..8].try_into().expect("length checked above")),
+            state_num: u64::from_le_bytes(data[8..16].try_into().expect("length checked above")),
+            base_id: u64::from_le_bytes(data[16..24].try_into().expect("length checked above")),
+            flags: data[24],
+        };
+        let payload_len =
+            u32::from_le_bytes(data[25..29].try_into().expect("length checked above")) as usize;
+
+        let end = CHECKPOINT_HEADER_SIZE + payload_len;
+        if data.len() < end {
+            return Err(CheckpointError::TooShort {
+                expected: end,
+                actual: data.len(),
+            });
+        }
+
+        let trailer = &data[end..];
+        let signature = if header.is_signed() {
+            let signature = trailer.try_into().map_err(|_| CheckpointError::InvalidSignatureLength {
+                expected: CHECKPOINT_SIGNATURE_SIZE,
+                actual: trailer.len(),
+            })?;
+            Some(signature)
+        } else if !trailer.is_empty() {
+            return Err(CheckpointError::InvalidFormat(format!(
+                "{} trailing bytes",
+                trailer.len()
+            )));
+        } else {
+            None
+        };
+
+        Ok(Self {
+            header,
+            payload: data[CHECKPOINT_HEADER_SIZE..end].to_vec(),
+            signature,
+        })
+    }
+}
+
+/// Request for a checkpoint from the peer
+///
+/// Wire format:
+/// ```text
+/// +0  Kind (1 byte: 0x00 = Latest, 0x01 = ById, 0x02 = IncrementalFrom)
+/// +1  Checkpoint ID (8 bytes LE64, absent for Latest)
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointRequest {
+    /// The peer's most recent full checkpoint
+    Latest,
+    /// A specific checkpoint
+    ById(u64),
+    /// A checkpoint relative to one we already hold
+    IncrementalFrom(u64),
+}
+
+impl CheckpointRequest {
+    const LATEST: u8 = 0x00;
+    const BY_ID: u8 = 0x01;
+    const INCREMENTAL_FROM: u8 = 0x02;
+
+    /// Encode to wire format
+    pub fn encode(&self) -> Vec<u8> {
+        match *self {
+            Self::Latest => vec![Self::LATEST],
+            Self::ById(id) => Self::encode_with_id(Self::BY_ID, id),
+            Self::IncrementalFrom(id) => Self::encode_with_id(Self::INCREMENTAL_FROM, id),
+        }
+    }
+
+    fn encode_with_id(kind: u8, id: u64) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(9);
+        buf.push(kind);
+        buf.extend_from_slice(&id.to_le_bytes());
+        buf
+    }
+
+    /// Decode from wire format
+    pub fn decode(data: &[u8]) -> Result<Self, CheckpointError> {
+        let (&kind, rest) = data.split_first().ok_or(CheckpointError::TooShort {
+            expected: 1,
+            actual: 0,
+        })?;
+        if kind == Self::LATEST {
+            return Ok(Self::Latest);
+        }
+
+        let id: [u8; 8] = rest.try_into().map_err(|_| CheckpointError::TooShort {
+            expected: 9,
+            actual: data.len(),
+        })?;
+        let id = u64::from_le_bytes(id);
+        match kind {
+            Self::BY_ID => Ok(Self::ById(id)),
+            Self::INCREMENTAL_FROM => Ok(Self::IncrementalFrom(id)),
+            other => Err(CheckpointError::InvalidFormat(format!(
+                "unknown request kind 0x{:02x}",
+                other
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{decode_diff_onto_default, encode_diff_from_default, ApplyError};
+
+    /// A small document: a title plus a list of lines
+    #[derive(Debug, Clone, Default, PartialEq)]
+    struct Document {
+        title: String,
+        lines: Vec<String>,
+    }
+
+    fn put_str(buf: &mut Vec<u8>, s: &str) {
+        buf.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn take_str(data: &mut &[u8]) -> Result<String, DecodeError> {
+        if data.len() < 4 {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        let len = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+        let bytes = data.get(4..4 + len).ok_or(DecodeError::UnexpectedEof)?;
+        let s = String::from_utf8(bytes.to_vec())
+            .map_err(|e| DecodeError::InvalidEncoding(e.to_string()))?;
+        *data = &data[4 + len..];
+        Ok(s)
+    }
+
+    /// New title if changed, plus the lines after the common prefix
+    #[derive(Debug, Clone, PartialEq)]
+    struct DocumentDiff {
+        title: Option<String>,
+        keep: u32,
+        tail: Vec<String>,
+    }
+
+    fn take_u32(data: &mut &[u8]) -> Result<u32, DecodeError> {
+        let bytes = data.get(..4).ok_or(DecodeError::UnexpectedEof)?;
+        let value = u32::from_le_bytes(bytes.try_into().unwrap());
+        *data = &data[4..];
+        Ok(value)
+    }
+
+    impl SyncState for Document {
+        type Diff = DocumentDiff;
+
+        const STATE_TYPE_ID: &'static str = "nomad.test.document.v1";
+
+        fn diff_from(&self, old: &Self) -> Self::Diff {
+            let keep = self
+                .lines
+                .iter()
+                .zip(&old.lines)
+                .take_while(|(new, old)| new == old)
+                .count();
+            DocumentDiff {
+                title: (self.title != old.title).then(|| self.title.clone()),
+                keep: keep as u32,
+                tail: self.lines[keep..].to_vec(),
+            }
+        }
+
+        fn apply_diff(&mut self, diff: &Self::Diff) -> Result<(), ApplyError> {
+            if diff.keep as usize > self.lines.len() {
+                return Err(ApplyError::InvalidFormat);
+            }
+            if let Some(title) = &diff.title {
+                self.title.clone_from(title);
+            }
+            self.lines.truncate(diff.keep as usize);
+            self.lines.extend_from_slice(&diff.tail);
+            Ok(())
+        }
+
+        fn encode_diff(diff: &Self::Diff) -> Vec<u8> {
+            let mut buf = Vec::new();
+            match &diff.title {
+                Some(title) => {
+                    buf.push(1);
+                    put_str(&mut buf, title);
+                }
+                None => buf.push(0),
+            }
+            buf.extend_from_slice(&diff.keep.to_le_bytes());
+            buf.extend_from_slice(&(diff.tail.len() as u32).to_le_bytes());
+            for line in &diff.tail {
+                put_str(&mut buf, line);
+            }
+            buf
+        }
+
+        fn decode_diff(mut data: &[u8]) -> Result<Self::Diff, DecodeError> {
+            let (&has_title, rest) = data.split_first().ok_or(DecodeError::UnexpectedEof)?;
+            data = rest;
+            let title = if has_title == 1 { Some(take_str(&mut data)?) } else { None };
+            let keep = take_u32(&mut data)?;
+            let count = take_u32(&mut data)?;
+            let tail = (0..count)
+                .map(|_| take_str(&mut data))
+                .collect::<Result<_, _>>()?;
+            Ok(DocumentDiff { title, keep, tail })
+        }
+
+        fn encode_full(&self) -> Option<Vec<u8>> {
+            Some(encode_diff_from_default(self))
+        }
+
+        fn decode_full(data: &[u8]) -> Result<Self, DecodeError> {
+            decode_diff_onto_default(data)
+        }
+    }
+
+    fn sample_document() -> Document {
+        Document {
+            title: "session log".to_string(),
+            lines: (0..200)
+                .map(|i| format!("line {:03}: the quick brown fox jumps over the lazy dog", i))
+                .collect(),
+        }
+    }
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    #[test]
+    fn test_compressed_signed_checkpoint_roundtrip() {
+        let doc = sample_document();
+        let key = signing_key(1);
+        let mut checkpoint = Checkpoint::from_state(&doc, 1, 42).unwrap().compressed(3);
+        checkpoint.sign(&key);
+        assert!(checkpoint.header.is_compressed());
+        assert!(checkpoint.header.is_signed());
+        assert!(checkpoint.payload.len() < encode_diff_from_default(&doc).len());
+
+        let encoded = checkpoint.encode();
+        assert_eq!(encoded.len(), checkpoint.wire_size());
+        let decoded = Checkpoint::decode(&encoded).unwrap();
+        assert_eq!(decoded, checkpoint);
+        assert!(decoded.verify(&key.verifying_key()));
+        assert_eq!(decoded.header.state_num, 42);
+        assert_eq!(decoded.into_state::<Document>().unwrap(), doc);
+    }
+
+    #[test]
+    fn test_tampered_payload_fails_verification() {
+        let key = signing_key(1);
+        let mut checkpoint = Checkpoint::from_state(&sample_document(), 1, 42).unwrap();
+        checkpoint.sign(&key);
+
+        let mut encoded = checkpoint.encode();
+        encoded[CHECKPOINT_HEADER_SIZE + 10] ^= 0x01;
+        let tampered = Checkpoint::decode(&encoded).unwrap();
+        assert!(!tampered.verify(&key.verifying_key()));
+
+        // The header is covered too
+        let mut encoded = checkpoint.encode();
+        encoded[8] ^= 0x01; // state_num
+        assert!(!Checkpoint::decode(&encoded).unwrap().verify(&key.verifying_key()));
+    }
+
+    #[test]
+    fn test_wrong_key_fails_verification() {
+        let mut checkpoint = Checkpoint::from_state(&sample_document(), 1, 42).unwrap();
+        checkpoint.sign(&signing_key(1));
+        assert!(!checkpoint.verify(&signing_key(2).verifying_key()));
+
+        // Unsigned checkpoints never verify
+        let unsigned = Checkpoint::from_state(&sample_document(), 1, 42).unwrap();
+        assert!(!unsigned.verify(&signing_key(1).verifying_key()));
+
+        // Compressing after signing drops the now-stale signature
+        let resigned = checkpoint.compressed(3);
+        assert!(!resigned.header.is_signed());
+        assert!(resigned.signature.is_none());
+    }
+
+    #[test]
+    fn test_decode_rejects_wrong_signature_length() {
+        let mut checkpoint = Checkpoint::from_state(&sample_document(), 1, 1).unwrap();
+        checkpoint.sign(&signing_key(1));
+        let encoded = checkpoint.encode();
+
+        for len in [encoded.len() - 1, encoded.len() - CHECKPOINT_SIGNATURE_SIZE] {
+            assert!(matches!(
+                Checkpoint::decode(&encoded[..len]),
+                Err(CheckpointError::InvalidSignatureLength { expected: 64, .. })
+            ));
+        }
+
+        let mut extended = encoded.clone();
+        extended.push(0);
+        assert!(matches!(
+            Checkpoint::decode(&extended),
+            Err(CheckpointError::InvalidSignatureLength { actual: 65, .. })
+        ));
+    }
+
+    #[test]
+    fn test_small_checkpoint_stays_uncompressed() {
+        let doc = Document {
+            title: "t".to_string(),
+            lines: Vec::new(),
+        };
+        let checkpoint = Checkpoint::from_state(&doc, 1, 1).unwrap().compressed(3);
+        assert!(!checkpoint.header.is_compressed());
+        assert_eq!(checkpoint.into_state::<Document>().unwrap(), doc);
+    }
+
+    #[test]
+    fn test_state_without_full_encoding_unsupported() {
+        #[derive(Clone)]
+        struct Opaque;
+
+        impl SyncState for Opaque {
+            type Diff = ();
+            const STATE_TYPE_ID: &'static str = "nomad.test.opaque.v1";
+            fn diff_from(&self, _old: &Self) {}
+            fn apply_diff(&mut self, _diff: &()) -> Result<(), ApplyError> {
+                Ok(())
+            }
+            fn encode_diff(_diff: &()) -> Vec<u8> {
+                Vec::new()
+            }
+            fn decode_diff(_data: &[u8]) -> Result<(), DecodeError> {
+                Ok(())
+            }
+        }
+
+        assert!(matches!(
+            Checkpoint::from_state(&Opaque, 1, 1),
+            Err(CheckpointError::Unsupported)
+        ));
+    }
+
+    #[test]
+    fn test_incremental_reconstructs_full_checkpoint() {
+        let doc = sample_document();
+        let base = Checkpoint::from_state(&doc, 1, 10).unwrap().compressed(3);
+
+        let mut current = doc.clone();
+        current.title = "session log (cont.)".to_string();
+        current.lines.truncate(198);
+        current.lines.extend(["tail a".to_string(), "tail b".to_string(), "tail c".to_string()]);
+
+        let incremental = Checkpoint::incremental_from(&base, &current, 2, 14).unwrap();
+        assert!(incremental.header.is_incremental());
+        assert_eq!(incremental.header.base_id, 1);
+        assert!(incremental.payload.len() < 100);
+
+        // Survives the wire, signed and all
+        let key = signing_key(3);
+        let mut signed = incremental.clone();
+        signed.sign(&key);
+        let received = Checkpoint::decode(&signed.encode()).unwrap();
+        assert!(received.verify(&key.verifying_key()));
+
+        let rebuilt = received.apply_incremental::<Document>(&base).unwrap();
+        assert_eq!(rebuilt, Checkpoint::from_state(&current, 2, 14).unwrap());
+        assert_eq!(rebuilt.into_state::<Document>().unwrap(), current);
+    }
+
+    #[test]
+    fn test_apply_incremental_rejects_wrong_base() {
+        let doc = sample_document();
+        let base = Checkpoint::from_state(&doc, 1, 10).unwrap();
+        let other = Checkpoint::from_state(&doc, 7, 10).unwrap();
+        let incremental = Checkpoint::incremental_from(&base, &doc, 2, 11).unwrap();
+
+        assert!(matches!(
+            incremental.apply_incremental::<Document>(&other),
+            Err(CheckpointError::BaseMismatch { expected: 1, actual: 7 })
+        ));
+        assert!(matches!(
+            base.apply_incremental::<Document>(&base),
+            Err(CheckpointError::NotIncremental)
+        ));
+
+        // Incremental checkpoints can't serve as a base themselves
+        assert!(matches!(
+            Checkpoint::incremental_from(&incremental, &doc, 3, 12),
+            Err(CheckpointError::Incremental { base_id: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_incremental_needs_base() {
+        let checkpoint = Checkpoint {
+            header: CheckpointHeader::incremental(2, 10, 1),
+            payload: Vec::new(),
+            signature: None,
+        };
+        assert!(checkpoint.header.is_incremental());
+        assert!(matches!(
+            checkpoint.into_state::<Document>(),
+            Err(CheckpointError::Incremental { base_id: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_lengths() {
+        let encoded = Checkpoint::from_state(&sample_document(), 1, 1).unwrap().encode();
+
+        assert!(matches!(
+            Checkpoint::decode(&encoded[..20]),
+            Err(CheckpointError::TooShort { .. })
+        ));
+        assert!(matches!(
+            Checkpoint::decode(&encoded[..encoded.len() - 1]),
+            Err(CheckpointError::TooShort { .. })
+        ));
+
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert!(matches!(
+            Checkpoint::decode(&trailing),
+            Err(CheckpointError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_request_roundtrip() {
+        for request in [
+            CheckpointRequest::Latest,
+            CheckpointRequest::ById(7),
+            CheckpointRequest::IncrementalFrom(u64::MAX),
+        ] {
+            assert_eq!(CheckpointRequest::decode(&request.encode()).unwrap(), request);
+        }
+        assert_eq!(CheckpointRequest::Latest.encode(), vec![0x00]);
+        assert!(CheckpointRequest::decode(&[0x01, 0x00]).is_err());
+        assert!(CheckpointRequest::decode(&[0x09; 9]).is_err());
+    }
+
+    #[cfg(feature = "sync")]
+    #[test]
+    fn test_bootstrap_from_latest_then_apply_diffs() {
+        use crate::sync::{ProcessResult, SyncEngine, SyncMessage};
+
+        fn engine() -> SyncEngine<Document, DocumentDiff> {
+            SyncEngine::new(
+                Document::encode_diff,
+                |data| Document::decode_diff(data).map_err(|e| e.to_string()),
+                |old, new| new.diff_from(old),
+                |state, diff| state.apply_diff(diff).map_err(|e| e.to_string()),
+                |_| false,
+            )
+        }
+
+        // Server has been running for a while
+        let mut server = engine();
+        server.init(Document::default());
+        for version in 1..=5 {
+            let mut doc = server.state().unwrap().clone();
+            doc.lines.push(format!("entry {}", version));
+            server.update_state(doc);
+        }
+
+        // Client asks for the latest checkpoint and restores from it
+        let request = CheckpointRequest::decode(&CheckpointRequest::Latest.encode()).unwrap();
+        assert_eq!(request, CheckpointRequest::Latest);
+        let checkpoint = Checkpoint::from_state(
+            server.state().unwrap(),
+            1,
+            server.current_version(),
+        )
+        .unwrap()
+        .compressed(3);
+        let checkpoint = Checkpoint::decode(&checkpoint.encode()).unwrap();
+
+        let mut client = engine();
+        client.init_at_peer_version(
+            checkpoint.into_state::<Document>().unwrap(),
+            checkpoint.header.state_num,
+        );
+        assert_eq!(client.state(), server.state());
+
+        // Incremental updates continue from the checkpoint's version
+        let mut doc = server.state().unwrap().clone();
+        doc.title = "renamed".to_string();
+        server.update_state(doc);
+        let msg = server.generate_message().unwrap().unwrap();
+        assert_eq!(client.process_message(&msg).unwrap(), ProcessResult::Updated);
+        assert_eq!(client.state(), server.state());
+        assert_eq!(client.generate_ack().unwrap().acked_state_num, 6);
+        // Versions the checkpoint already covers are duplicates
+        let noop = Document::default().diff_from(&Document::default());
+        let stale = SyncMessage::new(5, 0, 0, Document::encode_diff(&noop));
+        assert_eq!(client.process_message(&stale).unwrap(), ProcessResult::Duplicate);
+        assert_eq!(client.state(), server.state());
+    }
+}
@@ -2,15 +2,27 @@
 //!
 //! Implements zstd compression for sync message payloads.
 //! See 4-EXTENSIONS.md for specification.
+//!
+//! Small, repetitive payloads (typical state diffs) compress poorly on
+//! their own. [`CompressionContext`] holds a zstd dictionary trained from
+//! sample payloads; both peers load the same dictionary and confirm it by
+//! ID during extension negotiation.
+
+use std::time::{Duration, Instant};
 
 use thiserror::Error;
 
+use super::negotiation::Extension;
+
 /// Minimum payload size to attempt compression
 pub const MIN_COMPRESS_SIZE: usize = 64;
 
 /// Default zstd compression level (1-22, higher = smaller but slower)
 pub const DEFAULT_COMPRESSION_LEVEL: i32 = 3;
 
+/// Minimum fraction of the payload compression must save to be used
+pub const MIN_COMPRESSION_SAVINGS: f64 = 0.10;
+
 /// Errors from compression operations.
 #[derive(Debug, Error)]
 pub enum CompressionError {
@@ -34,8 +46,59 @@ pub enum CompressionError {
         /// Maximum allowed size.
         limit: usize,
     },
+
+    /// Data was compressed against a different dictionary (by generation
+    /// or dictionary ID).
+    #[error("dictionary mismatch: expected {expected}, got {actual}")]
+    DictionaryMismatch {
+        /// Local dictionary generation or ID.
+        expected: u32,
+        /// Generation or ID the data was compressed against.
+        actual: u32,
+    },
+
+    /// Dictionary training failed or the dictionary is malformed.
+    #[error("invalid dictionary: {0}")]
+    InvalidDictionary(String),
+}
+
+/// Compress a payload only if it pays off
+///
+/// Returns `(true, compressed)` when compression saves at least
+/// [`MIN_COMPRESSION_SAVINGS`] of the payload, otherwise `(false, data)`
+/// unchanged. Payloads below [`MIN_COMPRESS_SIZE`] are not attempted.
+/// The caller sets `FrameFlags::COMPRESSED` from the returned flag so the
+/// receiver knows whether to call [`maybe_decompress`].
+pub fn maybe_compress(data: &[u8], level: u8) -> (bool, Vec<u8>) {
+    if data.len() < MIN_COMPRESS_SIZE {
+        return (false, data.to_vec());
+    }
+
+    let level = (level as i32).clamp(1, 22);
+    let max_size = (data.len() as f64 * (1.0 - MIN_COMPRESSION_SAVINGS)) as usize;
+    match zstd::bulk::compress(data, level) {
+        Ok(compressed) if compressed.len() <= max_size => (true, compressed),
+        _ => (false, data.to_vec()),
+    }
 }
 
+/// Undo [`maybe_compress`] given the frame's COMPRESSED flag
+///
+/// Uncompressed payloads are returned as-is.
+pub fn maybe_decompress(compressed: bool, data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    if compressed {
+        Compressor::new().decompress(data)
+    } else {
+        Ok(data.to_vec())
+    }
+}
+
+/// Magic number at the start of a zstd dictionary
+const ZSTD_DICTIONARY_MAGIC: u32 = 0xEC30_A437;
+
+/// Header size for dictionary-compressed data (dictionary ID)
+pub const DICTIONARY_HEADER_SIZE: usize = 4;
+
 /// Compression configuration
 #[derive(Debug, Clone)]
 pub struct CompressionConfig {
@@ -199,6 +262,108 @@ impl Default for Compressor {
     }
 }
 
+/// Compression context holding a trained zstd dictionary
+///
+/// Output of [`compress_with_dict`](Self::compress_with_dict) is prefixed
+/// with the dictionary ID (LE32); data compressed against another
+/// dictionary is rejected instead of decoding garbage.
+#[derive(Debug, Clone)]
+pub struct CompressionContext {
+    config: CompressionConfig,
+    dictionary: Vec<u8>,
+    dictionary_id: u32,
+}
+
+impl CompressionContext {
+    /// Train a dictionary of at most `dict_size` bytes from sample payloads
+    ///
+    /// zstd needs a reasonable number of samples (typically hundreds) to
+    /// train a useful dictionary; too few makes training fail.
+    pub fn train(samples: &[&[u8]], dict_size: usize) -> Result<Self, CompressionError> {
+        let dictionary = zstd::dict::from_samples(samples, dict_size)
+            .map_err(|e| CompressionError::InvalidDictionary(e.to_string()))?;
+        Self::from_dictionary(dictionary)
+    }
+
+    /// Load a previously trained dictionary
+    pub fn from_dictionary(dictionary: Vec<u8>) -> Result<Self, CompressionError> {
+        if dictionary.len() < 8
+            || u32::from_le_bytes(dictionary[0..4].try_into().expect("length checked above"))
+                != ZSTD_DICTIONARY_MAGIC
+        {
+            return Err(CompressionError::InvalidDictionary(
+                "missing zstd dictionary header".to_string(),
+            ));
+        }
+        let dictionary_id =
+            u32::from_le_bytes(dictionary[4..8].try_into().expect("length checked above"));
+
+        Ok(Self {
+            config: CompressionConfig::default(),
+            dictionary,
+            dictionary_id,
+        })
+    }
+
+    /// Set the compression config
+    pub fn with_config(mut self, config: CompressionConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Get the dictionary ID (from the zstd dictionary header)
+    pub fn dictionary_id(&self) -> u32 {
+        self.dictionary_id
+    }
+
+    /// Get the raw dictionary, e.g. to ship it to the peer
+    pub fn dictionary(&self) -> &[u8] {
+        &self.dictionary
+    }
+
+    /// Compression extension advertising this dictionary
+    pub fn extension(&self) -> super::Extension {
+        super::Extension::compression_with_dictionary(self.config.level as u8, self.dictionary_id)
+    }
+
+    /// Compress data against the dictionary
+    pub fn compress_with_dict(&self, data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        let compressed = zstd::bulk::Compressor::with_dictionary(self.config.level, &self.dictionary)
+            .and_then(|mut c| c.compress(data))
+            .map_err(|e| CompressionError::CompressionFailed(e.to_string()))?;
+
+        let mut out = Vec::with_capacity(DICTIONARY_HEADER_SIZE + compressed.len());
+        out.extend_from_slice(&self.dictionary_id.to_le_bytes());
+        out.extend_from_slice(&compressed);
+        Ok(out)
+    }
+
+    /// Decompress data produced by the peer's `compress_with_dict`
+    ///
+    /// # Errors
+    /// Returns `DictionaryMismatch` if the data was compressed against a
+    /// different dictionary.
+    pub fn decompress_with_dict(&self, data: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        if data.len() < DICTIONARY_HEADER_SIZE {
+            return Err(CompressionError::InvalidData);
+        }
+
+        let dictionary_id = u32::from_le_bytes(data[0..4].try_into().expect("length checked above"));
+        if dictionary_id != self.dictionary_id {
+            return Err(CompressionError::DictionaryMismatch {
+                expected: self.dictionary_id,
+                actual: dictionary_id,
+            });
+        }
+
+        zstd::bulk::Decompressor::with_dictionary(&self.dictionary)
+            .and_then(|mut d| {
+                d.decompress(&data[DICTIONARY_HEADER_SIZE..], self.config.max_decompressed_size)
+            })
+            .map_err(|e| CompressionError::DecompressionFailed(e.to_string()))
+    }
+}
+
 /// Result of compression attempt
 #[derive(Debug, Clone)]
 pub enum CompressResult {
@@ -275,6 +440,124 @@ impl CompressionStats {
     }
 }
 
+/// Default CPU time budget for compressing one frame
+pub const DEFAULT_COMPRESSION_BUDGET: Duration = Duration::from_micros(500);
+
+/// Samples the tuner collects before each level adjustment
+pub const TUNER_SAMPLE_WINDOW: u32 = 8;
+
+/// Adjusts the zstd level to the local CPU
+///
+/// Fed with per-frame compression timings and sizes, it lowers the level
+/// when frames take longer than the CPU budget or when compression saves
+/// too little to be worth the work (see [`MIN_COMPRESSION_SAVINGS`]), and
+/// raises it while frames finish in under half the budget. Decisions are
+/// made once per [`TUNER_SAMPLE_WINDOW`] samples to avoid oscillating.
+/// The chosen level is offered with [`extension`](Self::extension) when
+/// renegotiating.
+#[derive(Debug, Clone)]
+pub struct CompressionTuner {
+    /// Current level
+    level: u8,
+    /// Lowest level the tuner will pick
+    min_level: u8,
+    /// Highest level the tuner will pick
+    max_level: u8,
+    /// CPU time allowed per frame
+    budget: Duration,
+    /// Compression time summed over the current window
+    window_time: Duration,
+    /// Bytes in over the current window
+    window_original: u64,
+    /// Bytes out over the current window
+    window_compressed: u64,
+    /// Samples in the current window
+    window_samples: u32,
+}
+
+impl CompressionTuner {
+    /// Create a tuner starting at [`DEFAULT_COMPRESSION_LEVEL`]
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            level: DEFAULT_COMPRESSION_LEVEL as u8,
+            min_level: 1,
+            max_level: 19,
+            budget,
+            window_time: Duration::ZERO,
+            window_original: 0,
+            window_compressed: 0,
+            window_samples: 0,
+        }
+    }
+
+    /// Restrict the levels the tuner may choose
+    pub fn with_level_range(mut self, min_level: u8, max_level: u8) -> Self {
+        self.min_level = min_level.clamp(1, 22);
+        self.max_level = max_level.clamp(self.min_level, 22);
+        self.level = self.level.clamp(self.min_level, self.max_level);
+        self
+    }
+
+    /// Current level
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// Compression extension advertising the current level
+    pub fn extension(&self) -> Extension {
+        Extension::compression(self.level)
+    }
+
+    /// Compress with [`maybe_compress`] at the current level, timing it
+    pub fn compress(&mut self, data: &[u8]) -> (bool, Vec<u8>) {
+        let start = Instant::now();
+        let (compressed, payload) = maybe_compress(data, self.level);
+        if data.len() >= MIN_COMPRESS_SIZE {
+            self.record(start.elapsed(), data.len(), payload.len());
+        }
+        (compressed, payload)
+    }
+
+    /// Record one compression taking `elapsed` from `original` to `compressed` bytes
+    ///
+    /// Returns the new level when this sample completed a window and the
+    /// level changed.
+    pub fn record(&mut self, elapsed: Duration, original: usize, compressed: usize) -> Option<u8> {
+        self.window_time += elapsed;
+        self.window_original += original as u64;
+        self.window_compressed += compressed as u64;
+        self.window_samples += 1;
+        if self.window_samples < TUNER_SAMPLE_WINDOW {
+            return None;
+        }
+
+        let mean_time = self.window_time / self.window_samples;
+        let savings = if self.window_original == 0 {
+            0.0
+        } else {
+            1.0 - self.window_compressed as f64 / self.window_original as f64
+        };
+        self.window_time = Duration::ZERO;
+        self.window_original = 0;
+        self.window_compressed = 0;
+        self.window_samples = 0;
+
+        let previous = self.level;
+        if mean_time > self.budget || savings < MIN_COMPRESSION_SAVINGS {
+            self.level = self.level.saturating_sub(1).max(self.min_level);
+        } else if mean_time < self.budget / 2 {
+            self.level = (self.level + 1).min(self.max_level);
+        }
+        (self.level != previous).then_some(self.level)
+    }
+}
+
+impl Default for CompressionTuner {
+    fn default() -> Self {
+        Self::new(DEFAULT_COMPRESSION_BUDGET)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -410,4 +693,148 @@ mod tests {
         let decompressed = compressor.decompress(&buf[..written]).unwrap();
         assert_eq!(decompressed, data);
     }
+
+    /// A ~200-byte diff resembling a terminal cell update
+    fn similar_diff(i: usize, prefix: &str) -> Vec<u8> {
+        let mut diff = format!(
+            "{{\"{}\":{{\"row\":{},\"col\":{},\"fg\":\"#c0c0c0\",\"bg\":\"#000000\",\"attrs\":[\"bold\"],\"text\":\"$ cargo build --release # step {}\"}},\"cursor\":{{\"row\":{},\"col\":{}}}}}",
+            prefix,
+            i % 48,
+            (i * 7) % 120,
+            i,
+            i % 48,
+            (i * 3) % 120
+        )
+        .into_bytes();
+        diff.resize(200, b' ');
+        diff
+    }
+
+    fn train(prefix: &str) -> CompressionContext {
+        let samples: Vec<Vec<u8>> = (0..400).map(|i| similar_diff(i, prefix)).collect();
+        let refs: Vec<&[u8]> = samples.iter().map(Vec::as_slice).collect();
+        CompressionContext::train(&refs, 4096).unwrap()
+    }
+
+    #[test]
+    fn test_dictionary_beats_plain_compression() {
+        let context = train("cells");
+
+        let mut dict_total = 0;
+        let mut plain_total = 0;
+        for i in 1000..1100 {
+            let diff = similar_diff(i, "cells");
+
+            let compressed = context.compress_with_dict(&diff).unwrap();
+            assert_eq!(context.decompress_with_dict(&compressed).unwrap(), diff);
+            dict_total += compressed.len();
+
+            plain_total += zstd::bulk::compress(&diff, DEFAULT_COMPRESSION_LEVEL)
+                .unwrap()
+                .len();
+        }
+
+        assert!(
+            dict_total * 2 < plain_total,
+            "dictionary {} vs plain {}",
+            dict_total,
+            plain_total
+        );
+        assert_eq!(
+            context.extension().compression_dictionary_id(),
+            Some(context.dictionary_id())
+        );
+    }
+
+    #[test]
+    fn test_mismatched_dictionary_rejected() {
+        let sender = train("cells");
+        let receiver = train("scrollback");
+        assert_ne!(sender.dictionary_id(), receiver.dictionary_id());
+
+        let compressed = sender.compress_with_dict(&similar_diff(1, "cells")).unwrap();
+        assert!(matches!(
+            receiver.decompress_with_dict(&compressed),
+            Err(CompressionError::DictionaryMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_from_dictionary_rejects_garbage() {
+        assert!(matches!(
+            CompressionContext::from_dictionary(vec![0; 16]),
+            Err(CompressionError::InvalidDictionary(_))
+        ));
+    }
+
+    #[test]
+    fn test_maybe_compress_skips_random_bytes() {
+        use rand_chacha::ChaCha20Rng;
+        use rand_chacha::rand_core::{RngCore, SeedableRng};
+
+        let mut data = vec![0u8; 4096];
+        ChaCha20Rng::seed_from_u64(7).fill_bytes(&mut data);
+
+        let (compressed, payload) = maybe_compress(&data, 3);
+        assert!(!compressed);
+        assert_eq!(payload, data);
+        assert_eq!(maybe_decompress(compressed, &payload).unwrap(), data);
+    }
+
+    #[test]
+    fn test_maybe_compress_repetitive_run() {
+        let data = vec![b'x'; 4096];
+
+        let (compressed, payload) = maybe_compress(&data, 3);
+        assert!(compressed);
+        assert!(payload.len() < data.len() / 10);
+        assert_eq!(maybe_decompress(compressed, &payload).unwrap(), data);
+    }
+
+    fn run_window(tuner: &mut CompressionTuner, elapsed: Duration, original: usize, compressed: usize) -> Option<u8> {
+        (0..TUNER_SAMPLE_WINDOW)
+            .map(|_| tuner.record(elapsed, original, compressed))
+            .last()
+            .flatten()
+    }
+
+    #[test]
+    fn test_tuner_slow_cpu_lowers_level() {
+        let budget = Duration::from_micros(500);
+        let mut tuner = CompressionTuner::new(budget);
+        let start = tuner.level();
+
+        // Compresses well, but over budget
+        assert_eq!(run_window(&mut tuner, budget * 2, 1000, 300), Some(start - 1));
+        run_window(&mut tuner, budget * 2, 1000, 300);
+        assert_eq!(tuner.level(), start - 2);
+        assert_eq!(tuner.extension().compression_level(), Some(start - 2));
+    }
+
+    #[test]
+    fn test_tuner_poor_ratio_lowers_level() {
+        let budget = Duration::from_micros(500);
+        let mut tuner = CompressionTuner::new(budget);
+        let start = tuner.level();
+
+        // Fast, but saves almost nothing
+        assert_eq!(run_window(&mut tuner, budget / 10, 1000, 970), Some(start - 1));
+    }
+
+    #[test]
+    fn test_tuner_holds_in_sweet_spot() {
+        let budget = Duration::from_micros(500);
+        let mut tuner = CompressionTuner::new(budget);
+        let start = tuner.level();
+
+        for _ in 0..4 {
+            assert_eq!(run_window(&mut tuner, budget * 3 / 4, 1000, 400), None);
+        }
+        assert_eq!(tuner.level(), start);
+
+        // Headroom raises it, bounded by the range
+        let mut tuner = CompressionTuner::new(budget).with_level_range(1, start + 1);
+        assert_eq!(run_window(&mut tuner, budget / 10, 1000, 400), Some(start + 1));
+        assert_eq!(run_window(&mut tuner, budget / 10, 1000, 400), None);
+    }
 }
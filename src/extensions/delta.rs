@@ -0,0 +1,285 @@
+//! Dictionary-primed delta compression
+//!
+//! Diffs of the same state type tend to repeat the same shapes (cursor
+//! moves, small edits to the same region), but each one is too short for
+//! standalone zstd to find much redundancy. `DeltaCompressor` keeps a
+//! rolling raw-content dictionary built from recently acked diffs, so each
+//! new diff is compressed against the pattern of the ones before it.
+//!
+//! # Determinism
+//!
+//! Sender and receiver each hold a `DeltaCompressor` and MUST prime them
+//! identically: the same encoded diffs, in the same order, taken from the
+//! acked diff stream. A diff the peer has not acked must never be primed,
+//! or the dictionaries diverge. Every compressed diff carries the
+//! dictionary generation (number of primed diffs) it was compressed
+//! against; a receiver at a different generation rejects it with
+//! `DictionaryMismatch` rather than decoding garbage.
+//!
+//! Wire format:
+//! ```text
+//! +0  Dictionary generation (4 bytes LE32)
+//! +4  Encoding (1 byte: 0x00 = raw, 0x01 = zstd)
+//! +5  Encoded diff, possibly compressed
+//! ```
+
+use std::marker::PhantomData;
+
+use crate::core::SyncState;
+
+use super::{CompressionConfig, CompressionError};
+
+/// Default maximum size of the rolling dictionary in bytes.
+pub const DEFAULT_DELTA_DICTIONARY_SIZE: usize = 16 * 1024;
+
+/// Header size for a delta-compressed diff (generation + encoding).
+pub const DELTA_HEADER_SIZE: usize = 5;
+
+/// Encoding byte: diff stored as-is.
+const ENCODING_RAW: u8 = 0x00;
+
+/// Encoding byte: diff compressed with the rolling dictionary.
+const ENCODING_ZSTD: u8 = 0x01;
+
+/// Compresses diffs of state type `S` against a rolling dictionary.
+#[derive(Debug, Clone)]
+pub struct DeltaCompressor<S: SyncState> {
+    /// Compression settings
+    config: CompressionConfig,
+    /// Recently acked encoded diffs, oldest first
+    dictionary: Vec<u8>,
+    /// Maximum dictionary size in bytes
+    max_dictionary_size: usize,
+    /// Number of diffs primed so far
+    generation: u32,
+    _state: PhantomData<fn() -> S>,
+}
+
+impl<S: SyncState> DeltaCompressor<S> {
+    /// Create a delta compressor with default settings and an empty dictionary.
+    pub fn new() -> Self {
+        Self::with_config(CompressionConfig::default())
+    }
+
+    /// Create a delta compressor with custom compression settings.
+    pub fn with_config(config: CompressionConfig) -> Self {
+        Self {
+            config,
+            dictionary: Vec::new(),
+            max_dictionary_size: DEFAULT_DELTA_DICTIONARY_SIZE,
+            generation: 0,
+            _state: PhantomData,
+        }
+    }
+
+    /// Set the maximum dictionary size.
+    ///
+    /// Both peers must use the same size.
+    pub fn with_dictionary_size(mut self, size: usize) -> Self {
+        self.max_dictionary_size = size;
+        self.trim_dictionary();
+        self
+    }
+
+    /// Get the dictionary generation (number of diffs primed).
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Get the current dictionary contents.
+    pub fn dictionary(&self) -> &[u8] {
+        &self.dictionary
+    }
+
+    /// Prime the dictionary with an acked diff.
+    pub fn prime(&mut self, diff: &S::Diff) {
+        self.prime_encoded(&S::encode_diff(diff));
+    }
+
+    /// Prime the dictionary with an acked diff that is already encoded.
+    pub fn prime_encoded(&mut self, encoded: &[u8]) {
+        self.dictionary.extend_from_slice(encoded);
+        self.trim_dictionary();
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    /// Drop the oldest bytes until the dictionary fits.
+    fn trim_dictionary(&mut self) {
+        if self.dictionary.len() > self.max_dictionary_size {
+            let excess = self.dictionary.len() - self.max_dictionary_size;
+            self.dictionary.drain(..excess);
+        }
+    }
+
+    /// Compress a diff against the current dictionary.
+    ///
+    /// Falls back to the raw encoding when the diff is small or compression
+    /// doesn't help.
+    pub fn compress_diff(&self, diff: &S::Diff) -> Result<Vec<u8>, CompressionError> {
+        let encoded = S::encode_diff(diff);
+
+        let mut out = Vec::with_capacity(DELTA_HEADER_SIZE + encoded.len());
+        out.extend_from_slice(&self.generation.to_le_bytes());
+
+        if encoded.len() >= self.config.min_size {
+            let compressed =
+                zstd::bulk::Compressor::with_dictionary(self.config.level, &self.dictionary)
+                    .and_then(|mut c| c.compress(&encoded))
+                    .map_err(|e| CompressionError::CompressionFailed(e.to_string()))?;
+
+            if compressed.len() < encoded.len() {
+                out.push(ENCODING_ZSTD);
+                out.extend_from_slice(&compressed);
+                return Ok(out);
+            }
+        }
+
+        out.push(ENCODING_RAW);
+        out.extend_from_slice(&encoded);
+        Ok(out)
+    }
+
+    /// Decompress and decode a diff produced by the peer's `compress_diff`.
+    ///
+    /// # Errors
+    /// Returns `DictionaryMismatch` if the diff was compressed against a
+    /// different dictionary generation, and `InvalidData` if it is
+    /// malformed or does not decode as `S::Diff`.
+    pub fn decompress_diff(&self, data: &[u8]) -> Result<S::Diff, CompressionError> {
+        if data.len() < DELTA_HEADER_SIZE {
+            return Err(CompressionError::InvalidData);
+        }
+
+        let generation = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        if generation != self.generation {
+            return Err(CompressionError::DictionaryMismatch {
+                expected: self.generation,
+                actual: generation,
+            });
+        }
+
+        let body = &data[DELTA_HEADER_SIZE..];
+        let encoded = match data[4] {
+            ENCODING_RAW => body.to_vec(),
+            ENCODING_ZSTD => zstd::bulk::Decompressor::with_dictionary(&self.dictionary)
+                .and_then(|mut d| d.decompress(body, self.config.max_decompressed_size))
+                .map_err(|e| CompressionError::DecompressionFailed(e.to_string()))?,
+            _ => return Err(CompressionError::InvalidData),
+        };
+
+        S::decode_diff(&encoded).map_err(|_| CompressionError::InvalidData)
+    }
+}
+
+impl<S: SyncState> Default for DeltaCompressor<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{ApplyError, DecodeError};
+
+    /// Editor-like state whose diffs are textual edit records.
+    #[derive(Debug, Clone, Default)]
+    struct EditorState {
+        log: Vec<String>,
+    }
+
+    impl SyncState for EditorState {
+        type Diff = String;
+
+        const STATE_TYPE_ID: &'static str = "nomad.test.editor.v1";
+
+        fn diff_from(&self, old: &Self) -> Self::Diff {
+            self.log[old.log.len()..].join("\n")
+        }
+
+        fn apply_diff(&mut self, diff: &Self::Diff) -> Result<(), ApplyError> {
+            self.log.push(diff.clone());
+            Ok(())
+        }
+
+        fn encode_diff(diff: &Self::Diff) -> Vec<u8> {
+            diff.as_bytes().to_vec()
+        }
+
+        fn decode_diff(data: &[u8]) -> Result<Self::Diff, DecodeError> {
+            String::from_utf8(data.to_vec())
+                .map_err(|e| DecodeError::InvalidEncoding(e.to_string()))
+        }
+    }
+
+    fn edit(i: usize) -> String {
+        format!(
+            "{{\"op\":\"insert\",\"buffer\":\"src/main.rs\",\"cursor\":{{\"line\":{},\"column\":{}}},\"text\":\"let value_{} = compute(value_{});\"}}",
+            100 + i,
+            4 + i % 8,
+            i,
+            i.saturating_sub(1)
+        )
+    }
+
+    #[test]
+    fn test_primed_stream_beats_standalone() {
+        let mut sender = DeltaCompressor::<EditorState>::new();
+        let mut receiver = DeltaCompressor::<EditorState>::new();
+        let standalone = super::super::Compressor::new();
+
+        let mut delta_total = 0;
+        let mut standalone_total = 0;
+
+        for i in 0..50 {
+            let diff = edit(i);
+            let wire = sender.compress_diff(&diff).unwrap();
+            assert_eq!(receiver.decompress_diff(&wire).unwrap(), diff);
+
+            delta_total += wire.len();
+            standalone_total += standalone.compress(diff.as_bytes()).unwrap().data().len();
+
+            // Both ends prime from the same acked diff
+            sender.prime(&diff);
+            receiver.prime(&diff);
+        }
+
+        assert_eq!(sender.dictionary(), receiver.dictionary());
+        assert!(
+            delta_total * 2 < standalone_total,
+            "delta {} vs standalone {}",
+            delta_total,
+            standalone_total
+        );
+    }
+
+    #[test]
+    fn test_generation_mismatch_rejected() {
+        let mut sender = DeltaCompressor::<EditorState>::new();
+        let receiver = DeltaCompressor::<EditorState>::new();
+
+        // Sender primes a diff the receiver never acked
+        sender.prime(&edit(0));
+        let wire = sender.compress_diff(&edit(1)).unwrap();
+
+        assert!(matches!(
+            receiver.decompress_diff(&wire),
+            Err(CompressionError::DictionaryMismatch {
+                expected: 0,
+                actual: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_dictionary_size_bounded() {
+        let mut compressor = DeltaCompressor::<EditorState>::new().with_dictionary_size(256);
+        for i in 0..20 {
+            compressor.prime(&edit(i));
+        }
+
+        assert_eq!(compressor.generation(), 20);
+        assert_eq!(compressor.dictionary().len(), 256);
+        assert!(compressor.dictionary().ends_with(edit(19).as_bytes()));
+    }
+}
@@ -0,0 +1,564 @@
+//! Metadata extension
+//!
+//! Attaches a timestamp and a vector clock to application messages
+//! (extension [`ext_type::METADATA`]). The clock tracks causality between
+//! participants of a multi-user session; its size is capped by the
+//! negotiated [`MetadataConfig::max_causality_entries`].
+//!
+//! Metadata wire format:
+//! ```text
+//! +0  Timestamp (8 bytes LE64, microseconds since the Unix epoch)
+//! +8  Causality (vector clock, below)
+//! ```
+//!
+//! Vector clock wire format:
+//! ```text
+//! +0  Entry Count (1 byte)
+//! Per entry:
+//!   +0  ID Length (1 byte)
+//!   +1  Participant ID (ID Length bytes)
+//!   +n  Counter (8 bytes LE64)
+//! ```
+//!
+//! [`ext_type::METADATA`]: super::ext_type::METADATA
+
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::NegotiationError;
+
+/// Default cap on vector clock entries
+pub const DEFAULT_MAX_CAUSALITY_ENTRIES: u8 = 16;
+
+/// Timestamp size in encoded metadata
+pub const METADATA_TIMESTAMP_SIZE: usize = 8;
+
+/// Counter size in an encoded vector clock entry
+const COUNTER_SIZE: usize = 8;
+
+/// Smallest encoded vector clock entry (empty ID)
+const MIN_ENTRY_SIZE: usize = 1 + COUNTER_SIZE;
+
+/// Largest encoded vector clock entry (255-byte ID)
+const MAX_ENTRY_SIZE: usize = 1 + u8::MAX as usize + COUNTER_SIZE;
+
+/// Cap on the encoded size of a vector clock
+///
+/// Fits a clock at the default entry cap with the longest IDs.
+pub const MAX_VECTOR_CLOCK_SIZE: usize = 1 + DEFAULT_MAX_CAUSALITY_ENTRIES as usize * MAX_ENTRY_SIZE;
+
+/// Smoothing factor for the clock offset estimate (1/8, as for SRTT)
+pub const SKEW_ALPHA: f64 = 0.125;
+
+/// Negotiated metadata settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MetadataConfig {
+    /// Maximum number of participants in an encoded vector clock
+    pub max_causality_entries: u8,
+}
+
+impl Default for MetadataConfig {
+    fn default() -> Self {
+        Self {
+            max_causality_entries: DEFAULT_MAX_CAUSALITY_ENTRIES,
+        }
+    }
+}
+
+impl MetadataConfig {
+    /// Combine our settings with the peer's, taking the tighter cap
+    pub fn negotiate(&self, peer: &Self) -> Self {
+        Self {
+            max_causality_entries: self.max_causality_entries.min(peer.max_causality_entries),
+        }
+    }
+}
+
+/// Per-participant event counters.
+///
+/// Participants are identified by opaque byte strings of up to 255 bytes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VectorClock {
+    entries: BTreeMap<Vec<u8>, u64>,
+}
+
+impl VectorClock {
+    /// Create an empty clock
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count an event by `participant`, returning its new counter
+    pub fn increment(&mut self, participant: &[u8]) -> u64 {
+        let counter = self.entries.entry(participant.to_vec()).or_insert(0);
+        *counter += 1;
+        *counter
+    }
+
+    /// Get a participant's counter (0 if absent)
+    pub fn get(&self, participant: &[u8]) -> u64 {
+        self.entries.get(participant).copied().unwrap_or(0)
+    }
+
+    /// Take the entrywise maximum with another clock
+    pub fn merge(&mut self, other: &Self) {
+        for (participant, &counter) in &other.entries {
+            let entry = self.entries.entry(participant.clone()).or_insert(0);
+            *entry = (*entry).max(counter);
+        }
+    }
+
+    /// Number of participants
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the clock has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate over (participant, counter) entries in participant order
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], u64)> {
+        self.entries
+            .iter()
+            .map(|(participant, &counter)| (participant.as_slice(), counter))
+    }
+
+    /// Drop the lowest-counter participants until at most `max_entries`
+    /// remain
+    ///
+    /// Ties are broken by participant ID, keeping the smaller one, so every
+    /// peer prunes the same clock the same way.
+    pub fn prune(&mut self, max_entries: usize) {
+        if self.entries.len() <= max_entries {
+            return;
+        }
+
+        let mut ranked: Vec<(Vec<u8>, u64)> =
+            std::mem::take(&mut self.entries).into_iter().collect();
+        ranked.sort_by(|(a_id, a), (b_id, b)| b.cmp(a).then_with(|| a_id.cmp(b_id)));
+        ranked.truncate(max_entries);
+        self.entries = ranked.into_iter().collect();
+    }
+
+    /// Copy of the clock pruned to the negotiated cap
+    pub fn truncated(&self, config: &MetadataConfig) -> Self {
+        let mut clock = self.clone();
+        clock.prune(config.max_causality_entries as usize);
+        clock
+    }
+
+    /// Encode to wire format
+    ///
+    /// Fails if there are more than 255 entries, an ID longer than 255
+    /// bytes, or the encoding would exceed [`MAX_VECTOR_CLOCK_SIZE`], which
+    /// the peer would refuse to decode.
+    pub fn encode(&self) -> Result<Vec<u8>, NegotiationError> {
+        let count = u8::try_from(self.entries.len()).map_err(|_| NegotiationError::InvalidData)?;
+        let mut buf = vec![count];
+        for (participant, counter) in &self.entries {
+            let id_len =
+                u8::try_from(participant.len()).map_err(|_| NegotiationError::InvalidData)?;
+            buf.push(id_len);
+            buf.extend_from_slice(participant);
+            buf.extend_from_slice(&counter.to_le_bytes());
+        }
+        if buf.len() > MAX_VECTOR_CLOCK_SIZE {
+            return Err(NegotiationError::InvalidData);
+        }
+        Ok(buf)
+    }
+
+    /// Decode from wire format, returning the clock and bytes consumed
+    ///
+    /// Equivalent to [`decode_bounded`](Self::decode_bounded) with no entry
+    /// cap beyond the format's 255.
+    pub fn decode(data: &[u8]) -> Result<(Self, usize), NegotiationError> {
+        Self::decode_bounded(data, u8::MAX as usize)
+    }
+
+    /// Decode from wire format, accepting at most `max_entries` entries
+    ///
+    /// The declared count is checked against `max_entries` and against the
+    /// bytes actually present before any entry is decoded, and the clock
+    /// may not span more than [`MAX_VECTOR_CLOCK_SIZE`] bytes, so malformed
+    /// input fails with `InvalidData` before doing much work.
+    pub fn decode_bounded(
+        data: &[u8],
+        max_entries: usize,
+    ) -> Result<(Self, usize), NegotiationError> {
+        let Some(&count) = data.first() else {
+            return Err(NegotiationError::TooShort {
+                expected: 1,
+                actual: 0,
+            });
+        };
+        let count = count as usize;
+        if count > max_entries || 1 + count * MIN_ENTRY_SIZE > data.len().min(MAX_VECTOR_CLOCK_SIZE) {
+            return Err(NegotiationError::InvalidData);
+        }
+
+        let mut offset = 1;
+        let mut entries = BTreeMap::new();
+        for _ in 0..count {
+            let id_len = *data.get(offset).ok_or(NegotiationError::TooShort {
+                expected: offset + 1,
+                actual: data.len(),
+            })? as usize;
+            let id_start = offset + 1;
+            let end = id_start + id_len + COUNTER_SIZE;
+            if end > MAX_VECTOR_CLOCK_SIZE {
+                return Err(NegotiationError::InvalidData);
+            }
+            if data.len() < end {
+                return Err(NegotiationError::TooShort {
+                    expected: end,
+                    actual: data.len(),
+                });
+            }
+            let participant = data[id_start..id_start + id_len].to_vec();
+            let counter = u64::from_le_bytes(
+                data[id_start + id_len..end]
+                    .try_into()
+                    .expect("length checked above"),
+            );
+            if entries.insert(participant, counter).is_some() {
+                // Duplicate participant
+                return Err(NegotiationError::InvalidData);
+            }
+            offset = end;
+        }
+
+        Ok((Self { entries }, offset))
+    }
+}
+
+/// Metadata carried alongside an application message.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Sender's wall-clock time in microseconds since the Unix epoch
+    pub timestamp: u64,
+    /// Sender's causality clock
+    pub causality: VectorClock,
+}
+
+impl Metadata {
+    /// Create metadata stamped with the current time
+    pub fn now(causality: VectorClock) -> Self {
+        Self {
+            timestamp: unix_micros(SystemTime::now()),
+            causality,
+        }
+    }
+
+    /// Encode to wire format
+    ///
+    /// Rejects a clock with more entries than the negotiated cap; use
+    /// [`VectorClock::truncated`] to fit it first.
+    pub fn encode(&self, config: &MetadataConfig) -> Result<Vec<u8>, NegotiationError> {
+        let max = config.max_causality_entries as usize;
+        if self.causality.len() > max {
+            return Err(NegotiationError::TooManyEntries {
+                count: self.causality.len(),
+                max,
+            });
+        }
+
+        let mut buf = self.timestamp.to_le_bytes().to_vec();
+        buf.extend_from_slice(&self.causality.encode()?);
+        Ok(buf)
+    }
+
+    /// Decode from wire format
+    ///
+    /// Rejects a clock declaring more entries than the negotiated cap.
+    pub fn decode(data: &[u8], config: &MetadataConfig) -> Result<Self, NegotiationError> {
+        if data.len() < METADATA_TIMESTAMP_SIZE {
+            return Err(NegotiationError::TooShort {
+                expected: METADATA_TIMESTAMP_SIZE,
+                actual: data.len(),
+            });
+        }
+        let timestamp = u64::from_le_bytes(
+            data[..METADATA_TIMESTAMP_SIZE]
+                .try_into()
+                .expect("length checked above"),
+        );
+        let (causality, _) = VectorClock::decode_bounded(
+            &data[METADATA_TIMESTAMP_SIZE..],
+            config.max_causality_entries as usize,
+        )?;
+        Ok(Self {
+            timestamp,
+            causality,
+        })
+    }
+}
+
+/// Clock skew estimator for metadata timestamps.
+///
+/// Each received [`Metadata::timestamp`] gives `receive - timestamp`, the
+/// one-way delay plus the offset between the clocks. Assuming a symmetric
+/// path, the one-way delay is half the RTT, which leaves an offset sample;
+/// samples are smoothed like SRTT. The offset is local minus remote time,
+/// so a peer whose clock runs ahead has a negative offset.
+///
+/// Complements the transport's `TimestampTracker`, which measures RTT from
+/// session-relative echoes and never compares wall clocks.
+#[derive(Debug, Clone, Default)]
+pub struct SkewEstimator {
+    /// Smoothed offset in microseconds
+    offset: Option<f64>,
+    /// Most recent `receive - timestamp`, in microseconds
+    last_raw: i64,
+}
+
+impl SkewEstimator {
+    /// Create an estimator with no samples
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record metadata received now, with the current RTT estimate
+    pub fn observe(&mut self, metadata: &Metadata, rtt: Duration) {
+        self.observe_at(metadata.timestamp, unix_micros(SystemTime::now()), rtt);
+    }
+
+    /// Record a remote timestamp received at `received_at` (both in
+    /// microseconds since the Unix epoch)
+    pub fn observe_at(&mut self, remote_timestamp: u64, received_at: u64, rtt: Duration) {
+        let raw = received_at as i64 - remote_timestamp as i64;
+        let sample = raw as f64 - rtt.as_micros() as f64 / 2.0;
+        self.last_raw = raw;
+        self.offset = Some(match self.offset {
+            Some(offset) => (1.0 - SKEW_ALPHA) * offset + SKEW_ALPHA * sample,
+            None => sample,
+        });
+    }
+
+    /// Smoothed offset of the local clock from the remote one, in
+    /// microseconds
+    pub fn offset_micros(&self) -> Option<i64> {
+        self.offset.map(|offset| offset.round() as i64)
+    }
+
+    /// One-way delay of the last sample, corrected for clock skew
+    ///
+    /// Clamped to zero when jitter makes the corrected delay negative.
+    pub fn estimated_owd(&self) -> Option<Duration> {
+        self.offset
+            .map(|offset| Duration::from_micros((self.last_raw as f64 - offset).max(0.0) as u64))
+    }
+
+    /// Convert a remote timestamp to local time using the current offset
+    pub fn to_local(&self, remote_timestamp: u64) -> Option<u64> {
+        self.offset_micros()
+            .map(|offset| remote_timestamp.saturating_add_signed(offset))
+    }
+}
+
+/// Microseconds since the Unix epoch (0 for times before it)
+fn unix_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_micros() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(entries: &[(&str, u64)]) -> VectorClock {
+        let mut clock = VectorClock::new();
+        for &(participant, counter) in entries {
+            for _ in 0..counter {
+                clock.increment(participant.as_bytes());
+            }
+        }
+        clock
+    }
+
+    #[test]
+    fn test_prune_keeps_highest_counters() {
+        let mut c = clock(&[
+            ("alice", 5),
+            ("bob", 1),
+            ("carol", 9),
+            ("dave", 3),
+            ("erin", 3),
+        ]);
+        c.prune(3);
+        assert_eq!(c.len(), 3);
+        assert_eq!(c.get(b"carol"), 9);
+        assert_eq!(c.get(b"alice"), 5);
+        // Tie at 3: the smaller ID survives
+        assert_eq!(c.get(b"dave"), 3);
+        assert_eq!(c.get(b"erin"), 0);
+        assert_eq!(c.get(b"bob"), 0);
+
+        // Under the limit: unchanged
+        let before = c.clone();
+        c.prune(10);
+        assert_eq!(c, before);
+    }
+
+    #[test]
+    fn test_truncated_uses_config_cap() {
+        let c = clock(&[("a", 1), ("b", 2), ("c", 3)]);
+        let config = MetadataConfig {
+            max_causality_entries: 2,
+        };
+        let truncated = c.truncated(&config);
+        assert_eq!(truncated.len(), 2);
+        assert_eq!(truncated.get(b"a"), 0);
+        assert_eq!(c.len(), 3);
+    }
+
+    #[test]
+    fn test_encode_rejects_oversized_clock() {
+        let config = MetadataConfig {
+            max_causality_entries: 2,
+        };
+        let metadata = Metadata {
+            timestamp: 1,
+            causality: clock(&[("a", 1), ("b", 1), ("c", 1)]),
+        };
+        assert_eq!(
+            metadata.encode(&config),
+            Err(NegotiationError::TooManyEntries { count: 3, max: 2 })
+        );
+
+        let fitted = Metadata {
+            causality: metadata.causality.truncated(&config),
+            ..metadata
+        };
+        assert!(fitted.encode(&config).is_ok());
+    }
+
+    #[test]
+    fn test_metadata_roundtrip() {
+        let metadata = Metadata {
+            timestamp: 1_700_000_000_123_456,
+            causality: clock(&[("alice", 2), ("bob", 7)]),
+        };
+        let config = MetadataConfig::default();
+        let encoded = metadata.encode(&config).unwrap();
+        assert_eq!(Metadata::decode(&encoded, &config).unwrap(), metadata);
+
+        assert!(matches!(
+            Metadata::decode(&encoded[..encoded.len() - 1], &config),
+            Err(NegotiationError::TooShort { .. })
+        ));
+    }
+
+    #[test]
+    fn test_clock_roundtrip_at_size_limit() {
+        // Default entry cap, every ID at the 255-byte maximum
+        let mut c = VectorClock::new();
+        for i in 0..DEFAULT_MAX_CAUSALITY_ENTRIES {
+            c.increment(&[i; 255]);
+        }
+        let encoded = c.encode().unwrap();
+        assert_eq!(encoded.len(), MAX_VECTOR_CLOCK_SIZE);
+        assert_eq!(VectorClock::decode(&encoded).unwrap(), (c.clone(), encoded.len()));
+
+        let metadata = Metadata {
+            timestamp: 1,
+            causality: c.clone(),
+        };
+        let config = MetadataConfig::default();
+        let encoded = metadata.encode(&config).unwrap();
+        assert_eq!(Metadata::decode(&encoded, &config).unwrap(), metadata);
+
+        // One more entry no longer fits
+        c.increment(b"x");
+        assert_eq!(c.encode(), Err(NegotiationError::InvalidData));
+    }
+
+    #[test]
+    fn test_decode_rejects_declared_count_early() {
+        let config = MetadataConfig::default();
+
+        // 255 entries declared, none present
+        let mut data = 7u64.to_le_bytes().to_vec();
+        data.push(255);
+        assert_eq!(Metadata::decode(&data, &config), Err(NegotiationError::InvalidData));
+        assert_eq!(VectorClock::decode(&data[8..]), Err(NegotiationError::InvalidData));
+
+        // Well-formed, but more entries than negotiated
+        let tight = MetadataConfig {
+            max_causality_entries: 1,
+        };
+        let encoded = Metadata {
+            timestamp: 1,
+            causality: clock(&[("a", 1), ("b", 1)]),
+        }
+        .encode(&config)
+        .unwrap();
+        assert_eq!(Metadata::decode(&encoded, &tight), Err(NegotiationError::InvalidData));
+    }
+
+    #[test]
+    fn test_decode_random_bytes_never_panics() {
+        use rand_chacha::ChaCha20Rng;
+        use rand_chacha::rand_core::{RngCore, SeedableRng};
+
+        let config = MetadataConfig::default();
+        let mut rng = ChaCha20Rng::seed_from_u64(0x6d65_7461);
+        for _ in 0..10_000 {
+            let mut data = vec![0u8; (rng.next_u32() % 96) as usize];
+            rng.fill_bytes(&mut data);
+            // Bias the count toward plausible values to reach entry parsing
+            if data.len() > METADATA_TIMESTAMP_SIZE && rng.next_u32() % 2 == 0 {
+                data[METADATA_TIMESTAMP_SIZE] %= 8;
+            }
+            if let Ok(metadata) = Metadata::decode(&data, &config) {
+                assert!(metadata.causality.len() <= DEFAULT_MAX_CAUSALITY_ENTRIES as usize);
+            }
+            let _ = VectorClock::decode(&data);
+        }
+    }
+
+    #[test]
+    fn test_merge_and_negotiate() {
+        let mut a = clock(&[("x", 3), ("y", 1)]);
+        a.merge(&clock(&[("y", 4), ("z", 2)]));
+        assert_eq!(
+            a.iter().collect::<Vec<_>>(),
+            vec![(&b"x"[..], 3), (&b"y"[..], 4), (&b"z"[..], 2)]
+        );
+
+        let ours = MetadataConfig::default();
+        let theirs = MetadataConfig {
+            max_causality_entries: 4,
+        };
+        assert_eq!(ours.negotiate(&theirs).max_causality_entries, 4);
+    }
+
+    #[test]
+    fn test_skew_estimator_converges_to_fixed_offset() {
+        const SECOND: u64 = 1_000_000;
+        let rtt = Duration::from_millis(40);
+        let mut estimator = SkewEstimator::new();
+        assert_eq!(estimator.estimated_owd(), None);
+
+        // Remote clock runs 50 ms ahead; one-way delay is 20 ms +- 5 ms
+        let mut remote_clock = 1_700_000_000 * SECOND;
+        for i in 0..200u64 {
+            let owd = 20_000 + (i * 7919 % 10_001) - 5_000;
+            let received_at = remote_clock - 50_000 + owd;
+            estimator.observe_at(remote_clock, received_at, rtt);
+            remote_clock += SECOND / 30;
+        }
+
+        let offset = estimator.offset_micros().unwrap();
+        assert!((offset + 50_000).abs() < 2_000, "offset {}", offset);
+
+        let owd = estimator.estimated_owd().unwrap();
+        assert!(owd > Duration::from_millis(13) && owd < Duration::from_millis(27));
+
+        // A remote timestamp maps back to local time
+        let local = estimator.to_local(remote_clock).unwrap();
+        assert!(local.abs_diff(remote_clock - 50_000) < 2_000);
+    }
+}
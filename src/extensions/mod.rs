@@ -2,10 +2,31 @@
 //!
 //! Implements:
 //! - Extension negotiation (TLV format)
-//! - zstd compression (extension 0x0001)
+//! - zstd compression (extension 0x0001), optionally with a trained dictionary
+//! - Dictionary-primed delta compression of sync diffs
+//! - Batching of several sync messages per frame (extension 0x0004)
+//! - Priority-ordered send queue (extension 0x0005)
+//! - Rate hints (extension 0x0006), enforced by `FramePacer::set_rate_limit`
+//! - Checkpoints of full state for initial sync (extension 0x0008)
+//! - Selective sync subscriptions by path glob (extension 0x0009)
+//! - Message metadata with bounded vector clocks (extension 0x000A)
 
+#[cfg(feature = "sync")]
+mod batching;
+mod checkpoint;
 mod compression;
+mod delta;
+mod metadata;
 mod negotiation;
+mod priority;
+mod selective;
 
+#[cfg(feature = "sync")]
+pub use batching::*;
+pub use checkpoint::*;
 pub use compression::*;
+pub use delta::*;
+pub use metadata::*;
 pub use negotiation::*;
+pub use priority::*;
+pub use selective::*;
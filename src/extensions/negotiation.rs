@@ -5,6 +5,8 @@
 
 use thiserror::Error;
 
+use super::compression::DEFAULT_COMPRESSION_LEVEL;
+
 /// Extension type identifiers
 pub mod ext_type {
     /// Compression extension (zstd)
@@ -13,6 +15,24 @@ pub mod ext_type {
     pub const SCROLLBACK: u16 = 0x0002;
     /// Prediction extension (terminal-specific)
     pub const PREDICTION: u16 = 0x0003;
+    /// Batching extension (several sync messages per frame)
+    pub const BATCHING: u16 = 0x0004;
+    /// Priority extension (per-message priority levels)
+    pub const PRIORITY: u16 = 0x0005;
+    /// Rate hints extension (peer's acceptable update frequency)
+    pub const RATE_HINTS: u16 = 0x0006;
+    /// Sync NACK extension (gap bitmap in sync messages)
+    pub const SYNC_NACK: u16 = 0x0007;
+    /// Checkpoint extension (full-state snapshots for initial sync)
+    pub const CHECKPOINT: u16 = 0x0008;
+    /// Selective sync extension (path-pattern subscriptions)
+    pub const SELECTIVE_SYNC: u16 = 0x0009;
+    /// Metadata extension (timestamps and vector clocks)
+    pub const METADATA: u16 = 0x000A;
+    /// Sync preamble extension (magic and wire version on sync messages)
+    pub const SYNC_PREAMBLE: u16 = 0x000B;
+    /// Flow control extension (receive-window advertisement in sync messages)
+    pub const FLOW_CONTROL: u16 = 0x000C;
 }
 
 /// Errors from extension negotiation.
@@ -38,6 +58,19 @@ pub enum NegotiationError {
     /// Output buffer is too small to hold encoded extension.
     #[error("buffer too small for encoding")]
     BufferTooSmall,
+
+    /// Subscription pattern does not compile.
+    #[error("invalid pattern: {0}")]
+    InvalidPattern(String),
+
+    /// More entries than the negotiated limit allows.
+    #[error("too many entries: {count} exceeds limit {max}")]
+    TooManyEntries {
+        /// Number of entries present.
+        count: usize,
+        /// Negotiated maximum.
+        max: usize,
+    },
 }
 
 /// Extension TLV (Type-Length-Value) format
@@ -81,6 +114,19 @@ impl Extension {
         }
     }
 
+    /// Create compression extension with level and a trained dictionary
+    ///
+    /// Data is the level followed by the dictionary ID (LE32), so both
+    /// sides can confirm they hold the same dictionary.
+    pub fn compression_with_dictionary(level: u8, dictionary_id: u32) -> Self {
+        let mut data = vec![level];
+        data.extend_from_slice(&dictionary_id.to_le_bytes());
+        Self {
+            ext_type: ext_type::COMPRESSION,
+            data,
+        }
+    }
+
     /// Get compression level if this is a compression extension
     pub fn compression_level(&self) -> Option<u8> {
         if self.ext_type == ext_type::COMPRESSION && !self.data.is_empty() {
@@ -90,6 +136,34 @@ impl Extension {
         }
     }
 
+    /// Get the dictionary ID if this is a compression extension with one
+    pub fn compression_dictionary_id(&self) -> Option<u32> {
+        if self.ext_type == ext_type::COMPRESSION && self.data.len() >= 5 {
+            Some(u32::from_le_bytes(self.data[1..5].try_into().expect("length checked above")))
+        } else {
+            None
+        }
+    }
+
+    /// Create rate hints extension carrying the given hint
+    #[cfg(feature = "transport")]
+    pub fn rate_hint(hint: crate::transport::RateHint) -> Self {
+        Self {
+            ext_type: ext_type::RATE_HINTS,
+            data: hint.encode().to_vec(),
+        }
+    }
+
+    /// Get the rate hint if this is a rate hints extension
+    #[cfg(feature = "transport")]
+    pub fn as_rate_hint(&self) -> Option<crate::transport::RateHint> {
+        if self.ext_type == ext_type::RATE_HINTS {
+            crate::transport::RateHint::decode(&self.data).ok()
+        } else {
+            None
+        }
+    }
+
     /// Total wire size
     pub fn wire_size(&self) -> usize {
         EXTENSION_HEADER_SIZE + self.data.len()
@@ -203,6 +277,12 @@ impl ExtensionSet {
             .and_then(|e| e.compression_level())
     }
 
+    /// Get the compression dictionary ID if one was negotiated
+    pub fn compression_dictionary_id(&self) -> Option<u32> {
+        self.get(ext_type::COMPRESSION)
+            .and_then(|e| e.compression_dictionary_id())
+    }
+
     /// Get all extensions
     pub fn iter(&self) -> impl Iterator<Item = &Extension> {
         self.extensions.iter()
@@ -233,11 +313,48 @@ impl ExtensionSet {
     }
 
     /// Decode all extensions from buffer
-    pub fn decode(mut data: &[u8]) -> Result<Self, NegotiationError> {
+    ///
+    /// Unbounded; use [`decode_bounded`](Self::decode_bounded) for
+    /// untrusted input.
+    ///
+    /// # Errors
+    /// Returns `InvalidData` if an extension type appears more than once.
+    pub fn decode(data: &[u8]) -> Result<Self, NegotiationError> {
+        Self::decode_bounded(data, usize::MAX, usize::MAX)
+    }
+
+    /// Decode at most `max_count` extensions totalling at most `max_total_bytes`.
+    ///
+    /// Both limits are checked before anything is allocated for the
+    /// offending extension, so a hostile peer cannot force large
+    /// allocations.
+    ///
+    /// # Errors
+    /// Returns `InvalidData` if the buffer exceeds `max_total_bytes` or an
+    /// extension type appears more than once, and `TooManyEntries` if it
+    /// holds more than `max_count` extensions.
+    pub fn decode_bounded(
+        mut data: &[u8],
+        max_count: usize,
+        max_total_bytes: usize,
+    ) -> Result<Self, NegotiationError> {
+        if data.len() > max_total_bytes {
+            return Err(NegotiationError::InvalidData);
+        }
+
         let mut set = Self::new();
 
         while !data.is_empty() {
+            if set.len() == max_count {
+                return Err(NegotiationError::TooManyEntries {
+                    count: set.len() + 1,
+                    max: max_count,
+                });
+            }
             let (ext, consumed) = Extension::decode_with_length(data)?;
+            if set.has(ext.ext_type) {
+                return Err(NegotiationError::InvalidData);
+            }
             set.add(ext);
             data = &data[consumed..];
         }
@@ -260,6 +377,36 @@ impl ExtensionSet {
     }
 }
 
+/// Extensions compiled into this build, with their default configs
+///
+/// Reflects compile-time features at runtime. Use it as the basis for a
+/// handshake offer, or to report capabilities. Extensions implemented in
+/// the sync or transport layers are listed only when that layer is
+/// compiled in. Extensions that are only assigned a type ID (e.g.
+/// scrollback, prediction) but have no implementation here are not listed.
+pub fn supported_extensions() -> ExtensionSet {
+    let mut set = ExtensionSet::new();
+    set.add_compression(DEFAULT_COMPRESSION_LEVEL as u8);
+    let sync = cfg!(feature = "sync");
+    let transport = cfg!(feature = "transport");
+    for (ext, compiled) in [
+        (ext_type::BATCHING, sync),
+        (ext_type::PRIORITY, true),
+        (ext_type::RATE_HINTS, transport),
+        (ext_type::SYNC_NACK, sync),
+        (ext_type::CHECKPOINT, true),
+        (ext_type::SELECTIVE_SYNC, true),
+        (ext_type::METADATA, true),
+        (ext_type::SYNC_PREAMBLE, sync),
+        (ext_type::FLOW_CONTROL, sync),
+    ] {
+        if compiled {
+            set.add(Extension::empty(ext));
+        }
+    }
+    set
+}
+
 /// Negotiate extensions between client and server offers
 ///
 /// Returns the intersection of supported extensions.
@@ -268,11 +415,18 @@ pub fn negotiate(offered: &ExtensionSet, supported: &ExtensionSet) -> ExtensionS
 
     for ext in offered.iter() {
         if let Some(supported_ext) = supported.get(ext.ext_type) {
-            // For compression, use the lower level
+            // For compression, use the lower level, and keep the dictionary
+            // only if both sides hold the same one
             if ext.ext_type == ext_type::COMPRESSION {
                 let offered_level = ext.compression_level().unwrap_or(3);
                 let supported_level = supported_ext.compression_level().unwrap_or(3);
-                result.add(Extension::compression(offered_level.min(supported_level)));
+                let level = offered_level.min(supported_level);
+                match (ext.compression_dictionary_id(), supported_ext.compression_dictionary_id()) {
+                    (Some(offered_id), Some(supported_id)) if offered_id == supported_id => {
+                        result.add(Extension::compression_with_dictionary(level, offered_id));
+                    }
+                    _ => result.add(Extension::compression(level)),
+                }
             } else {
                 // For other extensions, use the offered version
                 result.add(ext.clone());
@@ -367,6 +521,60 @@ mod tests {
         assert!(decoded.has(0x0100));
     }
 
+    #[test]
+    fn test_extension_set_decode_rejects_duplicate_type() {
+        let mut encoded = Extension::compression(3).encode();
+        encoded.extend_from_slice(&Extension::compression(10).encode());
+
+        assert!(matches!(ExtensionSet::decode(&encoded), Err(NegotiationError::InvalidData)));
+    }
+
+    #[test]
+    fn test_extension_set_decode_distinct_types() {
+        let mut encoded = Extension::compression(3).encode();
+        encoded.extend_from_slice(&Extension::new(0x0100, vec![0xAA]).encode());
+        encoded.extend_from_slice(&Extension::new(0x0101, vec![]).encode());
+
+        let decoded = ExtensionSet::decode(&encoded).unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded.compression_level(), Some(3));
+        assert!(decoded.has(0x0100) && decoded.has(0x0101));
+    }
+
+    fn encoded_set(count: u16) -> Vec<u8> {
+        (0..count).flat_map(|i| Extension::new(0x0100 + i, vec![]).encode()).collect()
+    }
+
+    #[test]
+    fn test_decode_bounded_count_limit() {
+        let at_limit = encoded_set(4);
+        assert_eq!(ExtensionSet::decode_bounded(&at_limit, 4, 1024).unwrap().len(), 4);
+
+        let over_limit = encoded_set(5);
+        assert_eq!(
+            ExtensionSet::decode_bounded(&over_limit, 4, 1024).unwrap_err(),
+            NegotiationError::TooManyEntries { count: 5, max: 4 }
+        );
+    }
+
+    #[test]
+    fn test_decode_bounded_size_limit() {
+        let encoded = encoded_set(4);
+        let size = encoded.len();
+        assert!(ExtensionSet::decode_bounded(&encoded, 16, size).is_ok());
+        assert_eq!(
+            ExtensionSet::decode_bounded(&encoded, 16, size - 1).unwrap_err(),
+            NegotiationError::InvalidData
+        );
+
+        // One extension alone larger than the budget
+        let oversized = Extension::new(0x0100, vec![0; 64]).encode();
+        assert_eq!(
+            ExtensionSet::decode_bounded(&oversized, 16, 64).unwrap_err(),
+            NegotiationError::InvalidData
+        );
+    }
+
     #[test]
     fn test_extension_set_replace() {
         let mut set = ExtensionSet::new();
@@ -409,6 +617,25 @@ mod tests {
         assert!(result.is_empty());
     }
 
+    #[test]
+    fn test_negotiate_compression_dictionary() {
+        let mut offered = ExtensionSet::new();
+        offered.add(Extension::compression_with_dictionary(5, 0xABCD));
+
+        let mut same = ExtensionSet::new();
+        same.add(Extension::compression_with_dictionary(3, 0xABCD));
+        let result = negotiate(&offered, &same);
+        assert_eq!(result.compression_level(), Some(3));
+        assert_eq!(result.compression_dictionary_id(), Some(0xABCD));
+
+        // Different dictionaries fall back to plain compression
+        let mut other = ExtensionSet::new();
+        other.add(Extension::compression_with_dictionary(3, 0x1234));
+        let result = negotiate(&offered, &other);
+        assert!(result.has_compression());
+        assert_eq!(result.compression_dictionary_id(), None);
+    }
+
     #[test]
     fn test_extension_set_remove() {
         let mut set = ExtensionSet::new();
@@ -443,4 +670,25 @@ mod tests {
         let result = ext.encode_into(&mut buf);
         assert!(matches!(result, Err(NegotiationError::BufferTooSmall)));
     }
+
+    #[test]
+    fn test_supported_extensions() {
+        let supported = supported_extensions();
+
+        assert!(supported.has_compression());
+        assert_eq!(
+            supported.compression_level(),
+            Some(DEFAULT_COMPRESSION_LEVEL as u8)
+        );
+        assert!(supported.has(ext_type::CHECKPOINT));
+        assert!(supported.has(ext_type::METADATA));
+        assert_eq!(supported.has(ext_type::RATE_HINTS), cfg!(feature = "transport"));
+        assert_eq!(supported.has(ext_type::BATCHING), cfg!(feature = "sync"));
+        assert_eq!(supported.has(ext_type::FLOW_CONTROL), cfg!(feature = "sync"));
+        assert!(!supported.has(ext_type::SCROLLBACK));
+        assert!(!supported.has(ext_type::PREDICTION));
+
+        // Negotiating against ourselves keeps everything
+        assert_eq!(negotiate(&supported, &supported).encode(), supported.encode());
+    }
 }
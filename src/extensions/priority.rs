@@ -0,0 +1,204 @@
+//! Priority extension
+//!
+//! Orders outgoing messages by priority level (extension
+//! [`ext_type::PRIORITY`]). Critical messages (e.g. input echoes) jump ahead
+//! of queued background ones (e.g. scrollback), while a starvation limit
+//! guarantees background traffic still drains under sustained load.
+//!
+//! [`ext_type::PRIORITY`]: super::ext_type::PRIORITY
+
+use std::collections::VecDeque;
+
+/// Default number of dequeues a waiting message can be passed over before
+/// it is promoted.
+pub const DEFAULT_STARVATION_LIMIT: u32 = 8;
+
+/// Number of priority levels.
+const LEVEL_COUNT: usize = 4;
+
+/// Priority level of an outgoing message, highest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum PriorityLevel {
+    /// Must go out in the next frame (e.g. input echo)
+    Critical = 0,
+    /// Interactive updates
+    High = 1,
+    /// Regular state updates
+    Normal = 2,
+    /// Bulk transfer that can wait (e.g. scrollback)
+    Background = 3,
+}
+
+impl PriorityLevel {
+    /// Convert from the wire byte.
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Critical),
+            1 => Some(Self::High),
+            2 => Some(Self::Normal),
+            3 => Some(Self::Background),
+            _ => None,
+        }
+    }
+
+    /// Get the wire byte.
+    pub fn as_byte(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Send queue that dequeues by priority with starvation avoidance.
+///
+/// Messages of the same level leave in FIFO order. Each time a non-empty
+/// level is passed over in favor of a higher one, its starvation counter
+/// grows; once it reaches the limit, that level's oldest message is
+/// promoted and dequeued next.
+#[derive(Debug, Clone)]
+pub struct PriorityQueue<T> {
+    /// One FIFO per level, indexed by `PriorityLevel as usize`
+    levels: [VecDeque<T>; LEVEL_COUNT],
+    /// Dequeues each level has been passed over for
+    skipped: [u32; LEVEL_COUNT],
+    /// Passes after which a waiting level is promoted
+    starvation_limit: u32,
+}
+
+impl<T> PriorityQueue<T> {
+    /// Create an empty queue with the default starvation limit.
+    pub fn new() -> Self {
+        Self {
+            levels: Default::default(),
+            skipped: [0; LEVEL_COUNT],
+            starvation_limit: DEFAULT_STARVATION_LIMIT,
+        }
+    }
+
+    /// Set the number of passes after which a waiting level is promoted.
+    pub fn with_starvation_limit(mut self, limit: u32) -> Self {
+        self.starvation_limit = limit.max(1);
+        self
+    }
+
+    /// Add a message at the given level.
+    pub fn enqueue(&mut self, item: T, level: PriorityLevel) {
+        self.levels[level as usize].push_back(item);
+    }
+
+    /// Remove the next message to send.
+    pub fn dequeue(&mut self) -> Option<T> {
+        // A starved level goes first; prefer the lowest priority if several
+        let starved = (0..LEVEL_COUNT)
+            .rev()
+            .find(|&i| !self.levels[i].is_empty() && self.skipped[i] >= self.starvation_limit);
+        let level = starved.or_else(|| (0..LEVEL_COUNT).find(|&i| !self.levels[i].is_empty()))?;
+
+        // Every other waiting lower level was passed over
+        for i in level + 1..LEVEL_COUNT {
+            if !self.levels[i].is_empty() {
+                self.skipped[i] += 1;
+            }
+        }
+        self.skipped[level] = 0;
+
+        self.levels[level].pop_front()
+    }
+
+    /// Number of queued messages.
+    pub fn len(&self) -> usize {
+        self.levels.iter().map(VecDeque::len).sum()
+    }
+
+    /// Check if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.levels.iter().all(VecDeque::is_empty)
+    }
+
+    /// Number of queued messages at a level.
+    pub fn len_at(&self, level: PriorityLevel) -> usize {
+        self.levels[level as usize].len()
+    }
+
+    /// Dequeue the next message if the pacer allows sending now.
+    ///
+    /// Call from the send loop each time it wakes: the pacer decides when a
+    /// frame may go out, the queue decides which message fills it.
+    #[cfg(feature = "transport")]
+    pub fn poll_send(&mut self, pacer: &crate::transport::FramePacer) -> Option<T> {
+        match pacer.poll() {
+            crate::transport::PacerAction::SendNow => self.dequeue(),
+            _ => None,
+        }
+    }
+}
+
+impl<T> Default for PriorityQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_critical_jumps_ahead_of_background() {
+        let mut queue = PriorityQueue::new();
+        queue.enqueue("bg-1", PriorityLevel::Background);
+        queue.enqueue("bg-2", PriorityLevel::Background);
+        queue.enqueue("crit", PriorityLevel::Critical);
+        queue.enqueue("normal", PriorityLevel::Normal);
+
+        assert_eq!(queue.dequeue(), Some("crit"));
+        assert_eq!(queue.dequeue(), Some("normal"));
+        assert_eq!(queue.dequeue(), Some("bg-1"));
+        assert_eq!(queue.dequeue(), Some("bg-2"));
+        assert_eq!(queue.dequeue(), None);
+    }
+
+    #[test]
+    fn test_starved_background_promoted() {
+        let mut queue = PriorityQueue::new().with_starvation_limit(3);
+        queue.enqueue(0, PriorityLevel::Background);
+
+        // Sustained critical traffic: a new critical message every round
+        let mut order = Vec::new();
+        for i in 1..=5 {
+            queue.enqueue(i, PriorityLevel::Critical);
+            order.push(queue.dequeue().unwrap());
+        }
+
+        // Background was passed over three times, then promoted
+        assert_eq!(order, vec![1, 2, 3, 0, 4]);
+        assert_eq!(queue.dequeue(), Some(5));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_fifo_within_level() {
+        let mut queue = PriorityQueue::new();
+        for i in 0..5 {
+            queue.enqueue(i, PriorityLevel::Normal);
+        }
+        assert_eq!(queue.len_at(PriorityLevel::Normal), 5);
+        let drained: Vec<_> = std::iter::from_fn(|| queue.dequeue()).collect();
+        assert_eq!(drained, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[cfg(feature = "transport")]
+    #[test]
+    fn test_poll_send_follows_pacer() {
+        let mut pacer = crate::transport::FramePacer::new();
+        let mut queue = PriorityQueue::new();
+        queue.enqueue("bg", PriorityLevel::Background);
+        queue.enqueue("crit", PriorityLevel::Critical);
+
+        // Nothing pending in the pacer: hold the queue
+        assert_eq!(queue.poll_send(&pacer), None);
+
+        pacer.on_state_change();
+        std::thread::sleep(crate::transport::pacing_constants::COLLECTION_INTERVAL);
+        assert_eq!(queue.poll_send(&pacer), Some("crit"));
+    }
+}
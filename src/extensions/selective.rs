@@ -0,0 +1,479 @@
+//! Selective sync extension
+//!
+//! Lets a client subscribe to parts of the peer's state by path pattern
+//! (extension [`ext_type::SELECTIVE_SYNC`]). Paths are `/`-separated, e.g.
+//! `users/42/settings`. Patterns are globs compiled once at subscribe time:
+//! - `?` matches one character within a segment
+//! - `*` matches any run of characters within a segment
+//! - `**` as a whole segment matches zero or more segments
+//!
+//! Patterns come from the peer, so they are capped at
+//! [`MAX_PATTERN_LEN`] bytes and [`MAX_PATTERN_SEGMENTS`] segments, and
+//! matching takes time proportional to pattern size times path size.
+//!
+//! Subscription update wire format:
+//! ```text
+//! +0  Op (1 byte: 0x01 = subscribe, 0x02 = unsubscribe)
+//! +1  Pattern Length (2 bytes LE16)
+//! +3  Pattern (UTF-8, Pattern Length bytes)
+//! ```
+//!
+//! [`ext_type::SELECTIVE_SYNC`]: super::ext_type::SELECTIVE_SYNC
+
+use super::NegotiationError;
+
+/// Subscription update header size (op + pattern length)
+pub const SUBSCRIPTION_UPDATE_HEADER_SIZE: usize = 3;
+
+/// Maximum pattern length in bytes
+pub const MAX_PATTERN_LEN: usize = 1024;
+
+/// Maximum number of `/`-separated segments in a pattern
+pub const MAX_PATTERN_SEGMENTS: usize = 64;
+
+/// One token of a compiled segment glob
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// Exact character
+    Char(char),
+    /// `?`: any single character
+    One,
+    /// `*`: any run of characters, possibly empty
+    Any,
+}
+
+/// One `/`-separated segment of a compiled pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// `**`: zero or more whole segments
+    AnyDepth,
+    /// Glob matched against exactly one path segment
+    Glob(Vec<Token>),
+}
+
+/// A compiled path glob.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl Pattern {
+    /// Compile a glob pattern
+    ///
+    /// Rejects empty patterns, empty segments (`a//b`, leading or trailing
+    /// `/`), `**` that is not a whole segment (`a**`), and patterns over
+    /// [`MAX_PATTERN_LEN`] bytes or [`MAX_PATTERN_SEGMENTS`] segments.
+    /// Consecutive `**` segments match the same paths as one and are
+    /// collapsed.
+    pub fn compile(pattern: &str) -> Result<Self, NegotiationError> {
+        if pattern.is_empty() {
+            return Err(NegotiationError::InvalidPattern("empty pattern".to_string()));
+        }
+        check_limits(pattern)?;
+
+        let mut segments: Vec<Segment> = pattern
+            .split('/')
+            .map(|segment| match segment {
+                "" => Err(NegotiationError::InvalidPattern(format!(
+                    "empty segment in {:?}",
+                    pattern
+                ))),
+                "**" => Ok(Segment::AnyDepth),
+                _ if segment.contains("**") => Err(NegotiationError::InvalidPattern(format!(
+                    "'**' must be a whole segment in {:?}",
+                    pattern
+                ))),
+                _ => Ok(Segment::Glob(
+                    segment
+                        .chars()
+                        .map(|c| match c {
+                            '?' => Token::One,
+                            '*' => Token::Any,
+                            c => Token::Char(c),
+                        })
+                        .collect(),
+                )),
+            })
+            .collect::<Result<_, _>>()?;
+        segments.dedup_by(|a, b| *a == Segment::AnyDepth && *b == Segment::AnyDepth);
+
+        Ok(Self {
+            source: pattern.to_string(),
+            segments,
+        })
+    }
+
+    /// Get the pattern as written
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Check if a `/`-separated path matches
+    pub fn matches(&self, path: &str) -> bool {
+        let parts: Vec<Vec<char>> = path.split('/').map(|part| part.chars().collect()).collect();
+        Self::match_segments(&self.segments, &parts)
+    }
+
+    /// Segment-level match, backtracking to the last `**`
+    ///
+    /// Same scheme as [`match_glob`](Self::match_glob) one level up: only
+    /// the most recent `**` is ever retried, so this makes at most
+    /// segments × parts glob matches instead of exploring every split.
+    fn match_segments(segments: &[Segment], parts: &[Vec<char>]) -> bool {
+        let (mut s, mut p) = (0, 0);
+        let mut backtrack: Option<(usize, usize)> = None;
+
+        while p < parts.len() {
+            match segments.get(s) {
+                Some(Segment::AnyDepth) => {
+                    backtrack = Some((s, p));
+                    s += 1;
+                }
+                Some(Segment::Glob(tokens)) if Self::match_glob(tokens, &parts[p]) => {
+                    s += 1;
+                    p += 1;
+                }
+                _ => match backtrack {
+                    // Let the last `**` swallow one more segment
+                    Some((star, from)) => {
+                        s = star + 1;
+                        p = from + 1;
+                        backtrack = Some((star, from + 1));
+                    }
+                    None => return false,
+                },
+            }
+        }
+
+        segments[s..].iter().all(|segment| *segment == Segment::AnyDepth)
+    }
+
+    /// Wildcard match within one segment, backtracking to the last `*`
+    fn match_glob(tokens: &[Token], chars: &[char]) -> bool {
+        let (mut t, mut c) = (0, 0);
+        let mut backtrack: Option<(usize, usize)> = None;
+
+        while c < chars.len() {
+            match tokens.get(t) {
+                Some(Token::Any) => {
+                    backtrack = Some((t, c));
+                    t += 1;
+                }
+                Some(Token::One) => {
+                    t += 1;
+                    c += 1;
+                }
+                Some(Token::Char(expected)) if *expected == chars[c] => {
+                    t += 1;
+                    c += 1;
+                }
+                _ => match backtrack {
+                    // Let the last `*` swallow one more character
+                    Some((star, from)) => {
+                        t = star + 1;
+                        c = from + 1;
+                        backtrack = Some((star, from + 1));
+                    }
+                    None => return false,
+                },
+            }
+        }
+
+        tokens[t..].iter().all(|token| *token == Token::Any)
+    }
+}
+
+/// A change to a client's subscriptions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubscriptionUpdate {
+    /// Start receiving paths matching the pattern
+    Subscribe(String),
+    /// Stop receiving paths matching the pattern
+    Unsubscribe(String),
+}
+
+impl SubscriptionUpdate {
+    const SUBSCRIBE: u8 = 0x01;
+    const UNSUBSCRIBE: u8 = 0x02;
+
+    /// Encode to wire format
+    ///
+    /// # Errors
+    /// Returns `InvalidPattern` if the pattern exceeds [`MAX_PATTERN_LEN`]
+    /// or [`MAX_PATTERN_SEGMENTS`], which the peer would reject.
+    pub fn encode(&self) -> Result<Vec<u8>, NegotiationError> {
+        let (op, pattern) = match self {
+            Self::Subscribe(pattern) => (Self::SUBSCRIBE, pattern),
+            Self::Unsubscribe(pattern) => (Self::UNSUBSCRIBE, pattern),
+        };
+        check_limits(pattern)?;
+        let len = u16::try_from(pattern.len()).expect("length checked");
+
+        let mut buf = Vec::with_capacity(SUBSCRIPTION_UPDATE_HEADER_SIZE + pattern.len());
+        buf.push(op);
+        buf.extend_from_slice(&len.to_le_bytes());
+        buf.extend_from_slice(pattern.as_bytes());
+        Ok(buf)
+    }
+
+    /// Decode from wire format
+    ///
+    /// Patterns over [`MAX_PATTERN_LEN`] or [`MAX_PATTERN_SEGMENTS`] are
+    /// rejected before they are read.
+    pub fn decode(data: &[u8]) -> Result<Self, NegotiationError> {
+        if data.len() < SUBSCRIPTION_UPDATE_HEADER_SIZE {
+            return Err(NegotiationError::TooShort {
+                expected: SUBSCRIPTION_UPDATE_HEADER_SIZE,
+                actual: data.len(),
+            });
+        }
+        let len = u16::from_le_bytes([data[1], data[2]]) as usize;
+        if len > MAX_PATTERN_LEN {
+            return Err(NegotiationError::InvalidPattern(format!(
+                "pattern is {} bytes (max {})",
+                len, MAX_PATTERN_LEN
+            )));
+        }
+        let end = SUBSCRIPTION_UPDATE_HEADER_SIZE + len;
+        if data.len() < end {
+            return Err(NegotiationError::TooShort {
+                expected: end,
+                actual: data.len(),
+            });
+        }
+        let pattern = std::str::from_utf8(&data[SUBSCRIPTION_UPDATE_HEADER_SIZE..end])
+            .map_err(|_| NegotiationError::InvalidData)?
+            .to_string();
+        check_limits(&pattern)?;
+
+        match data[0] {
+            Self::SUBSCRIBE => Ok(Self::Subscribe(pattern)),
+            Self::UNSUBSCRIBE => Ok(Self::Unsubscribe(pattern)),
+            _ => Err(NegotiationError::InvalidData),
+        }
+    }
+}
+
+/// Reject patterns over [`MAX_PATTERN_LEN`] or [`MAX_PATTERN_SEGMENTS`]
+fn check_limits(pattern: &str) -> Result<(), NegotiationError> {
+    if pattern.len() > MAX_PATTERN_LEN {
+        return Err(NegotiationError::InvalidPattern(format!(
+            "pattern is {} bytes (max {})",
+            pattern.len(),
+            MAX_PATTERN_LEN
+        )));
+    }
+    let segments = pattern.split('/').count();
+    if segments > MAX_PATTERN_SEGMENTS {
+        return Err(NegotiationError::InvalidPattern(format!(
+            "pattern has {} segments (max {})",
+            segments, MAX_PATTERN_SEGMENTS
+        )));
+    }
+    Ok(())
+}
+
+/// The set of path patterns a client is subscribed to.
+#[derive(Debug, Clone, Default)]
+pub struct SubscriptionState {
+    patterns: Vec<Pattern>,
+}
+
+impl SubscriptionState {
+    /// Create an empty subscription set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a subscription update
+    ///
+    /// Subscribe patterns are compiled here, once; an invalid pattern is
+    /// rejected and leaves the state unchanged. Subscribing twice to the
+    /// same pattern is a no-op, as is unsubscribing from one not held.
+    pub fn apply(&mut self, update: &SubscriptionUpdate) -> Result<(), NegotiationError> {
+        match update {
+            SubscriptionUpdate::Subscribe(pattern) => {
+                let compiled = Pattern::compile(pattern)?;
+                if !self.patterns.iter().any(|p| p.as_str() == pattern) {
+                    self.patterns.push(compiled);
+                }
+            }
+            SubscriptionUpdate::Unsubscribe(pattern) => {
+                self.patterns.retain(|p| p.as_str() != pattern);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if a path falls under any subscription
+    pub fn matches_pattern(&self, path: &str) -> bool {
+        self.patterns.iter().any(|pattern| pattern.matches(path))
+    }
+
+    /// Get the compiled subscriptions
+    pub fn patterns(&self) -> &[Pattern] {
+        &self.patterns
+    }
+
+    /// Check if there are no subscriptions
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscribed(patterns: &[&str]) -> SubscriptionState {
+        let mut state = SubscriptionState::new();
+        for pattern in patterns {
+            state
+                .apply(&SubscriptionUpdate::Subscribe(pattern.to_string()))
+                .unwrap();
+        }
+        state
+    }
+
+    #[test]
+    fn test_star_within_segment() {
+        let state = subscribed(&["users/*/settings"]);
+        assert!(state.matches_pattern("users/42/settings"));
+        assert!(state.matches_pattern("users/alice/settings"));
+        assert!(!state.matches_pattern("users/42/profile"));
+        assert!(!state.matches_pattern("users/42/x/settings"));
+        assert!(!state.matches_pattern("users/settings"));
+
+        let pattern = Pattern::compile("logs/*.txt").unwrap();
+        assert!(pattern.matches("logs/a.txt"));
+        assert!(pattern.matches("logs/.txt"));
+        assert!(pattern.matches("logs/a.b.txt"));
+        assert!(!pattern.matches("logs/a.txt.gz"));
+    }
+
+    #[test]
+    fn test_double_star_across_segments() {
+        let pattern = Pattern::compile("a/**/z").unwrap();
+        assert!(pattern.matches("a/z"));
+        assert!(pattern.matches("a/b/z"));
+        assert!(pattern.matches("a/b/c/d/z"));
+        assert!(!pattern.matches("a/b/y"));
+        assert!(!pattern.matches("b/z"));
+        assert!(!pattern.matches("a/z/extra"));
+
+        let trailing = Pattern::compile("docs/**").unwrap();
+        assert!(trailing.matches("docs"));
+        assert!(trailing.matches("docs/x/y"));
+        assert!(!trailing.matches("doc/x"));
+
+        let repeated = Pattern::compile("a/**/**/**/z").unwrap();
+        assert_eq!(repeated.segments.len(), 3);
+        assert!(repeated.matches("a/z"));
+        assert!(repeated.matches("a/b/c/z"));
+    }
+
+    #[test]
+    fn test_double_star_does_not_backtrack_exponentially() {
+        // Every `**` could split the path many ways; a recursive matcher
+        // explores all of them before failing on the final segment
+        let pattern = "**/a/".repeat(MAX_PATTERN_SEGMENTS / 2 - 1) + "**/b";
+        let pattern = Pattern::compile(&pattern).unwrap();
+        let path = vec!["a"; 200].join("/");
+        assert!(!pattern.matches(&path));
+        assert!(pattern.matches(&(path + "/b")));
+    }
+
+    #[test]
+    fn test_pattern_limits() {
+        let long = "a".repeat(MAX_PATTERN_LEN + 1);
+        let deep = vec!["a"; MAX_PATTERN_SEGMENTS + 1].join("/");
+        for bad in [&long, &deep] {
+            assert!(matches!(Pattern::compile(bad), Err(NegotiationError::InvalidPattern(_))));
+            assert!(matches!(
+                SubscriptionUpdate::Subscribe(bad.clone()).encode(),
+                Err(NegotiationError::InvalidPattern(_))
+            ));
+        }
+
+        // A peer's oversized pattern is rejected from the header alone
+        let mut data = vec![0x01];
+        data.extend_from_slice(&u16::MAX.to_le_bytes());
+        assert!(matches!(
+            SubscriptionUpdate::decode(&data),
+            Err(NegotiationError::InvalidPattern(_))
+        ));
+
+        // Too many segments within the length limit
+        let mut data = vec![0x01];
+        data.extend_from_slice(&(deep.len() as u16).to_le_bytes());
+        data.extend_from_slice(deep.as_bytes());
+        assert!(matches!(
+            SubscriptionUpdate::decode(&data),
+            Err(NegotiationError::InvalidPattern(_))
+        ));
+
+        let max = vec!["a"; MAX_PATTERN_SEGMENTS].join("/");
+        let update = SubscriptionUpdate::Subscribe(max);
+        assert_eq!(SubscriptionUpdate::decode(&update.encode().unwrap()).unwrap(), update);
+    }
+
+    #[test]
+    fn test_question_mark_single_char() {
+        let pattern = Pattern::compile("room/?").unwrap();
+        assert!(pattern.matches("room/a"));
+        assert!(pattern.matches("room/é"));
+        assert!(!pattern.matches("room/"));
+        assert!(!pattern.matches("room/ab"));
+
+        let mixed = Pattern::compile("v?/*-?").unwrap();
+        assert!(mixed.matches("v1/build-a"));
+        assert!(!mixed.matches("v12/build-a"));
+    }
+
+    #[test]
+    fn test_malformed_pattern_rejected() {
+        let mut state = SubscriptionState::new();
+        for bad in ["", "a//b", "/a", "a/", "a**/b", "x/**y"] {
+            assert!(
+                matches!(
+                    state.apply(&SubscriptionUpdate::Subscribe(bad.to_string())),
+                    Err(NegotiationError::InvalidPattern(_))
+                ),
+                "{:?} should be rejected",
+                bad
+            );
+        }
+        assert!(state.is_empty());
+    }
+
+    #[test]
+    fn test_unsubscribe_and_duplicates() {
+        let mut state = subscribed(&["a/*", "a/*", "b/**"]);
+        assert_eq!(state.patterns().len(), 2);
+
+        state
+            .apply(&SubscriptionUpdate::Unsubscribe("a/*".to_string()))
+            .unwrap();
+        assert!(!state.matches_pattern("a/x"));
+        assert!(state.matches_pattern("b/x/y"));
+    }
+
+    #[test]
+    fn test_update_roundtrip() {
+        for update in [
+            SubscriptionUpdate::Subscribe("users/*/settings".to_string()),
+            SubscriptionUpdate::Unsubscribe("a/**/z".to_string()),
+        ] {
+            assert_eq!(SubscriptionUpdate::decode(&update.encode().unwrap()).unwrap(), update);
+        }
+        assert!(matches!(
+            SubscriptionUpdate::decode(&[0x01, 5, 0, b'a']),
+            Err(NegotiationError::TooShort { .. })
+        ));
+        assert!(matches!(
+            SubscriptionUpdate::decode(&[0x07, 0, 0]),
+            Err(NegotiationError::InvalidData)
+        ));
+    }
+}
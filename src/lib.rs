@@ -15,6 +15,10 @@
 //!
 //! - `transport` (default): Transport layer (frames, RTT, pacing, sockets)
 //! - `crypto` (default): Security layer (Noise_IK, XChaCha20-Poly1305)
+//! - `session-resumption`: Export/import of live session keys; security-sensitive
+//! - `tracing`: Structured `tracing` spans on the crypto and transport hot paths
+//! - `metrics`: Aggregate counters with Prometheus text exposition
+//! - `mock-transport`: In-memory lossy network (`MockTransport`) for tests
 //!
 //! ## Modules
 //!
@@ -112,10 +116,10 @@ pub mod prelude {
     // Transport types (when enabled) - exclude SessionId to avoid conflict with crypto
     #[cfg(feature = "transport")]
     pub use crate::transport::{
-        ConnectionPhase, ConnectionState, DataFrame, DataFrameHeader, FrameFlags, FramePacer,
-        FrameType, MigrationState, NomadSocket, NomadSocketBuilder, PacerAction,
-        PayloadHeader, RetransmitController, RttEstimator, SendReason, TimestampTracker,
-        TransportError, TransportResult,
+        ConnectionEvent, ConnectionPhase, ConnectionState, DataFrame, DataFrameHeader, FrameFlags,
+        FramePacer, FrameType, JitterEstimator, MigrationState, NomadSocket, NomadSocketBuilder,
+        PacerAction, PayloadHeader, RetransmitController, RttEstimator, SendReason, TimerAction,
+        TimerEvent, TimestampTracker, TransportError, TransportResult,
     };
 
     // Crypto types (when enabled) - SessionId comes from here
@@ -128,6 +132,6 @@ pub use core::{ApplyError, DecodeError, NomadError, SyncState};
 
 #[cfg(feature = "transport")]
 pub use transport::{
-    ConnectionPhase, ConnectionState, DataFrame, DataFrameHeader, FrameFlags, FramePacer,
-    FrameType, NomadSocket, PayloadHeader, RttEstimator, SessionId,
+    ConnectionEvent, ConnectionPhase, ConnectionState, DataFrame, DataFrameHeader, FrameFlags,
+    FramePacer, FrameType, NomadSocket, PayloadHeader, RttEstimator, SessionId,
 };
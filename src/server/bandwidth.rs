@@ -0,0 +1,281 @@
+//! Per-client bandwidth accounting.
+//!
+//! Each session meters the bytes it sends and receives over a sliding
+//! window. When a cap is configured, outbound frames are delayed (via the
+//! pacer's [`PacerAction`]) and excess inbound frames are dropped before
+//! decryption, so no single client can monopolize the server's uplink or
+//! its crypto budget. The cap is a per-client ceiling, not a share of the
+//! total: fairness comes from every client being held to the same limit.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::transport::PacerAction;
+
+/// Default sliding window for rate measurement.
+pub const DEFAULT_BANDWIDTH_WINDOW: Duration = Duration::from_secs(1);
+
+/// Byte counts over a sliding window.
+#[derive(Debug, Clone, Default)]
+struct RateWindow {
+    /// (time, bytes) samples, oldest first.
+    samples: VecDeque<(Instant, u64)>,
+    /// Sum of bytes in `samples`.
+    total: u64,
+}
+
+impl RateWindow {
+    /// Drop samples older than the window.
+    fn prune(&mut self, now: Instant, window: Duration) {
+        while let Some(&(at, bytes)) = self.samples.front() {
+            if now.saturating_duration_since(at) < window {
+                break;
+            }
+            self.samples.pop_front();
+            self.total -= bytes;
+        }
+    }
+
+    /// Record bytes at the given time.
+    fn record(&mut self, now: Instant, bytes: u64, window: Duration) {
+        self.prune(now, window);
+        self.samples.push_back((now, bytes));
+        self.total += bytes;
+    }
+
+    /// Bytes within the window ending at `now`.
+    fn total_at(&self, now: Instant, window: Duration) -> u64 {
+        self.samples
+            .iter()
+            .filter(|(at, _)| now.saturating_duration_since(*at) < window)
+            .map(|(_, bytes)| bytes)
+            .sum()
+    }
+
+    /// Time until `bytes` more fit under `budget`, or `None` if they fit now.
+    fn delay_for(&self, now: Instant, bytes: u64, budget: u64, window: Duration) -> Option<Duration> {
+        let mut in_window = self.total_at(now, window);
+        if in_window + bytes <= budget {
+            return None;
+        }
+
+        // Wait for the oldest samples to age out until the frame fits
+        for &(at, sample) in &self.samples {
+            if now.saturating_duration_since(at) >= window {
+                continue;
+            }
+            in_window -= sample;
+            if in_window + bytes <= budget {
+                return Some((at + window).saturating_duration_since(now));
+            }
+        }
+
+        // Larger than the whole budget: allow it once the window is empty
+        self.samples
+            .back()
+            .map(|&(at, _)| (at + window).saturating_duration_since(now))
+    }
+}
+
+/// Sliding-window bandwidth meter with an optional cap.
+#[derive(Debug, Clone)]
+pub struct BandwidthMeter {
+    /// Outbound traffic.
+    outbound: RateWindow,
+    /// Inbound traffic.
+    inbound: RateWindow,
+    /// Measurement window.
+    window: Duration,
+    /// Cap in bytes per second (applies to each direction).
+    limit: Option<u64>,
+}
+
+impl BandwidthMeter {
+    /// Create an uncapped meter.
+    pub fn new() -> Self {
+        Self {
+            outbound: RateWindow::default(),
+            inbound: RateWindow::default(),
+            window: DEFAULT_BANDWIDTH_WINDOW,
+            limit: None,
+        }
+    }
+
+    /// Create a meter capped at `bytes_per_sec` in each direction.
+    pub fn with_limit(bytes_per_sec: u64) -> Self {
+        Self {
+            limit: Some(bytes_per_sec),
+            ..Self::new()
+        }
+    }
+
+    /// Set the measurement window.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Set or clear the cap.
+    pub fn set_limit(&mut self, bytes_per_sec: Option<u64>) {
+        self.limit = bytes_per_sec;
+    }
+
+    /// Get the cap in bytes per second.
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    /// Byte budget per window for the current cap.
+    fn budget(&self) -> Option<u64> {
+        self.limit
+            .map(|limit| (limit as f64 * self.window.as_secs_f64()) as u64)
+    }
+
+    /// Record an outbound frame.
+    pub fn record_sent(&mut self, bytes: usize) {
+        self.record_sent_at(bytes, Instant::now());
+    }
+
+    /// Record an outbound frame at a specific time (for testing).
+    pub fn record_sent_at(&mut self, bytes: usize, now: Instant) {
+        self.outbound.record(now, bytes as u64, self.window);
+    }
+
+    /// Record an inbound frame.
+    pub fn record_received(&mut self, bytes: usize) {
+        self.record_received_at(bytes, Instant::now());
+    }
+
+    /// Record an inbound frame at a specific time (for testing).
+    pub fn record_received_at(&mut self, bytes: usize, now: Instant) {
+        self.inbound.record(now, bytes as u64, self.window);
+    }
+
+    /// Outbound rate over the window.
+    pub fn bytes_per_sec_out(&self) -> f64 {
+        self.bytes_per_sec_out_at(Instant::now())
+    }
+
+    /// Outbound rate at a specific time (for testing).
+    pub fn bytes_per_sec_out_at(&self, now: Instant) -> f64 {
+        self.outbound.total_at(now, self.window) as f64 / self.window.as_secs_f64()
+    }
+
+    /// Inbound rate over the window.
+    pub fn bytes_per_sec_in(&self) -> f64 {
+        self.bytes_per_sec_in_at(Instant::now())
+    }
+
+    /// Inbound rate at a specific time (for testing).
+    pub fn bytes_per_sec_in_at(&self, now: Instant) -> f64 {
+        self.inbound.total_at(now, self.window) as f64 / self.window.as_secs_f64()
+    }
+
+    /// How long to hold an outbound frame of `bytes` to stay under the cap.
+    pub fn send_delay_at(&self, bytes: usize, now: Instant) -> Option<Duration> {
+        let budget = self.budget()?;
+        self.outbound.delay_for(now, bytes as u64, budget, self.window)
+    }
+
+    /// Apply the cap to a pacer decision.
+    ///
+    /// Turns `SendNow` into `WaitUntil` when sending `bytes` would exceed
+    /// the cap; other actions pass through unchanged.
+    pub fn throttle(&self, action: PacerAction, bytes: usize) -> PacerAction {
+        self.throttle_at(action, bytes, Instant::now())
+    }
+
+    /// Apply the cap to a pacer decision at a specific time (for testing).
+    pub fn throttle_at(&self, action: PacerAction, bytes: usize, now: Instant) -> PacerAction {
+        match (action, self.send_delay_at(bytes, now)) {
+            (PacerAction::SendNow, Some(delay)) => PacerAction::WaitUntil(now + delay),
+            (action, _) => action,
+        }
+    }
+
+    /// Check if an inbound frame should be dropped before decryption.
+    ///
+    /// Frames that are admitted are recorded; dropped frames are not.
+    pub fn admit_inbound(&mut self, bytes: usize) -> bool {
+        self.admit_inbound_at(bytes, Instant::now())
+    }
+
+    /// Inbound admission check at a specific time (for testing).
+    pub fn admit_inbound_at(&mut self, bytes: usize, now: Instant) -> bool {
+        if let Some(budget) = self.budget() {
+            self.inbound.prune(now, self.window);
+            if self.inbound.total + bytes as u64 > budget {
+                return false;
+            }
+        }
+        self.record_received_at(bytes, now);
+        true
+    }
+}
+
+impl Default for BandwidthMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uncapped_never_throttles() {
+        let mut meter = BandwidthMeter::new();
+        let now = Instant::now();
+        for _ in 0..100 {
+            meter.record_sent_at(1200, now);
+        }
+        assert_eq!(meter.send_delay_at(1200, now), None);
+        assert!(meter.admit_inbound_at(1200, now));
+        assert_eq!(meter.bytes_per_sec_out_at(now), 120_000.0);
+    }
+
+    #[test]
+    fn test_capped_sender_converges_to_cap() {
+        let cap = 10_000;
+        let frame = 1000;
+        let mut meter = BandwidthMeter::with_limit(cap);
+        let start = Instant::now();
+        let mut now = start;
+        let mut sent = 0u64;
+
+        // Client tries to send at ~100 KB/s for 10 seconds
+        while now < start + Duration::from_secs(10) {
+            match meter.throttle_at(PacerAction::SendNow, frame, now) {
+                PacerAction::SendNow => {
+                    meter.record_sent_at(frame, now);
+                    sent += frame as u64;
+                    now += Duration::from_millis(10);
+                }
+                PacerAction::WaitUntil(until) => {
+                    assert!(until > now);
+                    now = until;
+                }
+                PacerAction::Idle => unreachable!(),
+            }
+        }
+
+        let rate = sent as f64 / now.duration_since(start).as_secs_f64();
+        assert!((rate - cap as f64).abs() / (cap as f64) < 0.1, "rate {}", rate);
+        assert!(meter.bytes_per_sec_out_at(now) <= cap as f64);
+    }
+
+    #[test]
+    fn test_inbound_excess_dropped() {
+        let mut meter = BandwidthMeter::with_limit(5000);
+        let now = Instant::now();
+
+        let admitted = (0..10).filter(|_| meter.admit_inbound_at(1000, now)).count();
+        assert_eq!(admitted, 5);
+        assert_eq!(meter.bytes_per_sec_in_at(now), 5000.0);
+
+        // Window slides and capacity frees up
+        let later = now + DEFAULT_BANDWIDTH_WINDOW;
+        assert!(meter.admit_inbound_at(1000, later));
+    }
+}
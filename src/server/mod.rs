@@ -2,8 +2,11 @@
 //!
 //! High-level API for NOMAD servers.
 
+mod bandwidth;
+#[allow(clippy::module_inception)]
 mod server;
 mod session;
 
+pub use bandwidth::*;
 pub use server::*;
 pub use session::*;
@@ -3,6 +3,7 @@
 //! Provides `NomadServer<S>` for accepting client connections and synchronizing
 //! state of type `S: SyncState`.
 
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -12,8 +13,9 @@ use thiserror::Error;
 use tokio::net::UdpSocket;
 use tokio::sync::{mpsc, oneshot, RwLock};
 
-use super::session::{ServerSession, ServerSessionId};
-use crate::core::SyncState;
+use super::session::{MAX_SESSION_ID_ATTEMPTS, ServerSession, ServerSessionId};
+use crate::core::{NomadError, SyncState};
+use crate::transport::PacerAction;
 
 /// Errors that can occur in the NOMAD server.
 #[derive(Debug, Error)]
@@ -56,6 +58,25 @@ pub struct ServerConfig {
 
     /// Enable compression extension.
     pub enable_compression: bool,
+
+    /// Per-client bandwidth cap in bytes per second (None = unlimited).
+    ///
+    /// Set on each session's [`BandwidthMeter`](super::BandwidthMeter) when
+    /// the session is added.
+    pub max_client_bandwidth: Option<u64>,
+
+    /// Accept 0-RTT early data in handshake inits.
+    ///
+    /// Early data can be replayed, so only enable this if the application
+    /// treats it as idempotent.
+    pub accept_early_data: bool,
+
+    /// High byte of every session ID this server issues (None = random).
+    ///
+    /// Give each server in a fleet its own ID so their sessions never alias.
+    /// The remaining 40 bits are random; a collision within this server is
+    /// detected and redrawn when the session is added.
+    pub server_id: Option<u8>,
 }
 
 impl Default for ServerConfig {
@@ -68,6 +89,9 @@ impl Default for ServerConfig {
             max_sessions: 1000,
             session_timeout: Duration::from_secs(300),
             enable_compression: true,
+            max_client_bandwidth: None,
+            accept_early_data: false,
+            server_id: None,
         }
     }
 }
@@ -116,6 +140,24 @@ impl NomadServerBuilder {
         self
     }
 
+    /// Cap each client's bandwidth (bytes per second, each direction).
+    pub fn max_client_bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        self.config.max_client_bandwidth = Some(bytes_per_sec);
+        self
+    }
+
+    /// Enable or disable accepting 0-RTT early data.
+    pub fn early_data(mut self, accept: bool) -> Self {
+        self.config.accept_early_data = accept;
+        self
+    }
+
+    /// Prefix every session ID with this server's ID.
+    pub fn server_id(mut self, id: u8) -> Self {
+        self.config.server_id = Some(id);
+        self
+    }
+
     /// Build the server configuration.
     pub fn build(self) -> ServerConfig {
         self.config
@@ -270,17 +312,10 @@ impl<S: SyncState> NomadServer<S> {
 
             // For now, this is a placeholder that keeps the server "alive"
             let mut buf = [0u8; 65535];
-            loop {
-                match socket.recv_from(&mut buf).await {
-                    Ok((_len, _addr)) => {
-                        // TODO: Parse frame, handle handshake or data
-                        // For now, we just ignore incoming packets
-                    }
-                    Err(_e) => {
-                        // TODO: Handle error
-                        break;
-                    }
-                }
+            // TODO: Handle receive errors instead of exiting the loop
+            while let Ok((_len, _addr)) = socket.recv_from(&mut buf).await {
+                // TODO: Parse frame, handle handshake or data
+                // For now, we just ignore incoming packets
             }
         });
 
@@ -305,6 +340,54 @@ impl<S: SyncState> NomadServer<S> {
         self.sessions.read().await.len()
     }
 
+    /// Add a session for a new client under a fresh session ID.
+    ///
+    /// The ID is drawn and the session inserted under one write lock, so
+    /// two concurrent handshakes can never be handed the same ID. The
+    /// session is capped at the configured `max_client_bandwidth`.
+    ///
+    /// # Errors
+    /// Returns `NomadError::SessionIdExhausted` if every attempt collided.
+    pub async fn add_session(
+        &self,
+        client_addr: SocketAddr,
+        client_public_key: [u8; 32],
+        initial_state: S,
+    ) -> Result<ServerSessionId, NomadError> {
+        let server_id = self.config.server_id;
+        let bandwidth_limit = self.config.max_client_bandwidth;
+        let mut sessions = self.sessions.write().await;
+        insert_with_fresh_id(
+            &mut sessions,
+            || match server_id {
+                Some(prefix) => ServerSessionId::generate_prefixed(prefix),
+                None => ServerSessionId::generate(),
+            },
+            |id| {
+                let mut session =
+                    ServerSession::new(id, client_addr, client_public_key, initial_state);
+                session.set_bandwidth_limit(bandwidth_limit);
+                session
+            },
+        )
+    }
+
+    /// Apply a session's bandwidth cap to a pacer decision.
+    ///
+    /// Call before sending `bytes` to the client; a `SendNow` that would
+    /// exceed the cap becomes `WaitUntil`. Unknown sessions pass through.
+    pub async fn throttle(
+        &self,
+        session_id: ServerSessionId,
+        action: PacerAction,
+        bytes: usize,
+    ) -> PacerAction {
+        match self.sessions.read().await.get(&session_id) {
+            Some(session) => session.bandwidth().throttle(action, bytes),
+            None => action,
+        }
+    }
+
     /// Send state to a specific session.
     pub async fn send_to(&self, session_id: ServerSessionId, state: S) -> Result<(), ServerError> {
         self.state_tx
@@ -368,6 +451,26 @@ impl<S: SyncState> NomadServer<S> {
     }
 }
 
+/// Draw IDs from `generate` until one is not a key of `sessions`, and
+/// insert `make(id)` there.
+///
+/// With 48-bit IDs a collision is unlikely but not negligible across many
+/// concurrent sessions; one would alias two clients.
+fn insert_with_fresh_id<V>(
+    sessions: &mut HashMap<ServerSessionId, V>,
+    mut generate: impl FnMut() -> ServerSessionId,
+    make: impl FnOnce(ServerSessionId) -> V,
+) -> Result<ServerSessionId, NomadError> {
+    for _ in 0..MAX_SESSION_ID_ATTEMPTS {
+        let id = generate();
+        if let Entry::Vacant(slot) = sessions.entry(id) {
+            slot.insert(make(id));
+            return Ok(id);
+        }
+    }
+    Err(NomadError::SessionIdExhausted(MAX_SESSION_ID_ATTEMPTS))
+}
+
 impl<S: SyncState> Drop for NomadServer<S> {
     fn drop(&mut self) {
         // Send shutdown signal if not already sent
@@ -379,5 +482,102 @@ impl<S: SyncState> Drop for NomadServer<S> {
 
 #[cfg(test)]
 mod tests {
-    // TODO: Add tests once we have a concrete SyncState implementation
+    use super::*;
+    use crate::core::{ApplyError, DecodeError};
+
+    #[derive(Debug, Clone, Default)]
+    struct Counter(u64);
+
+    impl SyncState for Counter {
+        type Diff = u64;
+
+        const STATE_TYPE_ID: &'static str = "nomad.test.counter.v1";
+
+        fn diff_from(&self, _old: &Self) -> Self::Diff {
+            self.0
+        }
+
+        fn apply_diff(&mut self, diff: &Self::Diff) -> Result<(), ApplyError> {
+            self.0 = *diff;
+            Ok(())
+        }
+
+        fn encode_diff(diff: &Self::Diff) -> Vec<u8> {
+            diff.to_le_bytes().to_vec()
+        }
+
+        fn decode_diff(data: &[u8]) -> Result<Self::Diff, DecodeError> {
+            let bytes = data.try_into().map_err(|_| DecodeError::UnexpectedEof)?;
+            Ok(u64::from_le_bytes(bytes))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_client_bandwidth_caps_sessions() {
+        let config = NomadServerBuilder::new()
+            .bind_addr("127.0.0.1:0".parse().unwrap())
+            .max_client_bandwidth(1000)
+            .build();
+        let (server, _events) = NomadServer::bind(config, Counter::default).await.unwrap();
+        let id = server
+            .add_session("127.0.0.1:40000".parse().unwrap(), [7; 32], Counter(0))
+            .await
+            .unwrap();
+
+        // Within the cap the pacer's decision stands
+        assert_eq!(
+            server.throttle(id, PacerAction::SendNow, 800).await,
+            PacerAction::SendNow
+        );
+        server
+            .sessions
+            .write()
+            .await
+            .get_mut(&id)
+            .unwrap()
+            .bandwidth_mut()
+            .record_sent(800);
+
+        // Past it the send is held back
+        assert!(matches!(
+            server.throttle(id, PacerAction::SendNow, 800).await,
+            PacerAction::WaitUntil(_)
+        ));
+    }
+
+    #[test]
+    fn test_session_id_collision_regenerates() {
+        let taken = ServerSessionId::new([1, 2, 3, 4, 5, 6]);
+        let fresh = ServerSessionId::new([6, 5, 4, 3, 2, 1]);
+        let mut sessions = HashMap::from([(taken, "old")]);
+
+        // The first draw collides with a live session, which is kept
+        let mut draws = [taken, fresh].into_iter();
+        let id = insert_with_fresh_id(&mut sessions, || draws.next().unwrap(), |_| "new").unwrap();
+        assert_eq!(id, fresh);
+        assert_eq!(sessions[&taken], "old");
+        assert_eq!(sessions[&fresh], "new");
+    }
+
+    #[test]
+    fn test_session_id_exhausted() {
+        let taken = ServerSessionId::new([1, 2, 3, 4, 5, 6]);
+        let mut sessions = HashMap::from([(taken, ())]);
+
+        let mut attempts = 0;
+        let result = insert_with_fresh_id(
+            &mut sessions,
+            || {
+                attempts += 1;
+                taken
+            },
+            |_| (),
+        );
+        assert!(matches!(
+            result,
+            Err(NomadError::SessionIdExhausted(MAX_SESSION_ID_ATTEMPTS))
+        ));
+        assert_eq!(attempts, MAX_SESSION_ID_ATTEMPTS);
+        assert_eq!(sessions.len(), 1);
+    }
 }
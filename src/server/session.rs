@@ -8,8 +8,14 @@
 use std::net::SocketAddr;
 use std::time::Instant;
 
+use rand::{rngs::OsRng, RngCore};
+
+use super::bandwidth::BandwidthMeter;
 use crate::core::SyncState;
 
+/// Attempts at drawing an unused session ID before giving up.
+pub const MAX_SESSION_ID_ATTEMPTS: usize = 8;
+
 /// Session ID (48-bit, as per NOMAD spec).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ServerSessionId([u8; 6]);
@@ -20,26 +26,30 @@ impl ServerSessionId {
         Self(bytes)
     }
 
-    /// Generate a random session ID.
+    /// Generate a random session ID from the OS CSPRNG.
     pub fn generate() -> Self {
-        // TODO: Use proper crypto RNG from crypto module
-        use std::time::{SystemTime, UNIX_EPOCH};
-
-        let seed = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_nanos() as u64;
-
         let mut id = [0u8; 6];
-        let mut state = seed;
-        for byte in &mut id {
-            state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
-            *byte = (state >> 33) as u8;
-        }
-
+        OsRng.fill_bytes(&mut id);
         Self(id)
     }
 
+    /// Generate a random session ID whose high byte is `prefix`.
+    ///
+    /// Servers in a fleet each use their own prefix, so IDs drawn by
+    /// different servers can never collide. That leaves 40 random bits
+    /// per server; collisions within one server are caught when the ID is
+    /// allocated (see `NomadServer::add_session`).
+    pub fn generate_prefixed(prefix: u8) -> Self {
+        let mut id = Self::generate();
+        id.0[5] = prefix;
+        id
+    }
+
+    /// The high byte (the server prefix, for prefixed IDs).
+    pub fn prefix(&self) -> u8 {
+        self.0[5]
+    }
+
     /// Get the session ID as bytes.
     pub fn as_bytes(&self) -> &[u8; 6] {
         &self.0
@@ -116,6 +126,9 @@ pub struct ServerSession<S: SyncState> {
 
     /// Negotiated extensions.
     extensions: Vec<u16>,
+
+    /// Bandwidth accounting and cap.
+    bandwidth: BandwidthMeter,
 }
 
 impl<S: SyncState> ServerSession<S> {
@@ -138,6 +151,7 @@ impl<S: SyncState> ServerSession<S> {
             last_activity: now,
             created_at: now,
             extensions: Vec::new(),
+            bandwidth: BandwidthMeter::new(),
         }
     }
 
@@ -233,6 +247,21 @@ impl<S: SyncState> ServerSession<S> {
         &self.extensions
     }
 
+    /// Get the bandwidth meter.
+    pub fn bandwidth(&self) -> &BandwidthMeter {
+        &self.bandwidth
+    }
+
+    /// Get mutable access to the bandwidth meter.
+    pub fn bandwidth_mut(&mut self) -> &mut BandwidthMeter {
+        &mut self.bandwidth
+    }
+
+    /// Set the per-client bandwidth cap (bytes per second, each direction).
+    pub fn set_bandwidth_limit(&mut self, bytes_per_sec: Option<u64>) {
+        self.bandwidth.set_limit(bytes_per_sec);
+    }
+
     /// Check if compression is enabled.
     pub fn compression_enabled(&self) -> bool {
         // Extension 0x0001 is compression
@@ -253,6 +282,13 @@ mod tests {
         assert_ne!(id1, id2);
     }
 
+    #[test]
+    fn test_session_id_prefix_is_high_byte() {
+        let id = ServerSessionId::generate_prefixed(0xAB);
+        assert_eq!(id.prefix(), 0xAB);
+        assert_eq!(id.to_u64() >> 40, 0xAB);
+    }
+
     #[test]
     fn test_session_id_display() {
         let id = ServerSessionId::new([0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
@@ -4,6 +4,13 @@
 
 use std::time::{Duration, Instant};
 
+#[cfg(feature = "metrics")]
+use std::sync::Arc;
+
+#[cfg(feature = "metrics")]
+use crate::core::Metrics;
+use crate::core::{SharedClock, system_clock};
+
 /// Tracks pending acknowledgments for a message
 #[derive(Debug, Clone)]
 pub struct PendingAck {
@@ -20,9 +27,14 @@ pub struct PendingAck {
 impl PendingAck {
     /// Create a new pending ack
     pub fn new(version: u64, rto: Duration) -> Self {
+        Self::new_at(version, rto, Instant::now())
+    }
+
+    /// Create a pending ack for a message sent at `now`
+    pub fn new_at(version: u64, rto: Duration, now: Instant) -> Self {
         Self {
             version,
-            sent_at: Instant::now(),
+            sent_at: now,
             retransmit_count: 0,
             rto,
         }
@@ -30,12 +42,22 @@ impl PendingAck {
 
     /// Check if retransmission is needed
     pub fn needs_retransmit(&self) -> bool {
-        self.sent_at.elapsed() >= self.rto
+        self.needs_retransmit_at(Instant::now())
+    }
+
+    /// Check if retransmission is needed at a specific time
+    pub fn needs_retransmit_at(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.sent_at) >= self.rto
     }
 
     /// Mark as retransmitted with updated timeout
     pub fn retransmit(&mut self, backoff_multiplier: u32, max_rto: Duration) {
-        self.sent_at = Instant::now();
+        self.retransmit_at(backoff_multiplier, max_rto, Instant::now());
+    }
+
+    /// Mark as retransmitted at a specific time with updated timeout
+    pub fn retransmit_at(&mut self, backoff_multiplier: u32, max_rto: Duration, now: Instant) {
+        self.sent_at = now;
         self.retransmit_count += 1;
         // Exponential backoff
         self.rto = (self.rto * backoff_multiplier).min(max_rto);
@@ -43,12 +65,12 @@ impl PendingAck {
 
     /// Time until retransmission is needed
     pub fn time_until_retransmit(&self) -> Duration {
-        let elapsed = self.sent_at.elapsed();
-        if elapsed >= self.rto {
-            Duration::ZERO
-        } else {
-            self.rto - elapsed
-        }
+        self.time_until_retransmit_at(Instant::now())
+    }
+
+    /// Time until retransmission is needed, as of `now`
+    pub fn time_until_retransmit_at(&self, now: Instant) -> Duration {
+        self.rto.saturating_sub(now.saturating_duration_since(self.sent_at))
     }
 }
 
@@ -92,6 +114,13 @@ pub struct AckTracker {
     /// Smoothed RTT and RTT variance (RFC 6298)
     srtt: Option<Duration>,
     rttvar: Option<Duration>,
+
+    /// Time source
+    clock: SharedClock,
+
+    /// Registry for sent, retransmit and SRTT counts
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<Metrics>>,
 }
 
 impl AckTracker {
@@ -107,6 +136,9 @@ impl AckTracker {
             max_retransmits: DEFAULT_MAX_RETRANSMITS,
             srtt: None,
             rttvar: None,
+            clock: system_clock(),
+            #[cfg(feature = "metrics")]
+            metrics: None,
         }
     }
 
@@ -128,9 +160,27 @@ impl AckTracker {
             max_retransmits,
             srtt: None,
             rttvar: None,
+            clock: system_clock(),
+            #[cfg(feature = "metrics")]
+            metrics: None,
         }
     }
 
+    /// Use a custom time source instead of the system clock
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Report retransmits and SRTT to a metrics registry
+    ///
+    /// Sends are counted where datagrams go out (see `Metrics::frame_sent`).
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Register a sent message that needs acknowledgment
     pub fn register_sent(&mut self, version: u64) {
         // Don't register if already pending
@@ -139,7 +189,7 @@ impl AckTracker {
         }
 
         let rto = self.current_rto();
-        self.pending.push(PendingAck::new(version, rto));
+        self.pending.push(PendingAck::new_at(version, rto, self.clock.now()));
     }
 
     /// Process an incoming acknowledgment
@@ -147,31 +197,66 @@ impl AckTracker {
     /// Returns the RTT sample if this ack is for a pending message.
     pub fn process_ack(&mut self, acked_version: u64) -> Option<Duration> {
         if acked_version <= self.highest_acked {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(acked_version, highest_acked = self.highest_acked, "stale ack ignored");
             return None;
         }
 
         self.highest_acked = acked_version;
 
-        // Find and remove all pending acks up to this version
-        let mut rtt_sample = None;
-
-        self.pending.retain(|pending| {
-            if pending.version <= acked_version {
-                // Only use as RTT sample if not retransmitted
-                if pending.retransmit_count == 0 && rtt_sample.is_none() {
-                    rtt_sample = Some(pending.sent_at.elapsed());
-                }
-                false // Remove from pending
-            } else {
-                true // Keep in pending
+        // Remove all pending acks up to this version
+        let rtt_sample = self.remove_acked(|version| version <= acked_version);
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            acked_version,
+            pending = self.pending.len(),
+            rtt_sample = ?rtt_sample,
+            "ack processed"
+        );
+
+        rtt_sample
+    }
+
+    /// Process a selective acknowledgment
+    ///
+    /// `ranges` are inclusive `(first, last)` version ranges the peer has
+    /// received. Pending acks inside them are dropped so they are never
+    /// retransmitted, but `highest_acked` only moves with cumulative acks
+    /// ([`process_ack`](Self::process_ack)), so a gap below a range stays
+    /// pending.
+    ///
+    /// Returns the RTT sample if a range covered a pending message.
+    pub fn process_sack(&mut self, ranges: &[(u64, u64)]) -> Option<Duration> {
+        self.remove_acked(|version| {
+            ranges
+                .iter()
+                .any(|&(first, last)| (first..=last).contains(&version))
+        })
+    }
+
+    /// Drop pending acks for versions matching `acked`
+    ///
+    /// The RTT is sampled from the newest removed message, the one whose
+    /// arrival triggered the ack; older ones waited for it and would inflate
+    /// the sample. As with Karn's algorithm, there is no sample if that
+    /// message was retransmitted.
+    fn remove_acked(&mut self, acked: impl Fn(u64) -> bool) -> Option<Duration> {
+        let now = self.clock.now();
+        let mut newest: Option<&PendingAck> = None;
+        for pending in self.pending.iter().filter(|p| acked(p.version)) {
+            if newest.is_none_or(|n| pending.version > n.version) {
+                newest = Some(pending);
             }
-        });
+        }
+        let rtt_sample = newest
+            .filter(|pending| pending.retransmit_count == 0)
+            .map(|pending| now.saturating_duration_since(pending.sent_at));
 
-        // Update RTT estimates if we got a sample
+        self.pending.retain(|pending| !acked(pending.version));
         if let Some(rtt) = rtt_sample {
             self.update_rtt(rtt);
         }
-
         rtt_sample
     }
 
@@ -204,6 +289,11 @@ impl AckTracker {
             }
             _ => {}
         }
+
+        #[cfg(feature = "metrics")]
+        if let (Some(metrics), Some(srtt)) = (&self.metrics, self.srtt) {
+            metrics.record_srtt(srtt);
+        }
     }
 
     /// Get current RTO based on RTT estimates
@@ -233,9 +323,10 @@ impl AckTracker {
 
     /// Get pending acks that need retransmission
     pub fn needs_retransmit(&self) -> impl Iterator<Item = u64> + '_ {
+        let now = self.clock.now();
         self.pending
             .iter()
-            .filter(|p| p.needs_retransmit() && p.retransmit_count < self.max_retransmits)
+            .filter(move |p| p.needs_retransmit_at(now) && p.retransmit_count < self.max_retransmits)
             .map(|p| p.version)
     }
 
@@ -250,7 +341,11 @@ impl AckTracker {
     /// Mark a version as retransmitted
     pub fn mark_retransmitted(&mut self, version: u64) {
         if let Some(pending) = self.pending.iter_mut().find(|p| p.version == version) {
-            pending.retransmit(self.backoff_multiplier, self.max_rto);
+            pending.retransmit_at(self.backoff_multiplier, self.max_rto, self.clock.now());
+            #[cfg(feature = "metrics")]
+            if let Some(metrics) = &self.metrics {
+                metrics.frame_retransmitted();
+            }
         }
     }
 
@@ -271,10 +366,11 @@ impl AckTracker {
 
     /// Get time until next retransmission is needed
     pub fn time_until_retransmit(&self) -> Option<Duration> {
+        let now = self.clock.now();
         self.pending
             .iter()
             .filter(|p| p.retransmit_count < self.max_retransmits)
-            .map(|p| p.time_until_retransmit())
+            .map(|p| p.time_until_retransmit_at(now))
             .min()
     }
 
@@ -306,6 +402,7 @@ impl Default for AckTracker {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::TestClock;
     use std::thread;
 
     #[test]
@@ -350,6 +447,35 @@ mod tests {
         assert_eq!(tracker.highest_acked(), 2);
     }
 
+    #[test]
+    fn test_sack_retransmits_only_gap() {
+        let mut tracker = AckTracker::with_rto(
+            Duration::from_millis(10),
+            Duration::from_millis(10),
+            Duration::from_secs(1),
+            2,
+            3,
+        );
+        for version in 1..=5 {
+            tracker.register_sent(version);
+        }
+
+        // 1 and 2 arrive in order, 3 is lost, 4 and 5 arrive after the gap
+        tracker.process_ack(2);
+        assert!(tracker.process_sack(&[(4, 5)]).is_some());
+        assert_eq!(tracker.highest_acked(), 2);
+        assert_eq!(tracker.pending_count(), 1);
+
+        thread::sleep(Duration::from_millis(15));
+        let versions: Vec<_> = tracker.needs_retransmit().collect();
+        assert_eq!(versions, vec![3]);
+
+        // Repeated SACKs are harmless; the cumulative ack closes the gap
+        assert!(tracker.process_sack(&[(4, 5)]).is_none());
+        tracker.process_ack(5);
+        assert!(!tracker.has_pending());
+    }
+
     #[test]
     fn test_rtt_sample() {
         let mut tracker = AckTracker::new();
@@ -395,6 +521,60 @@ mod tests {
         assert_eq!(tracker.needs_retransmit().count(), 0);
     }
 
+    #[test]
+    fn test_rtt_sampled_from_newest_acked() {
+        let clock = TestClock::new();
+        let mut tracker = AckTracker::new().with_clock(clock.shared());
+
+        // 1 and 2 go out 80ms apart; one ack covers both 20ms after 2
+        tracker.register_sent(1);
+        clock.advance(Duration::from_millis(80));
+        tracker.register_sent(2);
+        clock.advance(Duration::from_millis(20));
+        assert_eq!(tracker.process_ack(2), Some(Duration::from_millis(20)));
+
+        // Same for a selective ack
+        tracker.register_sent(3);
+        tracker.register_sent(4);
+        clock.advance(Duration::from_millis(50));
+        tracker.register_sent(5);
+        clock.advance(Duration::from_millis(30));
+        assert_eq!(tracker.process_sack(&[(4, 5)]), Some(Duration::from_millis(30)));
+
+        // No sample when the newest acked message was retransmitted
+        tracker.mark_retransmitted(3);
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(tracker.process_ack(3), None);
+    }
+
+    #[test]
+    fn test_retransmit_on_test_clock() {
+        let clock = TestClock::new();
+        let mut tracker = AckTracker::with_rto(
+            Duration::from_millis(100),
+            Duration::from_millis(100),
+            Duration::from_secs(1),
+            2,
+            3,
+        )
+        .with_clock(clock.shared());
+
+        tracker.register_sent(1);
+        clock.advance(Duration::from_millis(99));
+        assert_eq!(tracker.needs_retransmit().count(), 0);
+        assert_eq!(tracker.time_until_retransmit(), Some(Duration::from_millis(1)));
+
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(tracker.needs_retransmit().collect::<Vec<_>>(), vec![1]);
+
+        // Backed off to 200ms
+        tracker.mark_retransmitted(1);
+        clock.advance(Duration::from_millis(199));
+        assert_eq!(tracker.needs_retransmit().count(), 0);
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(tracker.needs_retransmit().collect::<Vec<_>>(), vec![1]);
+    }
+
     #[test]
     fn test_max_retransmits() {
         let mut tracker = AckTracker::with_rto(
@@ -0,0 +1,209 @@
+//! Channel coalescing
+//!
+//! Packs sync messages from several multiplexed channels into a single
+//! DATA frame plaintext, so the fixed per-datagram overhead (header, AEAD
+//! tag, payload header) is paid once instead of once per channel.
+//!
+//! Wire format (repeated until the end of the payload):
+//! ```text
+//! +0  Channel ID (2 bytes LE16)
+//! +2  Entry Length (2 bytes LE16)
+//! +4  Sync Message (Entry Length bytes, see `SyncMessage`)
+//! ```
+
+use crate::core::RECOMMENDED_MAX_PAYLOAD;
+
+use super::{MessageError, SyncMessage};
+
+/// Per-entry header size in bytes (channel ID + length).
+pub const CHANNEL_ENTRY_HEADER_SIZE: usize = 4;
+
+/// A sync message tagged with the channel it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelMessage {
+    /// Channel this message belongs to
+    pub channel_id: u16,
+    /// Sync message for the channel
+    pub message: SyncMessage,
+}
+
+impl ChannelMessage {
+    /// Create a new channel message
+    pub fn new(channel_id: u16, message: SyncMessage) -> Self {
+        Self {
+            channel_id,
+            message,
+        }
+    }
+
+    /// Total wire size including the entry header
+    pub fn wire_size(&self) -> usize {
+        CHANNEL_ENTRY_HEADER_SIZE + self.message.wire_size()
+    }
+
+    /// Append this entry to a buffer
+    fn encode_into_vec(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.channel_id.to_le_bytes());
+        buf.extend_from_slice(&(self.message.wire_size() as u16).to_le_bytes());
+        buf.extend_from_slice(&self.message.encode());
+    }
+}
+
+/// Packs channel messages into MTU-sized frame payloads.
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelCoalescer {
+    /// Maximum bytes of coalesced data per frame
+    max_payload: usize,
+}
+
+impl ChannelCoalescer {
+    /// Create a coalescer with the given per-frame budget.
+    ///
+    /// `max_payload` is the space available for sync data in one frame,
+    /// i.e. the path MTU minus frame header, payload header and AEAD tag.
+    pub fn new(max_payload: usize) -> Self {
+        Self { max_payload }
+    }
+
+    /// Get the per-frame budget.
+    pub fn max_payload(&self) -> usize {
+        self.max_payload
+    }
+
+    /// Pack messages into as few frame payloads as possible.
+    ///
+    /// Messages are kept in order; when the next message would exceed the
+    /// budget, it spills into a new frame.
+    ///
+    /// # Errors
+    /// Returns `BufferTooSmall` if a single message cannot fit in one frame.
+    pub fn pack(&self, messages: &[ChannelMessage]) -> Result<Vec<Vec<u8>>, MessageError> {
+        let mut frames = Vec::new();
+        let mut current: Vec<u8> = Vec::new();
+
+        for msg in messages {
+            let size = msg.wire_size();
+            if size > self.max_payload || msg.message.wire_size() > u16::MAX as usize {
+                return Err(MessageError::BufferTooSmall {
+                    required: size,
+                    available: self.max_payload,
+                });
+            }
+
+            if current.len() + size > self.max_payload {
+                frames.push(std::mem::take(&mut current));
+            }
+            msg.encode_into_vec(&mut current);
+        }
+
+        if !current.is_empty() {
+            frames.push(current);
+        }
+
+        Ok(frames)
+    }
+}
+
+impl Default for ChannelCoalescer {
+    fn default() -> Self {
+        Self::new(RECOMMENDED_MAX_PAYLOAD)
+    }
+}
+
+/// Split a coalesced frame payload back into per-channel messages.
+pub fn demux_channels(data: &[u8]) -> Result<Vec<ChannelMessage>, MessageError> {
+    let mut messages = Vec::new();
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let remaining = &data[offset..];
+        if remaining.len() < CHANNEL_ENTRY_HEADER_SIZE {
+            return Err(MessageError::TooShort {
+                expected: CHANNEL_ENTRY_HEADER_SIZE,
+                actual: remaining.len(),
+            });
+        }
+
+        let channel_id = u16::from_le_bytes([remaining[0], remaining[1]]);
+        let len = u16::from_le_bytes([remaining[2], remaining[3]]) as usize;
+        let end = CHANNEL_ENTRY_HEADER_SIZE + len;
+        if remaining.len() < end {
+            return Err(MessageError::TooShort {
+                expected: end,
+                actual: remaining.len(),
+            });
+        }
+
+        let (message, consumed) =
+            SyncMessage::decode_with_length(&remaining[CHANNEL_ENTRY_HEADER_SIZE..end])?;
+        if consumed != len {
+            return Err(MessageError::InvalidFormat(format!(
+                "channel {} entry length {} does not match message size {}",
+                channel_id, len, consumed
+            )));
+        }
+
+        messages.push(ChannelMessage::new(channel_id, message));
+        offset += end;
+    }
+
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel_msg(channel_id: u16, diff_len: usize) -> ChannelMessage {
+        ChannelMessage::new(
+            channel_id,
+            SyncMessage::new(2, 1, 1, vec![channel_id as u8; diff_len]),
+        )
+    }
+
+    #[test]
+    fn test_small_channels_share_one_frame() {
+        let messages = vec![channel_msg(1, 10), channel_msg(2, 20), channel_msg(7, 5)];
+        let frames = ChannelCoalescer::default().pack(&messages).unwrap();
+
+        assert_eq!(frames.len(), 1);
+        assert_eq!(demux_channels(&frames[0]).unwrap(), messages);
+    }
+
+    #[test]
+    fn test_oversized_set_spills_into_second_frame() {
+        let messages = vec![channel_msg(1, 60), channel_msg(2, 60), channel_msg(3, 60)];
+        // Each entry is 4 + 28 + 60 = 92 bytes; only two fit in 200
+        let frames = ChannelCoalescer::new(200).pack(&messages).unwrap();
+
+        assert_eq!(frames.len(), 2);
+        assert!(frames.iter().all(|f| f.len() <= 200));
+
+        let demuxed: Vec<_> = frames
+            .iter()
+            .flat_map(|f| demux_channels(f).unwrap())
+            .collect();
+        assert_eq!(demuxed, messages);
+    }
+
+    #[test]
+    fn test_single_message_too_large() {
+        let messages = vec![channel_msg(1, 300)];
+        assert!(matches!(
+            ChannelCoalescer::new(200).pack(&messages),
+            Err(MessageError::BufferTooSmall { .. })
+        ));
+    }
+
+    #[test]
+    fn test_demux_truncated() {
+        let frames = ChannelCoalescer::default()
+            .pack(&[channel_msg(1, 10)])
+            .unwrap();
+        let truncated = &frames[0][..frames[0].len() - 1];
+        assert!(matches!(
+            demux_channels(truncated),
+            Err(MessageError::TooShort { .. })
+        ));
+    }
+}
@@ -3,10 +3,21 @@
 //! Coordinates state synchronization between two endpoints.
 //! Generic over the state type S which must implement SyncState.
 
-use super::message::{MessageError, SyncMessage};
+use super::message::{
+    MessageError, SyncMessage, FRAGMENT_HEADER_SIZE, NACK_BITMAP_SIZE, RECV_WINDOW_SIZE,
+    SYNC_MESSAGE_HEADER_SIZE, SYNC_PREAMBLE_SIZE,
+};
+use super::receiver::FragmentAssembler;
 use super::tracker::SyncTracker;
+#[cfg(feature = "extensions")]
+use crate::extensions::SubscriptionState;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
+/// Maximum number of sent-but-unacked state snapshots kept for diffing
+pub const MAX_SNAPSHOT_HISTORY: usize = 64;
+
 /// Errors from the sync engine.
 #[derive(Debug, Error)]
 pub enum SyncError {
@@ -34,6 +45,117 @@ pub enum SyncError {
     /// Operation requires initialized state but none exists.
     #[error("state not initialized")]
     NotInitialized,
+
+    /// Failed to decode persisted engine state.
+    #[error("persisted state decode error: {0}")]
+    PersistDecode(String),
+}
+
+/// Callback that merges a concurrent peer state into the local state
+pub type MergeFn<S> = fn(&mut S, &S) -> Result<(), String>;
+
+/// Callback that encodes the whole state as a snapshot, if supported
+pub type EncodeSnapshotFn<S> = fn(&S) -> Option<Vec<u8>>;
+
+/// Callback that decodes a snapshot back into a state
+pub type DecodeSnapshotFn<S> = fn(&[u8]) -> Result<S, String>;
+
+/// Callback that discards predictions, restoring the authoritative state
+pub type RollbackFn<S> = fn(&mut S, &S);
+
+/// Callback that checks a state's invariants after a peer update
+pub type ValidateFn<S> = fn(&S) -> Result<(), String>;
+
+/// Callback that names the region a single-region diff touches
+///
+/// `None` means the diff is not tied to a region and is always sent.
+pub type RegionOfFn<D> = fn(&D) -> Option<String>;
+
+/// Observer called with the new state and peer version after a diff applies
+pub type AppliedFn<S> = Box<dyn Fn(&S, u64) + Send + Sync>;
+
+/// Callback that splits a diff into parts touching one region each
+pub type SplitRegionsFn<D> = fn(&D) -> Vec<D>;
+
+/// Size of the fixed header of encoded [`EnginePersistState`]
+pub const ENGINE_PERSIST_HEADER_SIZE: usize = 36;
+
+/// Engine state persisted for crash recovery
+///
+/// Produced by [`SyncEngine::persist`] and consumed by
+/// [`SyncEngine::restore`], so a restarted endpoint resumes incremental
+/// sync from the acked snapshot instead of resyncing the whole state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnginePersistState<S> {
+    /// Local state version
+    pub current_version: u64,
+    /// Highest local version the peer acknowledged
+    pub last_acked_version: u64,
+    /// Highest peer version received
+    pub peer_version: u64,
+    /// Version of the acked snapshot outgoing diffs are computed from
+    pub acked_version: u64,
+    /// Snapshot the peer is known to hold
+    pub acked_state: S,
+    /// Local state, without outstanding predictions
+    pub state: S,
+}
+
+impl<S> EnginePersistState<S> {
+    /// Encode, using `encode_state` for both states
+    ///
+    /// Format:
+    /// ```text
+    /// +0   Current Version (8 bytes LE64)
+    /// +8   Last Acked Version (8 bytes LE64)
+    /// +16  Peer Version (8 bytes LE64)
+    /// +24  Acked Version (8 bytes LE64)
+    /// +32  Acked State Length (4 bytes LE32)
+    /// +36  Acked State
+    /// ...  State (remaining bytes)
+    /// ```
+    pub fn encode(&self, encode_state: fn(&S) -> Vec<u8>) -> Vec<u8> {
+        let acked = encode_state(&self.acked_state);
+        let state = encode_state(&self.state);
+        let mut buf = Vec::with_capacity(ENGINE_PERSIST_HEADER_SIZE + acked.len() + state.len());
+        buf.extend_from_slice(&self.current_version.to_le_bytes());
+        buf.extend_from_slice(&self.last_acked_version.to_le_bytes());
+        buf.extend_from_slice(&self.peer_version.to_le_bytes());
+        buf.extend_from_slice(&self.acked_version.to_le_bytes());
+        buf.extend_from_slice(&(acked.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&acked);
+        buf.extend_from_slice(&state);
+        buf
+    }
+
+    /// Decode, using `decode_state` for both states
+    pub fn decode(data: &[u8], decode_state: DecodeSnapshotFn<S>) -> Result<Self, SyncError> {
+        if data.len() < ENGINE_PERSIST_HEADER_SIZE {
+            return Err(SyncError::PersistDecode(format!("too short: {} bytes", data.len())));
+        }
+        let u64_at = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+        let acked_len = u32::from_le_bytes(data[32..36].try_into().unwrap()) as usize;
+        let acked_end = ENGINE_PERSIST_HEADER_SIZE
+            .checked_add(acked_len)
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| SyncError::PersistDecode("truncated acked state".to_string()))?;
+
+        let persisted = Self {
+            current_version: u64_at(0),
+            last_acked_version: u64_at(8),
+            peer_version: u64_at(16),
+            acked_version: u64_at(24),
+            acked_state: decode_state(&data[ENGINE_PERSIST_HEADER_SIZE..acked_end])
+                .map_err(SyncError::PersistDecode)?,
+            state: decode_state(&data[acked_end..]).map_err(SyncError::PersistDecode)?,
+        };
+        if persisted.acked_version > persisted.last_acked_version
+            || persisted.last_acked_version > persisted.current_version
+        {
+            return Err(SyncError::PersistDecode("inconsistent versions".to_string()));
+        }
+        Ok(persisted)
+    }
 }
 
 /// Result of processing an incoming sync message
@@ -45,6 +167,8 @@ pub enum ProcessResult {
     AckOnly,
     /// Duplicate message (already have this version)
     Duplicate,
+    /// Fragment buffered; the diff is applied once all fragments arrive
+    Fragment,
 }
 
 /// Sync engine for bidirectional state synchronization
@@ -64,9 +188,10 @@ pub struct SyncEngine<S, D> {
     /// Current local state
     state: Option<S>,
 
-    /// State snapshot at last_acked version (for diff computation)
-    /// This is the state we know the peer has
-    acked_snapshot: Option<S>,
+    /// Snapshots of sent states by version, oldest first (for diff
+    /// computation). The front is the newest state we know the peer has;
+    /// the rest were sent but not yet acked.
+    history: VecDeque<(u64, S)>,
 
     /// Callback for encoding diffs
     encode_diff: fn(&D) -> Vec<u8>,
@@ -82,6 +207,48 @@ pub struct SyncEngine<S, D> {
 
     /// Callback for checking if diff is empty
     is_diff_empty: fn(&D) -> bool,
+
+    /// Optional callback for dropping no-op components before encoding
+    compact_diff: Option<fn(&D) -> D>,
+
+    /// Optional callback for merging concurrent peer state (CRDTs)
+    merge: Option<MergeFn<S>>,
+
+    /// Optional callback for encoding absolute state snapshots
+    encode_snapshot: Option<EncodeSnapshotFn<S>>,
+
+    /// Optional callback for decoding absolute state snapshots
+    decode_snapshot: Option<DecodeSnapshotFn<S>>,
+
+    /// Optional callback for checking state invariants after updates
+    validate: Option<ValidateFn<S>>,
+
+    /// Reassembly buffer for fragmented incoming diffs
+    assembler: FragmentAssembler,
+
+    /// State without speculative changes, while predictions are outstanding
+    authoritative: Option<S>,
+
+    /// Unconfirmed predictions, oldest first, tagged with the local
+    /// version that carried the input
+    predictions: VecDeque<(u64, D)>,
+
+    /// Optional callback for rolling back predictions
+    rollback: Option<RollbackFn<S>>,
+
+    /// Optional callbacks for classifying diffs by region
+    #[cfg(feature = "extensions")]
+    regions: Option<(SplitRegionsFn<D>, RegionOfFn<D>)>,
+
+    /// Regions the peer subscribed to, if outgoing diffs are filtered
+    #[cfg(feature = "extensions")]
+    subscriptions: Option<SubscriptionState>,
+
+    /// Whether messages carry the magic and wire version preamble
+    preamble: bool,
+
+    /// Optional observer for applied peer updates
+    on_applied: Option<AppliedFn<S>>,
 }
 
 impl<S: Clone, D> SyncEngine<S, D> {
@@ -96,20 +263,178 @@ impl<S: Clone, D> SyncEngine<S, D> {
         Self {
             tracker: SyncTracker::new(),
             state: None,
-            acked_snapshot: None,
+            history: VecDeque::new(),
             encode_diff,
             decode_diff,
             compute_diff,
             apply_diff,
             is_diff_empty,
+            compact_diff: None,
+            merge: None,
+            encode_snapshot: None,
+            decode_snapshot: None,
+            validate: None,
+            assembler: FragmentAssembler::new(),
+            authoritative: None,
+            predictions: VecDeque::new(),
+            rollback: None,
+            #[cfg(feature = "extensions")]
+            regions: None,
+            #[cfg(feature = "extensions")]
+            subscriptions: None,
+            preamble: false,
+            on_applied: None,
         }
     }
 
+    /// Set a callback that trims no-op components from each outgoing diff
+    ///
+    /// Applied before the empty check and encoding (see
+    /// `SyncState::compact_diff`).
+    pub fn with_compact_diff(mut self, compact_diff: fn(&D) -> D) -> Self {
+        self.compact_diff = Some(compact_diff);
+        self
+    }
+
+    /// Set a callback that merges concurrent updates (see `SyncState::merge`)
+    ///
+    /// An incoming diff is concurrent when the peer's ack shows it had not
+    /// yet seen our current version, i.e. both sides changed the state
+    /// without seeing each other's change. With a merge callback set, the
+    /// engine reconstructs the peer's view (the diff applied to the acked
+    /// snapshot) and merges it into the local state instead of applying the
+    /// diff over local changes.
+    ///
+    /// Ordering: diffs the peer computed after seeing our current version
+    /// are still applied in order via `apply_diff`; only concurrent ones are
+    /// merged. Because the acked snapshot may be older than the peer's real
+    /// base, diffs should carry absolute values (as CRDT states do) rather
+    /// than deltas.
+    pub fn with_merge(mut self, merge: MergeFn<S>) -> Self {
+        self.merge = Some(merge);
+        self
+    }
+
+    /// Set callbacks for sending absolute snapshots (see
+    /// `SyncState::encode_snapshot`)
+    ///
+    /// With snapshots enabled, the engine encodes both the diff and the
+    /// full state and sends whichever is smaller, flagging snapshots so the
+    /// receiver replaces its state instead of applying a diff. This caps
+    /// the cost of a near-total change at the size of the state itself.
+    /// Both peers must enable snapshots.
+    pub fn with_snapshots(
+        mut self,
+        encode_snapshot: EncodeSnapshotFn<S>,
+        decode_snapshot: DecodeSnapshotFn<S>,
+    ) -> Self {
+        self.encode_snapshot = Some(encode_snapshot);
+        self.decode_snapshot = Some(decode_snapshot);
+        self
+    }
+
+    /// Set a callback that checks the state after each peer update (see
+    /// `SyncState::validate`)
+    ///
+    /// The state is cloned before a diff, snapshot or merge is applied; if
+    /// the result fails validation, the clone is restored and processing
+    /// returns `SyncError::DiffApply`.
+    pub fn with_validate(mut self, validate: ValidateFn<S>) -> Self {
+        self.validate = Some(validate);
+        self
+    }
+
+    /// Set a callback that rolls back predictions (see
+    /// `Predictable::rollback`)
+    ///
+    /// Without one, the engine restores the authoritative state by cloning
+    /// it over the predicted one.
+    pub fn with_rollback(mut self, rollback: RollbackFn<S>) -> Self {
+        self.rollback = Some(rollback);
+        self
+    }
+
+    /// Set callbacks for classifying diffs by state region
+    ///
+    /// `split` breaks a diff into parts that each touch one region, and
+    /// `region_of` names that region as a `/`-separated path. Once the
+    /// peer's subscriptions are set (see `set_subscriptions`), parts in
+    /// regions it has not subscribed to are left out of outgoing diffs.
+    #[cfg(feature = "extensions")]
+    pub fn with_regions(mut self, split: SplitRegionsFn<D>, region_of: RegionOfFn<D>) -> Self {
+        self.regions = Some((split, region_of));
+        self
+    }
+
+    /// Filter outgoing diffs by the peer's subscriptions
+    ///
+    /// Has no effect without `with_regions`. Suppressed parts are not
+    /// recorded as sent: the snapshot kept for the peer still lacks them,
+    /// so they go out once the peer subscribes to their region. Bumps the
+    /// version so that newly subscribed regions are sent.
+    #[cfg(feature = "extensions")]
+    pub fn set_subscriptions(&mut self, subscriptions: SubscriptionState) {
+        self.subscriptions = Some(subscriptions);
+        self.tracker.bump_version();
+    }
+
     /// Initialize the engine with initial state
     pub fn init(&mut self, initial_state: S) {
         self.state = Some(initial_state.clone());
-        self.acked_snapshot = Some(initial_state);
+        self.history.clear();
+        self.history.push_back((0, initial_state));
         self.tracker.reset();
+        self.authoritative = None;
+        self.predictions.clear();
+    }
+
+    /// Initialize the engine with the peer's state at a known version
+    ///
+    /// For bootstrapping from a checkpoint: `state` is the peer's state as
+    /// of `peer_version`, and incremental messages above that version apply
+    /// on top of it. The next ack we send acknowledges `peer_version`.
+    pub fn init_at_peer_version(&mut self, state: S, peer_version: u64) {
+        self.init(state);
+        self.tracker.set_peer_version(peer_version);
+    }
+
+    /// Capture the state needed to resume after a restart
+    ///
+    /// Outstanding predictions and unacked snapshots are not included;
+    /// after [`restore`](Self::restore), everything newer than the acked
+    /// snapshot is resent as one diff.
+    pub fn persist(&self) -> Result<EnginePersistState<S>, SyncError> {
+        let state = self.authoritative_state().ok_or(SyncError::NotInitialized)?;
+        let (acked_version, acked_state) = self.history.front().ok_or(SyncError::NotInitialized)?;
+        Ok(EnginePersistState {
+            current_version: self.tracker.current_version(),
+            last_acked_version: self.tracker.last_acked_version(),
+            peer_version: self.tracker.peer_version(),
+            acked_version: *acked_version,
+            acked_state: acked_state.clone(),
+            state: state.clone(),
+        })
+    }
+
+    /// Resume from state captured by [`persist`](Self::persist)
+    ///
+    /// Versions continue from the persisted ones, so the peer sees no
+    /// regression, and the next diff is computed from the acked snapshot.
+    /// The negotiated NACK setting is kept.
+    pub fn restore(&mut self, persisted: EnginePersistState<S>) {
+        let nack_enabled = self.tracker.nack_enabled();
+        self.tracker = SyncTracker::restore(
+            persisted.current_version,
+            persisted.last_acked_version,
+            persisted.peer_version,
+        );
+        self.tracker.set_nack_enabled(nack_enabled);
+        self.state = Some(persisted.state);
+        self.history.clear();
+        self.history.push_back((persisted.acked_version, persisted.acked_state));
+        self.assembler.reset();
+        self.authoritative = None;
+        self.predictions.clear();
     }
 
     /// Check if the engine is initialized
@@ -118,13 +443,23 @@ impl<S: Clone, D> SyncEngine<S, D> {
     }
 
     /// Get a reference to the current state
+    ///
+    /// Includes any outstanding predictions.
     pub fn state(&self) -> Option<&S> {
         self.state.as_ref()
     }
 
+    /// Get the state without outstanding predictions
+    pub fn authoritative_state(&self) -> Option<&S> {
+        self.authoritative.as_ref().or(self.state.as_ref())
+    }
+
     /// Get a mutable reference to the current state
     ///
-    /// Note: After modifying, call `mark_changed()` to bump version
+    /// Note: After modifying, call `mark_changed()` to bump version.
+    /// While predictions are outstanding this is the predicted view, and
+    /// changes made here are lost at the next reconcile; use
+    /// `update_state` instead.
     pub fn state_mut(&mut self) -> Option<&mut S> {
         self.state.as_mut()
     }
@@ -137,11 +472,83 @@ impl<S: Clone, D> SyncEngine<S, D> {
     }
 
     /// Update local state and bump version atomically
+    ///
+    /// While predictions are outstanding, `new_state` replaces the
+    /// authoritative state and the predictions are replayed on top of it.
     pub fn update_state(&mut self, new_state: S) -> u64 {
-        self.state = Some(new_state);
+        if self.authoritative.is_some() {
+            self.authoritative = Some(new_state.clone());
+            self.state = Some(new_state);
+            self.replay_predictions();
+        } else {
+            self.state = Some(new_state);
+        }
         self.tracker.bump_version()
     }
 
+    /// Apply a speculative diff to the local state
+    ///
+    /// The prediction is tagged with the current local version, so call
+    /// this after bumping the version that carries the triggering input.
+    /// It is kept until an authoritative message acks that version; until
+    /// then every authoritative update rolls the state back, applies the
+    /// update, and replays the prediction on top. Predictions are never
+    /// sent: outgoing diffs are computed from the authoritative state.
+    pub fn apply_predicted(&mut self, diff: D) -> Result<(), SyncError> {
+        let state = self.state.as_mut().ok_or(SyncError::NotInitialized)?;
+        let mut predicted = state.clone();
+        (self.apply_diff)(&mut predicted, &diff).map_err(SyncError::DiffApply)?;
+
+        if self.authoritative.is_none() {
+            self.authoritative = Some(std::mem::replace(state, predicted));
+        } else {
+            *state = predicted;
+        }
+        self.predictions.push_back((self.tracker.current_version(), diff));
+        Ok(())
+    }
+
+    /// Number of predictions not yet confirmed by the peer
+    pub fn pending_predictions(&self) -> usize {
+        self.predictions.len()
+    }
+
+    /// Restore the authoritative state, dropping the predicted view
+    fn roll_back_predictions(&mut self) {
+        let (Some(state), Some(authoritative)) = (self.state.as_mut(), self.authoritative.as_ref())
+        else {
+            return;
+        };
+        match self.rollback {
+            Some(rollback) => rollback(state, authoritative),
+            None => state.clone_from(authoritative),
+        }
+    }
+
+    /// Re-apply outstanding predictions over the current state
+    ///
+    /// A prediction that no longer applies was mispredicted and is dropped.
+    fn replay_predictions(&mut self) {
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
+        if self.predictions.is_empty() {
+            self.authoritative = None;
+            return;
+        }
+
+        self.authoritative = Some(state.clone());
+        let apply_diff = self.apply_diff;
+        self.predictions.retain(|(_, diff)| {
+            let mut predicted = state.clone();
+            let applied = apply_diff(&mut predicted, diff).is_ok();
+            if applied {
+                *state = predicted;
+            }
+            applied
+        });
+    }
+
     /// Get the tracker for inspection
     pub fn tracker(&self) -> &SyncTracker {
         &self.tracker
@@ -157,26 +564,128 @@ impl<S: Clone, D> SyncEngine<S, D> {
         self.tracker.needs_ack()
     }
 
+    /// Enable the sync message preamble once the peer has negotiated it
+    ///
+    /// Affects [`encode_message`](Self::encode_message),
+    /// [`decode_message`](Self::decode_message), and the fragment budget of
+    /// [`generate_messages`](Self::generate_messages).
+    pub fn set_preamble_enabled(&mut self, enabled: bool) {
+        self.preamble = enabled;
+    }
+
+    /// Check if the sync message preamble is enabled
+    pub fn preamble_enabled(&self) -> bool {
+        self.preamble
+    }
+
+    /// Encode a message in the negotiated wire format
+    pub fn encode_message(&self, msg: &SyncMessage) -> Vec<u8> {
+        if self.preamble {
+            msg.encode_with_preamble()
+        } else {
+            msg.encode()
+        }
+    }
+
+    /// Decode a message in the negotiated wire format
+    ///
+    /// With the preamble enabled, decoding is strict (see
+    /// [`SyncMessage::decode_strict`]).
+    pub fn decode_message(&self, data: &[u8]) -> Result<SyncMessage, SyncError> {
+        let msg = if self.preamble {
+            SyncMessage::decode_strict(data)?
+        } else {
+            SyncMessage::decode(data)?
+        };
+        Ok(msg)
+    }
+
+    /// Enable NACK bitmaps once the peer has negotiated them
+    ///
+    /// See [`SyncTracker::set_nack_enabled`].
+    pub fn set_nack_enabled(&mut self, enabled: bool) {
+        self.tracker.set_nack_enabled(enabled);
+    }
+
+    /// Advertise a receive window once the peer has negotiated flow control
+    ///
+    /// See [`SyncTracker::set_recv_window`].
+    pub fn set_recv_window(&mut self, window: Option<u32>) {
+        self.tracker.set_recv_window(window);
+    }
+
+    /// Treat unacked messages as lost once the oldest is older than `rto`
+    ///
+    /// Returns `true` if they were; pending state is then resent as one
+    /// diff from the acked version, even while the peer's receive window
+    /// was full. See [`SyncTracker::on_retransmit_timeout`].
+    pub fn on_retransmit_timeout(&mut self, rto: Duration) -> bool {
+        self.tracker.on_retransmit_timeout(rto)
+    }
+
+    /// Like [`on_retransmit_timeout`](Self::on_retransmit_timeout), at `now`
+    pub fn on_retransmit_timeout_at(&mut self, now: Instant, rto: Duration) -> bool {
+        self.tracker.on_retransmit_timeout_at(now, rto)
+    }
+
+    /// Versions the peer NACKed since the last call
+    ///
+    /// Hand these to [`SyncSender::resend_versions`](super::SyncSender::resend_versions).
+    pub fn take_requested_retransmits(&mut self) -> Vec<u64> {
+        self.tracker.take_requested_retransmits()
+    }
+
+    /// Our versions the peer has, from its latest ack
+    ///
+    /// Hand these to [`SyncSender::prune_acked`](super::SyncSender::prune_acked).
+    pub fn acked_ranges(&self) -> &[(u64, u64)] {
+        self.tracker.acked_ranges()
+    }
+
     /// Generate a sync message to send to peer
     ///
-    /// Returns None if there's nothing to send
+    /// Returns None if there's nothing to send. Pending state is held back
+    /// while the peer's receive window is full (an ack may still go out).
     pub fn generate_message(&mut self) -> Result<Option<SyncMessage>, SyncError> {
-        let state = self.state.as_ref().ok_or(SyncError::NotInitialized)?;
+        let state = self.authoritative_state().ok_or(SyncError::NotInitialized)?;
 
-        // If no pending updates and no ack needed, nothing to send
-        if !self.tracker.has_pending_updates() && !self.tracker.needs_ack() {
+        // If no sendable updates and no ack needed, nothing to send
+        if !self.tracker.can_send_updates() && !self.tracker.needs_ack() {
             return Ok(None);
         }
 
         // If only need ack, send ack-only
-        if !self.tracker.has_pending_updates() {
+        if !self.tracker.can_send_updates() {
             let msg = self.tracker.create_ack();
             return Ok(Some(msg));
         }
 
-        // Compute diff from acked snapshot
-        let base_state = self.acked_snapshot.as_ref().ok_or(SyncError::NotInitialized)?;
-        let diff = (self.compute_diff)(base_state, state);
+        // Compute diff from the snapshot the peer acked
+        let (base_version, base_state) = self.history.front().ok_or(SyncError::NotInitialized)?;
+        let base_version = *base_version;
+        let mut diff = (self.compute_diff)(base_state, state);
+        if let Some(compact_diff) = self.compact_diff {
+            diff = compact_diff(&diff);
+        }
+
+        // Drop parts in regions the peer has not subscribed to, diffing
+        // against the view the peer will actually hold
+        #[cfg(feature = "extensions")]
+        let peer_view = self.filter_regions(base_state, &diff)?;
+        #[cfg(not(feature = "extensions"))]
+        let peer_view: Option<S> = None;
+        if let Some(view) = &peer_view {
+            diff = (self.compute_diff)(base_state, view);
+            if let Some(compact_diff) = self.compact_diff {
+                diff = compact_diff(&diff);
+            }
+            if (self.is_diff_empty)(&diff) {
+                // Everything pending was suppressed
+                self.tracker.record_sent(self.tracker.current_version());
+                return Ok(self.tracker.needs_ack().then(|| self.tracker.create_ack()));
+            }
+        }
+        let state = peer_view.as_ref().unwrap_or(state);
 
         // If diff is empty but we have pending updates, still send it
         // (version bump matters even without content change)
@@ -186,13 +695,97 @@ impl<S: Clone, D> SyncEngine<S, D> {
             (self.encode_diff)(&diff)
         };
 
-        let base_version = self.tracker.diff_base_version();
-        let msg = self.tracker.create_message(diff_bytes, base_version);
-        self.tracker.record_sent(self.tracker.current_version());
+        // Send the full state instead when it encodes smaller than the diff
+        let snapshot = self
+            .encode_snapshot
+            .and_then(|encode| encode(state))
+            .filter(|snapshot| snapshot.len() < diff_bytes.len());
+
+        let msg = match snapshot {
+            Some(snapshot) => self.tracker.create_message(snapshot, base_version).with_snapshot(),
+            None => self.tracker.create_message(diff_bytes, base_version),
+        };
+        let sent = (self.tracker.current_version(), state.clone());
+        self.record_snapshot(sent);
+        self.tracker.record_sent_bytes(self.tracker.current_version(), msg.diff.len());
 
         Ok(Some(msg))
     }
 
+    /// Encoded diff bytes sent but not yet acked by the peer
+    ///
+    /// Every message carrying state counts until the peer acks its
+    /// version (or a later one), so a congestion controller can cap new
+    /// sends against its window.
+    pub fn bytes_in_flight(&self) -> usize {
+        self.tracker.bytes_in_flight()
+    }
+
+    /// Generate sync messages that each fit within `max_payload` bytes
+    ///
+    /// Like [`generate_message`](Self::generate_message), but an encoded diff
+    /// too large for one frame is split into ordered fragments. Only the last
+    /// fragment carries the acknowledgment; the receiver applies nothing
+    /// until all fragments arrive.
+    ///
+    /// `max_payload` is the space for the sync message in one frame, i.e.
+    /// the frame payload budget minus the payload header.
+    pub fn generate_messages(&mut self, max_payload: usize) -> Result<Vec<SyncMessage>, SyncError> {
+        let Some(msg) = self.generate_message()? else {
+            return Ok(Vec::new());
+        };
+
+        let preamble_size = if self.preamble { SYNC_PREAMBLE_SIZE } else { 0 };
+        if preamble_size + msg.wire_size() <= max_payload {
+            return Ok(vec![msg]);
+        }
+
+        let nack_size = if msg.nack.is_some() { NACK_BITMAP_SIZE } else { 0 };
+        let window_size = if msg.recv_window.is_some() { RECV_WINDOW_SIZE } else { 0 };
+        let overhead = preamble_size
+            + SYNC_MESSAGE_HEADER_SIZE
+            + FRAGMENT_HEADER_SIZE
+            + nack_size
+            + window_size;
+        let chunk_size = max_payload.saturating_sub(overhead);
+        if chunk_size == 0 {
+            return Err(MessageError::BufferTooSmall {
+                required: overhead + 1,
+                available: max_payload,
+            }
+            .into());
+        }
+
+        let count = msg.diff.len().div_ceil(chunk_size);
+        let count = u16::try_from(count).map_err(|_| {
+            MessageError::InvalidFormat(format!("diff needs {} fragments (max {})", count, u16::MAX))
+        })?;
+
+        let fragments = msg
+            .diff
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let index = index as u16;
+                let last = index + 1 == count;
+                let acked = if last { msg.acked_state_num } else { 0 };
+                let nack = if last { msg.nack.unwrap_or(0) } else { 0 };
+                let mut fragment =
+                    SyncMessage::new(msg.sender_state_num, acked, msg.base_state_num, chunk.to_vec())
+                        .with_fragment(index, count)
+                        .with_nack(nack);
+                fragment.recv_window = msg.recv_window;
+                if msg.snapshot {
+                    fragment.with_snapshot()
+                } else {
+                    fragment
+                }
+            })
+            .collect();
+
+        Ok(fragments)
+    }
+
     /// Generate an ack-only message
     pub fn generate_ack(&self) -> Result<SyncMessage, SyncError> {
         if !self.is_initialized() {
@@ -201,13 +794,70 @@ impl<S: Clone, D> SyncEngine<S, D> {
         Ok(self.tracker.create_ack())
     }
 
+    /// Register an observer for applied peer updates
+    ///
+    /// Called after [`process_message`](Self::process_message) applies new
+    /// peer state (a diff, a snapshot, or a completed fragmented diff), with
+    /// the resulting state and the peer version it reflects. Not called for
+    /// duplicates, ack-only messages, or buffered fragments.
+    pub fn set_on_applied(&mut self, on_applied: impl Fn(&S, u64) + Send + Sync + 'static) {
+        self.on_applied = Some(Box::new(on_applied));
+    }
+
     /// Process an incoming sync message
     ///
     /// Returns the result of processing
     pub fn process_message(&mut self, msg: &SyncMessage) -> Result<ProcessResult, SyncError> {
-        let state = self.state.as_mut().ok_or(SyncError::NotInitialized)?;
+        let result = self.process_unobserved(msg)?;
+        if result == ProcessResult::Updated
+            && let (Some(on_applied), Some(state)) = (&self.on_applied, &self.state)
+        {
+            on_applied(state, self.tracker.peer_version());
+        }
+        Ok(result)
+    }
+
+    /// Process an incoming sync message without notifying the observer
+    fn process_unobserved(&mut self, msg: &SyncMessage) -> Result<ProcessResult, SyncError> {
+        if self.state.is_none() {
+            return Err(SyncError::NotInitialized);
+        }
 
-        // Update tracker first (this handles ack fields)
+        // Hold fragments back until the whole diff is available
+        if msg.fragment.is_some() {
+            return match self.assembler.push(msg.clone())? {
+                Some(complete) => self.process_unobserved(&complete),
+                None => Ok(ProcessResult::Fragment),
+            };
+        }
+
+        if self.authoritative.is_none() {
+            return self.process_authoritative(msg);
+        }
+
+        // Apply the update to the authoritative state, then replay the
+        // predictions the peer has not confirmed yet
+        self.roll_back_predictions();
+        let result = self.process_authoritative(msg);
+        let acked = self.tracker.last_acked_version();
+        self.predictions.retain(|(version, _)| *version > acked);
+        self.replay_predictions();
+        result
+    }
+
+    /// Process a complete message against the authoritative state
+    fn process_authoritative(&mut self, msg: &SyncMessage) -> Result<ProcessResult, SyncError> {
+        if self.state.is_none() {
+            return Err(SyncError::NotInitialized);
+        }
+
+        // The peer hasn't seen our current version: its diff is concurrent
+        let concurrent = msg.acked_state_num < self.tracker.current_version();
+
+        // Update tracker first (this handles ack fields), keeping the old
+        // one in case the update is rejected: a version we did not apply
+        // must be neither acked nor treated as a duplicate when resent
+        let previous_tracker = (!msg.is_ack_only()).then(|| self.tracker.clone());
         let is_new = self.tracker.process_incoming(msg);
 
         if msg.is_ack_only() {
@@ -222,12 +872,11 @@ impl<S: Clone, D> SyncEngine<S, D> {
             return Ok(ProcessResult::Duplicate);
         }
 
-        // Decode and apply diff
-        if !msg.diff.is_empty() {
-            let diff = (self.decode_diff)(&msg.diff)
-                .map_err(SyncError::DiffDecode)?;
-            (self.apply_diff)(state, &diff)
-                .map_err(SyncError::DiffApply)?;
+        if let Err(e) = self.apply_incoming(msg, concurrent) {
+            if let Some(previous) = previous_tracker {
+                self.tracker = previous;
+            }
+            return Err(e);
         }
 
         // Update acked snapshot if peer acked new version
@@ -238,16 +887,112 @@ impl<S: Clone, D> SyncEngine<S, D> {
         Ok(ProcessResult::Updated)
     }
 
-    /// Update the acked snapshot to current state
-    fn update_acked_snapshot(&mut self) {
-        if let Some(state) = &self.state {
-            // Only update if we have a valid ack
-            if self.tracker.last_acked_version() > 0 {
-                // For simplicity, snapshot current state
-                // In practice, might want versioned history
-                self.acked_snapshot = Some(state.clone());
+    /// Apply the state carried by a new message
+    ///
+    /// On a validation failure the state is restored to what it was before.
+    fn apply_incoming(&mut self, msg: &SyncMessage, concurrent: bool) -> Result<(), SyncError> {
+        let state = self.state.as_mut().ok_or(SyncError::NotInitialized)?;
+
+        // Keep the pre-update state to restore if validation fails
+        let previous = self.validate.map(|_| state.clone());
+
+        if msg.snapshot {
+            // Absolute state: replace ours, or merge it if concurrent
+            let decode_snapshot = self.decode_snapshot.ok_or_else(|| {
+                SyncError::DiffDecode("received snapshot but snapshots are not enabled".to_string())
+            })?;
+            let snapshot = decode_snapshot(&msg.diff).map_err(SyncError::DiffDecode)?;
+            match self.merge {
+                Some(merge) if concurrent => {
+                    merge(state, &snapshot).map_err(SyncError::DiffApply)?;
+                }
+                _ => *state = snapshot,
+            }
+        } else if !msg.diff.is_empty() {
+            // Decode and apply diff
+            let diff = (self.decode_diff)(&msg.diff)
+                .map_err(SyncError::DiffDecode)?;
+            match (self.merge, self.history.front()) {
+                (Some(merge), Some((_, base))) if concurrent => {
+                    let mut peer_view = base.clone();
+                    (self.apply_diff)(&mut peer_view, &diff)
+                        .map_err(SyncError::DiffApply)?;
+                    merge(state, &peer_view).map_err(SyncError::DiffApply)?;
+                }
+                _ => {
+                    (self.apply_diff)(state, &diff)
+                        .map_err(SyncError::DiffApply)?;
+                }
             }
         }
+
+        if let (Some(validate), Some(previous)) = (self.validate, previous)
+            && let Err(e) = validate(state)
+        {
+            *state = previous;
+            return Err(SyncError::DiffApply(format!("invalid state: {}", e)));
+        }
+
+        Ok(())
+    }
+
+    /// Restrict a diff to the regions the peer subscribes to
+    ///
+    /// Returns `None` when no filter is set or every part is visible;
+    /// otherwise the state the peer will hold: `base` plus the visible
+    /// parts only.
+    #[cfg(feature = "extensions")]
+    fn filter_regions(&self, base: &S, diff: &D) -> Result<Option<S>, SyncError> {
+        let (Some((split, region_of)), Some(subscriptions)) =
+            (self.regions, self.subscriptions.as_ref())
+        else {
+            return Ok(None);
+        };
+
+        let visible =
+            |part: &D| region_of(part).is_none_or(|path| subscriptions.matches_pattern(&path));
+        let parts = split(diff);
+        if parts.iter().all(visible) {
+            return Ok(None);
+        }
+
+        let mut view = base.clone();
+        for part in parts.iter().filter(|part| visible(part)) {
+            (self.apply_diff)(&mut view, part).map_err(SyncError::DiffApply)?;
+        }
+        Ok(Some(view))
+    }
+
+    /// Remember the state sent as `version` until the peer acks it
+    fn record_snapshot(&mut self, (version, state): (u64, S)) {
+        match self.history.back_mut() {
+            Some(last) if last.0 == version => last.1 = state,
+            _ => self.history.push_back((version, state)),
+        }
+
+        // Over capacity: drop the oldest outstanding snapshot, never the
+        // acked base at the front
+        if self.history.len() > MAX_SNAPSHOT_HISTORY {
+            self.history.remove(1);
+        }
+    }
+
+    /// Advance the diff base to the snapshot the peer last acked
+    ///
+    /// Snapshots older than the acked version are evicted. If the acked
+    /// version itself was evicted, the newest older snapshot is kept as the
+    /// base, so diffs stay correct (if larger) at the cost of resending
+    /// some already-acked changes.
+    fn update_acked_snapshot(&mut self) {
+        let acked = self.tracker.last_acked_version();
+        while self.history.len() > 1 && self.history[1].0 <= acked {
+            self.history.pop_front();
+        }
+    }
+
+    /// Version of the snapshot outgoing diffs are computed from
+    pub fn diff_base_version(&self) -> u64 {
+        self.history.front().map_or(0, |(version, _)| *version)
     }
 
     /// Get current local version
@@ -269,7 +1014,10 @@ impl<S: Clone, D> SyncEngine<S, D> {
     pub fn reset(&mut self) {
         self.tracker.reset();
         self.state = None;
-        self.acked_snapshot = None;
+        self.history.clear();
+        self.assembler.reset();
+        self.authoritative = None;
+        self.predictions.clear();
     }
 }
 
@@ -320,6 +1068,130 @@ mod tests {
         SyncEngine::new(encode_diff, decode_diff, compute_diff, apply_diff, is_diff_empty)
     }
 
+    #[test]
+    fn test_mispredicted_diff_corrected_by_authoritative_update() {
+        let mut client = create_engine();
+        client.init(TestState { value: 0 });
+
+        // Input goes out as version 1; we guess it adds 1
+        client.mark_changed();
+        client.apply_predicted(TestDiff { delta: 1 }).unwrap();
+        assert_eq!(client.state().unwrap().value, 1);
+        assert_eq!(client.authoritative_state().unwrap().value, 0);
+
+        // The prediction itself is never sent to the peer
+        let outgoing = client.generate_message().unwrap().unwrap();
+        assert!(outgoing.diff.is_empty());
+
+        // Server saw version 1, and the real effect was +2
+        let authoritative = SyncMessage::new(1, 1, 0, encode_diff(&TestDiff { delta: 2 }));
+        assert_eq!(client.process_message(&authoritative).unwrap(), ProcessResult::Updated);
+        assert_eq!(client.state().unwrap().value, 2);
+        assert_eq!(client.pending_predictions(), 0);
+        assert_eq!(client.authoritative_state().unwrap().value, 2);
+    }
+
+    #[test]
+    fn test_unconfirmed_predictions_replayed_over_server_state() {
+        fn rollback(state: &mut TestState, to: &TestState) {
+            state.value = to.value;
+        }
+
+        let mut client = create_engine().with_rollback(rollback);
+        client.init(TestState { value: 0 });
+
+        client.mark_changed();
+        client.apply_predicted(TestDiff { delta: 1 }).unwrap();
+        client.mark_changed();
+        client.apply_predicted(TestDiff { delta: 10 }).unwrap();
+        assert_eq!(client.state().unwrap().value, 11);
+
+        // Server has only seen the first input, which actually added 2
+        let first = SyncMessage::new(1, 1, 0, encode_diff(&TestDiff { delta: 2 }));
+        client.process_message(&first).unwrap();
+        assert_eq!(client.authoritative_state().unwrap().value, 2);
+        assert_eq!(client.state().unwrap().value, 12);
+        assert_eq!(client.pending_predictions(), 1);
+
+        // An ack-only message confirming nothing new keeps the prediction
+        client.process_message(&SyncMessage::ack_only(1, 1)).unwrap();
+        assert_eq!(client.state().unwrap().value, 12);
+
+        // Second input confirmed as predicted
+        let second = SyncMessage::new(2, 2, 1, encode_diff(&TestDiff { delta: 10 }));
+        client.process_message(&second).unwrap();
+        assert_eq!(client.state().unwrap().value, 12);
+        assert_eq!(client.pending_predictions(), 0);
+    }
+
+    #[test]
+    fn test_persist_restore_resumes_incremental_diffs() {
+        fn encode_state(state: &TestState) -> Vec<u8> {
+            state.value.to_le_bytes().to_vec()
+        }
+        fn decode_state(data: &[u8]) -> Result<TestState, String> {
+            let bytes = data.try_into().map_err(|_| "invalid state length".to_string())?;
+            Ok(TestState {
+                value: i32::from_le_bytes(bytes),
+            })
+        }
+
+        let mut server = create_engine();
+        let mut client = create_engine();
+        server.init(TestState { value: 0 });
+        client.init(TestState { value: 0 });
+
+        // Version 1 is delivered and acked
+        server.update_state(TestState { value: 5 });
+        let msg = server.generate_message().unwrap().unwrap();
+        client.process_message(&msg).unwrap();
+        server.process_message(&client.generate_ack().unwrap()).unwrap();
+
+        // Version 2 is sent but lost, then the server restarts
+        server.update_state(TestState { value: 8 });
+        server.generate_message().unwrap().unwrap();
+        let bytes = server.persist().unwrap().encode(encode_state);
+        drop(server);
+
+        let persisted = EnginePersistState::decode(&bytes, decode_state).unwrap();
+        assert_eq!(persisted.current_version, 2);
+        assert_eq!(persisted.last_acked_version, 1);
+        let mut restored = create_engine();
+        restored.restore(persisted);
+        assert_eq!(restored.current_version(), 2);
+        assert_eq!(restored.diff_base_version(), 1);
+        assert!(restored.has_pending_updates());
+
+        // The next diff builds on the acked snapshot and versions keep rising
+        assert_eq!(restored.update_state(TestState { value: 10 }), 3);
+        let msg = restored.generate_message().unwrap().unwrap();
+        assert_eq!(msg.base_state_num, 1);
+        assert_eq!(decode_diff(&msg.diff).unwrap(), TestDiff { delta: 5 });
+        assert_eq!(client.process_message(&msg).unwrap(), ProcessResult::Updated);
+        assert_eq!(client.state().unwrap().value, 10);
+
+        assert!(matches!(
+            EnginePersistState::decode(&bytes[..20], decode_state),
+            Err(SyncError::PersistDecode(_))
+        ));
+    }
+
+    #[test]
+    fn test_init_at_peer_version() {
+        let mut client = create_engine();
+        client.init_at_peer_version(TestState { value: 40 }, 7);
+        assert_eq!(client.peer_version(), 7);
+        assert_eq!(client.generate_ack().unwrap().acked_state_num, 7);
+
+        // Already covered by the restored state
+        let stale = SyncMessage::new(7, 0, 6, encode_diff(&TestDiff { delta: 1 }));
+        assert_eq!(client.process_message(&stale).unwrap(), ProcessResult::Duplicate);
+
+        let next = SyncMessage::new(8, 0, 7, encode_diff(&TestDiff { delta: 2 }));
+        assert_eq!(client.process_message(&next).unwrap(), ProcessResult::Updated);
+        assert_eq!(client.state().unwrap().value, 42);
+    }
+
     #[test]
     fn test_init() {
         let mut engine = create_engine();
@@ -468,4 +1340,775 @@ mod tests {
         assert!(engine.state().is_none());
         assert_eq!(engine.current_version(), 0);
     }
+
+    // Multi-field state whose naive diff lists every field
+    #[derive(Debug, Clone, PartialEq)]
+    struct MultiState {
+        fields: [i32; 4],
+    }
+
+    // (field index, old value, new value) for each field
+    #[derive(Debug, Clone, PartialEq)]
+    struct MultiDiff {
+        changes: Vec<(u8, i32, i32)>,
+    }
+
+    fn multi_encode(diff: &MultiDiff) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for (index, _, new) in &diff.changes {
+            buf.push(*index);
+            buf.extend_from_slice(&new.to_le_bytes());
+        }
+        buf
+    }
+
+    fn multi_decode(data: &[u8]) -> Result<MultiDiff, String> {
+        let changes = data
+            .chunks_exact(5)
+            .map(|c| (c[0], 0, i32::from_le_bytes(c[1..5].try_into().unwrap())))
+            .collect();
+        Ok(MultiDiff { changes })
+    }
+
+    fn multi_compute(old: &MultiState, new: &MultiState) -> MultiDiff {
+        let changes = (0..4)
+            .map(|i| (i as u8, old.fields[i], new.fields[i]))
+            .collect();
+        MultiDiff { changes }
+    }
+
+    fn multi_apply(state: &mut MultiState, diff: &MultiDiff) -> Result<(), String> {
+        for (index, _, new) in &diff.changes {
+            state.fields[*index as usize] = *new;
+        }
+        Ok(())
+    }
+
+    fn multi_is_empty(diff: &MultiDiff) -> bool {
+        diff.changes.is_empty()
+    }
+
+    fn multi_compact(diff: &MultiDiff) -> MultiDiff {
+        let changes = diff
+            .changes
+            .iter()
+            .copied()
+            .filter(|(_, old, new)| old != new)
+            .collect();
+        MultiDiff { changes }
+    }
+
+    #[test]
+    fn test_compact_diff_drops_unchanged_fields() {
+        let new_engine = || {
+            SyncEngine::new(multi_encode, multi_decode, multi_compute, multi_apply, multi_is_empty)
+        };
+        let mut raw = new_engine();
+        let mut compacted = new_engine().with_compact_diff(multi_compact);
+
+        for engine in [&mut raw, &mut compacted] {
+            engine.init(MultiState { fields: [1, 2, 3, 4] });
+            engine.update_state(MultiState { fields: [1, 20, 3, 4] });
+        }
+
+        let raw_msg = raw.generate_message().unwrap().unwrap();
+        let compacted_msg = compacted.generate_message().unwrap().unwrap();
+        assert_eq!(raw_msg.diff.len(), 4 * 5);
+        assert_eq!(compacted_msg.diff.len(), 5);
+
+        // Compacted diff still produces the same state on the peer
+        let mut peer = new_engine();
+        peer.init(MultiState { fields: [1, 2, 3, 4] });
+        peer.process_message(&compacted_msg).unwrap();
+        assert_eq!(peer.state().unwrap().fields, [1, 20, 3, 4]);
+    }
+
+    #[cfg(feature = "extensions")]
+    fn multi_split(diff: &MultiDiff) -> Vec<MultiDiff> {
+        diff.changes
+            .iter()
+            .map(|&change| MultiDiff { changes: vec![change] })
+            .collect()
+    }
+
+    // Each field is its own region, "fields/<index>"
+    #[cfg(feature = "extensions")]
+    fn multi_region_of(diff: &MultiDiff) -> Option<String> {
+        match diff.changes.as_slice() {
+            [(index, _, _)] => Some(format!("fields/{}", index)),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "extensions")]
+    #[test]
+    fn test_diffs_filtered_by_subscribed_region() {
+        use crate::extensions::SubscriptionUpdate;
+
+        let new_engine = || {
+            let mut engine =
+                SyncEngine::new(multi_encode, multi_decode, multi_compute, multi_apply, multi_is_empty)
+                    .with_compact_diff(multi_compact)
+                    .with_regions(multi_split, multi_region_of);
+            engine.init(MultiState { fields: [0; 4] });
+            engine
+        };
+        let subscribe = |engine: &mut SyncEngine<MultiState, MultiDiff>, patterns: &[&str]| {
+            let mut subscriptions = SubscriptionState::new();
+            for pattern in patterns {
+                subscriptions
+                    .apply(&SubscriptionUpdate::Subscribe(pattern.to_string()))
+                    .unwrap();
+            }
+            engine.set_subscriptions(subscriptions);
+        };
+
+        // One server-side engine per client, subscribed to disjoint regions
+        let (mut to_a, mut to_b) = (new_engine(), new_engine());
+        subscribe(&mut to_a, &["fields/0"]);
+        subscribe(&mut to_b, &["fields/1"]);
+        let (mut client_a, mut client_b) = (new_engine(), new_engine());
+
+        // A single change touching both regions
+        for server in [&mut to_a, &mut to_b] {
+            server.update_state(MultiState { fields: [10, 20, 0, 0] });
+        }
+
+        let msg = to_a.generate_message().unwrap().unwrap();
+        client_a.process_message(&msg).unwrap();
+        assert_eq!(client_a.state().unwrap().fields, [10, 0, 0, 0]);
+
+        let msg = to_b.generate_message().unwrap().unwrap();
+        client_b.process_message(&msg).unwrap();
+        assert_eq!(client_b.state().unwrap().fields, [0, 20, 0, 0]);
+
+        // Acks advance each client's snapshot to its filtered view
+        to_a.process_message(&client_a.generate_message().unwrap().unwrap())
+            .unwrap();
+        to_b.process_message(&client_b.generate_message().unwrap().unwrap())
+            .unwrap();
+        assert!(to_a.generate_message().unwrap().is_none());
+
+        // A change only in region 1 is suppressed for client A
+        for server in [&mut to_a, &mut to_b] {
+            server.update_state(MultiState { fields: [10, 21, 0, 0] });
+        }
+        assert!(to_a.generate_message().unwrap().is_none());
+        assert!(!to_a.has_pending_updates());
+        let msg = to_b.generate_message().unwrap().unwrap();
+        client_b.process_message(&msg).unwrap();
+        assert_eq!(client_b.state().unwrap().fields, [0, 21, 0, 0]);
+
+        // Region 1 was never marked sent to A, so subscribing delivers it
+        subscribe(&mut to_a, &["fields/0", "fields/1"]);
+        let msg = to_a.generate_message().unwrap().unwrap();
+        client_a.process_message(&msg).unwrap();
+        assert_eq!(client_a.state().unwrap().fields, [10, 21, 0, 0]);
+    }
+
+    // Opaque blob state whose diff is the full new contents
+    fn blob_engine() -> SyncEngine<Vec<u8>, Vec<u8>> {
+        SyncEngine::new(
+            |diff: &Vec<u8>| diff.clone(),
+            |data: &[u8]| Ok(data.to_vec()),
+            |_old: &Vec<u8>, new: &Vec<u8>| new.clone(),
+            |state: &mut Vec<u8>, diff: &Vec<u8>| {
+                state.clone_from(diff);
+                Ok(())
+            },
+            |diff: &Vec<u8>| diff.is_empty(),
+        )
+    }
+
+    #[test]
+    fn test_generate_messages_fragments_large_diff() {
+        let blob: Vec<u8> = (0..4600).map(|i| i as u8).collect();
+
+        let mut sender = blob_engine();
+        sender.init(Vec::new());
+        sender.update_state(blob.clone());
+
+        let messages = sender.generate_messages(1200).unwrap();
+        assert_eq!(messages.len(), 4);
+        assert!(messages.iter().all(|m| m.wire_size() <= 1200));
+        for (i, msg) in messages.iter().enumerate() {
+            let fragment = msg.fragment.unwrap();
+            assert_eq!(fragment.index as usize, i);
+            assert_eq!(fragment.count, 4);
+            assert_eq!(msg.sender_state_num, 1);
+        }
+
+        let mut receiver = blob_engine();
+        receiver.init(Vec::new());
+        for msg in &messages[..3] {
+            let encoded = msg.encode();
+            let decoded = SyncMessage::decode(&encoded).unwrap();
+            assert_eq!(receiver.process_message(&decoded).unwrap(), ProcessResult::Fragment);
+            assert_eq!(receiver.peer_version(), 0);
+        }
+        assert_eq!(
+            receiver.process_message(&messages[3]).unwrap(),
+            ProcessResult::Updated
+        );
+        assert_eq!(receiver.state().unwrap(), &blob);
+        assert_eq!(receiver.peer_version(), 1);
+    }
+
+    #[test]
+    fn test_on_applied_fires_once_per_applied_diff() {
+        use std::sync::{Arc, Mutex};
+
+        let mut sender = blob_engine();
+        sender.init(Vec::new());
+        let mut receiver = blob_engine();
+        receiver.init(Vec::new());
+
+        let applied = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&applied);
+        receiver.set_on_applied(move |state: &Vec<u8>, version| {
+            seen.lock().unwrap().push((state.clone(), version));
+        });
+
+        sender.update_state(vec![1]);
+        let first = sender.generate_message().unwrap().unwrap();
+        sender.update_state(vec![2]);
+        let second = sender.generate_message().unwrap().unwrap();
+
+        assert_eq!(receiver.process_message(&first).unwrap(), ProcessResult::Updated);
+        // Duplicates and ack-only messages are not observed
+        assert_eq!(receiver.process_message(&first).unwrap(), ProcessResult::Duplicate);
+        let ack = SyncMessage::ack_only(1, 0);
+        assert_eq!(receiver.process_message(&ack).unwrap(), ProcessResult::AckOnly);
+        assert_eq!(receiver.process_message(&second).unwrap(), ProcessResult::Updated);
+
+        // A fragmented diff is observed once, when it completes
+        sender.update_state(vec![0xAB; 3000]);
+        let fragments = sender.generate_messages(1200).unwrap();
+        assert!(fragments.len() > 1);
+        for fragment in &fragments {
+            receiver.process_message(fragment).unwrap();
+        }
+
+        let applied = applied.lock().unwrap();
+        assert_eq!(
+            *applied,
+            vec![(vec![1], 1), (vec![2], 2), (vec![0xAB; 3000], 3)]
+        );
+    }
+
+    #[test]
+    fn test_recv_window_stalls_sender_until_ack() {
+        let mut sender = blob_engine();
+        sender.init(Vec::new());
+        let mut receiver = blob_engine();
+        receiver.init(Vec::new());
+        receiver.set_recv_window(Some(2));
+
+        // The receiver advertises its window in an ack
+        let ack = receiver.generate_ack().unwrap();
+        assert_eq!(ack.recv_window, Some(2));
+        sender.process_message(&ack).unwrap();
+        assert_eq!(sender.tracker().peer_recv_window(), Some(2));
+
+        let mut in_flight = Vec::new();
+        for version in 1..=2u8 {
+            sender.update_state(vec![version]);
+            in_flight.push(sender.generate_message().unwrap().unwrap());
+        }
+
+        // Two versions unacked: further state waits
+        sender.update_state(vec![3]);
+        assert!(sender.has_pending_updates());
+        assert_eq!(sender.generate_message().unwrap(), None);
+
+        for msg in &in_flight {
+            receiver.process_message(msg).unwrap();
+        }
+        let ack = receiver.generate_ack().unwrap();
+        assert_eq!(ack.acked_state_num, 2);
+        sender.process_message(&ack).unwrap();
+
+        let msg = sender.generate_message().unwrap().unwrap();
+        assert_eq!(msg.sender_state_num, 3);
+        receiver.process_message(&msg).unwrap();
+        assert_eq!(receiver.state().unwrap(), &vec![3]);
+    }
+
+    #[test]
+    fn test_recv_window_reopens_after_lost_messages() {
+        let rto = Duration::from_secs(1);
+        let mut sender = blob_engine();
+        sender.init(Vec::new());
+        let mut receiver = blob_engine();
+        receiver.init(Vec::new());
+        receiver.set_recv_window(Some(1));
+        sender.process_message(&receiver.generate_ack().unwrap()).unwrap();
+
+        // The only in-flight message is lost
+        sender.update_state(vec![1]);
+        let lost = sender.generate_message().unwrap().unwrap();
+        assert_eq!(lost.sender_state_num, 1);
+        sender.update_state(vec![2]);
+        assert_eq!(sender.generate_message().unwrap(), None);
+
+        // No ack will ever come; the retransmit timeout reopens the window
+        assert!(sender.on_retransmit_timeout_at(Instant::now() + rto, rto));
+        let msg = sender.generate_message().unwrap().unwrap();
+        assert_eq!(msg.sender_state_num, 2);
+        assert_eq!(msg.base_state_num, 0);
+        receiver.process_message(&msg).unwrap();
+        assert_eq!(receiver.state().unwrap(), &vec![2]);
+
+        sender.process_message(&receiver.generate_ack().unwrap()).unwrap();
+        assert!(sender.is_synchronized());
+    }
+
+    #[test]
+    fn test_nack_resend_converges_after_loss() {
+        use crate::sync::{SyncSender, SyncTracker};
+
+        let mut receiver = create_engine();
+        receiver.init(TestState { value: 0 });
+        receiver.set_nack_enabled(true);
+
+        // Each version adds one, diffed from the version before; 3 and 4
+        // are lost in transit
+        let mut sender = SyncSender::new();
+        let mut tracker = SyncTracker::with_initial_version(5);
+        tracker.record_sent(5);
+        for version in 1..=5 {
+            sender.queue_message(SyncMessage::new(version, 0, version - 1, encode_diff(&TestDiff { delta: 1 })));
+            let msg = sender.take_message().unwrap();
+            if !matches!(version, 3 | 4) {
+                assert_eq!(receiver.process_message(&msg).unwrap(), ProcessResult::Updated);
+            }
+        }
+        assert_eq!(receiver.state().unwrap().value, 3);
+
+        // The ack NACKs the gap and the sender resends exactly those versions
+        tracker.process_incoming(&receiver.generate_ack().unwrap());
+        let requested = tracker.take_requested_retransmits();
+        assert_eq!(requested, vec![4, 3]);
+        assert_eq!(sender.prune_acked(tracker.acked_ranges()), 3);
+        assert_eq!(sender.resend_versions(&requested), 2);
+
+        // The resends are applied rather than dropped as duplicates
+        while let Some(resend) = sender.take_message() {
+            assert_eq!(receiver.process_message(&resend).unwrap(), ProcessResult::Updated);
+        }
+        assert_eq!(receiver.state().unwrap().value, 5);
+
+        // The gap is closed: a second copy is a duplicate, and the ack
+        // covers everything
+        let late = SyncMessage::new(3, 0, 2, encode_diff(&TestDiff { delta: 1 }));
+        assert_eq!(receiver.process_message(&late).unwrap(), ProcessResult::Duplicate);
+        let ack = receiver.generate_ack().unwrap();
+        assert_eq!(ack.nack, None);
+        tracker.process_incoming(&ack);
+        assert_eq!(tracker.acked_ranges(), &[(1, 5)]);
+        assert!(tracker.take_requested_retransmits().is_empty());
+    }
+
+    #[test]
+    fn test_nack_rides_on_last_fragment() {
+        // Peer sends versions 1..5, each diffed from the one before; 3 and
+        // 4 never arrive
+        let mut peer = blob_engine();
+        peer.init(Vec::new());
+        let mut sent = Vec::new();
+        for version in 1..=5u8 {
+            peer.update_state(vec![version]);
+            let mut msg = peer.generate_message().unwrap().unwrap();
+            msg.base_state_num = u64::from(version) - 1;
+            sent.push(msg);
+        }
+
+        let mut engine = blob_engine();
+        engine.init(Vec::new());
+        engine.set_nack_enabled(true);
+        for msg in sent.iter().filter(|m| !matches!(m.sender_state_num, 3 | 4)) {
+            engine.process_message(msg).unwrap();
+        }
+
+        engine.update_state(vec![0xCD; 4600]);
+        let messages = engine.generate_messages(1200).unwrap();
+        assert!(messages.len() > 1);
+        assert!(messages.iter().all(|m| m.wire_size() <= 1200));
+        let (last, rest) = messages.split_last().unwrap();
+        assert!(rest.iter().all(|m| m.nack.is_none()));
+        assert_eq!(last.nack, Some(0b11));
+
+        for msg in &messages {
+            peer.process_message(&SyncMessage::decode(&msg.encode()).unwrap()).unwrap();
+        }
+        assert_eq!(peer.take_requested_retransmits(), vec![4, 3]);
+    }
+
+    #[test]
+    fn test_preamble_fragments_fit_budget() {
+        let mut sender = blob_engine();
+        sender.init(Vec::new());
+        sender.set_preamble_enabled(true);
+        sender.update_state(vec![0xEF; 4600]);
+        let messages = sender.generate_messages(1200).unwrap();
+        assert!(messages.len() > 1);
+
+        let mut receiver = blob_engine();
+        receiver.init(Vec::new());
+        receiver.set_preamble_enabled(true);
+        for msg in &messages {
+            let encoded = sender.encode_message(msg);
+            assert!(encoded.len() <= 1200);
+            receiver.process_message(&receiver.decode_message(&encoded).unwrap()).unwrap();
+        }
+        assert_eq!(receiver.state().unwrap(), &vec![0xEF; 4600]);
+
+        // A peer without the preamble is rejected rather than misread
+        assert!(matches!(
+            receiver.decode_message(&messages[0].encode()),
+            Err(SyncError::Message(MessageError::BadMagic(_)))
+        ));
+    }
+
+    #[test]
+    fn test_dropped_fragment_leaves_pre_diff_state() {
+        let mut sender = blob_engine();
+        sender.init(vec![1, 2, 3]);
+        sender.update_state(vec![0xAB; 4600]);
+        let messages = sender.generate_messages(1200).unwrap();
+
+        let mut receiver = blob_engine();
+        receiver.init(vec![1, 2, 3]);
+        for (i, msg) in messages.iter().enumerate() {
+            if i == 1 {
+                continue; // Dropped in transit
+            }
+            assert_eq!(receiver.process_message(msg).unwrap(), ProcessResult::Fragment);
+        }
+
+        assert_eq!(receiver.state().unwrap(), &vec![1, 2, 3]);
+        assert_eq!(receiver.peer_version(), 0);
+        assert!(!receiver.needs_ack());
+    }
+
+    #[test]
+    fn test_generate_messages_small_diff_unfragmented() {
+        let mut engine = create_engine();
+        engine.init(TestState { value: 0 });
+        engine.update_state(TestState { value: 5 });
+
+        let messages = engine.generate_messages(1200).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].fragment.is_none());
+    }
+
+    #[test]
+    fn test_invalid_update_rolled_back() {
+        fn non_negative(state: &TestState) -> Result<(), String> {
+            if state.value < 0 {
+                return Err(format!("negative value {}", state.value));
+            }
+            Ok(())
+        }
+
+        let mut engine = create_engine().with_validate(non_negative);
+        engine.init(TestState { value: 5 });
+
+        // Applies cleanly but leaves the counter negative
+        let bad = SyncMessage::new(1, 0, 0, encode_diff(&TestDiff { delta: -10 }));
+        assert!(matches!(engine.process_message(&bad), Err(SyncError::DiffApply(_))));
+        assert_eq!(engine.state().unwrap().value, 5);
+
+        // The rejected version is neither acked nor a duplicate when resent
+        assert_eq!(engine.peer_version(), 0);
+        assert!(!engine.needs_ack());
+        assert_eq!(engine.generate_message().unwrap(), None);
+        assert!(matches!(engine.process_message(&bad), Err(SyncError::DiffApply(_))));
+
+        let good = SyncMessage::new(2, 0, 0, encode_diff(&TestDiff { delta: 1 }));
+        assert_eq!(engine.process_message(&good).unwrap(), ProcessResult::Updated);
+        assert_eq!(engine.state().unwrap().value, 6);
+    }
+
+    /// Grow-only counter CRDT: one slot per replica, merged by max.
+    #[derive(Debug, Clone, PartialEq, Default)]
+    struct GCounter {
+        counts: [u64; 2],
+    }
+
+    impl GCounter {
+        fn total(&self) -> u64 {
+            self.counts.iter().sum()
+        }
+    }
+
+    // Full-state diffs: applying one replaces every slot
+    fn gc_encode(diff: &GCounter) -> Vec<u8> {
+        diff.counts.iter().flat_map(|c| c.to_le_bytes()).collect()
+    }
+
+    fn gc_decode(data: &[u8]) -> Result<GCounter, String> {
+        if data.len() != 16 {
+            return Err("invalid counter length".to_string());
+        }
+        Ok(GCounter {
+            counts: [
+                u64::from_le_bytes(data[..8].try_into().unwrap()),
+                u64::from_le_bytes(data[8..].try_into().unwrap()),
+            ],
+        })
+    }
+
+    fn gc_compute(_old: &GCounter, new: &GCounter) -> GCounter {
+        new.clone()
+    }
+
+    fn gc_apply(state: &mut GCounter, diff: &GCounter) -> Result<(), String> {
+        *state = diff.clone();
+        Ok(())
+    }
+
+    fn gc_merge(state: &mut GCounter, other: &GCounter) -> Result<(), String> {
+        for (mine, theirs) in state.counts.iter_mut().zip(other.counts) {
+            *mine = (*mine).max(theirs);
+        }
+        Ok(())
+    }
+
+    fn gc_engine(merge: bool) -> SyncEngine<GCounter, GCounter> {
+        let engine = SyncEngine::new(gc_encode, gc_decode, gc_compute, gc_apply, |_| false);
+        let mut engine = if merge { engine.with_merge(gc_merge) } else { engine };
+        engine.init(GCounter::default());
+        engine
+    }
+
+    /// Both replicas increment their own slot, then exchange messages.
+    fn concurrent_increments(merge: bool) -> (GCounter, GCounter) {
+        let mut a = gc_engine(merge);
+        let mut b = gc_engine(merge);
+
+        a.update_state(GCounter { counts: [1, 0] });
+        b.update_state(GCounter { counts: [0, 1] });
+
+        let from_a = a.generate_message().unwrap().unwrap();
+        let from_b = b.generate_message().unwrap().unwrap();
+        a.process_message(&from_b).unwrap();
+        b.process_message(&from_a).unwrap();
+
+        (a.state().unwrap().clone(), b.state().unwrap().clone())
+    }
+
+    #[test]
+    fn test_concurrent_increments_merge() {
+        let (a, b) = concurrent_increments(true);
+        assert_eq!(a, GCounter { counts: [1, 1] });
+        assert_eq!(a, b);
+        assert_eq!(a.total(), 2);
+    }
+
+    #[test]
+    fn test_concurrent_increments_without_merge_lose_update() {
+        // Blind application overwrites the local increment on both sides
+        let (a, b) = concurrent_increments(false);
+        assert_eq!(a.total(), 1);
+        assert_eq!(b.total(), 1);
+    }
+
+    #[test]
+    fn test_sequential_diff_not_merged() {
+        let mut a = gc_engine(true);
+        let mut b = gc_engine(true);
+
+        a.update_state(GCounter { counts: [1, 0] });
+        let from_a = a.generate_message().unwrap().unwrap();
+        b.process_message(&from_a).unwrap();
+
+        // B has seen A's version, so its diff applies in order
+        b.update_state(GCounter { counts: [1, 1] });
+        let from_b = b.generate_message().unwrap().unwrap();
+        assert_eq!(from_b.acked_state_num, a.current_version());
+        a.process_message(&from_b).unwrap();
+        assert_eq!(a.state().unwrap(), &GCounter { counts: [1, 1] });
+    }
+
+    #[test]
+    fn test_diff_computed_from_acked_version() {
+        let mut a = create_engine();
+        let mut b = create_engine();
+        a.init(TestState { value: 0 });
+        b.init(TestState { value: 0 });
+
+        // A sends versions 1 and 2; B receives version 2
+        a.update_state(TestState { value: 10 });
+        a.generate_message().unwrap().unwrap();
+        a.update_state(TestState { value: 20 });
+        let msg = a.generate_message().unwrap().unwrap();
+        b.process_message(&msg).unwrap();
+        assert_eq!(b.state().unwrap().value, 20);
+
+        // A advances to version 5 before B's ack of version 2 arrives
+        a.update_state(TestState { value: 30 });
+        a.update_state(TestState { value: 40 });
+        a.update_state(TestState { value: 50 });
+        assert_eq!(a.current_version(), 5);
+
+        let ack = b.generate_message().unwrap().unwrap();
+        assert_eq!(ack.acked_state_num, 2);
+        a.process_message(&ack).unwrap();
+        assert_eq!(a.diff_base_version(), 2);
+
+        // The next diff encodes 2 -> 5, not 5 -> 5
+        let msg = a.generate_message().unwrap().unwrap();
+        assert_eq!(msg.base_state_num, 2);
+        assert_eq!(msg.sender_state_num, 5);
+        assert_eq!(decode_diff(&msg.diff).unwrap(), TestDiff { delta: 30 });
+
+        b.process_message(&msg).unwrap();
+        assert_eq!(b.state().unwrap().value, 50);
+    }
+
+    #[test]
+    fn test_bytes_in_flight_released_on_ack() {
+        let mut a = create_engine();
+        a.init(TestState { value: 0 });
+
+        let mut sent = Vec::new();
+        for value in [10, 20, 30] {
+            a.update_state(TestState { value });
+            sent.push(a.generate_message().unwrap().unwrap().diff.len());
+        }
+        assert_eq!(a.bytes_in_flight(), sent.iter().sum::<usize>());
+
+        // Acking version 1 releases only the first diff
+        a.process_message(&SyncMessage::ack_only(0, 1)).unwrap();
+        assert_eq!(a.bytes_in_flight(), sent[1] + sent[2]);
+
+        a.process_message(&SyncMessage::ack_only(0, 3)).unwrap();
+        assert_eq!(a.bytes_in_flight(), 0);
+    }
+
+    #[test]
+    fn test_snapshot_history_bounded_and_evicted() {
+        let mut engine = create_engine();
+        engine.init(TestState { value: 0 });
+
+        for value in 1..=(MAX_SNAPSHOT_HISTORY as i32 + 10) {
+            engine.update_state(TestState { value });
+            engine.generate_message().unwrap();
+        }
+        assert_eq!(engine.history.len(), MAX_SNAPSHOT_HISTORY);
+        assert_eq!(engine.diff_base_version(), 0);
+
+        // Acking version 40 evicts everything older
+        engine.process_message(&SyncMessage::ack_only(0, 40)).unwrap();
+        assert_eq!(engine.diff_base_version(), 40);
+        assert!(engine.history.iter().all(|(version, _)| *version >= 40));
+    }
+
+    // Grid state: a diff lists each changed cell as (index, value), so a
+    // near-total change encodes larger than the grid itself
+    #[derive(Debug, Clone, PartialEq)]
+    struct Grid {
+        cells: Vec<u32>,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct GridDiff(Vec<(u32, u32)>);
+
+    fn grid_encode(diff: &GridDiff) -> Vec<u8> {
+        diff.0
+            .iter()
+            .flat_map(|(i, v)| [i.to_le_bytes(), v.to_le_bytes()])
+            .flatten()
+            .collect()
+    }
+
+    fn grid_decode(data: &[u8]) -> Result<GridDiff, String> {
+        Ok(GridDiff(data
+            .chunks_exact(8)
+            .map(|c| {
+                (
+                    u32::from_le_bytes(c[0..4].try_into().unwrap()),
+                    u32::from_le_bytes(c[4..8].try_into().unwrap()),
+                )
+            })
+            .collect()))
+    }
+
+    fn grid_compute(old: &Grid, new: &Grid) -> GridDiff {
+        GridDiff(
+            (0..new.cells.len())
+                .filter(|&i| old.cells[i] != new.cells[i])
+                .map(|i| (i as u32, new.cells[i]))
+                .collect(),
+        )
+    }
+
+    fn grid_apply(state: &mut Grid, diff: &GridDiff) -> Result<(), String> {
+        for &(i, v) in &diff.0 {
+            state.cells[i as usize] = v;
+        }
+        Ok(())
+    }
+
+    fn grid_is_empty(diff: &GridDiff) -> bool {
+        diff.0.is_empty()
+    }
+
+    fn grid_encode_snapshot(state: &Grid) -> Option<Vec<u8>> {
+        Some(state.cells.iter().flat_map(|v| v.to_le_bytes()).collect())
+    }
+
+    fn grid_decode_snapshot(data: &[u8]) -> Result<Grid, String> {
+        Ok(Grid {
+            cells: data
+                .chunks_exact(4)
+                .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+                .collect(),
+        })
+    }
+
+    fn grid_engine() -> SyncEngine<Grid, GridDiff> {
+        let mut engine =
+            SyncEngine::new(grid_encode, grid_decode, grid_compute, grid_apply, grid_is_empty)
+                .with_snapshots(grid_encode_snapshot, grid_decode_snapshot);
+        engine.init(Grid { cells: vec![0; 64] });
+        engine
+    }
+
+    #[test]
+    fn test_near_total_change_sends_snapshot() {
+        let mut sender = grid_engine();
+        let mut receiver = grid_engine();
+
+        let changed = Grid {
+            cells: (0..64).map(|i| if i == 0 { 0 } else { i + 100 }).collect(),
+        };
+        sender.update_state(changed.clone());
+
+        let msg = sender.generate_message().unwrap().unwrap();
+        assert!(msg.snapshot);
+        assert_eq!(msg.diff.len(), 64 * 4);
+
+        let decoded = SyncMessage::decode(&msg.encode()).unwrap();
+        assert_eq!(receiver.process_message(&decoded).unwrap(), ProcessResult::Updated);
+        assert_eq!(receiver.state().unwrap(), &changed);
+    }
+
+    #[test]
+    fn test_small_change_sends_diff() {
+        let mut sender = grid_engine();
+        let mut receiver = grid_engine();
+
+        let mut changed = Grid { cells: vec![0; 64] };
+        changed.cells[5] = 9;
+        sender.update_state(changed.clone());
+
+        let msg = sender.generate_message().unwrap().unwrap();
+        assert!(!msg.snapshot);
+        assert_eq!(msg.diff.len(), 8);
+
+        receiver.process_message(&msg).unwrap();
+        assert_eq!(receiver.state().unwrap(), &changed);
+    }
 }
@@ -14,6 +14,49 @@ use thiserror::Error;
 /// +24  Diff Length (4 bytes LE32)
 /// +28  Diff Payload (variable)
 /// ```
+///
+/// Fragmented messages set [`FRAGMENT_FLAG`] in the diff length field and
+/// insert a 4-byte fragment header before the diff:
+/// ```text
+/// +28  Fragment Index (2 bytes LE16)
+/// +30  Fragment Count (2 bytes LE16)
+/// +32  Diff Fragment (variable)
+/// ```
+///
+/// When [`SNAPSHOT_FLAG`] is set, the payload is the sender's full state
+/// (see `SyncState::encode_snapshot`) rather than a diff from the base
+/// version. Fragments of a snapshot each carry the flag.
+///
+/// When [`NACK_FLAG`] is set, an 8-byte gap bitmap follows the fragment
+/// header (if any) and precedes the diff:
+/// ```text
+/// +28  NACK Bitmap (8 bytes LE64)
+/// +36  Diff Payload (variable)
+/// ```
+/// Bit `i` requests a resend of version `acked_state_num - 1 - i`. Peers
+/// only emit the bitmap once the sync NACK extension has been negotiated,
+/// so older peers never see the flag.
+///
+/// When [`WINDOW_FLAG`] is set, a 4-byte receive window follows the NACK
+/// bitmap (if any) and precedes the diff:
+/// ```text
+/// +28  Receive Window (4 bytes LE32)
+/// +32  Diff Payload (variable)
+/// ```
+/// It advertises how many unacknowledged messages the sender of the
+/// message will buffer; the peer stops sending new state once that many
+/// are in flight. Only emitted once the flow control extension has been
+/// negotiated.
+///
+/// Once the sync preamble extension has been negotiated, messages are
+/// prefixed with a magic and wire version (see
+/// [`encode_with_preamble`](Self::encode_with_preamble)), so corrupt or
+/// cross-protocol payloads are rejected instead of misread:
+/// ```text
+/// +0   Magic (2 bytes, "NM")
+/// +2   Wire Version (1 byte)
+/// +3   Sync message as above
+/// ```
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SyncMessage {
     /// Version of sender's current state
@@ -24,11 +67,68 @@ pub struct SyncMessage {
     pub base_state_num: u64,
     /// Application-specific diff encoding
     pub diff: Vec<u8>,
+    /// Position of this message within a fragmented diff, if fragmented
+    pub fragment: Option<Fragment>,
+    /// Payload is an absolute state snapshot rather than a diff
+    pub snapshot: bool,
+    /// Bitmap of missing peer versions below `acked_state_num`, if any
+    pub nack: Option<u64>,
+    /// Maximum unacked messages the sender will buffer, if advertised
+    pub recv_window: Option<u32>,
 }
 
 /// Header size in bytes (3 x u64 + u32 = 28)
 pub const SYNC_MESSAGE_HEADER_SIZE: usize = 28;
 
+/// Magic prefixed to sync messages when the preamble is negotiated
+pub const SYNC_MESSAGE_MAGIC: [u8; 2] = *b"NM";
+
+/// Sync message wire version carried in the preamble
+pub const SYNC_WIRE_VERSION: u8 = 1;
+
+/// Preamble size in bytes (magic + wire version)
+pub const SYNC_PREAMBLE_SIZE: usize = 3;
+
+/// Fragment header size in bytes (index + count)
+pub const FRAGMENT_HEADER_SIZE: usize = 4;
+
+/// Bit set in the diff length field when a fragment header follows
+pub const FRAGMENT_FLAG: u32 = 0x8000_0000;
+
+/// Bit set in the diff length field when the payload is a full snapshot
+pub const SNAPSHOT_FLAG: u32 = 0x4000_0000;
+
+/// Bit set in the diff length field when a NACK bitmap follows
+pub const NACK_FLAG: u32 = 0x2000_0000;
+
+/// NACK bitmap size in bytes
+pub const NACK_BITMAP_SIZE: usize = 8;
+
+/// Bit set in the diff length field when a receive window follows
+pub const WINDOW_FLAG: u32 = 0x1000_0000;
+
+/// Receive window size in bytes
+pub const RECV_WINDOW_SIZE: usize = 4;
+
+/// Mask of all flag bits in the diff length field
+const LENGTH_FLAGS: u32 = FRAGMENT_FLAG | SNAPSHOT_FLAG | NACK_FLAG | WINDOW_FLAG;
+
+/// Position of a fragment within a diff split across several messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fragment {
+    /// Zero-based index of this fragment
+    pub index: u16,
+    /// Total number of fragments
+    pub count: u16,
+}
+
+impl Fragment {
+    /// Check if this is the final fragment
+    pub fn is_last(&self) -> bool {
+        self.index + 1 == self.count
+    }
+}
+
 impl SyncMessage {
     /// Create a new sync message
     pub fn new(
@@ -42,9 +142,70 @@ impl SyncMessage {
             acked_state_num,
             base_state_num,
             diff,
+            fragment: None,
+            snapshot: false,
+            nack: None,
+            recv_window: None,
         }
     }
 
+    /// Mark this message as one fragment of a larger diff
+    pub fn with_fragment(mut self, index: u16, count: u16) -> Self {
+        self.fragment = Some(Fragment { index, count });
+        self
+    }
+
+    /// Mark this message as carrying a full state snapshot
+    pub fn with_snapshot(mut self) -> Self {
+        self.snapshot = true;
+        self
+    }
+
+    /// Attach a NACK bitmap of missing peer versions
+    ///
+    /// An empty bitmap is dropped, since it requests nothing.
+    pub fn with_nack(mut self, bitmap: u64) -> Self {
+        self.nack = (bitmap != 0).then_some(bitmap);
+        self
+    }
+
+    /// Advertise the receive window
+    pub fn with_recv_window(mut self, window: u32) -> Self {
+        self.recv_window = Some(window);
+        self
+    }
+
+    /// Peer versions this message asks to have resent, newest first
+    pub fn nacked_versions(&self) -> impl Iterator<Item = u64> + '_ {
+        let bitmap = self.nack.unwrap_or(0);
+        (0..64u64)
+            .filter(move |bit| bitmap & (1 << bit) != 0)
+            .filter_map(|bit| self.acked_state_num.checked_sub(bit + 1))
+            .filter(|&version| version > 0)
+    }
+
+    /// Peer versions this message acknowledges, as inclusive ranges, oldest first
+    ///
+    /// Everything up to `acked_state_num` except the versions in the NACK
+    /// bitmap. Versions below the bitmap's reach count as acknowledged,
+    /// since they can no longer be NACKed.
+    pub fn acked_ranges(&self) -> Vec<(u64, u64)> {
+        let mut ranges = Vec::new();
+        let mut start = 1;
+        let mut missing: Vec<u64> = self.nacked_versions().collect();
+        missing.reverse();
+        for version in missing {
+            if version > start {
+                ranges.push((start, version - 1));
+            }
+            start = version + 1;
+        }
+        if self.acked_state_num >= start {
+            ranges.push((start, self.acked_state_num));
+        }
+        ranges
+    }
+
     /// Create an ack-only message (empty diff)
     pub fn ack_only(current_version: u64, acked_version: u64) -> Self {
         Self {
@@ -52,17 +213,72 @@ impl SyncMessage {
             acked_state_num: acked_version,
             base_state_num: 0,
             diff: Vec::new(),
+            fragment: None,
+            snapshot: false,
+            nack: None,
+            recv_window: None,
         }
     }
 
     /// Check if this is an ack-only message
+    ///
+    /// A NACK bitmap does not count as state: ack-only messages may carry one.
     pub fn is_ack_only(&self) -> bool {
-        self.diff.is_empty()
+        self.diff.is_empty() && !self.snapshot
     }
 
     /// Total wire size
     pub fn wire_size(&self) -> usize {
-        SYNC_MESSAGE_HEADER_SIZE + self.diff.len()
+        SYNC_MESSAGE_HEADER_SIZE
+            + self.fragment_header_size()
+            + self.nack_size()
+            + self.recv_window_size()
+            + self.diff.len()
+    }
+
+    /// Size of the fragment header (0 if not fragmented)
+    fn fragment_header_size(&self) -> usize {
+        if self.fragment.is_some() {
+            FRAGMENT_HEADER_SIZE
+        } else {
+            0
+        }
+    }
+
+    /// Size of the NACK bitmap (0 if absent)
+    fn nack_size(&self) -> usize {
+        if self.nack.is_some() {
+            NACK_BITMAP_SIZE
+        } else {
+            0
+        }
+    }
+
+    /// Size of the receive window (0 if not advertised)
+    fn recv_window_size(&self) -> usize {
+        if self.recv_window.is_some() {
+            RECV_WINDOW_SIZE
+        } else {
+            0
+        }
+    }
+
+    /// Diff length field, with the fragment, snapshot, NACK and window flags
+    fn length_field(&self) -> u32 {
+        let mut field = self.diff.len() as u32;
+        if self.fragment.is_some() {
+            field |= FRAGMENT_FLAG;
+        }
+        if self.snapshot {
+            field |= SNAPSHOT_FLAG;
+        }
+        if self.nack.is_some() {
+            field |= NACK_FLAG;
+        }
+        if self.recv_window.is_some() {
+            field |= WINDOW_FLAG;
+        }
+        field
     }
 
     /// Encode to wire format (28-byte header + diff)
@@ -71,7 +287,17 @@ impl SyncMessage {
         buf.extend_from_slice(&self.sender_state_num.to_le_bytes());
         buf.extend_from_slice(&self.acked_state_num.to_le_bytes());
         buf.extend_from_slice(&self.base_state_num.to_le_bytes());
-        buf.extend_from_slice(&(self.diff.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&self.length_field().to_le_bytes());
+        if let Some(fragment) = self.fragment {
+            buf.extend_from_slice(&fragment.index.to_le_bytes());
+            buf.extend_from_slice(&fragment.count.to_le_bytes());
+        }
+        if let Some(nack) = self.nack {
+            buf.extend_from_slice(&nack.to_le_bytes());
+        }
+        if let Some(window) = self.recv_window {
+            buf.extend_from_slice(&window.to_le_bytes());
+        }
         buf.extend_from_slice(&self.diff);
         buf
     }
@@ -89,8 +315,22 @@ impl SyncMessage {
         buf[0..8].copy_from_slice(&self.sender_state_num.to_le_bytes());
         buf[8..16].copy_from_slice(&self.acked_state_num.to_le_bytes());
         buf[16..24].copy_from_slice(&self.base_state_num.to_le_bytes());
-        buf[24..28].copy_from_slice(&(self.diff.len() as u32).to_le_bytes());
-        buf[28..size].copy_from_slice(&self.diff);
+        buf[24..28].copy_from_slice(&self.length_field().to_le_bytes());
+        let mut offset = SYNC_MESSAGE_HEADER_SIZE;
+        if let Some(fragment) = self.fragment {
+            buf[28..30].copy_from_slice(&fragment.index.to_le_bytes());
+            buf[30..32].copy_from_slice(&fragment.count.to_le_bytes());
+            offset += FRAGMENT_HEADER_SIZE;
+        }
+        if let Some(nack) = self.nack {
+            buf[offset..offset + NACK_BITMAP_SIZE].copy_from_slice(&nack.to_le_bytes());
+            offset += NACK_BITMAP_SIZE;
+        }
+        if let Some(window) = self.recv_window {
+            buf[offset..offset + RECV_WINDOW_SIZE].copy_from_slice(&window.to_le_bytes());
+            offset += RECV_WINDOW_SIZE;
+        }
+        buf[offset..size].copy_from_slice(&self.diff);
 
         Ok(size)
     }
@@ -110,30 +350,124 @@ impl SyncMessage {
             u64::from_le_bytes(data[8..16].try_into().expect("length checked above"));
         let base_state_num =
             u64::from_le_bytes(data[16..24].try_into().expect("length checked above"));
-        let diff_len =
-            u32::from_le_bytes(data[24..28].try_into().expect("length checked above")) as usize;
-
-        if data.len() < SYNC_MESSAGE_HEADER_SIZE + diff_len {
+        let length_field =
+            u32::from_le_bytes(data[24..28].try_into().expect("length checked above"));
+        let diff_len = (length_field & !LENGTH_FLAGS) as usize;
+
+        let mut offset = SYNC_MESSAGE_HEADER_SIZE;
+        let fragment = if length_field & FRAGMENT_FLAG != 0 {
+            if data.len() < offset + FRAGMENT_HEADER_SIZE {
+                return Err(MessageError::TooShort {
+                    expected: offset + FRAGMENT_HEADER_SIZE,
+                    actual: data.len(),
+                });
+            }
+            let index = u16::from_le_bytes([data[28], data[29]]);
+            let count = u16::from_le_bytes([data[30], data[31]]);
+            if index >= count {
+                return Err(MessageError::InvalidFormat(format!(
+                    "fragment index {} out of range for count {}",
+                    index, count
+                )));
+            }
+            offset += FRAGMENT_HEADER_SIZE;
+            Some(Fragment { index, count })
+        } else {
+            None
+        };
+
+        let nack = if length_field & NACK_FLAG != 0 {
+            if data.len() < offset + NACK_BITMAP_SIZE {
+                return Err(MessageError::TooShort {
+                    expected: offset + NACK_BITMAP_SIZE,
+                    actual: data.len(),
+                });
+            }
+            let bitmap = u64::from_le_bytes(
+                data[offset..offset + NACK_BITMAP_SIZE]
+                    .try_into()
+                    .expect("length checked above"),
+            );
+            offset += NACK_BITMAP_SIZE;
+            Some(bitmap)
+        } else {
+            None
+        };
+
+        let recv_window = if length_field & WINDOW_FLAG != 0 {
+            if data.len() < offset + RECV_WINDOW_SIZE {
+                return Err(MessageError::TooShort {
+                    expected: offset + RECV_WINDOW_SIZE,
+                    actual: data.len(),
+                });
+            }
+            let window = u32::from_le_bytes(
+                data[offset..offset + RECV_WINDOW_SIZE]
+                    .try_into()
+                    .expect("length checked above"),
+            );
+            offset += RECV_WINDOW_SIZE;
+            Some(window)
+        } else {
+            None
+        };
+
+        if data.len() < offset + diff_len {
             return Err(MessageError::TooShort {
-                expected: SYNC_MESSAGE_HEADER_SIZE + diff_len,
+                expected: offset + diff_len,
                 actual: data.len(),
             });
         }
 
-        let diff = data[SYNC_MESSAGE_HEADER_SIZE..SYNC_MESSAGE_HEADER_SIZE + diff_len].to_vec();
+        let diff = data[offset..offset + diff_len].to_vec();
 
         Ok(Self {
             sender_state_num,
             acked_state_num,
             base_state_num,
             diff,
+            fragment,
+            snapshot: length_field & SNAPSHOT_FLAG != 0,
+            nack,
+            recv_window,
         })
     }
 
+    /// Encode with the magic and wire version preamble
+    pub fn encode_with_preamble(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(SYNC_PREAMBLE_SIZE + self.wire_size());
+        buf.extend_from_slice(&SYNC_MESSAGE_MAGIC);
+        buf.push(SYNC_WIRE_VERSION);
+        buf.extend_from_slice(&self.encode());
+        buf
+    }
+
+    /// Decode a message carrying the preamble, rejecting any other input
+    ///
+    /// # Errors
+    /// Returns `BadMagic` or `UnsupportedVersion` for a foreign or future
+    /// preamble, and `TooShort` for truncated input.
+    pub fn decode_strict(data: &[u8]) -> Result<Self, MessageError> {
+        if data.len() < SYNC_PREAMBLE_SIZE {
+            return Err(MessageError::TooShort {
+                expected: SYNC_PREAMBLE_SIZE + SYNC_MESSAGE_HEADER_SIZE,
+                actual: data.len(),
+            });
+        }
+        let magic = [data[0], data[1]];
+        if magic != SYNC_MESSAGE_MAGIC {
+            return Err(MessageError::BadMagic(magic));
+        }
+        if data[2] != SYNC_WIRE_VERSION {
+            return Err(MessageError::UnsupportedVersion(data[2]));
+        }
+        Self::decode(&data[SYNC_PREAMBLE_SIZE..])
+    }
+
     /// Decode from wire format, returning message and bytes consumed
     pub fn decode_with_length(data: &[u8]) -> Result<(Self, usize), MessageError> {
         let msg = Self::decode(data)?;
-        let consumed = SYNC_MESSAGE_HEADER_SIZE + msg.diff.len();
+        let consumed = msg.wire_size();
         Ok((msg, consumed))
     }
 }
@@ -162,6 +496,14 @@ pub enum MessageError {
     /// Message format is invalid or corrupted.
     #[error("invalid format: {0}")]
     InvalidFormat(String),
+
+    /// Preamble magic does not identify a sync message.
+    #[error("bad magic: {0:02x?}")]
+    BadMagic([u8; 2]),
+
+    /// Preamble carries a wire version we do not support.
+    #[error("unsupported wire version {0}")]
+    UnsupportedVersion(u8),
 }
 
 #[cfg(test)]
@@ -247,4 +589,151 @@ mod tests {
         assert_eq!(decoded, msg);
         assert_eq!(consumed, SYNC_MESSAGE_HEADER_SIZE + 3);
     }
+
+    #[test]
+    fn test_fragment_roundtrip() {
+        let msg = SyncMessage::new(10, 0, 5, vec![7; 40]).with_fragment(1, 3);
+        assert_eq!(
+            msg.wire_size(),
+            SYNC_MESSAGE_HEADER_SIZE + FRAGMENT_HEADER_SIZE + 40
+        );
+
+        let encoded = msg.encode();
+        assert_eq!(encoded.len(), msg.wire_size());
+        let decoded = SyncMessage::decode(&encoded).unwrap();
+        assert_eq!(decoded, msg);
+        assert!(!decoded.fragment.unwrap().is_last());
+    }
+
+    #[test]
+    fn test_snapshot_flag_roundtrip() {
+        let msg = SyncMessage::new(10, 0, 5, vec![7; 40])
+            .with_fragment(0, 2)
+            .with_snapshot();
+
+        let decoded = SyncMessage::decode(&msg.encode()).unwrap();
+        assert_eq!(decoded, msg);
+        assert_eq!(decoded.diff.len(), 40);
+
+        // An empty snapshot still carries state
+        assert!(!SyncMessage::new(1, 0, 0, Vec::new()).with_snapshot().is_ack_only());
+    }
+
+    #[test]
+    fn test_nack_roundtrip() {
+        // Peer acked 5 but is missing 3 and 4
+        let msg = SyncMessage::ack_only(7, 5).with_nack(0b11);
+        assert!(msg.is_ack_only());
+        assert_eq!(msg.wire_size(), SYNC_MESSAGE_HEADER_SIZE + NACK_BITMAP_SIZE);
+        assert_eq!(msg.nacked_versions().collect::<Vec<_>>(), vec![4, 3]);
+
+        let decoded = SyncMessage::decode(&msg.encode()).unwrap();
+        assert_eq!(decoded, msg);
+
+        let mut buf = [0u8; 64];
+        let written = msg.encode_into(&mut buf).unwrap();
+        assert_eq!(SyncMessage::decode(&buf[..written]).unwrap(), msg);
+
+        // Alongside a fragment header and diff
+        let msg = SyncMessage::new(9, 5, 4, vec![1, 2, 3])
+            .with_fragment(0, 2)
+            .with_nack(1 << 2);
+        let decoded = SyncMessage::decode(&msg.encode()).unwrap();
+        assert_eq!(decoded, msg);
+        assert_eq!(decoded.diff, vec![1, 2, 3]);
+        assert_eq!(decoded.nacked_versions().collect::<Vec<_>>(), vec![2]);
+
+        // Empty bitmaps are not sent
+        assert_eq!(SyncMessage::ack_only(1, 1).with_nack(0).nack, None);
+    }
+
+    #[test]
+    fn test_acked_ranges_skip_nacked_gaps() {
+        // Acked 10, missing 8, 6 and 5
+        let msg = SyncMessage::ack_only(1, 10).with_nack(0b11010);
+        assert_eq!(msg.acked_ranges(), vec![(1, 4), (7, 7), (9, 10)]);
+
+        assert_eq!(SyncMessage::ack_only(1, 4).acked_ranges(), vec![(1, 4)]);
+        assert!(SyncMessage::ack_only(1, 0).acked_ranges().is_empty());
+    }
+
+    #[test]
+    fn test_recv_window_roundtrip() {
+        let msg = SyncMessage::ack_only(3, 2).with_recv_window(2);
+        assert!(msg.is_ack_only());
+        assert_eq!(msg.wire_size(), SYNC_MESSAGE_HEADER_SIZE + RECV_WINDOW_SIZE);
+        assert_eq!(SyncMessage::decode(&msg.encode()).unwrap(), msg);
+
+        // After the fragment header and NACK bitmap, before the diff
+        let msg = SyncMessage::new(9, 5, 4, vec![1, 2, 3])
+            .with_fragment(1, 2)
+            .with_nack(0b1)
+            .with_recv_window(16);
+        let mut buf = [0u8; 64];
+        let written = msg.encode_into(&mut buf).unwrap();
+        assert_eq!(&buf[..written], msg.encode().as_slice());
+        let decoded = SyncMessage::decode(&buf[..written]).unwrap();
+        assert_eq!(decoded, msg);
+        assert_eq!(decoded.diff, vec![1, 2, 3]);
+
+        // Truncated window
+        let encoded = SyncMessage::ack_only(3, 2).with_recv_window(2).encode();
+        assert!(matches!(
+            SyncMessage::decode(&encoded[..encoded.len() - 1]),
+            Err(MessageError::TooShort { .. })
+        ));
+    }
+
+    #[test]
+    fn test_fragment_index_out_of_range() {
+        let mut encoded = SyncMessage::new(1, 0, 0, vec![1]).with_fragment(0, 1).encode();
+        encoded[28] = 1; // index == count
+        assert!(matches!(
+            SyncMessage::decode(&encoded),
+            Err(MessageError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_preamble_roundtrip() {
+        let msg = SyncMessage::new(9, 4, 3, vec![7; 12]).with_nack(0b10);
+        let encoded = msg.encode_with_preamble();
+        assert_eq!(&encoded[..2], &SYNC_MESSAGE_MAGIC);
+        assert_eq!(encoded.len(), SYNC_PREAMBLE_SIZE + msg.wire_size());
+        assert_eq!(SyncMessage::decode_strict(&encoded).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_strict_decode_rejects_wrong_magic_and_version() {
+        let mut encoded = SyncMessage::new(1, 0, 0, vec![1, 2, 3]).encode_with_preamble();
+
+        // A legacy message without preamble is not mistaken for one
+        let legacy = SyncMessage::new(1, 0, 0, vec![1, 2, 3]).encode();
+        assert!(matches!(
+            SyncMessage::decode_strict(&legacy),
+            Err(MessageError::BadMagic(_))
+        ));
+
+        encoded[2] = SYNC_WIRE_VERSION + 1;
+        assert_eq!(
+            SyncMessage::decode_strict(&encoded),
+            Err(MessageError::UnsupportedVersion(SYNC_WIRE_VERSION + 1))
+        );
+        encoded[0] = b'X';
+        assert_eq!(
+            SyncMessage::decode_strict(&encoded),
+            Err(MessageError::BadMagic([b'X', b'M']))
+        );
+    }
+
+    #[test]
+    fn test_strict_decode_truncated_header() {
+        let encoded = SyncMessage::new(1, 0, 0, vec![1, 2, 3]).encode_with_preamble();
+        for len in 0..encoded.len() {
+            assert!(matches!(
+                SyncMessage::decode_strict(&encoded[..len]),
+                Err(MessageError::TooShort { .. })
+            ));
+        }
+    }
 }
@@ -5,8 +5,10 @@
 //! - Idempotent diff generation and application
 //! - Acknowledgment tracking
 //! - Eventual consistency guarantees
+//! - Coalescing multiple channels into one frame
 
 mod ack;
+mod coalesce;
 mod engine;
 mod message;
 mod receiver;
@@ -14,6 +16,7 @@ mod sender;
 mod tracker;
 
 pub use ack::*;
+pub use coalesce::*;
 pub use engine::*;
 pub use message::*;
 pub use receiver::*;
@@ -265,6 +265,94 @@ impl Default for OrderedReceiver {
     }
 }
 
+/// Reassembles diffs that were split across several sync messages.
+///
+/// Fragments of one diff share the same `sender_state_num`. Only the last
+/// fragment carries the acknowledgment; until every fragment has arrived,
+/// nothing is released, so a lost fragment leaves the caller's state (and
+/// version tracking) untouched. The sender retransmits the whole diff.
+#[derive(Debug, Clone, Default)]
+pub struct FragmentAssembler {
+    /// Version of the diff currently being reassembled
+    version: u64,
+    /// Received fragments, indexed by fragment index
+    fragments: Vec<Option<SyncMessage>>,
+}
+
+impl FragmentAssembler {
+    /// Create a new assembler
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check if a partial diff is buffered
+    pub fn is_pending(&self) -> bool {
+        !self.fragments.is_empty()
+    }
+
+    /// Add a message, returning the complete message once available.
+    ///
+    /// Unfragmented messages are returned immediately. A fragment for a
+    /// newer version discards any older partial diff; fragments for an
+    /// older version are ignored.
+    pub fn push(&mut self, msg: SyncMessage) -> Result<Option<SyncMessage>, MessageError> {
+        let Some(fragment) = msg.fragment else {
+            return Ok(Some(msg));
+        };
+
+        if fragment.index >= fragment.count {
+            return Err(MessageError::InvalidFormat(format!(
+                "fragment index {} out of range for count {}",
+                fragment.index, fragment.count
+            )));
+        }
+
+        if !self.is_pending() || msg.sender_state_num > self.version {
+            self.version = msg.sender_state_num;
+            self.fragments = vec![None; fragment.count as usize];
+        } else if msg.sender_state_num < self.version {
+            return Ok(None);
+        } else if self.fragments.len() != fragment.count as usize {
+            return Err(MessageError::InvalidFormat(format!(
+                "fragment count changed from {} to {}",
+                self.fragments.len(),
+                fragment.count
+            )));
+        }
+
+        self.fragments[fragment.index as usize] = Some(msg);
+        if self.fragments.iter().any(Option::is_none) {
+            return Ok(None);
+        }
+
+        // All fragments present: concatenate in order
+        let fragments = std::mem::take(&mut self.fragments);
+        let mut diff = Vec::new();
+        let mut last = None;
+        for part in fragments.into_iter().flatten() {
+            diff.extend_from_slice(&part.diff);
+            last = Some(part);
+        }
+        let last = last.expect("fragment count is at least one");
+
+        let mut complete = SyncMessage::new(
+            last.sender_state_num,
+            last.acked_state_num,
+            last.base_state_num,
+            diff,
+        );
+        complete.snapshot = last.snapshot;
+        complete.nack = last.nack;
+        Ok(Some(complete))
+    }
+
+    /// Discard any partial diff
+    pub fn reset(&mut self) {
+        self.version = 0;
+        self.fragments.clear();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -420,4 +508,55 @@ mod tests {
             assert!(!receiver.has_received(5));
         }
     }
+
+    mod fragment_assembler {
+        use super::*;
+
+        fn fragments(version: u64, count: u16) -> Vec<SyncMessage> {
+            (0..count)
+                .map(|i| {
+                    let acked = if i + 1 == count { 9 } else { 0 };
+                    SyncMessage::new(version, acked, 1, vec![i as u8; 4]).with_fragment(i, count)
+                })
+                .collect()
+        }
+
+        #[test]
+        fn test_unfragmented_passthrough() {
+            let mut assembler = FragmentAssembler::new();
+            let msg = create_state_msg(1);
+            assert_eq!(assembler.push(msg.clone()).unwrap(), Some(msg));
+            assert!(!assembler.is_pending());
+        }
+
+        #[test]
+        fn test_reassemble_out_of_order() {
+            let mut assembler = FragmentAssembler::new();
+            let parts = fragments(5, 3);
+
+            assert_eq!(assembler.push(parts[2].clone()).unwrap(), None);
+            assert_eq!(assembler.push(parts[0].clone()).unwrap(), None);
+            let msg = assembler.push(parts[1].clone()).unwrap().unwrap();
+
+            assert_eq!(msg.sender_state_num, 5);
+            assert_eq!(msg.acked_state_num, 9);
+            assert_eq!(msg.diff, [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2]);
+            assert!(msg.fragment.is_none());
+            assert!(!assembler.is_pending());
+        }
+
+        #[test]
+        fn test_newer_version_discards_partial() {
+            let mut assembler = FragmentAssembler::new();
+            let old = fragments(5, 2);
+            let new = fragments(6, 2);
+
+            assert_eq!(assembler.push(old[0].clone()).unwrap(), None);
+            assert_eq!(assembler.push(new[0].clone()).unwrap(), None);
+            // Stale fragment is ignored
+            assert_eq!(assembler.push(old[1].clone()).unwrap(), None);
+            let msg = assembler.push(new[1].clone()).unwrap().unwrap();
+            assert_eq!(msg.sender_state_num, 6);
+        }
+    }
 }
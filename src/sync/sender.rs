@@ -2,6 +2,7 @@
 //!
 //! Manages outbound sync messages with pacing and batching.
 
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
 use super::message::SyncMessage;
@@ -12,6 +13,11 @@ pub const DEFAULT_COLLECTION_INTERVAL: Duration = Duration::from_millis(8);
 /// Default delayed ack timeout
 pub const DEFAULT_DELAYED_ACK_TIMEOUT: Duration = Duration::from_millis(100);
 
+/// Number of sent state messages kept for NACK-driven resends
+///
+/// Matches the 64-version reach of a NACK bitmap.
+pub const RETRANSMIT_HISTORY: usize = 64;
+
 /// Sender state for managing outbound sync messages
 #[derive(Debug)]
 pub struct SyncSender {
@@ -35,6 +41,12 @@ pub struct SyncSender {
 
     /// Pending message to send
     pending_message: Option<SyncMessage>,
+
+    /// Recently sent state messages, oldest first
+    sent_history: VecDeque<SyncMessage>,
+
+    /// NACKed messages waiting to be resent
+    retransmit_queue: VecDeque<SyncMessage>,
 }
 
 impl SyncSender {
@@ -48,6 +60,8 @@ impl SyncSender {
             pending_since: None,
             ack_pending_since: None,
             pending_message: None,
+            sent_history: VecDeque::new(),
+            retransmit_queue: VecDeque::new(),
         }
     }
 
@@ -65,6 +79,8 @@ impl SyncSender {
             pending_since: None,
             ack_pending_since: None,
             pending_message: None,
+            sent_history: VecDeque::new(),
+            retransmit_queue: VecDeque::new(),
         }
     }
 
@@ -99,15 +115,20 @@ impl SyncSender {
 
     /// Check if we should send at a given time
     pub fn should_send_at(&self, now: Instant) -> bool {
-        let Some(msg) = self.pending_message.as_ref() else {
-            return false;
-        };
-
         // Check pacing interval
         if self.last_send_time.is_some_and(|last| now.duration_since(last) < self.min_send_interval) {
             return false;
         }
 
+        // Resends skip the collection interval: the peer is already waiting
+        if !self.retransmit_queue.is_empty() {
+            return true;
+        }
+
+        let Some(msg) = self.pending_message.as_ref() else {
+            return false;
+        };
+
         if msg.is_ack_only() {
             // Ack-only: wait for delayed ack timeout
             self.ack_pending_since
@@ -139,17 +160,93 @@ impl SyncSender {
     }
 
     /// Force-take the pending message at a given time
+    ///
+    /// Queued resends go out before the pending message.
     fn take_message_at(&mut self, now: Instant) -> Option<SyncMessage> {
+        if let Some(msg) = self.retransmit_queue.pop_front() {
+            self.last_send_time = Some(now);
+            return Some(msg);
+        }
+
         if let Some(msg) = self.pending_message.take() {
             self.last_send_time = Some(now);
             self.pending_since = None;
             self.ack_pending_since = None;
+            if !msg.is_ack_only() {
+                self.remember_sent(&msg);
+            }
             Some(msg)
         } else {
             None
         }
     }
 
+    /// Keep a sent state message around in case the peer NACKs it
+    fn remember_sent(&mut self, msg: &SyncMessage) {
+        self.sent_history
+            .retain(|sent| sent.sender_state_num != msg.sender_state_num || sent.fragment != msg.fragment);
+        if self.sent_history.len() == RETRANSMIT_HISTORY {
+            self.sent_history.pop_front();
+        }
+        self.sent_history.push_back(msg.clone());
+    }
+
+    /// Queue the peer's NACKed versions for immediate resend
+    ///
+    /// Feed this from [`SyncTracker::take_requested_retransmits`]. Versions
+    /// that were never sent, or have aged out of the history, are skipped.
+    /// Resent messages keep their original fields but drop any stale NACK
+    /// bitmap. Returns the number of messages queued.
+    ///
+    /// [`SyncTracker::take_requested_retransmits`]: super::SyncTracker::take_requested_retransmits
+    pub fn resend_versions(&mut self, versions: &[u64]) -> usize {
+        let mut queued = 0;
+        for sent in &self.sent_history {
+            if !versions.contains(&sent.sender_state_num) {
+                continue;
+            }
+            let already_queued = self.retransmit_queue.iter().any(|queued| {
+                queued.sender_state_num == sent.sender_state_num && queued.fragment == sent.fragment
+            });
+            if !already_queued {
+                let mut resend = sent.clone();
+                resend.nack = None;
+                self.retransmit_queue.push_back(resend);
+                queued += 1;
+            }
+        }
+        queued
+    }
+
+    /// Forget sent messages the peer has acknowledged
+    ///
+    /// Feed this from [`SyncTracker::acked_ranges`]. Messages inside the
+    /// ranges will never be NACKed, so only those in the peer's gaps (and
+    /// newer ones) stay available for resend. Returns the number dropped.
+    ///
+    /// [`SyncTracker::acked_ranges`]: super::SyncTracker::acked_ranges
+    pub fn prune_acked(&mut self, ranges: &[(u64, u64)]) -> usize {
+        let acked = |msg: &SyncMessage| {
+            ranges
+                .iter()
+                .any(|&(first, last)| (first..=last).contains(&msg.sender_state_num))
+        };
+        let before = self.sent_history.len() + self.retransmit_queue.len();
+        self.sent_history.retain(|msg| !acked(msg));
+        self.retransmit_queue.retain(|msg| !acked(msg));
+        before - self.sent_history.len() - self.retransmit_queue.len()
+    }
+
+    /// Number of sent messages kept for resend
+    pub fn retained_for_resend(&self) -> usize {
+        self.sent_history.len()
+    }
+
+    /// Number of NACKed messages waiting to be resent
+    pub fn pending_retransmits(&self) -> usize {
+        self.retransmit_queue.len()
+    }
+
     /// Get time until next allowed send
     pub fn time_until_send(&self) -> Option<Duration> {
         self.time_until_send_at(Instant::now())
@@ -157,14 +254,18 @@ impl SyncSender {
 
     /// Get time until next allowed send at a given time
     pub fn time_until_send_at(&self, now: Instant) -> Option<Duration> {
-        let msg = self.pending_message.as_ref()?;
-
         // Time until pacing allows
         let pacing_remaining = self.last_send_time.map_or(Duration::ZERO, |last| {
             let elapsed = now.duration_since(last);
             self.min_send_interval.saturating_sub(elapsed)
         });
 
+        if !self.retransmit_queue.is_empty() {
+            return Some(pacing_remaining);
+        }
+
+        let msg = self.pending_message.as_ref()?;
+
         // Time until collection/ack timeout
         let batch_remaining = if msg.is_ack_only() {
             self.ack_pending_since.map_or(Duration::ZERO, |since| {
@@ -211,6 +312,8 @@ impl SyncSender {
         self.pending_since = None;
         self.ack_pending_since = None;
         self.pending_message = None;
+        self.sent_history.clear();
+        self.retransmit_queue.clear();
     }
 }
 
@@ -223,9 +326,10 @@ impl Default for SyncSender {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sync::SyncTracker;
 
     fn create_state_msg(version: u64) -> SyncMessage {
-        SyncMessage::new(version, 0, 0, vec![1, 2, 3])
+        SyncMessage::new(version, 0, version.saturating_sub(1), vec![1, 2, 3])
     }
 
     fn create_ack_msg(version: u64) -> SyncMessage {
@@ -370,6 +474,99 @@ mod tests {
         assert!(!sender.has_pending());
     }
 
+    #[test]
+    fn test_nack_triggers_targeted_resend() {
+        let mut sender = SyncSender::with_intervals(
+            Duration::from_millis(20),
+            Duration::from_millis(10),
+            Duration::from_millis(100),
+        );
+        let mut peer = SyncTracker::new();
+        peer.set_nack_enabled(true);
+
+        // Send versions 1..5; 3 and 4 are lost in transit
+        let mut now = Instant::now();
+        for version in 1..=5 {
+            sender.queue_message(create_state_msg(version));
+            let msg = sender.take_message_at(now).unwrap();
+            if !matches!(version, 3 | 4) {
+                peer.process_incoming(&msg);
+            }
+            now += Duration::from_millis(20);
+        }
+
+        // The peer's ack NACKs the gap and the sender's tracker surfaces it
+        let mut tracker = SyncTracker::with_initial_version(5);
+        tracker.record_sent(5);
+        tracker.process_incoming(&peer.create_ack());
+        let requested = tracker.take_requested_retransmits();
+        assert_eq!(requested, vec![4, 3]);
+
+        // Resend goes out on the next pacing slot, well before any RTO,
+        // and ahead of newer pending state
+        assert_eq!(sender.resend_versions(&requested), 2);
+        assert_eq!(sender.resend_versions(&requested), 0);
+        sender.queue_message(create_state_msg(6));
+        assert_eq!(sender.time_until_send_at(now), Some(Duration::ZERO));
+
+        let resent: Vec<u64> = std::iter::from_fn(|| {
+            let msg = sender.take_if_ready_at(now);
+            now += Duration::from_millis(20);
+            msg
+        })
+        .take(2)
+        .map(|msg| msg.sender_state_num)
+        .collect();
+        assert_eq!(resent, vec![3, 4]);
+        assert_eq!(sender.pending_retransmits(), 0);
+        assert_eq!(sender.pending_message().unwrap().sender_state_num, 6);
+    }
+
+    #[test]
+    fn test_sack_prunes_resend_history_above_gap() {
+        let mut sender = SyncSender::new();
+        let mut peer = SyncTracker::new();
+        peer.set_nack_enabled(true);
+        let mut tracker = SyncTracker::with_initial_version(5);
+        tracker.record_sent(5);
+
+        // Send versions 1..5; 2 is lost
+        for version in 1..=5 {
+            sender.queue_message(create_state_msg(version));
+            let msg = sender.take_message().unwrap();
+            if version != 2 {
+                peer.process_incoming(&msg);
+            }
+        }
+        assert_eq!(sender.retained_for_resend(), 5);
+
+        // The SACK covers 1 and 3..5; only the gap is kept
+        tracker.process_incoming(&peer.create_ack());
+        assert_eq!(tracker.acked_ranges(), &[(1, 1), (3, 5)]);
+        assert_eq!(tracker.lowest_unacked_version(), 2);
+        assert_eq!(sender.prune_acked(tracker.acked_ranges()), 4);
+        assert_eq!(sender.resend_versions(&[2]), 1);
+
+        // Once the gap fills, nothing is left to resend
+        peer.process_incoming(&create_state_msg(2));
+        tracker.process_incoming(&peer.create_ack());
+        assert_eq!(tracker.lowest_unacked_version(), 6);
+        assert_eq!(sender.prune_acked(tracker.acked_ranges()), 2);
+        assert_eq!(sender.retained_for_resend(), 0);
+        assert_eq!(sender.pending_retransmits(), 0);
+    }
+
+    #[test]
+    fn test_resend_unknown_version_skipped() {
+        let mut sender = SyncSender::new();
+        sender.queue_message(create_ack_msg(1));
+        sender.take_message();
+
+        // Ack-only messages are not kept for resend
+        assert_eq!(sender.resend_versions(&[1, 2]), 0);
+        assert!(!sender.should_send());
+    }
+
     #[test]
     fn test_reset() {
         let mut sender = SyncSender::new();
@@ -3,6 +3,8 @@
 //! Tracks local and remote state versions for synchronization.
 //! Each endpoint maintains its own tracker instance.
 
+use std::time::{Duration, Instant};
+
 use super::message::SyncMessage;
 
 /// Sync tracker state (each endpoint maintains this)
@@ -12,6 +14,12 @@ use super::message::SyncMessage;
 /// - `last_sent_num`: Version of last state we sent to peer
 /// - `last_acked`: Highest version our peer acknowledged receiving
 /// - `peer_state_num`: Highest version we've received from peer
+///
+/// The tracker also remembers which of the last 64 peer versions arrived
+/// with state. A message covers every version between its base and its own
+/// version, so versions the peer coalesced are not gaps. With NACKs enabled
+/// (see [`set_nack_enabled`](Self::set_nack_enabled)), outgoing messages
+/// ask for the real gaps to be resent.
 #[derive(Debug, Clone, Default)]
 pub struct SyncTracker {
     /// Version of current local state (monotonic)
@@ -22,6 +30,22 @@ pub struct SyncTracker {
     last_acked: u64,
     /// Highest version received from peer
     peer_state_num: u64,
+    /// Whether the peer negotiated NACK bitmaps
+    nack_enabled: bool,
+    /// Peer versions covered by a received state message; bit i =
+    /// `peer_state_num - i`
+    received_window: u64,
+    /// Our versions the peer asked to have resent
+    requested_retransmits: Vec<u64>,
+    /// Receive window we advertise, if flow control is enabled
+    recv_window: Option<u32>,
+    /// Receive window the peer last advertised
+    peer_recv_window: Option<u32>,
+    /// Sent messages the peer has not acked: version, encoded payload
+    /// size, and send time
+    in_flight: Vec<(u64, usize, Instant)>,
+    /// Our versions the peer has, from its latest ack
+    acked_ranges: Vec<(u64, u64)>,
 }
 
 impl SyncTracker {
@@ -34,10 +58,91 @@ impl SyncTracker {
     pub fn with_initial_version(version: u64) -> Self {
         Self {
             current_num: version,
-            last_sent_num: 0,
-            last_acked: 0,
-            peer_state_num: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Create a tracker continuing from persisted versions
+    ///
+    /// Nothing above `last_acked` counts as sent, so any newer local
+    /// version goes out again. Versions up to `last_acked` count as held by
+    /// the peer, and peer versions up to `peer` count as received.
+    pub fn restore(current: u64, last_acked: u64, peer: u64) -> Self {
+        Self {
+            current_num: current,
+            last_sent_num: last_acked,
+            last_acked,
+            peer_state_num: peer,
+            received_window: if peer > 0 { u64::MAX } else { 0 },
+            acked_ranges: if last_acked > 0 { vec![(1, last_acked)] } else { Vec::new() },
+            ..Self::default()
+        }
+    }
+
+    /// Enable or disable NACK bitmaps in outgoing messages
+    ///
+    /// Only enable this once the peer has negotiated the sync NACK
+    /// extension; older peers reject the unknown length flag.
+    pub fn set_nack_enabled(&mut self, enabled: bool) {
+        self.nack_enabled = enabled;
+    }
+
+    /// Check if NACK bitmaps are enabled
+    pub fn nack_enabled(&self) -> bool {
+        self.nack_enabled
+    }
+
+    /// Set the receive window advertised in outgoing messages
+    ///
+    /// The window is the maximum number of unacknowledged messages we will
+    /// buffer. Only set this once the peer has negotiated the flow control
+    /// extension; older peers reject the unknown length flag.
+    pub fn set_recv_window(&mut self, window: Option<u32>) {
+        self.recv_window = window;
+    }
+
+    /// Get the receive window we advertise
+    pub fn recv_window(&self) -> Option<u32> {
+        self.recv_window
+    }
+
+    /// Get the receive window the peer last advertised
+    pub fn peer_recv_window(&self) -> Option<u32> {
+        self.peer_recv_window
+    }
+
+    /// Check if the peer's receive window is exhausted
+    ///
+    /// True once as many messages as the peer advertised are in flight
+    /// without being acked; new state must wait for an ack or a
+    /// retransmit timeout (see
+    /// [`on_retransmit_timeout`](Self::on_retransmit_timeout)). Acks can
+    /// still be sent.
+    pub fn is_window_full(&self) -> bool {
+        self.peer_recv_window
+            .is_some_and(|window| self.in_flight.len() >= window as usize)
+    }
+
+    /// Treat in-flight messages as lost if the oldest is older than `rto`
+    ///
+    /// Returns `true` if they were: nothing above `last_acked` counts as
+    /// sent any more, so the window reopens and pending state goes out
+    /// again as one diff from the acked version.
+    pub fn on_retransmit_timeout(&mut self, rto: Duration) -> bool {
+        self.on_retransmit_timeout_at(Instant::now(), rto)
+    }
+
+    /// Like [`on_retransmit_timeout`](Self::on_retransmit_timeout), at `now`
+    pub fn on_retransmit_timeout_at(&mut self, now: Instant, rto: Duration) -> bool {
+        let Some(&(_, _, oldest)) = self.in_flight.first() else {
+            return false;
+        };
+        if now.saturating_duration_since(oldest) < rto {
+            return false;
         }
+        self.last_sent_num = self.last_acked;
+        self.in_flight.clear();
+        true
     }
 
     /// Get current local state version
@@ -60,11 +165,25 @@ impl SyncTracker {
         self.peer_state_num
     }
 
+    /// Record the peer's state version without a message
+    ///
+    /// Used when the peer's state arrives out of band, e.g. restored from a
+    /// checkpoint; later messages at or below this version are duplicates.
+    pub fn set_peer_version(&mut self, version: u64) {
+        self.peer_state_num = version;
+        self.received_window = if version > 0 { u64::MAX } else { 0 };
+    }
+
     /// Check if we have pending updates to send
     pub fn has_pending_updates(&self) -> bool {
         self.current_num > self.last_sent_num
     }
 
+    /// Check if we have pending updates the peer's window lets us send
+    pub fn can_send_updates(&self) -> bool {
+        self.has_pending_updates() && !self.is_window_full()
+    }
+
     /// Check if we need to send an ack
     pub fn needs_ack(&self) -> bool {
         self.peer_state_num > self.last_acked
@@ -90,48 +209,175 @@ impl SyncTracker {
         }
     }
 
+    /// Record that we sent `bytes` of encoded diff for `sent_version`
+    ///
+    /// Like [`record_sent`](Self::record_sent), and counts the message as
+    /// in flight until the peer acks that version.
+    pub fn record_sent_bytes(&mut self, sent_version: u64, bytes: usize) {
+        self.record_sent_bytes_at(sent_version, bytes, Instant::now());
+    }
+
+    /// Like [`record_sent_bytes`](Self::record_sent_bytes), sent at `now`
+    pub fn record_sent_bytes_at(&mut self, sent_version: u64, bytes: usize, now: Instant) {
+        self.record_sent(sent_version);
+        if sent_version > self.last_acked {
+            self.in_flight.push((sent_version, bytes, now));
+        }
+    }
+
+    /// Encoded diff bytes sent but not yet acked by the peer
+    pub fn bytes_in_flight(&self) -> usize {
+        self.in_flight.iter().map(|(_, bytes, _)| bytes).sum()
+    }
+
+    /// Messages carrying state sent but not yet acked by the peer
+    pub fn messages_in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
     /// Process an incoming sync message
     ///
     /// Updates:
     /// - `peer_state_num` from the sender's current version
     /// - `last_acked` from the sender's ack field
+    /// - the requested retransmits, from the sender's NACK bitmap
+    /// - the peer's receive window, if advertised
     ///
-    /// Returns `true` if the message contained new state (not just an ack).
+    /// Returns `true` if the message contained new state (not just an ack):
+    /// a version newer than any we have, or an older one we never received,
+    /// such as a resend of a NACKed gap.
     pub fn process_incoming(&mut self, msg: &SyncMessage) -> bool {
         // Update what peer has acked about our state
+        if msg.acked_state_num >= self.last_acked {
+            self.acked_ranges = msg.acked_ranges();
+        }
         if msg.acked_state_num > self.last_acked {
             self.last_acked = msg.acked_state_num;
+            let acked = self.last_acked;
+            self.in_flight.retain(|(version, _, _)| *version > acked);
+        }
+
+        if let Some(window) = msg.recv_window {
+            self.peer_recv_window = Some(window);
+        }
+
+        // Queue the versions the peer says it is missing
+        for version in msg.nacked_versions() {
+            if version <= self.last_sent_num && !self.requested_retransmits.contains(&version) {
+                self.requested_retransmits.push(version);
+            }
         }
 
+        // An older version is only new if it never arrived
+        let is_new_state = msg.sender_state_num > self.peer_state_num
+            || msg.sender_state_num > 0 && !msg.is_ack_only() && !self.has_received(msg.sender_state_num);
+
         // Update peer's state version if this is newer
-        let is_new_state = msg.sender_state_num > self.peer_state_num;
-        if is_new_state {
+        if msg.sender_state_num > self.peer_state_num {
+            let shift = msg.sender_state_num - self.peer_state_num;
+            self.received_window = if shift >= 64 { 0 } else { self.received_window << shift };
             self.peer_state_num = msg.sender_state_num;
         }
 
+        // Remember which versions the state covers: everything since its
+        // base, or everything for a snapshot
+        if !msg.is_ack_only() {
+            let offset = self.peer_state_num - msg.sender_state_num;
+            if offset < 64 {
+                let base = if msg.snapshot { 0 } else { msg.base_state_num };
+                let span = msg.sender_state_num.saturating_sub(base).clamp(1, 64 - offset);
+                let covered = if span == 64 { u64::MAX } else { (1u64 << span) - 1 };
+                self.received_window |= covered << offset;
+            }
+        }
+
         is_new_state && !msg.is_ack_only()
     }
 
+    /// Whether a peer version at or below `peer_state_num` arrived with state
+    ///
+    /// Versions older than the 64-version window count as received.
+    fn has_received(&self, version: u64) -> bool {
+        let offset = self.peer_state_num - version;
+        offset >= 64 || self.received_window & (1 << offset) != 0
+    }
+
+    /// Bitmap of peer versions missing below `peer_state_num`
+    ///
+    /// Bit `i` is set when version `peer_state_num - 1 - i` never arrived
+    /// with state, counting only versions newer than the oldest one we did
+    /// receive (earlier versions may simply have been coalesced away).
+    /// Returns 0 when NACKs are disabled.
+    pub fn missing_bitmap(&self) -> u64 {
+        if !self.nack_enabled || self.received_window == 0 {
+            return 0;
+        }
+
+        // Window bit 0 is peer_state_num itself; the NACK bitmap starts one below
+        let below = self.received_window >> 1;
+        if below == 0 {
+            return 0;
+        }
+        let span = 64 - below.leading_zeros();
+        let mask = if span == 64 { u64::MAX } else { (1u64 << span) - 1 };
+        !below & mask
+    }
+
+    /// Our versions the peer has, as inclusive ranges, from its latest ack
+    ///
+    /// Unlike [`last_acked_version`](Self::last_acked_version), gaps the
+    /// peer NACKed are left out.
+    pub fn acked_ranges(&self) -> &[(u64, u64)] {
+        &self.acked_ranges
+    }
+
+    /// Lowest version we sent that the peer has not acknowledged
+    ///
+    /// The first NACKed gap, or the version after the latest ack.
+    pub fn lowest_unacked_version(&self) -> u64 {
+        match self.acked_ranges.first() {
+            Some(&(1, last)) => last + 1,
+            _ => 1,
+        }
+    }
+
+    /// Versions the peer asked to have resent since the last call
+    pub fn take_requested_retransmits(&mut self) -> Vec<u64> {
+        std::mem::take(&mut self.requested_retransmits)
+    }
+
     /// Create a sync message with current state info
     ///
     /// The caller should fill in the diff payload.
     pub fn create_message(&self, diff: Vec<u8>, base_state_num: u64) -> SyncMessage {
-        SyncMessage::new(
+        let mut msg = SyncMessage::new(
             self.current_num,
             self.peer_state_num,
             base_state_num,
             diff,
         )
+        .with_nack(self.missing_bitmap());
+        msg.recv_window = self.recv_window;
+        msg
     }
 
     /// Create an ack-only message
     pub fn create_ack(&self) -> SyncMessage {
-        SyncMessage::ack_only(self.current_num, self.peer_state_num)
+        let mut msg = SyncMessage::ack_only(self.current_num, self.peer_state_num)
+            .with_nack(self.missing_bitmap());
+        msg.recv_window = self.recv_window;
+        msg
     }
 
     /// Reset the tracker to initial state
+    ///
+    /// The negotiated NACK setting and our receive window survive the reset.
     pub fn reset(&mut self) {
-        *self = Self::default();
+        *self = Self {
+            nack_enabled: self.nack_enabled,
+            recv_window: self.recv_window,
+            ..Self::default()
+        };
     }
 
     /// Get the base state number that should be used for diff computation
@@ -145,6 +391,7 @@ impl SyncTracker {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sync::SYNC_MESSAGE_HEADER_SIZE;
 
     #[test]
     fn test_new_tracker() {
@@ -299,6 +546,22 @@ mod tests {
         assert_eq!(tracker.last_sent_version(), 0);
     }
 
+    #[test]
+    fn test_restore() {
+        let tracker = SyncTracker::restore(12, 10, 7);
+        assert_eq!(tracker.current_version(), 12);
+        assert_eq!(tracker.last_sent_version(), 10);
+        assert_eq!(tracker.peer_version(), 7);
+        assert_eq!(tracker.acked_ranges(), &[(1, 10)]);
+        assert_eq!(tracker.lowest_unacked_version(), 11);
+        assert!(tracker.has_pending_updates());
+
+        // Nothing acked yet: nothing held by the peer
+        let tracker = SyncTracker::restore(3, 0, 0);
+        assert!(tracker.acked_ranges().is_empty());
+        assert_eq!(tracker.lowest_unacked_version(), 1);
+    }
+
     #[test]
     fn test_reset() {
         let mut tracker = SyncTracker::new();
@@ -316,4 +579,147 @@ mod tests {
         assert_eq!(tracker.last_acked_version(), 0);
         assert_eq!(tracker.peer_version(), 0);
     }
+
+    #[test]
+    fn test_nack_gap_in_sequence() {
+        let mut receiver = SyncTracker::new();
+        receiver.set_nack_enabled(true);
+
+        // Peer sends 1..5 but 3 and 4 are lost
+        for version in [1, 2, 5] {
+            receiver.process_incoming(&SyncMessage::new(version, 0, version - 1, vec![1]));
+        }
+
+        let ack = receiver.create_ack();
+        assert_eq!(ack.acked_state_num, 5);
+        assert_eq!(ack.nack, Some(0b11));
+        assert_eq!(ack.nacked_versions().collect::<Vec<_>>(), vec![4, 3]);
+
+        // The sender learns which versions to resend
+        let mut sender = SyncTracker::new();
+        for _ in 0..5 {
+            sender.bump_version();
+        }
+        sender.record_sent(5);
+        sender.process_incoming(&SyncMessage::decode(&ack.encode()).unwrap());
+        assert_eq!(sender.take_requested_retransmits(), vec![4, 3]);
+        assert!(sender.take_requested_retransmits().is_empty());
+
+        // Late arrival of 3 and 4 fills the gap
+        receiver.process_incoming(&SyncMessage::new(3, 0, 2, vec![1]));
+        assert_eq!(receiver.missing_bitmap(), 0b01);
+        receiver.process_incoming(&SyncMessage::new(4, 0, 3, vec![1]));
+        assert_eq!(receiver.missing_bitmap(), 0);
+        assert_eq!(receiver.create_ack().nack, None);
+    }
+
+    #[test]
+    fn test_nack_disabled_by_default() {
+        let mut tracker = SyncTracker::new();
+        for version in [1, 2, 5] {
+            tracker.process_incoming(&SyncMessage::new(version, 0, 0, vec![1]));
+        }
+
+        // Without negotiation the wire format is unchanged
+        assert_eq!(tracker.missing_bitmap(), 0);
+        assert_eq!(tracker.create_ack().encode().len(), SYNC_MESSAGE_HEADER_SIZE);
+    }
+
+    #[test]
+    fn test_nack_ignores_coalesced_prefix_and_unsent_versions() {
+        let mut tracker = SyncTracker::new();
+        tracker.set_nack_enabled(true);
+
+        // First state we see is 4: versions 1..3 were coalesced, not lost
+        tracker.process_incoming(&SyncMessage::new(4, 0, 0, vec![1]));
+        tracker.process_incoming(&SyncMessage::new(6, 0, 5, vec![1]));
+        assert_eq!(tracker.missing_bitmap(), 0b1);
+
+        // 8 is diffed from 6, so 7 was coalesced into it too
+        tracker.process_incoming(&SyncMessage::new(8, 0, 6, vec![1]));
+        assert_eq!(tracker.missing_bitmap(), 0b100);
+
+        // A NACK for versions we never sent is dropped
+        tracker.bump_version();
+        tracker.record_sent(1);
+        tracker.process_incoming(&SyncMessage::ack_only(6, 4).with_nack(0b111));
+        assert_eq!(tracker.take_requested_retransmits(), vec![1]);
+    }
+
+    #[test]
+    fn test_peer_recv_window_stalls_sends() {
+        let mut tracker = SyncTracker::new();
+        assert_eq!(tracker.peer_recv_window(), None);
+
+        // Peer advertises room for two unacked messages
+        tracker.process_incoming(&SyncMessage::ack_only(0, 0).with_recv_window(2));
+        assert_eq!(tracker.peer_recv_window(), Some(2));
+
+        for _ in 0..2 {
+            tracker.bump_version();
+            assert!(tracker.can_send_updates());
+            tracker.record_sent_bytes(tracker.current_version(), 1);
+        }
+        tracker.bump_version();
+        assert!(tracker.is_window_full());
+        assert!(!tracker.can_send_updates());
+
+        // An ack reopens the window
+        tracker.process_incoming(&SyncMessage::ack_only(0, 2).with_recv_window(2));
+        assert!(tracker.can_send_updates());
+    }
+
+    #[test]
+    fn test_recv_window_counts_messages_not_versions() {
+        let mut tracker = SyncTracker::new();
+        tracker.process_incoming(&SyncMessage::ack_only(0, 0).with_recv_window(2));
+
+        // Ten local changes coalesced into one message fill one slot
+        for _ in 0..10 {
+            tracker.bump_version();
+        }
+        tracker.record_sent_bytes(tracker.current_version(), 1);
+        tracker.bump_version();
+        assert_eq!(tracker.messages_in_flight(), 1);
+        assert!(tracker.can_send_updates());
+    }
+
+    #[test]
+    fn test_retransmit_timeout_reopens_window() {
+        let rto = Duration::from_secs(1);
+        let sent_at = Instant::now();
+        let mut tracker = SyncTracker::new();
+        tracker.process_incoming(&SyncMessage::ack_only(0, 0).with_recv_window(1));
+
+        tracker.bump_version();
+        tracker.record_sent_bytes_at(1, 10, sent_at);
+        tracker.bump_version();
+        assert!(!tracker.can_send_updates());
+
+        // Not yet timed out
+        assert!(!tracker.on_retransmit_timeout_at(sent_at + rto / 2, rto));
+        assert!(!tracker.can_send_updates());
+
+        // Lost: everything above the ack goes out again
+        assert!(tracker.on_retransmit_timeout_at(sent_at + rto, rto));
+        assert_eq!(tracker.last_sent_version(), 0);
+        assert_eq!(tracker.bytes_in_flight(), 0);
+        assert!(tracker.can_send_updates());
+
+        // Nothing in flight, nothing to time out
+        assert!(!tracker.on_retransmit_timeout_at(sent_at + rto * 2, rto));
+    }
+
+    #[test]
+    fn test_recv_window_advertised_in_acks() {
+        let mut tracker = SyncTracker::new();
+        assert_eq!(tracker.create_ack().recv_window, None);
+
+        tracker.set_recv_window(Some(8));
+        assert_eq!(tracker.create_ack().recv_window, Some(8));
+        assert_eq!(tracker.create_message(vec![1], 0).recv_window, Some(8));
+
+        tracker.reset();
+        assert_eq!(tracker.recv_window(), Some(8));
+    }
 }
@@ -0,0 +1,141 @@
+//! Tunable transport timing.
+//!
+//! The `pacing` and `timing` constants are the protocol's recommended
+//! values and remain the defaults here. `TransportConfig` lets a deployment
+//! (or a test) override them: a LAN can use tighter keepalives and RTO
+//! bounds, a satellite link looser ones.
+
+use std::time::Duration;
+
+use super::pacing::constants as pacing;
+use super::timing::constants as timing;
+
+/// Timing thresholds used by [`FramePacer`] and [`RetransmitController`].
+///
+/// [`FramePacer`]: super::FramePacer
+/// [`RetransmitController`]: super::RetransmitController
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransportConfig {
+    /// Idle time after which a keepalive is sent.
+    pub keepalive_interval: Duration,
+    /// Silence after which the connection is considered dead.
+    pub dead_interval: Duration,
+    /// Wait after a state change before sending, to batch rapid changes.
+    pub collection_interval: Duration,
+    /// Maximum time to delay an ack-only frame.
+    pub delayed_ack_timeout: Duration,
+    /// Lower bound on the interval between frames.
+    pub min_frame_interval: Duration,
+    /// Hard cap on the frame rate.
+    pub max_frame_rate_hz: u32,
+    /// Retransmission timeout before the first RTT sample.
+    pub initial_rto: Duration,
+    /// Minimum retransmission timeout.
+    pub min_rto: Duration,
+    /// Maximum retransmission timeout (also caps backoff).
+    pub max_rto: Duration,
+    /// Retransmits before giving up.
+    pub max_retransmits: u32,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        Self {
+            keepalive_interval: pacing::KEEPALIVE_INTERVAL,
+            dead_interval: pacing::DEAD_INTERVAL,
+            collection_interval: pacing::COLLECTION_INTERVAL,
+            delayed_ack_timeout: pacing::DELAYED_ACK_TIMEOUT,
+            min_frame_interval: pacing::MIN_FRAME_INTERVAL_FLOOR,
+            max_frame_rate_hz: pacing::MAX_FRAME_RATE_HZ,
+            initial_rto: timing::INITIAL_RTO,
+            min_rto: timing::MIN_RTO,
+            max_rto: timing::MAX_RTO,
+            max_retransmits: pacing::MAX_RETRANSMITS,
+        }
+    }
+}
+
+impl TransportConfig {
+    /// Create a config with the protocol defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the keepalive interval.
+    pub fn keepalive_interval(mut self, interval: Duration) -> Self {
+        self.keepalive_interval = interval;
+        self
+    }
+
+    /// Set the dead interval.
+    pub fn dead_interval(mut self, interval: Duration) -> Self {
+        self.dead_interval = interval;
+        self
+    }
+
+    /// Set the collection interval.
+    pub fn collection_interval(mut self, interval: Duration) -> Self {
+        self.collection_interval = interval;
+        self
+    }
+
+    /// Set the delayed ACK timeout.
+    pub fn delayed_ack_timeout(mut self, timeout: Duration) -> Self {
+        self.delayed_ack_timeout = timeout;
+        self
+    }
+
+    /// Set the minimum interval between frames.
+    pub fn min_frame_interval(mut self, interval: Duration) -> Self {
+        self.min_frame_interval = interval;
+        self
+    }
+
+    /// Set the frame rate cap (at least 1 Hz).
+    pub fn max_frame_rate_hz(mut self, hz: u32) -> Self {
+        self.max_frame_rate_hz = hz.max(1);
+        self
+    }
+
+    /// Set the RTO bounds and initial value.
+    ///
+    /// `initial` is clamped into `[min, max]`.
+    pub fn rto(mut self, initial: Duration, min: Duration, max: Duration) -> Self {
+        self.min_rto = min.min(max);
+        self.max_rto = max;
+        self.initial_rto = initial.clamp(self.min_rto, self.max_rto);
+        self
+    }
+
+    /// Set the number of retransmits before giving up.
+    pub fn max_retransmits(mut self, retransmits: u32) -> Self {
+        self.max_retransmits = retransmits;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_match_constants() {
+        let config = TransportConfig::default();
+        assert_eq!(config.keepalive_interval, pacing::KEEPALIVE_INTERVAL);
+        assert_eq!(config.dead_interval, pacing::DEAD_INTERVAL);
+        assert_eq!(config.max_frame_rate_hz, pacing::MAX_FRAME_RATE_HZ);
+        assert_eq!(config.initial_rto, timing::INITIAL_RTO);
+        assert_eq!(config.max_rto, timing::MAX_RTO);
+    }
+
+    #[test]
+    fn test_rto_clamped() {
+        let config = TransportConfig::new().rto(
+            Duration::from_secs(5),
+            Duration::from_millis(10),
+            Duration::from_secs(2),
+        );
+        assert_eq!(config.initial_rto, Duration::from_secs(2));
+        assert_eq!(config.min_rto, Duration::from_millis(10));
+    }
+}
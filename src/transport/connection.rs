@@ -5,9 +5,12 @@
 use std::net::SocketAddr;
 use std::time::Instant;
 
-use super::frame::SessionId;
+use crate::core::REKEY_AFTER_TIME;
+
+use super::error::TransportError;
+use super::frame::{CloseDirection, CloseReason, SessionId};
 use super::migration::MigrationState;
-use super::pacing::{FramePacer, RetransmitController};
+use super::pacing::{FramePacer, PacerAction, RetransmitController};
 use super::timing::{RttEstimator, TimestampTracker};
 
 /// Connection lifecycle state.
@@ -25,6 +28,99 @@ pub enum ConnectionPhase {
     Failed,
 }
 
+impl ConnectionPhase {
+    /// Phases this phase may legally move to.
+    ///
+    /// `Closed` and `Failed` are terminal.
+    pub fn allowed_next(self) -> &'static [ConnectionPhase] {
+        use ConnectionPhase::*;
+        match self {
+            Handshaking => &[Established, Closed, Failed],
+            Established => &[Closing, Closed, Failed],
+            Closing => &[Closed, Failed],
+            Closed | Failed => &[],
+        }
+    }
+
+    /// Check if moving to `to` is a legal transition.
+    pub fn can_transition_to(self, to: ConnectionPhase) -> bool {
+        self.allowed_next().contains(&to)
+    }
+}
+
+/// Connection lifecycle event, emitted on every phase transition and
+/// when the peer closes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// The connection moved from one phase to another.
+    PhaseChanged {
+        /// Previous phase.
+        from: ConnectionPhase,
+        /// New phase.
+        to: ConnectionPhase,
+    },
+    /// The peer sent an authenticated close frame.
+    ///
+    /// Emitted before the resulting [`PhaseChanged`](Self::PhaseChanged).
+    PeerClosed {
+        /// Which directions the peer closed.
+        direction: CloseDirection,
+        /// Why the peer closed.
+        reason: CloseReason,
+    },
+}
+
+/// Observer called with every [`ConnectionEvent`]
+pub type EventFn = Box<dyn FnMut(&ConnectionEvent) + Send>;
+
+/// [`EventFn`] wrapper so [`ConnectionState`] can derive `Debug`
+struct EventCallback(EventFn);
+
+impl std::fmt::Debug for EventCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventCallback")
+    }
+}
+
+/// Timer-driven work for a connection, in priority order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TimerEvent {
+    /// Nothing heard for the dead interval; fail the connection.
+    Dead,
+    /// The epoch reached `REKEY_AFTER_TIME`; start a rekey. While one is in
+    /// flight, due again only once the retransmission timeout passes
+    /// without a reply, to resend the request.
+    Rekey,
+    /// Unacked data timed out; resend it.
+    Retransmit,
+    /// The pacer allows pending state to go out.
+    SendData,
+    /// The delayed ACK is due.
+    SendAck,
+    /// The connection has been idle for the keepalive interval.
+    SendKeepalive,
+}
+
+/// Result of [`ConnectionState::poll_timeouts`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimerAction {
+    /// Most urgent timer that is due, if any.
+    pub due: Option<TimerEvent>,
+    /// Earliest deadline of the timers that are not due yet.
+    pub wake_at: Option<Instant>,
+}
+
+/// Snapshot of [`NonceWindow`] counters, for observability.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NonceWindowStats {
+    /// Nonces rejected for falling below the window.
+    pub rejected_below_window: u64,
+    /// Nonces rejected as duplicates within the window.
+    pub rejected_duplicate: u64,
+    /// Times a jump of at least `WINDOW_SIZE` cleared the whole window.
+    pub full_resets: u64,
+}
+
 /// Anti-replay window using a bitfield.
 ///
 /// Tracks received nonces to detect and reject replayed frames.
@@ -36,6 +132,8 @@ pub struct NonceWindow {
     /// Bitfield for nonces below highest (bit i = highest - 1 - i).
     /// We track 2048 nonces below the highest.
     window: [u64; 32], // 32 * 64 = 2048 bits
+    /// Rejection and reset counters.
+    stats: NonceWindowStats,
 }
 
 impl Default for NonceWindow {
@@ -53,9 +151,15 @@ impl NonceWindow {
         Self {
             highest: 0,
             window: [0; 32],
+            stats: NonceWindowStats::default(),
         }
     }
 
+    /// Get a snapshot of the rejection and reset counters.
+    pub fn stats(&self) -> NonceWindowStats {
+        self.stats
+    }
+
     /// Check if a nonce is valid (not replayed) and mark it as seen.
     ///
     /// Returns `true` if the nonce is valid and should be accepted,
@@ -75,12 +179,14 @@ impl NonceWindow {
             true
         } else if nonce == self.highest {
             // Duplicate of the highest
+            self.stats.rejected_duplicate += 1;
             false
         } else {
             // Nonce below highest - check window
             let offset = (self.highest - nonce) as usize;
             if offset > Self::WINDOW_SIZE {
                 // Too old, outside our window
+                self.stats.rejected_below_window += 1;
                 return false;
             }
 
@@ -91,6 +197,7 @@ impl NonceWindow {
 
             if self.window[word_idx] & mask != 0 {
                 // Already seen
+                self.stats.rejected_duplicate += 1;
                 false
             } else {
                 // Mark as seen
@@ -105,6 +212,7 @@ impl NonceWindow {
         if shift >= Self::WINDOW_SIZE {
             // Complete reset
             self.window = [0; 32];
+            self.stats.full_resets += 1;
             return;
         }
 
@@ -148,14 +256,18 @@ impl NonceWindow {
 pub struct ConnectionState {
     /// Session identifier from handshake.
     pub session_id: SessionId,
-    /// Current connection phase.
-    pub phase: ConnectionPhase,
+    /// Current connection phase; changed only through [`Self::transition`].
+    phase: ConnectionPhase,
+    /// Callback invoked for every connection event.
+    on_event: Option<EventCallback>,
     /// Remote peer address (may change during migration).
     pub remote_endpoint: SocketAddr,
     /// When we last received an authenticated frame.
     pub last_received: Instant,
     /// Current epoch (increments on rekey).
     pub epoch: u32,
+    /// When the current epoch started.
+    pub epoch_started: Instant,
 
     /// Outbound nonce counter (monotonically increasing).
     pub send_nonce: u64,
@@ -179,6 +291,13 @@ pub struct ConnectionState {
     pub remote_state_version: u64,
     /// Highest state version the peer has acknowledged from us.
     pub acked_state_version: u64,
+
+    /// We sent a close and no longer send data.
+    local_closed: bool,
+    /// The peer sent a close and no longer sends data.
+    remote_closed: bool,
+    /// When the in-flight rekey request was sent, if any.
+    rekey_started: Option<Instant>,
 }
 
 impl ConnectionState {
@@ -188,9 +307,11 @@ impl ConnectionState {
         Self {
             session_id,
             phase: ConnectionPhase::Established,
+            on_event: None,
             remote_endpoint,
             last_received: now,
             epoch: 0,
+            epoch_started: now,
 
             send_nonce: 0,
             recv_nonce_window: NonceWindow::new(),
@@ -204,6 +325,10 @@ impl ConnectionState {
             local_state_version: 0,
             remote_state_version: 0,
             acked_state_version: 0,
+
+            local_closed: false,
+            remote_closed: false,
+            rekey_started: None,
         }
     }
 
@@ -213,9 +338,11 @@ impl ConnectionState {
         Self {
             session_id: SessionId::zero(),
             phase: ConnectionPhase::Handshaking,
+            on_event: None,
             remote_endpoint,
             last_received: now,
             epoch: 0,
+            epoch_started: now,
 
             send_nonce: 0,
             recv_nonce_window: NonceWindow::new(),
@@ -229,6 +356,56 @@ impl ConnectionState {
             local_state_version: 0,
             remote_state_version: 0,
             acked_state_version: 0,
+
+            local_closed: false,
+            remote_closed: false,
+            rekey_started: None,
+        }
+    }
+
+    /// Set a callback invoked for every [`ConnectionEvent`].
+    ///
+    /// The callback may capture state, e.g. a channel sender to forward
+    /// events to another task.
+    pub fn with_event_callback(
+        mut self,
+        callback: impl FnMut(&ConnectionEvent) + Send + 'static,
+    ) -> Self {
+        self.on_event = Some(EventCallback(Box::new(callback)));
+        self
+    }
+
+    /// Get the current connection phase.
+    pub fn phase(&self) -> ConnectionPhase {
+        self.phase
+    }
+
+    /// Get the phases the connection may move to next.
+    pub fn allowed_transitions(&self) -> &'static [ConnectionPhase] {
+        self.phase.allowed_next()
+    }
+
+    /// Move to another phase, enforcing the connection state machine.
+    ///
+    /// Emits [`ConnectionEvent::PhaseChanged`] on success.
+    ///
+    /// # Errors
+    /// Returns `InvalidTransition` for a move the state machine does not
+    /// allow (including staying in the same phase); the phase is unchanged.
+    pub fn transition(&mut self, to: ConnectionPhase) -> Result<(), TransportError> {
+        let from = self.phase;
+        if !from.can_transition_to(to) {
+            return Err(TransportError::InvalidTransition { from, to });
+        }
+        self.phase = to;
+        self.emit(ConnectionEvent::PhaseChanged { from, to });
+        Ok(())
+    }
+
+    /// Pass an event to the callback, if one is set.
+    fn emit(&mut self, event: ConnectionEvent) {
+        if let Some(EventCallback(callback)) = &mut self.on_event {
+            callback(&event);
         }
     }
 
@@ -244,6 +421,71 @@ impl ConnectionState {
         self.recv_nonce_window.check_and_mark(nonce)
     }
 
+    /// Collect every connection timer into one wakeup.
+    ///
+    /// Considers the pacer (data and delayed ACK), retransmission, rekey,
+    /// keepalive and dead-connection timers. `due` is the most urgent one
+    /// whose deadline has passed at `now`, ordered as [`TimerEvent`]; after
+    /// handling it, poll again. With nothing due, sleep until `wake_at`.
+    /// Closed and failed connections have no timers.
+    pub fn poll_timeouts(&self, now: Instant) -> TimerAction {
+        if matches!(self.phase, ConnectionPhase::Closed | ConnectionPhase::Failed) {
+            return TimerAction {
+                due: None,
+                wake_at: None,
+            };
+        }
+
+        let send = match self.pacer.poll_at(now) {
+            PacerAction::SendNow => Some(now),
+            PacerAction::WaitUntil(at) => Some(at),
+            PacerAction::Idle => None,
+        };
+        let send_event = if self.pacer.has_data_pending() {
+            TimerEvent::SendData
+        } else {
+            TimerEvent::SendAck
+        };
+        // A pending frame doubles as the keepalive
+        let keepalive = send.is_none().then(|| self.pacer.keepalive_deadline()).flatten();
+        let retransmit = self
+            .has_unacked_data()
+            .then(|| self.pacer.last_frame_sent())
+            .flatten()
+            .and_then(|sent| self.retransmit.retransmit_deadline(sent));
+
+        let timers = [
+            (TimerEvent::Dead, Some(self.last_received + self.pacer.config().dead_interval)),
+            (TimerEvent::Rekey, Some(self.rekey_deadline())),
+            (TimerEvent::Retransmit, retransmit),
+            (send_event, send),
+            (TimerEvent::SendKeepalive, keepalive),
+        ];
+
+        let armed = timers
+            .into_iter()
+            .filter_map(|(event, deadline)| Some((event, deadline?)));
+        let due = armed
+            .clone()
+            .filter(|&(_, deadline)| deadline <= now)
+            .map(|(event, _)| event)
+            .min();
+        let wake_at = armed
+            .filter(|&(_, deadline)| deadline > now)
+            .map(|(_, deadline)| deadline)
+            .min();
+        TimerAction { due, wake_at }
+    }
+
+    /// When the rekey timer fires: at `REKEY_AFTER_TIME` into the epoch, or
+    /// one retransmission timeout after an unanswered rekey request.
+    fn rekey_deadline(&self) -> Instant {
+        match self.rekey_started {
+            Some(started) => started + self.retransmit.current_timeout(),
+            None => self.epoch_started + REKEY_AFTER_TIME,
+        }
+    }
+
     /// Update state after receiving an authenticated frame.
     pub fn on_authenticated_frame(&mut self, from: SocketAddr) {
         self.last_received = Instant::now();
@@ -279,37 +521,137 @@ impl ConnectionState {
         }
     }
 
-    /// Transition to closed state.
+    /// Start closing gracefully: we send no more data.
+    ///
+    /// The caller sends a full [`CloseFrame`](super::CloseFrame). The
+    /// connection moves to `Closing`, and to `Closed` once the peer's close
+    /// arrives, whether a full close or a half-close reply. Ignored unless
+    /// the connection is established or already closing.
     pub fn close(&mut self) {
-        self.phase = ConnectionPhase::Closing;
+        if !self.is_open() {
+            return;
+        }
+        self.local_closed = true;
+        let _ = self.transition(ConnectionPhase::Closing);
+        self.close_if_both_done();
+    }
+
+    /// Half-close: stop sending data but keep receiving.
+    ///
+    /// The caller sends a [`CloseFrame::half_close`](super::CloseFrame::half_close).
+    /// Our side of the state is the same as after [`close`](Self::close):
+    /// `Closing`, then `Closed` once the peer has closed its direction too.
+    pub fn close_send(&mut self) {
+        self.close();
+    }
+
+    /// Handle an authenticated close frame from the peer.
+    ///
+    /// A full close tears the connection down; a half-close only ends
+    /// inbound data, and we may keep sending until we close too. Emits
+    /// [`ConnectionEvent::PeerClosed`] with the frame's reason.
+    pub fn on_close_frame(&mut self, direction: CloseDirection, reason: CloseReason) {
+        self.emit(ConnectionEvent::PeerClosed { direction, reason });
+        self.remote_closed = true;
+        if direction == CloseDirection::Both {
+            self.local_closed = true;
+        }
+        let _ = self.transition(ConnectionPhase::Closing);
+        self.close_if_both_done();
+    }
+
+    /// Whether we may still send data frames.
+    pub fn can_send_data(&self) -> bool {
+        !self.local_closed && self.is_open()
+    }
+
+    /// Whether the peer may still send us data frames.
+    pub fn can_receive_data(&self) -> bool {
+        !self.remote_closed && self.is_open()
+    }
+
+    /// Whether we have closed our sending direction.
+    pub fn is_local_closed(&self) -> bool {
+        self.local_closed
+    }
+
+    /// Whether the peer has closed its sending direction.
+    pub fn is_remote_closed(&self) -> bool {
+        self.remote_closed
+    }
+
+    fn is_open(&self) -> bool {
+        matches!(self.phase, ConnectionPhase::Established | ConnectionPhase::Closing)
+    }
+
+    fn close_if_both_done(&mut self) {
+        if self.local_closed && self.remote_closed {
+            self.mark_closed();
+        }
     }
 
     /// Mark as fully closed.
+    ///
+    /// Ignored if the connection already closed or failed.
     pub fn mark_closed(&mut self) {
-        self.phase = ConnectionPhase::Closed;
+        let _ = self.transition(ConnectionPhase::Closed);
     }
 
     /// Mark as failed.
+    ///
+    /// Ignored if the connection already closed or failed.
     pub fn mark_failed(&mut self) {
-        self.phase = ConnectionPhase::Failed;
+        let _ = self.transition(ConnectionPhase::Failed);
     }
 
     /// Complete handshake and transition to established.
-    pub fn complete_handshake(&mut self, session_id: SessionId) {
+    ///
+    /// # Errors
+    /// Returns `InvalidTransition` unless the connection is handshaking.
+    pub fn complete_handshake(&mut self, session_id: SessionId) -> Result<(), TransportError> {
+        self.transition(ConnectionPhase::Established)?;
         self.session_id = session_id;
-        self.phase = ConnectionPhase::Established;
         self.timestamps = TimestampTracker::new(); // Reset timestamps
+        self.epoch_started = Instant::now();
+        Ok(())
     }
 
     /// Increment epoch (on rekey).
     pub fn on_rekey(&mut self) {
+        self.on_rekey_at(Instant::now());
+    }
+
+    /// Increment epoch for a rekey at a specific time (for testing).
+    pub fn on_rekey_at(&mut self, now: Instant) {
         self.epoch = self.epoch.saturating_add(1);
+        self.epoch_started = now;
+        self.rekey_started = None;
+    }
+
+    /// Record that a rekey request was sent.
+    ///
+    /// Quiets [`TimerEvent::Rekey`] until the reply completes the rekey
+    /// ([`on_rekey`](Self::on_rekey)) or the retransmission timeout passes,
+    /// so the other timers keep firing meanwhile.
+    pub fn on_rekey_started(&mut self) {
+        self.on_rekey_started_at(Instant::now());
+    }
+
+    /// Record a rekey request sent at a specific time (for testing).
+    pub fn on_rekey_started_at(&mut self, now: Instant) {
+        self.rekey_started = Some(now);
+    }
+
+    /// Check if a rekey request is waiting for its reply.
+    pub fn is_rekey_pending(&self) -> bool {
+        self.rekey_started.is_some()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::{Clock, TestClock};
     use std::net::{IpAddr, Ipv4Addr};
 
     fn test_addr(port: u16) -> SocketAddr {
@@ -361,6 +703,36 @@ mod tests {
         assert!(!window.check_and_mark(500)); // 3000 - 500 = 2500 > 2048
     }
 
+    #[test]
+    fn test_nonce_window_stats() {
+        let mut window = NonceWindow::new();
+        assert_eq!(window.stats(), NonceWindowStats::default());
+
+        assert!(window.check_and_mark(10));
+        assert!(window.check_and_mark(11));
+
+        // Large gap clears the window
+        assert!(window.check_and_mark(10_000));
+        assert!(window.check_and_mark(9_999));
+
+        // Duplicates of the highest and of a nonce inside the window
+        assert!(!window.check_and_mark(10_000));
+        assert!(!window.check_and_mark(9_999));
+        assert!(!window.check_and_mark(9_999));
+
+        // Left behind by the gap
+        assert!(!window.check_and_mark(11));
+
+        assert_eq!(
+            window.stats(),
+            NonceWindowStats {
+                rejected_below_window: 1,
+                rejected_duplicate: 3,
+                full_resets: 1,
+            }
+        );
+    }
+
     #[test]
     fn test_connection_state_nonces() {
         let mut conn = ConnectionState::new(SessionId::zero(), test_addr(8080));
@@ -379,20 +751,184 @@ mod tests {
         let addr = test_addr(8080);
         let mut conn = ConnectionState::handshaking(addr);
 
-        assert_eq!(conn.phase, ConnectionPhase::Handshaking);
+        assert_eq!(conn.phase(), ConnectionPhase::Handshaking);
 
         // Complete handshake
         let session_id = SessionId::from_bytes([1, 2, 3, 4, 5, 6]);
-        conn.complete_handshake(session_id);
-        assert_eq!(conn.phase, ConnectionPhase::Established);
+        conn.complete_handshake(session_id).unwrap();
+        assert_eq!(conn.phase(), ConnectionPhase::Established);
         assert_eq!(conn.session_id, session_id);
 
         // Close
         conn.close();
-        assert_eq!(conn.phase, ConnectionPhase::Closing);
+        assert_eq!(conn.phase(), ConnectionPhase::Closing);
+        assert!(!conn.can_send_data());
 
         conn.mark_closed();
-        assert_eq!(conn.phase, ConnectionPhase::Closed);
+        assert_eq!(conn.phase(), ConnectionPhase::Closed);
+    }
+
+    #[test]
+    fn test_half_close_keeps_reverse_direction() {
+        let mut client = ConnectionState::new(SessionId::zero(), test_addr(8080));
+        let mut server = ConnectionState::new(SessionId::zero(), test_addr(9090));
+
+        // Client finishes sending
+        client.close_send();
+        server.on_close_frame(CloseDirection::Send, CloseReason::Graceful);
+        assert_eq!(client.phase(), ConnectionPhase::Closing);
+        assert_eq!(server.phase(), ConnectionPhase::Closing);
+        assert!(!client.can_send_data());
+        assert!(!server.can_receive_data());
+
+        // Server data still flows to the client
+        assert!(server.can_send_data());
+        assert!(client.can_receive_data());
+
+        // Server closes its side; both ends tear down
+        server.close_send();
+        client.on_close_frame(CloseDirection::Send, CloseReason::Graceful);
+        assert_eq!(server.phase(), ConnectionPhase::Closed);
+        assert_eq!(client.phase(), ConnectionPhase::Closed);
+        assert!(!client.can_receive_data());
+        assert!(!server.can_send_data());
+    }
+
+    #[test]
+    fn test_close_completes_on_half_close_reply() {
+        let mut conn = ConnectionState::new(SessionId::zero(), test_addr(8080));
+        conn.close();
+        assert!(conn.is_local_closed());
+        assert!(!conn.can_send_data());
+        assert_eq!(conn.phase(), ConnectionPhase::Closing);
+
+        // The peer answers with a half-close of its own direction
+        conn.on_close_frame(CloseDirection::Send, CloseReason::Graceful);
+        assert_eq!(conn.phase(), ConnectionPhase::Closed);
+
+        // Closing before the handshake finished changes nothing
+        let mut handshaking = ConnectionState::handshaking(test_addr(8080));
+        handshaking.close();
+        assert!(!handshaking.is_local_closed());
+        assert_eq!(handshaking.phase(), ConnectionPhase::Handshaking);
+    }
+
+    #[test]
+    fn test_full_close_tears_down_both_directions() {
+        use std::sync::mpsc;
+
+        let (tx, rx) = mpsc::channel();
+        let mut conn = ConnectionState::new(SessionId::zero(), test_addr(8080))
+            .with_event_callback(move |event: &ConnectionEvent| {
+                let _ = tx.send(*event);
+            });
+        conn.on_close_frame(CloseDirection::Both, CloseReason::KeyExpired);
+
+        // The reason reaches the application ahead of the phase change
+        assert_eq!(
+            rx.try_recv(),
+            Ok(ConnectionEvent::PeerClosed {
+                direction: CloseDirection::Both,
+                reason: CloseReason::KeyExpired,
+            })
+        );
+        assert_eq!(
+            rx.try_recv(),
+            Ok(ConnectionEvent::PhaseChanged {
+                from: ConnectionPhase::Established,
+                to: ConnectionPhase::Closing,
+            })
+        );
+        assert_eq!(conn.phase(), ConnectionPhase::Closed);
+        assert!(conn.is_local_closed() && conn.is_remote_closed());
+        assert!(!conn.can_send_data());
+        assert!(!conn.can_receive_data());
+    }
+
+    fn in_phase(phase: ConnectionPhase) -> ConnectionState {
+        let mut conn = ConnectionState::handshaking(test_addr(8080));
+        conn.phase = phase;
+        conn
+    }
+
+    #[test]
+    fn test_legal_transitions() {
+        use ConnectionPhase::*;
+        let legal = [
+            (Handshaking, Established),
+            (Handshaking, Closed),
+            (Handshaking, Failed),
+            (Established, Closing),
+            (Established, Closed),
+            (Established, Failed),
+            (Closing, Closed),
+            (Closing, Failed),
+        ];
+        for (from, to) in legal {
+            let mut conn = in_phase(from);
+            assert!(conn.allowed_transitions().contains(&to));
+            conn.transition(to).unwrap();
+            assert_eq!(conn.phase(), to);
+        }
+    }
+
+    #[test]
+    fn test_illegal_transitions_rejected() {
+        use ConnectionPhase::*;
+        let illegal = [
+            (Closed, Established),
+            (Failed, Established),
+            (Closing, Established),
+            (Established, Handshaking),
+            (Closed, Failed),
+            (Established, Established),
+        ];
+        for (from, to) in illegal {
+            let mut conn = in_phase(from);
+            assert!(matches!(
+                conn.transition(to),
+                Err(TransportError::InvalidTransition { from: f, to: t }) if f == from && t == to
+            ));
+            assert_eq!(conn.phase(), from);
+        }
+        assert!(in_phase(Closed).allowed_transitions().is_empty());
+
+        // Completing a handshake on a closed connection is refused
+        let mut conn = in_phase(Closed);
+        assert!(conn.complete_handshake(SessionId::from_bytes([1; 6])).is_err());
+        assert_eq!(conn.session_id, SessionId::zero());
+    }
+
+    #[test]
+    fn test_transition_emits_event() {
+        use std::sync::mpsc;
+
+        // Forward events to a channel, as an application would
+        let (tx, rx) = mpsc::channel();
+        let mut conn = ConnectionState::new(SessionId::zero(), test_addr(8080))
+            .with_event_callback(move |event: &ConnectionEvent| {
+                let _ = tx.send(*event);
+            });
+        conn.close();
+        conn.mark_closed();
+        // Rejected transitions emit nothing
+        conn.mark_closed();
+        drop(conn);
+
+        let events: Vec<_> = rx.iter().collect();
+        assert_eq!(
+            events,
+            [
+                ConnectionEvent::PhaseChanged {
+                    from: ConnectionPhase::Established,
+                    to: ConnectionPhase::Closing,
+                },
+                ConnectionEvent::PhaseChanged {
+                    from: ConnectionPhase::Closing,
+                    to: ConnectionPhase::Closed,
+                },
+            ]
+        );
     }
 
     #[test]
@@ -417,4 +953,111 @@ mod tests {
         assert!(conn.is_alive());
         assert!(!conn.is_failed());
     }
+
+    fn clocked_state(clock: &TestClock) -> ConnectionState {
+        let mut conn = ConnectionState::new(SessionId::zero(), test_addr(8080));
+        conn.pacer = FramePacer::new().with_clock(clock.shared());
+        conn.retransmit = RetransmitController::new(super::super::timing::constants::INITIAL_RTO).with_clock(clock.shared());
+        conn.last_received = clock.now();
+        conn.epoch_started = clock.now();
+        conn
+    }
+
+    #[test]
+    fn test_poll_timeouts_reports_earliest_and_advances() {
+        let clock = TestClock::new();
+        let mut conn = clocked_state(&clock);
+        let start = clock.now();
+        let config = *conn.pacer.config();
+
+        // Fresh connection: only the dead and rekey timers are armed
+        let action = conn.poll_timeouts(start);
+        assert_eq!(action.due, None);
+        assert_eq!(action.wake_at, Some(start + config.dead_interval));
+
+        // Local change: the collection window is the nearest timer
+        conn.pacer.on_state_change();
+        let action = conn.poll_timeouts(start);
+        assert_eq!(action.due, None);
+        assert_eq!(action.wake_at, Some(start + config.collection_interval));
+
+        clock.advance(config.collection_interval);
+        let now = clock.now();
+        assert_eq!(conn.poll_timeouts(now).due, Some(TimerEvent::SendData));
+
+        // Send it; the retransmit timer takes over
+        conn.pacer.on_frame_sent_at(now);
+        conn.local_state_version = 1;
+        let action = conn.poll_timeouts(now);
+        assert_eq!(action.due, None);
+        let retransmit_at = now + conn.retransmit.current_timeout();
+        assert_eq!(action.wake_at, Some(retransmit_at));
+
+        clock.advance(retransmit_at - now);
+        let now = clock.now();
+        assert_eq!(conn.poll_timeouts(now).due, Some(TimerEvent::Retransmit));
+
+        // Acked: next up is the keepalive for the idle link
+        conn.on_ack(1);
+        let action = conn.poll_timeouts(now);
+        assert_eq!(action.due, None);
+        let keepalive_at = conn.pacer.keepalive_deadline().unwrap();
+        assert_eq!(action.wake_at, Some(keepalive_at));
+
+        clock.advance(keepalive_at - now);
+        assert_eq!(conn.poll_timeouts(clock.now()).due, Some(TimerEvent::SendKeepalive));
+    }
+
+    #[test]
+    fn test_poll_timeouts_rekey_quiet_while_in_flight() {
+        let clock = TestClock::new();
+        let mut conn = clocked_state(&clock);
+
+        // Keep the link busy so the dead timer stays out of the way
+        clock.advance(REKEY_AFTER_TIME);
+        conn.last_received = clock.now();
+        let now = clock.now();
+        conn.pacer.on_state_change();
+        assert_eq!(conn.poll_timeouts(now).due, Some(TimerEvent::Rekey));
+
+        // Request sent: pending data goes out instead of Rekey again
+        conn.on_rekey_started_at(now);
+        assert!(conn.is_rekey_pending());
+        let retry_at = now + conn.retransmit.current_timeout();
+        clock.advance(conn.pacer.config().collection_interval);
+        let now = clock.now();
+        assert_eq!(conn.poll_timeouts(now).due, Some(TimerEvent::SendData));
+
+        // No reply within the RTO: Rekey fires again to resend the request
+        conn.pacer.on_frame_sent_at(now);
+        clock.advance(retry_at - now);
+        assert_eq!(conn.poll_timeouts(clock.now()).due, Some(TimerEvent::Rekey));
+
+        // The reply completes the rekey and restarts the epoch timer
+        conn.on_rekey_at(clock.now());
+        assert!(!conn.is_rekey_pending());
+        let action = conn.poll_timeouts(clock.now());
+        assert_ne!(action.due, Some(TimerEvent::Rekey));
+    }
+
+    #[test]
+    fn test_poll_timeouts_dead_takes_precedence() {
+        let clock = TestClock::new();
+        let mut conn = clocked_state(&clock);
+        let dead_interval = conn.pacer.config().dead_interval;
+
+        conn.pacer.on_state_change();
+        conn.local_state_version = 1;
+        clock.advance(dead_interval);
+        assert_eq!(conn.poll_timeouts(clock.now()).due, Some(TimerEvent::Dead));
+
+        conn.mark_failed();
+        assert_eq!(
+            conn.poll_timeouts(clock.now()),
+            TimerAction {
+                due: None,
+                wake_at: None
+            }
+        );
+    }
 }
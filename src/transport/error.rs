@@ -7,6 +7,9 @@ use std::io;
 
 use thiserror::Error;
 
+use crate::core::CryptoError;
+
+use super::connection::ConnectionPhase;
 use super::frame::FrameError;
 
 /// Transport layer errors.
@@ -65,10 +68,58 @@ pub enum TransportError {
     #[error("migration rate limited")]
     MigrationRateLimited,
 
+    /// Illegal connection phase transition.
+    #[error("invalid phase transition from {from:?} to {to:?}")]
+    InvalidTransition {
+        /// Phase the connection was in.
+        from: ConnectionPhase,
+        /// Phase that was requested.
+        to: ConnectionPhase,
+    },
+
     /// Counter exhaustion - nonce counter overflow.
     /// This is a critical security error requiring session termination.
     #[error("nonce counter exhaustion - session must be terminated")]
     CounterExhaustion,
+
+    /// Session keys outlived `REJECT_AFTER_TIME`.
+    /// The session must rekey or be terminated.
+    #[error("session keys expired - session must rekey or terminate")]
+    SessionExpired,
+
+    /// Other crypto layer error.
+    #[error("crypto error: {0}")]
+    Crypto(CryptoError),
+}
+
+/// Connection migration errors.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationError {
+    /// Too many new-address validations in the current window.
+    #[error("too many migration attempts")]
+    TooManyAttempts,
+}
+
+impl From<MigrationError> for TransportError {
+    fn from(err: MigrationError) -> Self {
+        match err {
+            MigrationError::TooManyAttempts => TransportError::MigrationRateLimited,
+        }
+    }
+}
+
+impl From<CryptoError> for TransportError {
+    fn from(err: CryptoError) -> Self {
+        match err {
+            CryptoError::DecryptionFailed => TransportError::AuthenticationFailed,
+            CryptoError::ReplayDetected => TransportError::NonceReplay,
+            CryptoError::CounterExhaustion | CryptoError::EpochExhaustion => {
+                TransportError::CounterExhaustion
+            }
+            CryptoError::SessionExpired => TransportError::SessionExpired,
+            other => TransportError::Crypto(other),
+        }
+    }
 }
 
 impl TransportError {
@@ -95,6 +146,7 @@ impl TransportError {
                 | TransportError::MaxRetransmitsExceeded
                 | TransportError::ConnectionClosed
                 | TransportError::CounterExhaustion
+                | TransportError::SessionExpired
         )
     }
 
@@ -106,6 +158,7 @@ impl TransportError {
                 | TransportError::NonceReplay
                 | TransportError::NonceTooOld
                 | TransportError::CounterExhaustion
+                | TransportError::SessionExpired
         )
     }
 }
@@ -126,7 +179,7 @@ mod tests {
         assert!(TransportError::FrameTooSmall.is_silent_drop());
 
         assert!(!TransportError::ConnectionTimeout.is_silent_drop());
-        assert!(!TransportError::Io(io::Error::new(io::ErrorKind::Other, "test")).is_silent_drop());
+        assert!(!TransportError::Io(io::Error::other("test")).is_silent_drop());
     }
 
     #[test]
@@ -140,6 +193,18 @@ mod tests {
         assert!(!TransportError::NonceReplay.is_fatal());
     }
 
+    #[test]
+    fn test_from_crypto_error() {
+        assert!(TransportError::from(CryptoError::DecryptionFailed).is_silent_drop());
+        assert!(TransportError::from(CryptoError::ReplayDetected).is_silent_drop());
+        assert!(TransportError::from(CryptoError::EpochExhaustion).is_fatal());
+        assert!(TransportError::from(CryptoError::SessionExpired).is_fatal());
+        assert!(matches!(
+            TransportError::from(CryptoError::EncryptionFailed),
+            TransportError::Crypto(CryptoError::EncryptionFailed)
+        ));
+    }
+
     #[test]
     fn test_security_errors() {
         assert!(TransportError::AuthenticationFailed.is_security_error());
@@ -3,6 +3,8 @@
 //! Implements frame formats from 2-TRANSPORT.md:
 //! - Data frame (0x03)
 //! - Close frame (0x05)
+//! - Retry frame (0x06)
+//! - Handshake flags (the `Reserved` byte of 0x01/0x02)
 
 use thiserror::Error;
 
@@ -20,6 +22,14 @@ pub mod sizes {
     pub const MIN_FRAME_SIZE: usize = DATA_FRAME_HEADER_SIZE + AEAD_TAG_SIZE;
     /// Payload header size (timestamp + echo + length).
     pub const PAYLOAD_HEADER_SIZE: usize = 4 + 4 + 2;
+    /// Retry cookie size (see `crypto::CookieGenerator`).
+    pub const RETRY_COOKIE_SIZE: usize = 24;
+    /// Close plaintext size (reason + final ack).
+    pub const CLOSE_PLAINTEXT_SIZE: usize = 1 + 8;
+    /// Close plaintext size before reason codes (final ack only).
+    pub const LEGACY_CLOSE_PLAINTEXT_SIZE: usize = 8;
+    /// Retry frame size (type + reserved + cookie).
+    pub const RETRY_FRAME_SIZE: usize = 1 + 1 + RETRY_COOKIE_SIZE;
     /// Recommended maximum payload size for mobile networks.
     pub const DEFAULT_MAX_PAYLOAD: usize = 1200;
 }
@@ -38,6 +48,8 @@ pub enum FrameType {
     Rekey = 0x04,
     /// Graceful connection close.
     Close = 0x05,
+    /// Stateless handshake retry carrying a cookie.
+    Retry = 0x06,
 }
 
 impl FrameType {
@@ -49,6 +61,7 @@ impl FrameType {
             0x03 => Some(Self::Data),
             0x04 => Some(Self::Rekey),
             0x05 => Some(Self::Close),
+            0x06 => Some(Self::Retry),
             _ => None,
         }
     }
@@ -70,6 +83,10 @@ impl FrameFlags {
     pub const ACK_ONLY: Self = Self(0x01);
     /// Extension data follows payload.
     pub const HAS_EXTENSION: Self = Self(0x02);
+    /// Payload is compressed (compression extension).
+    pub const COMPRESSED: Self = Self(0x04);
+    /// Close frame only ends the sender's direction (half-close).
+    pub const HALF_CLOSE: Self = Self(0x08);
 
     /// Create flags from a raw byte.
     pub fn from_byte(byte: u8) -> Self {
@@ -91,6 +108,11 @@ impl FrameFlags {
         self.0 & 0x02 != 0
     }
 
+    /// Check if COMPRESSED flag is set.
+    pub fn is_compressed(self) -> bool {
+        self.0 & 0x04 != 0
+    }
+
     /// Set ACK_ONLY flag.
     pub fn with_ack_only(self) -> Self {
         Self(self.0 | 0x01)
@@ -101,9 +123,106 @@ impl FrameFlags {
         Self(self.0 | 0x02)
     }
 
+    /// Set COMPRESSED flag.
+    pub fn with_compressed(self) -> Self {
+        Self(self.0 | 0x04)
+    }
+
     /// Check if reserved bits are valid (must be zero).
     pub fn is_valid(self) -> bool {
-        self.0 & 0xFC == 0
+        self.0 & 0xF0 == 0
+    }
+}
+
+/// How strictly to validate the handshake flags byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HandshakeValidation {
+    /// Reject any flag bit this implementation does not understand.
+    #[default]
+    Strict,
+    /// Accept unknown flag bits and surface them to the caller.
+    Lenient,
+}
+
+/// Flags carried in the `Reserved` byte of handshake frames.
+///
+/// Wire format (HandshakeInit / HandshakeResp):
+/// ```text
+/// [Type:1][Flags:1][Version:2 | SessionID:6][Noise message...]
+/// ```
+///
+/// # Forward compatibility
+///
+/// Protocol v1 defines two flags, [`EARLY_DATA`](Self::EARLY_DATA) and
+/// [`COOKIE`](Self::COOKIE); all other bits MUST be zero. Later versions may assign bits to optional
+/// features. A peer
+/// that sees bits it does not understand either rejects the handshake
+/// ([`HandshakeValidation::Strict`]) or proceeds as if they were clear
+/// ([`HandshakeValidation::Lenient`]). In both cases the raw byte is
+/// preserved, so a newer feature is never silently lost: it either fails
+/// loudly or remains visible via [`unknown_bits`](Self::unknown_bits).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HandshakeFlags(u8);
+
+impl HandshakeFlags {
+    /// No flags set.
+    pub const NONE: Self = Self(0);
+    /// The init carries 0-RTT early data; on a response, the server
+    /// accepted it (see `crypto::noise`).
+    pub const EARLY_DATA: Self = Self(0x01);
+    /// The init echoes a retry cookie, placed right after the version:
+    /// `[Type:1][Flags:1][Version:2][Cookie:24][Noise message...]`.
+    pub const COOKIE: Self = Self(0x02);
+    /// Bits defined by this protocol version.
+    pub const KNOWN_MASK: u8 = 0x03;
+
+    /// Create flags from a raw byte.
+    pub fn from_byte(byte: u8) -> Self {
+        Self(byte)
+    }
+
+    /// Get the raw byte value.
+    pub fn as_byte(self) -> u8 {
+        self.0
+    }
+
+    /// Check if the early data flag is set.
+    pub fn has_early_data(self) -> bool {
+        self.0 & Self::EARLY_DATA.0 != 0
+    }
+
+    /// Check if the retry cookie flag is set.
+    pub fn has_cookie(self) -> bool {
+        self.0 & Self::COOKIE.0 != 0
+    }
+
+    /// Combine with another set of flags.
+    pub fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Get the bits not defined by this protocol version.
+    pub fn unknown_bits(self) -> u8 {
+        self.0 & !Self::KNOWN_MASK
+    }
+
+    /// Check if all set bits are defined by this protocol version.
+    pub fn is_valid(self) -> bool {
+        self.unknown_bits() == 0
+    }
+
+    /// Parse and validate a handshake flags byte.
+    ///
+    /// # Errors
+    /// Returns `InvalidHandshakeFlags` in strict mode if unknown bits are set.
+    pub fn parse(byte: u8, validation: HandshakeValidation) -> Result<Self, FrameError> {
+        let flags = Self(byte);
+        match validation {
+            HandshakeValidation::Strict if !flags.is_valid() => {
+                Err(FrameError::InvalidHandshakeFlags(byte))
+            }
+            _ => Ok(flags),
+        }
     }
 }
 
@@ -331,15 +450,169 @@ impl DataFrame {
     pub fn aad(&self) -> [u8; sizes::DATA_FRAME_HEADER_SIZE] {
         self.header.to_bytes()
     }
+
+    /// Encrypt the frame under `session` and return the full on-wire bytes.
+    ///
+    /// The session assigns the nonce counter and owns the session ID, so
+    /// both are written back into `self.header` before serializing.
+    #[cfg(feature = "crypto")]
+    pub fn encode(
+        &mut self,
+        session: &mut crate::crypto::CryptoSession,
+    ) -> Result<Vec<u8>, super::TransportError> {
+        self.header.session_id = SessionId::from_bytes(session.session_id().0);
+        let (nonce_counter, ciphertext) = session.encrypt_frame(
+            self.header.frame_type.as_byte(),
+            self.header.flags.as_byte(),
+            &self.plaintext(),
+        )?;
+        self.header.nonce_counter = nonce_counter;
+
+        let mut wire = Vec::with_capacity(sizes::DATA_FRAME_HEADER_SIZE + ciphertext.len());
+        wire.extend_from_slice(&self.header.to_bytes());
+        wire.extend_from_slice(&ciphertext);
+        Ok(wire)
+    }
+
+    /// Parse, decrypt and split a received frame.
+    ///
+    /// # Errors
+    /// Returns `Frame` for malformed input, and the silent-drop
+    /// `AuthenticationFailed` / `NonceReplay` errors from decryption.
+    #[cfg(feature = "crypto")]
+    pub fn decode(
+        data: &[u8],
+        session: &mut crate::crypto::CryptoSession,
+    ) -> Result<Self, super::TransportError> {
+        let header = parse_frame_header(data)?;
+        let plaintext = session.decrypt_frame(
+            header.frame_type.as_byte(),
+            header.flags.as_byte(),
+            header.nonce_counter,
+            &data[sizes::DATA_FRAME_HEADER_SIZE..],
+        )?;
+        let (payload_header, sync_message) = parse_payload(&plaintext)?;
+
+        Ok(Self {
+            header,
+            payload_header,
+            sync_message: sync_message.to_vec(),
+        })
+    }
+}
+
+/// Which directions a close frame shuts down.
+///
+/// Carried in the close frame's flags byte, so peers that predate
+/// half-close read every close as [`Both`](Self::Both).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CloseDirection {
+    /// The sender is done in both directions.
+    #[default]
+    Both,
+    /// The sender is done sending but still receives (half-close).
+    Send,
+}
+
+impl CloseDirection {
+    /// Close frame flag bit marking a half-close.
+    ///
+    /// Distinct from [`FrameFlags::ACK_ONLY`], so a half-close is never
+    /// mistaken for an ack.
+    pub const HALF_CLOSE_FLAG: u8 = FrameFlags::HALF_CLOSE.0;
+
+    /// Read the direction from a close frame's flags.
+    pub fn from_flags(flags: FrameFlags) -> Self {
+        if flags.as_byte() & Self::HALF_CLOSE_FLAG != 0 {
+            Self::Send
+        } else {
+            Self::Both
+        }
+    }
+
+    /// Flags encoding this direction.
+    pub fn as_flags(self) -> FrameFlags {
+        match self {
+            Self::Both => FrameFlags::NONE,
+            Self::Send => FrameFlags::from_byte(Self::HALF_CLOSE_FLAG),
+        }
+    }
+}
+
+/// Why a connection was closed, carried in the close plaintext.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CloseReason {
+    /// Normal shutdown by the application.
+    #[default]
+    Graceful,
+    /// Nothing was received for too long.
+    Idle,
+    /// Keys reached their lifetime limit and could not be rekeyed.
+    KeyExpired,
+    /// The peer violated the protocol.
+    ProtocolError,
+    /// The peer's new address failed path validation.
+    MigrationFailed,
+    /// The endpoint is shutting down.
+    Shutdown,
+    /// A code this implementation does not know.
+    Other(UnknownCloseCode),
+}
+
+/// A close reason code with no named [`CloseReason`] variant.
+///
+/// Only [`CloseReason::from_byte`] creates one, so a known code always
+/// parses to its named variant and every reason round-trips.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownCloseCode(u8);
+
+impl UnknownCloseCode {
+    /// Get the raw reason code.
+    pub fn code(self) -> u8 {
+        self.0
+    }
+}
+
+impl CloseReason {
+    /// Parse a reason code.
+    pub fn from_byte(byte: u8) -> Self {
+        match byte {
+            0x00 => Self::Graceful,
+            0x01 => Self::Idle,
+            0x02 => Self::KeyExpired,
+            0x03 => Self::ProtocolError,
+            0x04 => Self::MigrationFailed,
+            0x05 => Self::Shutdown,
+            other => Self::Other(UnknownCloseCode(other)),
+        }
+    }
+
+    /// Get the reason code.
+    pub fn as_byte(self) -> u8 {
+        match self {
+            Self::Graceful => 0x00,
+            Self::Idle => 0x01,
+            Self::KeyExpired => 0x02,
+            Self::ProtocolError => 0x03,
+            Self::MigrationFailed => 0x04,
+            Self::Shutdown => 0x05,
+            Self::Other(code) => code.0,
+        }
+    }
 }
 
 /// A close frame for graceful termination.
+///
+/// Plaintext: `[Reason:1][FinalAck:8]`. Peers predating reason codes send
+/// only the final ack, which decodes as [`CloseReason::Graceful`].
 #[derive(Debug, Clone, Copy)]
 pub struct CloseFrame {
     /// The frame header.
     pub header: DataFrameHeader,
     /// Highest state version acknowledged (encrypted).
     pub final_ack: u64,
+    /// Why the connection is closing (encrypted).
+    pub reason: CloseReason,
 }
 
 impl CloseFrame {
@@ -348,12 +621,62 @@ impl CloseFrame {
         Self {
             header: DataFrameHeader::close(session_id, nonce_counter),
             final_ack,
+            reason: CloseReason::Graceful,
         }
     }
 
+    /// Set the close reason.
+    pub fn with_reason(mut self, reason: CloseReason) -> Self {
+        self.reason = reason;
+        self
+    }
+
+    /// Create a half-close frame: we are done sending, but keep receiving.
+    pub fn half_close(session_id: SessionId, nonce_counter: u64, final_ack: u64) -> Self {
+        Self::new(session_id, nonce_counter, final_ack).with_direction(CloseDirection::Send)
+    }
+
+    /// Set which directions this frame closes.
+    pub fn with_direction(mut self, direction: CloseDirection) -> Self {
+        self.header.flags = direction.as_flags();
+        self
+    }
+
+    /// Which directions this frame closes.
+    pub fn direction(&self) -> CloseDirection {
+        CloseDirection::from_flags(self.header.flags)
+    }
+
     /// Get the plaintext that will be encrypted.
-    pub fn plaintext(&self) -> [u8; 8] {
-        self.final_ack.to_le_bytes()
+    pub fn plaintext(&self) -> [u8; sizes::CLOSE_PLAINTEXT_SIZE] {
+        Self::encode_plaintext(self.reason, self.final_ack)
+    }
+
+    /// Encode a close plaintext.
+    pub fn encode_plaintext(reason: CloseReason, final_ack: u64) -> [u8; sizes::CLOSE_PLAINTEXT_SIZE] {
+        let mut buf = [0u8; sizes::CLOSE_PLAINTEXT_SIZE];
+        buf[0] = reason.as_byte();
+        buf[1..].copy_from_slice(&final_ack.to_le_bytes());
+        buf
+    }
+
+    /// Decode a decrypted close plaintext into its reason and final ack.
+    ///
+    /// An 8-byte plaintext is the format from before reason codes and
+    /// decodes as [`CloseReason::Graceful`].
+    pub fn decode_plaintext(plaintext: &[u8]) -> Result<(CloseReason, u64), FrameError> {
+        if plaintext.len() == sizes::LEGACY_CLOSE_PLAINTEXT_SIZE {
+            let final_ack = u64::from_le_bytes(plaintext.try_into().expect("length checked"));
+            return Ok((CloseReason::Graceful, final_ack));
+        }
+        if plaintext.len() < sizes::CLOSE_PLAINTEXT_SIZE {
+            return Err(FrameError::TooShort {
+                expected: sizes::LEGACY_CLOSE_PLAINTEXT_SIZE,
+                actual: plaintext.len(),
+            });
+        }
+        let final_ack = u64::from_le_bytes(plaintext[1..9].try_into().expect("length checked"));
+        Ok((CloseReason::from_byte(plaintext[0]), final_ack))
     }
 
     /// Get the AAD.
@@ -362,6 +685,47 @@ impl CloseFrame {
     }
 }
 
+/// A stateless retry, sent in reply to a handshake init without a cookie.
+///
+/// Wire format: `[Type:1][Reserved:1][Cookie:24]`. The client resends its
+/// init with [`HandshakeFlags::COOKIE`] set and the cookie echoed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryFrame {
+    /// Opaque cookie to echo.
+    pub cookie: [u8; sizes::RETRY_COOKIE_SIZE],
+}
+
+impl RetryFrame {
+    /// Create a retry frame.
+    pub fn new(cookie: [u8; sizes::RETRY_COOKIE_SIZE]) -> Self {
+        Self { cookie }
+    }
+
+    /// Serialize to bytes.
+    pub fn to_bytes(&self) -> [u8; sizes::RETRY_FRAME_SIZE] {
+        let mut buf = [0u8; sizes::RETRY_FRAME_SIZE];
+        buf[0] = FrameType::Retry.as_byte();
+        buf[2..].copy_from_slice(&self.cookie);
+        buf
+    }
+
+    /// Parse from bytes.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, FrameError> {
+        if data.len() < sizes::RETRY_FRAME_SIZE {
+            return Err(FrameError::TooShort {
+                expected: sizes::RETRY_FRAME_SIZE,
+                actual: data.len(),
+            });
+        }
+        if data[0] != FrameType::Retry.as_byte() {
+            return Err(FrameError::InvalidType(data[0]));
+        }
+        let mut cookie = [0u8; sizes::RETRY_COOKIE_SIZE];
+        cookie.copy_from_slice(&data[2..sizes::RETRY_FRAME_SIZE]);
+        Ok(Self { cookie })
+    }
+}
+
 /// Errors that can occur during frame parsing.
 #[derive(Debug, Error)]
 pub enum FrameError {
@@ -382,6 +746,10 @@ pub enum FrameError {
     #[error("invalid flags: 0x{0:02x} (reserved bits must be 0)")]
     InvalidFlags(u8),
 
+    /// Unknown handshake flag bits set (strict validation).
+    #[error("invalid handshake flags: 0x{0:02x} (unknown bits set)")]
+    InvalidHandshakeFlags(u8),
+
     /// Payload length mismatch.
     #[error("payload length mismatch: header says {expected}, but {actual} bytes available")]
     PayloadLengthMismatch {
@@ -422,6 +790,25 @@ pub fn parse_payload(data: &[u8]) -> Result<(PayloadHeader, &[u8]), FrameError>
     Ok((header, &data[sync_start..sync_end]))
 }
 
+/// Parse a decrypted payload, also returning the trailing extension region.
+///
+/// When `flags` has `HAS_EXTENSION`, the bytes after the sync message are
+/// the extension TLVs (for `ExtensionSet::decode`); otherwise trailing
+/// bytes are ignored, as in [`parse_payload`], and the region is empty.
+pub fn parse_payload_with_extensions(
+    data: &[u8],
+    flags: FrameFlags,
+) -> Result<(PayloadHeader, &[u8], &[u8]), FrameError> {
+    let (header, sync_message) = parse_payload(data)?;
+    let sync_end = sizes::PAYLOAD_HEADER_SIZE + sync_message.len();
+    let extensions = if flags.has_extension() {
+        &data[sync_end..]
+    } else {
+        &[]
+    };
+    Ok((header, sync_message, extensions))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -434,6 +821,7 @@ mod tests {
             FrameType::Data,
             FrameType::Rekey,
             FrameType::Close,
+            FrameType::Retry,
         ] {
             assert_eq!(FrameType::from_byte(t.as_byte()), Some(t));
         }
@@ -458,11 +846,74 @@ mod tests {
         assert!(flags.has_extension());
         assert!(flags.is_valid());
 
+        let flags = FrameFlags::NONE.with_compressed();
+        assert!(flags.is_compressed());
+        assert!(!flags.is_ack_only());
+        assert!(flags.is_valid());
+
         // Reserved bits must be zero
-        let invalid = FrameFlags::from_byte(0x04);
+        let invalid = FrameFlags::from_byte(0x10);
         assert!(!invalid.is_valid());
     }
 
+    #[test]
+    fn test_compressed_flag_combinations_roundtrip() {
+        let combos = [
+            FrameFlags::COMPRESSED,
+            FrameFlags::COMPRESSED.with_ack_only(),
+            FrameFlags::COMPRESSED.with_extension(),
+            FrameFlags::NONE.with_ack_only().with_extension().with_compressed(),
+        ];
+        for flags in combos {
+            let mut header = DataFrameHeader::new(SessionId::from_bytes([7; 6]), 99);
+            header.flags = flags;
+            let parsed = DataFrameHeader::from_bytes(&header.to_bytes()).unwrap();
+            assert_eq!(parsed, header);
+            assert!(parsed.flags.is_compressed());
+            assert_eq!(parsed.flags.is_ack_only(), flags.is_ack_only());
+            assert_eq!(parsed.flags.has_extension(), flags.has_extension());
+        }
+
+        // 0x04 was reserved before the compression bit; 0x10 still is
+        let mut bytes = DataFrameHeader::new(SessionId::zero(), 1).to_bytes();
+        bytes[1] = 0x04;
+        assert!(DataFrameHeader::from_bytes(&bytes).is_ok());
+        bytes[1] = 0x14;
+        assert!(matches!(
+            DataFrameHeader::from_bytes(&bytes),
+            Err(FrameError::InvalidFlags(0x14))
+        ));
+    }
+
+    #[cfg(feature = "extensions")]
+    #[test]
+    fn test_parse_payload_with_trailing_compression_extension() {
+        use crate::extensions::{ExtensionSet, ext_type};
+
+        let mut extensions = ExtensionSet::new();
+        extensions.add_compression(3);
+        let mut plaintext = PayloadHeader::new(10, 5, 4).to_bytes().to_vec();
+        plaintext.extend_from_slice(b"sync");
+        plaintext.extend_from_slice(&extensions.encode());
+
+        let flags = FrameFlags::NONE.with_extension();
+        let (header, sync, trailing) = parse_payload_with_extensions(&plaintext, flags).unwrap();
+        assert_eq!(header.payload_length, 4);
+        assert_eq!(sync, b"sync");
+        let decoded = ExtensionSet::decode(trailing).unwrap();
+        assert!(decoded.has(ext_type::COMPRESSION));
+        assert_eq!(decoded.compression_level(), Some(3));
+
+        // Without HAS_EXTENSION the trailing bytes are not extensions
+        let (_, sync, trailing) = parse_payload_with_extensions(&plaintext, FrameFlags::NONE).unwrap();
+        assert_eq!(sync, b"sync");
+        assert!(trailing.is_empty());
+
+        // Flag set but nothing follows
+        let (_, _, trailing) = parse_payload_with_extensions(&plaintext[..14], flags).unwrap();
+        assert!(trailing.is_empty());
+    }
+
     #[test]
     fn test_session_id() {
         let id = SessionId::from_bytes([0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
@@ -537,7 +988,56 @@ mod tests {
         assert_eq!(frame.final_ack, 12345);
 
         let plaintext = frame.plaintext();
-        assert_eq!(plaintext, 12345u64.to_le_bytes());
+        assert_eq!(plaintext[0], CloseReason::Graceful.as_byte());
+        assert_eq!(plaintext[1..], 12345u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_close_reason_roundtrip() {
+        for reason in [
+            CloseReason::Graceful,
+            CloseReason::Idle,
+            CloseReason::KeyExpired,
+            CloseReason::ProtocolError,
+            CloseReason::MigrationFailed,
+            CloseReason::Shutdown,
+            CloseReason::from_byte(0x7F),
+        ] {
+            let frame = CloseFrame::new(SessionId::zero(), 1, 42).with_reason(reason);
+            assert_eq!(CloseFrame::decode_plaintext(&frame.plaintext()).unwrap(), (reason, 42));
+        }
+
+        // Known codes never parse as Other
+        for byte in 0..=u8::MAX {
+            let reason = CloseReason::from_byte(byte);
+            assert_eq!(reason.as_byte(), byte);
+            assert_eq!(matches!(reason, CloseReason::Other(_)), byte > 0x05);
+        }
+    }
+
+    #[test]
+    fn test_legacy_close_decodes_as_graceful() {
+        let legacy = 12345u64.to_le_bytes();
+        assert_eq!(
+            CloseFrame::decode_plaintext(&legacy).unwrap(),
+            (CloseReason::Graceful, 12345)
+        );
+        assert!(CloseFrame::decode_plaintext(&[0u8; 4]).is_err());
+    }
+
+    #[test]
+    fn test_close_frame_direction_roundtrip() {
+        let full = CloseFrame::new(SessionId::zero(), 1, 0);
+        assert_eq!(full.direction(), CloseDirection::Both);
+
+        let half = CloseFrame::half_close(SessionId::zero(), 2, 0);
+        assert_eq!(half.direction(), CloseDirection::Send);
+
+        // The direction survives the wire header
+        let header = DataFrameHeader::from_bytes(&half.aad()).unwrap();
+        assert_eq!(header.frame_type, FrameType::Close);
+        assert_eq!(CloseDirection::from_flags(header.flags), CloseDirection::Send);
+        assert!(!header.flags.is_ack_only());
     }
 
     #[test]
@@ -558,4 +1058,133 @@ mod tests {
             Err(FrameError::InvalidType(0xFF))
         ));
     }
+
+    #[test]
+    fn test_handshake_flags_zero_accepted() {
+        for mode in [HandshakeValidation::Strict, HandshakeValidation::Lenient] {
+            let flags = HandshakeFlags::parse(0x00, mode).unwrap();
+            assert_eq!(flags, HandshakeFlags::NONE);
+            assert!(flags.is_valid());
+        }
+    }
+
+    #[test]
+    fn test_handshake_flags_strict_rejects_unknown() {
+        assert!(matches!(
+            HandshakeFlags::parse(0x04, HandshakeValidation::Strict),
+            Err(FrameError::InvalidHandshakeFlags(0x04))
+        ));
+        assert!(matches!(
+            HandshakeFlags::parse(0x80, HandshakeValidation::Strict),
+            Err(FrameError::InvalidHandshakeFlags(0x80))
+        ));
+    }
+
+    #[test]
+    fn test_handshake_flags_lenient_tolerates_unknown() {
+        let flags = HandshakeFlags::parse(0x81, HandshakeValidation::Lenient).unwrap();
+        assert!(!flags.is_valid());
+        assert_eq!(flags.as_byte(), 0x81);
+        assert_eq!(flags.unknown_bits(), 0x80);
+        assert!(flags.has_early_data());
+    }
+
+    #[test]
+    fn test_handshake_flags_early_data_known() {
+        let flags = HandshakeFlags::parse(0x01, HandshakeValidation::Strict).unwrap();
+        assert_eq!(flags, HandshakeFlags::EARLY_DATA);
+        assert!(flags.is_valid());
+        assert!(flags.has_early_data());
+        assert!(!HandshakeFlags::NONE.has_early_data());
+    }
+
+    #[test]
+    fn test_retry_frame_roundtrip() {
+        let frame = RetryFrame::new([0xAB; sizes::RETRY_COOKIE_SIZE]);
+        let bytes = frame.to_bytes();
+        assert_eq!(bytes[0], 0x06);
+        assert_eq!(RetryFrame::from_bytes(&bytes).unwrap(), frame);
+        assert!(matches!(
+            RetryFrame::from_bytes(&bytes[..10]),
+            Err(FrameError::TooShort { .. })
+        ));
+
+        let flags = HandshakeFlags::parse(0x03, HandshakeValidation::Strict).unwrap();
+        assert!(flags.has_cookie() && flags.has_early_data());
+        assert_eq!(HandshakeFlags::EARLY_DATA.union(HandshakeFlags::COOKIE), flags);
+    }
+
+    #[cfg(feature = "crypto")]
+    fn session_pair() -> (crate::crypto::CryptoSession, crate::crypto::CryptoSession) {
+        use crate::crypto::{CryptoSession, Role, SessionKey};
+
+        let session_id = crate::crypto::SessionId::generate();
+        let initiator_key = SessionKey::from_bytes([0x01; 32]);
+        let responder_key = SessionKey::from_bytes([0x02; 32]);
+        let handshake_hash = [0x42; 32];
+
+        let initiator = CryptoSession::new(
+            session_id,
+            Role::Initiator,
+            initiator_key.clone(),
+            responder_key.clone(),
+            handshake_hash,
+        );
+        let responder = CryptoSession::new(
+            session_id,
+            Role::Responder,
+            responder_key,
+            initiator_key,
+            handshake_hash,
+        );
+        (initiator, responder)
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_data_frame_encode_decode_roundtrip() {
+        let (mut initiator, mut responder) = session_pair();
+        let mut frame = DataFrame::new(SessionId::zero(), 0, 1000, 750, b"sync message".to_vec());
+
+        let wire = frame.encode(&mut initiator).unwrap();
+        assert_eq!(
+            wire.len(),
+            sizes::DATA_FRAME_HEADER_SIZE + sizes::PAYLOAD_HEADER_SIZE + 12 + sizes::AEAD_TAG_SIZE
+        );
+        assert_eq!(frame.header.session_id.as_bytes(), &initiator.session_id().0);
+
+        let decoded = DataFrame::decode(&wire, &mut responder).unwrap();
+        assert_eq!(decoded.header, frame.header);
+        assert_eq!(decoded.payload_header.timestamp, 1000);
+        assert_eq!(decoded.payload_header.timestamp_echo, 750);
+        assert_eq!(decoded.sync_message, b"sync message");
+
+        // Same bytes again: replay
+        assert!(matches!(
+            DataFrame::decode(&wire, &mut responder),
+            Err(crate::transport::TransportError::NonceReplay)
+        ));
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn test_ack_only_frame_encode_decode() {
+        let (mut initiator, mut responder) = session_pair();
+        let mut frame = DataFrame::ack_only(SessionId::zero(), 0, 42, 0);
+
+        let mut wire = frame.encode(&mut initiator).unwrap();
+        let decoded = DataFrame::decode(&wire, &mut responder).unwrap();
+        assert!(decoded.header.flags.is_ack_only());
+        assert!(decoded.sync_message.is_empty());
+        assert_eq!(decoded.payload_header, frame.payload_header);
+
+        // Tampered flags fail authentication
+        let mut frame = DataFrame::ack_only(SessionId::zero(), 0, 43, 0);
+        wire = frame.encode(&mut initiator).unwrap();
+        wire[1] = FrameFlags::NONE.as_byte();
+        assert!(matches!(
+            DataFrame::decode(&wire, &mut responder),
+            Err(crate::transport::TransportError::AuthenticationFailed)
+        ));
+    }
 }
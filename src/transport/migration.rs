@@ -1,11 +1,26 @@
 //! Connection migration (roaming) support.
 //!
 //! Implements seamless IP address changes from 2-TRANSPORT.md.
+//!
+//! A new address is only trusted after it echoes a path challenge: the
+//! endpoint sends a random token to the candidate address and commits the
+//! migration when the same token comes back. An attacker spoofing a source
+//! address never sees the token, so it cannot redirect the session. Until
+//! then the old path stays authoritative for sending.
+//!
+//! Both peers may roam at once, each then validating the other's new
+//! address. A challenge from the peer is answered even while our own is
+//! outstanding, and repeated packets from the candidate keep the same
+//! token, so the two validations complete independently.
 
 use std::collections::HashMap;
 use std::net::{IpAddr, SocketAddr};
 use std::time::{Duration, Instant};
 
+use rand::{rngs::OsRng, RngCore};
+
+use super::MigrationError;
+
 /// Migration rate limiting constants.
 pub mod constants {
     use std::time::Duration;
@@ -15,6 +30,36 @@ pub mod constants {
 
     /// Maximum bytes to send to unvalidated address (anti-amplification).
     pub const AMPLIFICATION_FACTOR: usize = 3;
+
+    /// How long to wait for a path response before abandoning a candidate.
+    pub const PATH_VALIDATION_TIMEOUT: Duration = Duration::from_secs(3);
+
+    /// Default maximum new-address validations per window.
+    pub const MAX_VALIDATION_ATTEMPTS: u32 = 3;
+
+    /// Default window for counting validation attempts.
+    pub const VALIDATION_ATTEMPT_WINDOW: Duration = Duration::from_secs(10);
+}
+
+/// Size of a path challenge token.
+pub const PATH_CHALLENGE_SIZE: usize = 8;
+
+/// An outstanding path challenge to a candidate address.
+#[derive(Debug, Clone)]
+struct PathChallenge {
+    /// Address being validated.
+    address: SocketAddr,
+    /// Token the address must echo.
+    token: [u8; PATH_CHALLENGE_SIZE],
+    /// When the challenge was sent.
+    sent_at: Instant,
+}
+
+/// Generate an unpredictable challenge token from the OS CSPRNG.
+fn challenge_token() -> [u8; PATH_CHALLENGE_SIZE] {
+    let mut token = [0u8; PATH_CHALLENGE_SIZE];
+    OsRng.fill_bytes(&mut token);
+    token
 }
 
 /// Tracks the validation state of an address.
@@ -75,6 +120,16 @@ pub struct MigrationState {
     addresses: HashMap<SocketAddr, AddressState>,
     /// Last migration time per subnet (for rate limiting).
     subnet_last_migration: HashMap<Vec<u8>, Instant>,
+    /// Path challenge awaiting a response, if any.
+    pending: Option<PathChallenge>,
+    /// Maximum validations started per window.
+    max_attempts: u32,
+    /// Window for counting validation attempts.
+    attempt_window: Duration,
+    /// Start of the current attempt window.
+    window_start: Option<Instant>,
+    /// Validations started in the current window.
+    attempts: u32,
 }
 
 impl MigrationState {
@@ -89,9 +144,21 @@ impl MigrationState {
             current_address: initial_address,
             addresses,
             subnet_last_migration: HashMap::new(),
+            pending: None,
+            max_attempts: constants::MAX_VALIDATION_ATTEMPTS,
+            attempt_window: constants::VALIDATION_ATTEMPT_WINDOW,
+            window_start: None,
+            attempts: 0,
         }
     }
 
+    /// Set the maximum number of validations started per window.
+    pub fn with_validation_limit(mut self, max_attempts: u32, window: Duration) -> Self {
+        self.max_attempts = max_attempts;
+        self.attempt_window = window;
+        self
+    }
+
     /// Get the current validated address.
     pub fn current_address(&self) -> SocketAddr {
         self.current_address
@@ -155,6 +222,138 @@ impl MigrationState {
         true
     }
 
+    /// Start validating a candidate address.
+    ///
+    /// Returns the token to send to `candidate` in a PATH_CHALLENGE. A new
+    /// challenge replaces any outstanding one. The current address stays
+    /// authoritative until [`on_path_response`](Self::on_path_response)
+    /// accepts the echoed token.
+    ///
+    /// Calling this again for the address already being validated returns
+    /// the outstanding token (and uses no attempt), so further packets from
+    /// the candidate don't invalidate a challenge already in flight.
+    ///
+    /// # Errors
+    /// Returns `TooManyAttempts` once the per-window validation budget is
+    /// spent, so spoofed packets from ever-new addresses cannot make us
+    /// generate challenges without bound. A successful validation resets
+    /// the window.
+    pub fn begin_validation(
+        &mut self,
+        candidate: SocketAddr,
+    ) -> Result<[u8; PATH_CHALLENGE_SIZE], MigrationError> {
+        self.begin_validation_at(candidate, Instant::now())
+    }
+
+    /// Start validating a candidate address at a specific time (for testing).
+    pub fn begin_validation_at(
+        &mut self,
+        candidate: SocketAddr,
+        now: Instant,
+    ) -> Result<[u8; PATH_CHALLENGE_SIZE], MigrationError> {
+        self.expire_validation_at(now);
+        if let Some(challenge) = &self.pending
+            && challenge.address == candidate
+        {
+            return Ok(challenge.token);
+        }
+
+        let window_expired = self
+            .window_start
+            .is_none_or(|start| now.saturating_duration_since(start) >= self.attempt_window);
+        if window_expired {
+            self.window_start = Some(now);
+            self.attempts = 0;
+        }
+        if self.attempts >= self.max_attempts {
+            return Err(MigrationError::TooManyAttempts);
+        }
+        self.attempts += 1;
+
+        let token = challenge_token();
+        self.pending = Some(PathChallenge {
+            address: candidate,
+            token,
+            sent_at: now,
+        });
+        Ok(token)
+    }
+
+    /// Handle a PATH_RESPONSE echoing `token`.
+    ///
+    /// Commits the migration and returns `true` only if the token matches
+    /// the outstanding challenge. A mismatched token is ignored, leaving the
+    /// challenge open for the genuine response.
+    pub fn on_path_response(&mut self, token: [u8; PATH_CHALLENGE_SIZE]) -> bool {
+        let Some(challenge) = &self.pending else {
+            return false;
+        };
+
+        // Constant-time comparison
+        let diff = challenge
+            .token
+            .iter()
+            .zip(&token)
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if diff != 0 {
+            return false;
+        }
+
+        let address = challenge.address;
+        self.pending = None;
+        self.window_start = None;
+        self.attempts = 0;
+        self.addresses
+            .entry(address)
+            .or_insert_with(AddressState::new)
+            .validated = true;
+        self.current_address = address;
+        true
+    }
+
+    /// Handle a PATH_CHALLENGE of `bytes` bytes received from `from`.
+    ///
+    /// Returns `true` if the PATH_RESPONSE may be sent; it always goes back
+    /// to `from`, not to the current address, since the peer is validating
+    /// the path it sent from. An unvalidated sender is answered within the
+    /// anti-amplification limit (a response is no larger than the
+    /// challenge). Our own pending challenge is left untouched.
+    pub fn on_path_challenge(&mut self, from: SocketAddr, bytes: usize) -> bool {
+        self.on_receive(from, bytes);
+        if !self.can_send(from, bytes) {
+            return false;
+        }
+        self.on_send(from, bytes);
+        true
+    }
+
+    /// Abandon a challenge that has gone unanswered too long.
+    ///
+    /// Returns `true` if a pending validation timed out; sending continues
+    /// on the current (old) path.
+    pub fn expire_validation(&mut self) -> bool {
+        self.expire_validation_at(Instant::now())
+    }
+
+    /// Abandon an unanswered challenge at a specific time (for testing).
+    pub fn expire_validation_at(&mut self, now: Instant) -> bool {
+        match &self.pending {
+            Some(challenge)
+                if now.saturating_duration_since(challenge.sent_at)
+                    >= constants::PATH_VALIDATION_TIMEOUT =>
+            {
+                self.pending = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Get the address currently being validated, if any.
+    pub fn pending_address(&self) -> Option<SocketAddr> {
+        self.pending.as_ref().map(|challenge| challenge.address)
+    }
+
     /// Check if an address is validated.
     pub fn is_validated(&self, addr: SocketAddr) -> bool {
         self.addresses
@@ -286,4 +485,135 @@ mod tests {
         state.cleanup(Duration::from_nanos(1));
         assert!(state.is_validated(initial));
     }
+
+    #[test]
+    fn test_path_validation_success() {
+        let initial = addr_v4(192, 168, 1, 100, 8080);
+        let mut state = MigrationState::new(initial);
+        let new_addr = addr_v4(10, 0, 0, 50, 9090);
+
+        let token = state.begin_validation(new_addr).unwrap();
+        assert_eq!(state.pending_address(), Some(new_addr));
+
+        // Old path stays authoritative while validating
+        assert_eq!(state.current_address(), initial);
+        assert!(!state.is_validated(new_addr));
+
+        assert!(state.on_path_response(token));
+        assert_eq!(state.current_address(), new_addr);
+        assert!(state.is_validated(new_addr));
+        assert_eq!(state.pending_address(), None);
+    }
+
+    #[test]
+    fn test_path_validation_mismatched_token() {
+        let initial = addr_v4(192, 168, 1, 100, 8080);
+        let mut state = MigrationState::new(initial);
+        let new_addr = addr_v4(10, 0, 0, 50, 9090);
+
+        let token = state.begin_validation(new_addr).unwrap();
+        let mut forged = token;
+        forged[0] ^= 0xFF;
+
+        assert!(!state.on_path_response(forged));
+        assert_eq!(state.current_address(), initial);
+        assert!(!state.is_validated(new_addr));
+
+        // The genuine response still completes the migration
+        assert!(state.on_path_response(token));
+        assert_eq!(state.current_address(), new_addr);
+    }
+
+    #[test]
+    fn test_path_validation_timeout() {
+        let initial = addr_v4(192, 168, 1, 100, 8080);
+        let mut state = MigrationState::new(initial);
+        let new_addr = addr_v4(10, 0, 0, 50, 9090);
+
+        let start = Instant::now();
+        let token = state.begin_validation_at(new_addr, start).unwrap();
+        assert!(!state.expire_validation_at(start + Duration::from_secs(1)));
+
+        let later = start + constants::PATH_VALIDATION_TIMEOUT;
+        assert!(state.expire_validation_at(later));
+        assert_eq!(state.pending_address(), None);
+        assert_eq!(state.current_address(), initial);
+
+        // A late response is no longer accepted
+        assert!(!state.on_path_response(token));
+        assert_eq!(state.current_address(), initial);
+    }
+
+    #[test]
+    fn test_simultaneous_migration_converges() {
+        let (a_old, a_new) = (addr_v4(192, 168, 1, 10, 4000), addr_v4(10, 0, 0, 10, 4001));
+        let (b_old, b_new) = (addr_v4(192, 168, 2, 20, 5000), addr_v4(172, 16, 0, 20, 5001));
+        // Each side tracks the other's address
+        let mut a = MigrationState::new(b_old);
+        let mut b = MigrationState::new(a_old);
+        let now = Instant::now();
+
+        // Same tick: both roam and see the other's packets from a new source
+        a.on_receive(b_new, 100);
+        b.on_receive(a_new, 100);
+        let a_token = a.begin_validation_at(b_new, now).unwrap();
+        let b_token = b.begin_validation_at(a_new, now).unwrap();
+
+        // More data from the candidate keeps the challenge in flight
+        assert_eq!(a.begin_validation_at(b_new, now).unwrap(), a_token);
+        assert_eq!(b.begin_validation_at(a_new, now).unwrap(), b_token);
+
+        // Each answers the other's challenge while its own is outstanding
+        assert!(a.on_path_challenge(b_new, 40));
+        assert!(b.on_path_challenge(a_new, 40));
+        assert_eq!(a.pending_address(), Some(b_new));
+        assert_eq!(b.pending_address(), Some(a_new));
+
+        // The responses complete both validations
+        assert!(b.on_path_response(b_token));
+        assert!(a.on_path_response(a_token));
+        assert_eq!(a.current_address(), b_new);
+        assert_eq!(b.current_address(), a_new);
+        assert!(a.can_send(b_new, 10_000) && b.can_send(a_new, 10_000));
+    }
+
+    #[test]
+    fn test_validation_attempts_rate_limited() {
+        let initial = addr_v4(192, 168, 1, 100, 8080);
+        let mut state = MigrationState::new(initial);
+        let start = Instant::now();
+
+        // Spoofed packets from three new addresses use up the budget
+        for i in 1..=3 {
+            let spoofed = addr_v4(10, 0, 0, i, 9090);
+            assert!(state.begin_validation_at(spoofed, start).is_ok());
+        }
+        let fourth = addr_v4(10, 0, 0, 4, 9090);
+        assert_eq!(
+            state.begin_validation_at(fourth, start + Duration::from_secs(5)),
+            Err(MigrationError::TooManyAttempts)
+        );
+
+        // A new window allows attempts again
+        let later = start + constants::VALIDATION_ATTEMPT_WINDOW;
+        assert!(state.begin_validation_at(fourth, later).is_ok());
+    }
+
+    #[test]
+    fn test_successful_validation_resets_attempts() {
+        let initial = addr_v4(192, 168, 1, 100, 8080);
+        let mut state = MigrationState::new(initial);
+        let now = Instant::now();
+
+        state.begin_validation_at(addr_v4(10, 0, 0, 1, 9090), now).unwrap();
+        state.begin_validation_at(addr_v4(10, 0, 0, 2, 9090), now).unwrap();
+        let token = state.begin_validation_at(addr_v4(10, 0, 0, 3, 9090), now).unwrap();
+        assert!(state.on_path_response(token));
+
+        // Counter reset: a full budget is available within the same window
+        for i in 4..=6 {
+            assert!(state.begin_validation_at(addr_v4(10, 0, 0, i, 9090), now).is_ok());
+        }
+        assert!(state.begin_validation_at(addr_v4(10, 0, 0, 7, 9090), now).is_err());
+    }
 }
@@ -0,0 +1,429 @@
+//! In-memory lossy network for tests.
+//!
+//! [`MockNetwork`] connects any number of [`MockTransport`] endpoints
+//! through a simulated path with configurable loss, latency and
+//! reordering. All randomness comes from a fixed seed, and delivery runs
+//! on tokio's clock, so a test using a paused runtime replays exactly.
+//! Every packet is recorded in a timeline for assertions.
+//!
+//! [`MockTransport`] mirrors the datagram API of
+//! [`NomadSocket`](super::NomadSocket) (`local_addr`, `send_to`,
+//! `recv_from`), so code written against those calls can run over it.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// Path behavior for a [`MockNetwork`].
+#[derive(Debug, Clone, Copy)]
+pub struct MockConfig {
+    /// Probability in `[0, 1]` that a packet is dropped.
+    pub drop_probability: f64,
+    /// One-way delay added to every packet.
+    pub latency: Duration,
+    /// Probability in `[0, 1]` that a packet is held back.
+    pub reorder_probability: f64,
+    /// Extra delay for held-back packets, letting later ones overtake.
+    pub reorder_delay: Duration,
+    /// Seed for all random decisions.
+    pub seed: u64,
+}
+
+impl MockConfig {
+    /// A perfect path (no loss, latency or reordering) with `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            drop_probability: 0.0,
+            latency: Duration::ZERO,
+            reorder_probability: 0.0,
+            reorder_delay: Duration::ZERO,
+            seed,
+        }
+    }
+
+    /// Drop packets with `probability`.
+    pub fn with_drop_probability(mut self, probability: f64) -> Self {
+        self.drop_probability = probability;
+        self
+    }
+
+    /// Delay every packet by `latency`.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Hold back packets with `probability` by an extra `delay`.
+    pub fn with_reordering(mut self, probability: f64, delay: Duration) -> Self {
+        self.reorder_probability = probability;
+        self.reorder_delay = delay;
+        self
+    }
+}
+
+/// What happened to a packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketFate {
+    /// Lost on the path.
+    Dropped,
+    /// Delivered after the given time since the network was created.
+    Delivered {
+        /// Arrival time.
+        at: Duration,
+        /// Whether the packet was held back for reordering.
+        reordered: bool,
+    },
+}
+
+/// One packet in a [`MockNetwork`] timeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketRecord {
+    /// Send order across the whole network, from 0.
+    pub seq: u64,
+    /// Sender.
+    pub from: SocketAddr,
+    /// Destination.
+    pub to: SocketAddr,
+    /// Datagram size.
+    pub len: usize,
+    /// Send time since the network was created.
+    pub sent_at: Duration,
+    /// Outcome.
+    pub fate: PacketFate,
+}
+
+/// A packet on its way to an endpoint.
+#[derive(Debug)]
+struct InFlight {
+    deliver_at: Instant,
+    seq: u64,
+    from: SocketAddr,
+    data: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct NetworkInner {
+    config: MockConfig,
+    rng: u64,
+    started: Instant,
+    next_seq: u64,
+    queues: HashMap<SocketAddr, Vec<InFlight>>,
+    timeline: Vec<PacketRecord>,
+}
+
+impl NetworkInner {
+    /// Next value in `[0, 1)` from a SplitMix64 stream.
+    fn next_f64(&mut self) -> f64 {
+        self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// A simulated network shared by [`MockTransport`] endpoints.
+#[derive(Debug, Clone)]
+pub struct MockNetwork {
+    inner: Arc<Mutex<NetworkInner>>,
+    notify: Arc<Notify>,
+}
+
+impl MockNetwork {
+    /// Create a network with the given path behavior.
+    pub fn new(config: MockConfig) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(NetworkInner {
+                config,
+                rng: config.seed,
+                started: Instant::now(),
+                next_seq: 0,
+                queues: HashMap::new(),
+                timeline: Vec::new(),
+            })),
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Attach an endpoint at `addr`.
+    ///
+    /// # Errors
+    /// Returns `AddrInUse` if an endpoint already has this address.
+    pub fn bind(&self, addr: SocketAddr) -> io::Result<MockTransport> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.queues.contains_key(&addr) {
+            return Err(io::ErrorKind::AddrInUse.into());
+        }
+        inner.queues.insert(addr, Vec::new());
+        Ok(MockTransport {
+            network: self.clone(),
+            local: addr,
+            recv_buffer: Vec::new(),
+        })
+    }
+
+    /// Every packet sent so far, in send order.
+    pub fn timeline(&self) -> Vec<PacketRecord> {
+        self.inner.lock().unwrap().timeline.clone()
+    }
+}
+
+/// An endpoint on a [`MockNetwork`].
+#[derive(Debug)]
+pub struct MockTransport {
+    network: MockNetwork,
+    local: SocketAddr,
+    recv_buffer: Vec<u8>,
+}
+
+impl MockTransport {
+    /// Get the local address.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.local)
+    }
+
+    /// Send data to a specific address.
+    ///
+    /// Like UDP, a datagram to an address with no endpoint is silently
+    /// lost (and recorded as dropped).
+    pub async fn send_to(&self, data: &[u8], addr: SocketAddr) -> io::Result<usize> {
+        let now = Instant::now();
+        let mut inner = self.network.inner.lock().unwrap();
+        let config = inner.config;
+        let seq = inner.next_seq;
+        inner.next_seq += 1;
+
+        let dropped = inner.next_f64() < config.drop_probability;
+        let reordered = inner.next_f64() < config.reorder_probability;
+        let mut delay = config.latency;
+        if reordered {
+            delay += config.reorder_delay;
+        }
+        let deliver_at = now + delay;
+
+        let delivered = !dropped && inner.queues.contains_key(&addr);
+        let fate = if delivered {
+            PacketFate::Delivered {
+                at: deliver_at - inner.started,
+                reordered,
+            }
+        } else {
+            PacketFate::Dropped
+        };
+        let record = PacketRecord {
+            seq,
+            from: self.local,
+            to: addr,
+            len: data.len(),
+            sent_at: now - inner.started,
+            fate,
+        };
+        inner.timeline.push(record);
+
+        if delivered && let Some(queue) = inner.queues.get_mut(&addr) {
+            queue.push(InFlight {
+                deliver_at,
+                seq,
+                from: self.local,
+                data: data.to_vec(),
+            });
+        }
+        drop(inner);
+        self.network.notify.notify_waiters();
+        Ok(data.len())
+    }
+
+    /// Receive the next datagram that has arrived, waiting if none has.
+    ///
+    /// Datagrams come out in arrival order (ties in send order).
+    pub async fn recv_from(&mut self) -> io::Result<(&[u8], SocketAddr)> {
+        loop {
+            let notified = self.network.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let next_arrival = {
+                let mut inner = self.network.inner.lock().unwrap();
+                let queue = inner.queues.get_mut(&self.local).expect("endpoint is bound");
+                let earliest = queue
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, packet)| (packet.deliver_at, packet.seq))
+                    .map(|(index, packet)| (index, packet.deliver_at));
+                match earliest {
+                    Some((index, deliver_at)) if deliver_at <= Instant::now() => {
+                        let packet = queue.swap_remove(index);
+                        self.recv_buffer = packet.data;
+                        return Ok((&self.recv_buffer, packet.from));
+                    }
+                    Some((_, deliver_at)) => Some(deliver_at),
+                    None => None,
+                }
+            };
+
+            match next_arrival {
+                Some(deliver_at) => {
+                    tokio::select! {
+                        _ = &mut notified => {}
+                        _ = tokio::time::sleep_until(deliver_at) => {}
+                    }
+                }
+                None => notified.await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_latency_and_timeline() {
+        let network = MockNetwork::new(MockConfig::new(1).with_latency(Duration::from_millis(30)));
+        let a = network.bind(addr(1)).unwrap();
+        let mut b = network.bind(addr(2)).unwrap();
+        assert!(network.bind(addr(2)).is_err());
+
+        let start = Instant::now();
+        a.send_to(b"hello", addr(2)).await.unwrap();
+        a.send_to(b"nobody", addr(3)).await.unwrap();
+        let (data, from) = b.recv_from().await.unwrap();
+        assert_eq!((data, from), (&b"hello"[..], addr(1)));
+        assert_eq!(start.elapsed(), Duration::from_millis(30));
+
+        let timeline = network.timeline();
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(
+            timeline[0].fate,
+            PacketFate::Delivered {
+                at: Duration::from_millis(30),
+                reordered: false
+            }
+        );
+        assert_eq!(timeline[1].fate, PacketFate::Dropped);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_seeded_loss_and_reordering_replay() {
+        let run = async || {
+            let config = MockConfig::new(42)
+                .with_drop_probability(0.2)
+                .with_reordering(0.3, Duration::from_millis(50));
+            let network = MockNetwork::new(config);
+            let a = network.bind(addr(1)).unwrap();
+            let _b = network.bind(addr(2)).unwrap();
+            for i in 0..100u8 {
+                a.send_to(&[i], addr(2)).await.unwrap();
+            }
+            network.timeline().into_iter().map(|record| record.fate).collect::<Vec<_>>()
+        };
+
+        let fates = run().await;
+        assert_eq!(fates, run().await);
+        let dropped = fates.iter().filter(|fate| **fate == PacketFate::Dropped).count();
+        let reordered = fates
+            .iter()
+            .filter(|fate| matches!(fate, PacketFate::Delivered { reordered: true, .. }))
+            .count();
+        assert!((5..40).contains(&dropped), "dropped {dropped}");
+        assert!(reordered > 0);
+    }
+
+    #[cfg(feature = "sync")]
+    #[tokio::test(start_paused = true)]
+    async fn test_sync_converges_over_lossy_path() {
+        use crate::sync::SyncEngine;
+
+        // State is a single value; diffs carry the new value, so resending
+        // a diff is harmless
+        #[derive(Debug, Clone, PartialEq)]
+        struct Counter(u64);
+
+        fn encode(diff: &u64) -> Vec<u8> {
+            diff.to_le_bytes().to_vec()
+        }
+        fn decode(data: &[u8]) -> Result<u64, String> {
+            Ok(u64::from_le_bytes(data.try_into().map_err(|_| "bad diff")?))
+        }
+        fn compute(_old: &Counter, new: &Counter) -> u64 {
+            new.0
+        }
+        fn apply(state: &mut Counter, diff: &u64) -> Result<(), String> {
+            state.0 = *diff;
+            Ok(())
+        }
+        fn is_empty(_: &u64) -> bool {
+            false
+        }
+        let engine = || {
+            let mut engine = SyncEngine::new(encode, decode, compute, apply, is_empty);
+            engine.init(Counter(0));
+            engine
+        };
+
+        let config = MockConfig::new(7)
+            .with_drop_probability(0.1)
+            .with_latency(Duration::from_millis(20))
+            .with_reordering(0.1, Duration::from_millis(15));
+        let network = MockNetwork::new(config);
+        let mut client_socket = network.bind(addr(1)).unwrap();
+        let mut server_socket = network.bind(addr(2)).unwrap();
+        let mut client = engine();
+        let mut server = engine();
+
+        let tick = Duration::from_millis(10);
+        let retransmit_after = 10; // ticks without progress
+        let mut idle_ticks = 0;
+        for round in 0..2000u64 {
+            if round < 50 {
+                client.update_state(Counter(round + 1));
+            }
+
+            if let Some(msg) = client.generate_message().unwrap() {
+                client_socket.send_to(&client.encode_message(&msg), addr(2)).await.unwrap();
+                idle_ticks = 0;
+            } else if !client.is_synchronized() {
+                idle_ticks += 1;
+                if idle_ticks >= retransmit_after {
+                    // Resend everything since the acked base under a new version
+                    client.mark_changed();
+                    idle_ticks = 0;
+                }
+            }
+
+            // Drain what arrives within one tick on each side
+            let deadline = Instant::now() + tick;
+            while let Ok(Ok((data, _))) = tokio::time::timeout_at(deadline, server_socket.recv_from()).await {
+                let msg = server.decode_message(data).unwrap();
+                server.process_message(&msg).unwrap();
+            }
+            if server.needs_ack() {
+                let ack = server.generate_ack().unwrap();
+                server_socket.send_to(&server.encode_message(&ack), addr(1)).await.unwrap();
+            }
+            while let Ok(Ok((data, _))) = tokio::time::timeout(Duration::ZERO, client_socket.recv_from()).await {
+                let msg = client.decode_message(data).unwrap();
+                client.process_message(&msg).unwrap();
+            }
+
+            if round >= 50 && client.is_synchronized() {
+                break;
+            }
+        }
+
+        assert!(client.is_synchronized());
+        assert_eq!(server.state(), Some(&Counter(50)));
+        assert!(network.timeline().iter().any(|record| record.fate == PacketFate::Dropped));
+    }
+}
@@ -6,9 +6,15 @@
 //! - **Frame encoding/decoding**: [`DataFrame`], [`CloseFrame`], and wire format handling
 //! - **Connection state machine**: [`ConnectionState`] with lifecycle management
 //! - **RTT estimation**: [`RttEstimator`] implementing RFC 6298
+//! - **Jitter estimation**: [`JitterEstimator`] implementing RFC 3550 interarrival jitter
 //! - **Frame pacing**: [`FramePacer`] to prevent buffer bloat
 //! - **Connection migration**: [`MigrationState`] for seamless IP roaming
+//! - **Path MTU**: [`PathMtu`] probing with blackhole detection
 //! - **Async sockets**: [`NomadSocket`] wrapper for tokio UDP
+//! - **Test network**: `MockTransport`, an in-memory lossy drop-in for
+//!   `NomadSocket` (requires `mock-transport`)
+//! - **Stream adapter**: `NomadStream` over a socket and crypto session
+//!   (requires `crypto`)
 //!
 //! # Architecture
 //!
@@ -29,20 +35,35 @@
 //! └─────────────────────────────────────────┘
 //! ```
 
+mod config;
 mod connection;
 mod error;
 mod frame;
 mod migration;
+#[cfg(any(test, feature = "mock-transport"))]
+mod mock;
+mod mtu;
 mod pacing;
 mod socket;
+#[cfg(feature = "crypto")]
+mod stream;
 mod timing;
 
+pub use config::TransportConfig;
 pub use connection::*;
 pub use error::*;
 pub use frame::*;
-pub use migration::MigrationState;
+pub use migration::{MigrationState, PATH_CHALLENGE_SIZE};
+#[cfg(any(test, feature = "mock-transport"))]
+pub use mock::*;
+pub use mtu::{constants as mtu_constants, PathMtu};
 pub use pacing::{
-    constants as pacing_constants, FramePacer, PacerAction, RetransmitController, SendReason,
+    constants as pacing_constants, FramePacer, PacerAction, PacingMode, RateHint, RetransmitController,
+    SendReason, RATE_HINT_SIZE,
 };
 pub use socket::*;
-pub use timing::{constants as timing_constants, RttEstimator, TimestampTracker};
+#[cfg(feature = "crypto")]
+pub use stream::NomadStream;
+pub use timing::{
+    constants as timing_constants, timestamp_delta, JitterEstimator, RttEstimator, TimestampTracker,
+};
@@ -0,0 +1,286 @@
+//! Path MTU probing and blackhole detection.
+//!
+//! Every session starts at a conservative datagram size that is safe on
+//! virtually any path. `PathMtu` periodically schedules a padded probe at
+//! the next larger candidate size; an acked probe raises the effective MTU.
+//!
+//! Paths can also shrink mid-session (e.g. roaming onto a tunnel). When a
+//! run of large frames goes unacked while small frames still get through,
+//! the path is treated as a PMTU blackhole: the MTU drops back to the base
+//! size and the failed size is untrusted (not probed) for a cooldown.
+//!
+//! Sizes are full UDP payload sizes (frame header, ciphertext and tag).
+
+use std::time::{Duration, Instant};
+
+/// Path MTU constants.
+pub mod constants {
+    use std::time::Duration;
+
+    /// Starting datagram size, safe on virtually any path.
+    pub const BASE_MTU: usize = 1200;
+
+    /// Largest datagram size worth probing (1500 Ethernet minus IPv6/UDP).
+    pub const MAX_MTU: usize = 1452;
+
+    /// Candidate sizes probed in order, smallest first.
+    pub const PROBE_SIZES: [usize; 3] = [1280, 1400, 1452];
+
+    /// Default time between probes.
+    pub const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+    /// Default time a failed size stays untrusted.
+    pub const DEFAULT_BLACKHOLE_COOLDOWN: Duration = Duration::from_secs(300);
+
+    /// Default number of consecutive lost large frames that signal a blackhole.
+    pub const DEFAULT_BLACKHOLE_THRESHOLD: u32 = 3;
+}
+
+use constants::*;
+
+/// Path MTU state for one session.
+#[derive(Debug, Clone)]
+pub struct PathMtu {
+    /// Current effective datagram size.
+    mtu: usize,
+    /// Upper bound for probing.
+    max_mtu: usize,
+    /// Time between probes.
+    probe_interval: Duration,
+    /// Time a failed size stays untrusted.
+    cooldown: Duration,
+    /// Consecutive lost large frames that signal a blackhole.
+    blackhole_threshold: u32,
+    /// When the last probe was sent.
+    last_probe: Option<Instant>,
+    /// Size of the probe awaiting a result.
+    probe_in_flight: Option<usize>,
+    /// Sizes at or above this are untrusted until the given time.
+    untrusted: Option<(usize, Instant)>,
+    /// Consecutive large frames lost since the last large frame was acked.
+    large_losses: u32,
+    /// Whether a small frame was acked during the current loss run.
+    small_acked: bool,
+}
+
+impl PathMtu {
+    /// Create path MTU state starting at [`BASE_MTU`].
+    pub fn new() -> Self {
+        Self {
+            mtu: BASE_MTU,
+            max_mtu: MAX_MTU,
+            probe_interval: DEFAULT_PROBE_INTERVAL,
+            cooldown: DEFAULT_BLACKHOLE_COOLDOWN,
+            blackhole_threshold: DEFAULT_BLACKHOLE_THRESHOLD,
+            last_probe: None,
+            probe_in_flight: None,
+            untrusted: None,
+            large_losses: 0,
+            small_acked: false,
+        }
+    }
+
+    /// Set the largest size to probe.
+    pub fn with_max_mtu(mut self, max_mtu: usize) -> Self {
+        self.max_mtu = max_mtu.max(BASE_MTU);
+        self
+    }
+
+    /// Set the time between probes.
+    pub fn with_probe_interval(mut self, interval: Duration) -> Self {
+        self.probe_interval = interval;
+        self
+    }
+
+    /// Set how long a failed size stays untrusted.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Set the number of consecutive lost large frames that signal a blackhole.
+    pub fn with_blackhole_threshold(mut self, threshold: u32) -> Self {
+        self.blackhole_threshold = threshold.max(1);
+        self
+    }
+
+    /// Get the current effective MTU.
+    pub fn mtu(&self) -> usize {
+        self.mtu
+    }
+
+    /// Check if `size` is untrusted at `now`.
+    pub fn is_untrusted(&self, size: usize, now: Instant) -> bool {
+        self.untrusted
+            .is_some_and(|(floor, until)| size >= floor && now < until)
+    }
+
+    /// Next candidate size above the current MTU, if any.
+    fn next_candidate(&self, now: Instant) -> Option<usize> {
+        PROBE_SIZES
+            .iter()
+            .copied()
+            .find(|&size| size > self.mtu && size <= self.max_mtu)
+            .filter(|&size| !self.is_untrusted(size, now))
+    }
+
+    /// Check if a probe should be sent now, returning its size.
+    ///
+    /// A probe is due when none is outstanding, the probe interval has
+    /// elapsed, and a larger trusted candidate exists.
+    pub fn probe_due(&self, now: Instant) -> Option<usize> {
+        if self.probe_in_flight.is_some() {
+            return None;
+        }
+        if let Some(last) = self.last_probe
+            && now.saturating_duration_since(last) < self.probe_interval
+        {
+            return None;
+        }
+        self.next_candidate(now)
+    }
+
+    /// Record that a probe of `size` bytes was sent.
+    pub fn on_probe_sent(&mut self, size: usize, now: Instant) {
+        self.probe_in_flight = Some(size);
+        self.last_probe = Some(now);
+    }
+
+    /// Handle an acked probe: the path carries `size`, so raise the MTU.
+    pub fn on_probe_acked(&mut self, size: usize) {
+        if self.probe_in_flight == Some(size) {
+            self.probe_in_flight = None;
+        }
+        self.mtu = self.mtu.max(size);
+    }
+
+    /// Handle a lost probe: untrust `size` for the cooldown.
+    pub fn on_probe_lost(&mut self, size: usize, now: Instant) {
+        if self.probe_in_flight == Some(size) {
+            self.probe_in_flight = None;
+        }
+        self.untrust(size, now);
+    }
+
+    /// Record an acked regular frame of `size` bytes.
+    pub fn on_frame_acked(&mut self, size: usize) {
+        if size > BASE_MTU {
+            self.large_losses = 0;
+            self.small_acked = false;
+        } else {
+            self.small_acked = true;
+        }
+    }
+
+    /// Record a lost regular frame of `size` bytes.
+    ///
+    /// Returns `true` if this loss completed a blackhole pattern and the
+    /// MTU was dropped back to [`BASE_MTU`].
+    pub fn on_frame_lost(&mut self, size: usize, now: Instant) -> bool {
+        if size <= BASE_MTU {
+            return false;
+        }
+
+        self.large_losses += 1;
+        if self.large_losses < self.blackhole_threshold || !self.small_acked {
+            return false;
+        }
+
+        // Large frames vanish while small ones arrive: PMTU blackhole
+        self.untrust(self.mtu, now);
+        self.mtu = BASE_MTU;
+        self.large_losses = 0;
+        self.small_acked = false;
+        self.last_probe = Some(now);
+        true
+    }
+
+    /// Untrust sizes at or above `size` until the cooldown expires.
+    fn untrust(&mut self, size: usize, now: Instant) {
+        let floor = match self.untrusted {
+            Some((floor, until)) if now < until => floor.min(size),
+            _ => size,
+        };
+        self.untrusted = Some((floor, now + self.cooldown));
+    }
+}
+
+impl Default for PathMtu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_raises_mtu() {
+        let mut pmtu = PathMtu::new();
+        let now = Instant::now();
+
+        assert_eq!(pmtu.probe_due(now), Some(1280));
+        pmtu.on_probe_sent(1280, now);
+        assert_eq!(pmtu.probe_due(now), None);
+        pmtu.on_probe_acked(1280);
+        assert_eq!(pmtu.mtu(), 1280);
+
+        // Next probe waits for the interval
+        assert_eq!(pmtu.probe_due(now), None);
+        let later = now + DEFAULT_PROBE_INTERVAL;
+        assert_eq!(pmtu.probe_due(later), Some(1400));
+    }
+
+    #[test]
+    fn test_blackhole_backs_off_and_cools_down() {
+        let mut pmtu = PathMtu::new().with_probe_interval(Duration::from_secs(1));
+        let start = Instant::now();
+
+        // Path initially carries 1400
+        pmtu.on_probe_sent(1280, start);
+        pmtu.on_probe_acked(1280);
+        let t = start + Duration::from_secs(1);
+        pmtu.on_probe_sent(1400, t);
+        pmtu.on_probe_acked(1400);
+        assert_eq!(pmtu.mtu(), 1400);
+
+        // Path changes: large frames vanish, small frames still get through
+        let t = t + Duration::from_secs(1);
+        pmtu.on_frame_acked(200);
+        assert!(!pmtu.on_frame_lost(1400, t));
+        assert!(!pmtu.on_frame_lost(1400, t));
+        assert!(pmtu.on_frame_lost(1400, t));
+        assert_eq!(pmtu.mtu(), BASE_MTU);
+
+        // 1280 is still trusted and gets re-probed; it fails too
+        let t = t + Duration::from_secs(1);
+        assert_eq!(pmtu.probe_due(t), Some(1280));
+        pmtu.on_probe_sent(1280, t);
+        pmtu.on_probe_lost(1280, t);
+        assert_eq!(pmtu.mtu(), BASE_MTU);
+
+        // No probing during the cooldown
+        for secs in [2, 30, 200] {
+            assert_eq!(pmtu.probe_due(t + Duration::from_secs(secs)), None);
+        }
+
+        // Probing resumes once the cooldown expires
+        let after = t + DEFAULT_BLACKHOLE_COOLDOWN;
+        assert_eq!(pmtu.probe_due(after), Some(1280));
+    }
+
+    #[test]
+    fn test_large_losses_without_small_acks_not_blackhole() {
+        let mut pmtu = PathMtu::new();
+        let now = Instant::now();
+        pmtu.on_probe_sent(1280, now);
+        pmtu.on_probe_acked(1280);
+
+        // Everything is being lost (outage, not MTU): keep the MTU
+        for _ in 0..10 {
+            assert!(!pmtu.on_frame_lost(1280, now));
+        }
+        assert_eq!(pmtu.mtu(), 1280);
+    }
+}
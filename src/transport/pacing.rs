@@ -3,8 +3,12 @@
 //! Implements the frame pacing algorithm from 2-TRANSPORT.md to prevent
 //! buffer bloat and network congestion.
 
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 
+use super::{FrameError, TransportConfig};
+use crate::core::{SharedClock, system_clock};
+
 /// Frame pacing constants from the protocol specification.
 pub mod constants {
     use std::time::Duration;
@@ -16,6 +20,9 @@ pub mod constants {
     /// Wait after state change before sending (batch rapid changes).
     pub const COLLECTION_INTERVAL: Duration = Duration::from_millis(8);
 
+    /// Upper bound on the collection interval when widened for jitter.
+    pub const MAX_COLLECTION_INTERVAL: Duration = Duration::from_millis(50);
+
     /// Maximum time to delay an ack-only frame.
     pub const DELAYED_ACK_TIMEOUT: Duration = Duration::from_millis(100);
 
@@ -33,6 +40,134 @@ pub mod constants {
 
     /// Retransmit backoff multiplier.
     pub const RETRANSMIT_BACKOFF: u32 = 2;
+
+    /// Default randomized jitter applied to each backoff interval (off).
+    ///
+    /// Around 0.25 (±25%) spreads out synchronized retransmits when enabled.
+    pub const DEFAULT_RETRANSMIT_JITTER: f64 = 0.0;
+
+    /// Minimum interval between frames in delivery-rate mode.
+    pub const DELIVERY_RATE_MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+    /// Frame size assumed by delivery-rate pacing before any frame is sent.
+    pub const DEFAULT_PACING_FRAME_SIZE: usize = 1200;
+
+    /// Number of delivery-rate samples in the max filter.
+    pub const DELIVERY_RATE_SAMPLES: usize = 10;
+
+    /// Pacing gain cycle for delivery-rate mode, one phase per min-RTT.
+    ///
+    /// Probe for more bandwidth, drain the queue that probing built, then
+    /// cruise at the estimated rate.
+    pub const PACING_GAIN_CYCLE: [f64; 8] = [1.25, 0.75, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0];
+
+    /// In-flight cap as a multiple of the bandwidth-delay product.
+    pub const IN_FLIGHT_GAIN: f64 = 2.0;
+}
+
+/// How the pacer derives the minimum interval between frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PacingMode {
+    /// `max(SRTT/2, 20ms)` capped at 50 Hz, per 2-TRANSPORT.md.
+    #[default]
+    SrttFraction,
+    /// BBR-style pacing from the estimated delivery rate.
+    ///
+    /// The interval is the last frame size divided by the delivery rate
+    /// times the current pacing gain, and frames are held while in-flight
+    /// bytes exceed twice the bandwidth-delay product. Not bound by the
+    /// 50 Hz cap. Falls back to `SrttFraction` until both a delivery rate
+    /// and a min-RTT are known.
+    DeliveryRate,
+}
+
+/// Server-requested cap on the client's update frequency (rate-hints
+/// extension).
+///
+/// Wire format:
+/// ```text
+/// +0  Max Updates Per Second (2 bytes LE16, 0 = no cap)
+/// +2  Burst (2 bytes LE16)
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateHint {
+    /// Sustained frames per second the peer accepts (0 = no cap)
+    pub max_updates_per_sec: u16,
+    /// Frames that may be sent back-to-back before the cap applies
+    pub burst: u16,
+}
+
+/// Encoded size of a [`RateHint`].
+pub const RATE_HINT_SIZE: usize = 4;
+
+impl RateHint {
+    /// Create a rate hint.
+    pub fn new(max_updates_per_sec: u16, burst: u16) -> Self {
+        Self {
+            max_updates_per_sec,
+            burst,
+        }
+    }
+
+    /// Encode to wire format.
+    pub fn encode(&self) -> [u8; RATE_HINT_SIZE] {
+        let mut buf = [0u8; RATE_HINT_SIZE];
+        buf[0..2].copy_from_slice(&self.max_updates_per_sec.to_le_bytes());
+        buf[2..4].copy_from_slice(&self.burst.to_le_bytes());
+        buf
+    }
+
+    /// Decode from wire format.
+    pub fn decode(data: &[u8]) -> Result<Self, FrameError> {
+        if data.len() < RATE_HINT_SIZE {
+            return Err(FrameError::TooShort {
+                expected: RATE_HINT_SIZE,
+                actual: data.len(),
+            });
+        }
+        Ok(Self {
+            max_updates_per_sec: u16::from_le_bytes([data[0], data[1]]),
+            burst: u16::from_le_bytes([data[2], data[3]]),
+        })
+    }
+}
+
+/// Token bucket enforcing a [`RateHint`].
+#[derive(Debug, Clone)]
+struct RateLimit {
+    /// Refill rate in frames per second
+    rate: f64,
+    /// Bucket capacity (burst allowance)
+    capacity: f64,
+    /// Tokens as of `updated`
+    tokens: f64,
+    /// When `tokens` was last brought up to date
+    updated: Option<Instant>,
+}
+
+impl RateLimit {
+    /// Tokens available at `now`.
+    fn tokens_at(&self, now: Instant) -> f64 {
+        match self.updated {
+            Some(updated) => {
+                let elapsed = now.saturating_duration_since(updated).as_secs_f64();
+                (self.tokens + elapsed * self.rate).min(self.capacity)
+            }
+            None => self.capacity,
+        }
+    }
+
+    /// When the next token is available, or `None` if one is available now.
+    fn next_token_at(&self, now: Instant) -> Option<Instant> {
+        let tokens = self.tokens_at(now);
+        (tokens < 1.0).then(|| now + Duration::from_secs_f64((1.0 - tokens) / self.rate))
+    }
+
+    /// Spend a token for a frame sent at `now`.
+    fn consume(&mut self, now: Instant) {
+        self.tokens = (self.tokens_at(now) - 1.0).max(0.0);
+        self.updated = Some(now);
+    }
 }
 
 /// Reason why a frame should be sent.
@@ -46,6 +181,8 @@ pub enum SendReason {
     Keepalive,
     /// Retransmitting unacknowledged data.
     Retransmit,
+    /// Graceful close; never delayed by pacing.
+    Close,
 }
 
 /// Action the pacer recommends.
@@ -66,8 +203,12 @@ pub enum PacerAction {
 /// - Collection interval to batch rapid state changes (8ms)
 /// - Delayed ACK to piggyback on data frames (100ms max)
 /// - Frame rate cap at 50 Hz
+///
+/// See [`PacingMode`] for the opt-in delivery-rate mode.
 #[derive(Debug, Clone)]
 pub struct FramePacer {
+    /// How the minimum frame interval is computed.
+    mode: PacingMode,
     /// When we last sent a frame.
     last_frame_sent: Option<Instant>,
     /// When a state change occurred that needs to be sent.
@@ -78,6 +219,24 @@ pub struct FramePacer {
     data_pending: bool,
     /// Current smoothed RTT in milliseconds (from RTT estimator).
     srtt_ms: f64,
+    /// Windowed minimum RTT in milliseconds (0 if unknown).
+    min_rtt_ms: f64,
+    /// Smoothed interarrival jitter in milliseconds (0 if unknown).
+    jitter_ms: f64,
+    /// Recent delivery-rate samples in bytes per second.
+    delivery_rates: VecDeque<f64>,
+    /// Bytes sent but not yet acknowledged.
+    bytes_in_flight: usize,
+    /// Size of the most recently sent frame.
+    last_frame_bytes: usize,
+    /// Start of the pacing gain cycle.
+    gain_cycle_start: Option<Instant>,
+    /// Peer-requested frame rate cap, if any.
+    rate_limit: Option<RateLimit>,
+    /// Timing thresholds.
+    config: TransportConfig,
+    /// Time source.
+    clock: SharedClock,
 }
 
 impl Default for FramePacer {
@@ -89,24 +248,119 @@ impl Default for FramePacer {
 impl FramePacer {
     /// Create a new frame pacer.
     pub fn new() -> Self {
+        Self::with_mode(PacingMode::default())
+    }
+
+    /// Create a frame pacer with the given pacing mode.
+    pub fn with_mode(mode: PacingMode) -> Self {
         Self {
+            mode,
             last_frame_sent: None,
             state_change_time: None,
             ack_pending_since: None,
             data_pending: false,
             srtt_ms: 0.0,
+            min_rtt_ms: 0.0,
+            jitter_ms: 0.0,
+            delivery_rates: VecDeque::new(),
+            bytes_in_flight: 0,
+            last_frame_bytes: constants::DEFAULT_PACING_FRAME_SIZE,
+            gain_cycle_start: None,
+            rate_limit: None,
+            config: TransportConfig::default(),
+            clock: system_clock(),
         }
     }
 
+    /// Create a frame pacer with custom timing thresholds.
+    pub fn with_config(config: TransportConfig) -> Self {
+        Self {
+            config,
+            ..Self::new()
+        }
+    }
+
+    /// Use a custom time source instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Set the idle time after which a keepalive is due.
+    pub fn with_keepalive_interval(mut self, interval: Duration) -> Self {
+        self.config.keepalive_interval = interval;
+        self
+    }
+
+    /// Get the timing thresholds.
+    pub fn config(&self) -> &TransportConfig {
+        &self.config
+    }
+
+    /// Get the pacing mode.
+    pub fn mode(&self) -> PacingMode {
+        self.mode
+    }
+
+    /// When the last frame was sent, if any.
+    pub fn last_frame_sent(&self) -> Option<Instant> {
+        self.last_frame_sent
+    }
+
+    /// Check if state changes are waiting to be sent (not just an ACK).
+    pub fn has_data_pending(&self) -> bool {
+        self.data_pending
+    }
+
+    /// When the next keepalive is due, if a frame has been sent.
+    pub fn keepalive_deadline(&self) -> Option<Instant> {
+        self.last_frame_sent
+            .map(|last| last + self.config.keepalive_interval)
+    }
+
     /// Update the SRTT from the RTT estimator.
     pub fn set_srtt(&mut self, srtt: Duration) {
         self.srtt_ms = srtt.as_secs_f64() * 1000.0;
     }
 
+    /// Update the windowed minimum RTT from the RTT estimator.
+    ///
+    /// With both SRTT and min-RTT known, the pacer treats `SRTT - minRTT` as
+    /// queuing delay and widens the frame interval by that amount.
+    pub fn set_min_rtt(&mut self, min_rtt: Duration) {
+        self.min_rtt_ms = min_rtt.as_secs_f64() * 1000.0;
+    }
+
+    /// Update the interarrival jitter from the jitter estimator.
+    ///
+    /// Jittery links deliver changes in bursts anyway, so the pacer widens
+    /// the collection interval by the jitter (up to
+    /// [`MAX_COLLECTION_INTERVAL`](constants::MAX_COLLECTION_INTERVAL)) and
+    /// batches more changes per frame.
+    pub fn set_jitter(&mut self, jitter: Duration) {
+        self.jitter_ms = jitter.as_secs_f64() * 1000.0;
+    }
+
+    /// Get the collection interval, widened by the current jitter.
+    pub fn collection_interval(&self) -> Duration {
+        let base = self.config.collection_interval;
+        let widened = base + Duration::from_secs_f64(self.jitter_ms / 1000.0);
+        widened.min(constants::MAX_COLLECTION_INTERVAL.max(base))
+    }
+
+    /// Estimated queuing delay in milliseconds (SRTT above min-RTT).
+    fn queuing_delay_ms(&self) -> f64 {
+        if self.min_rtt_ms > 0.0 {
+            (self.srtt_ms - self.min_rtt_ms).max(0.0)
+        } else {
+            0.0
+        }
+    }
+
     /// Notify the pacer that local state has changed.
     pub fn on_state_change(&mut self) {
         if self.state_change_time.is_none() {
-            self.state_change_time = Some(Instant::now());
+            self.state_change_time = Some(self.clock.now());
         }
         self.data_pending = true;
     }
@@ -114,41 +368,164 @@ impl FramePacer {
     /// Notify the pacer that we received a frame and should send an ACK.
     pub fn on_ack_needed(&mut self) {
         if self.ack_pending_since.is_none() {
-            self.ack_pending_since = Some(Instant::now());
+            self.ack_pending_since = Some(self.clock.now());
         }
     }
 
+    /// Honor a peer's rate hint.
+    ///
+    /// Up to `burst` frames (at least one) may go out at the normal pacing
+    /// interval; after that frames are spaced to `max_updates_per_sec`. A
+    /// hint of 0 updates per second removes the cap.
+    pub fn set_rate_limit(&mut self, hint: RateHint) {
+        self.rate_limit = (hint.max_updates_per_sec > 0).then(|| {
+            let capacity = f64::from(hint.burst.max(1));
+            RateLimit {
+                rate: f64::from(hint.max_updates_per_sec),
+                capacity,
+                tokens: capacity,
+                updated: None,
+            }
+        });
+    }
+
     /// Notify the pacer that a frame was sent.
     pub fn on_frame_sent(&mut self) {
-        self.last_frame_sent = Some(Instant::now());
+        self.on_frame_sent_at(self.clock.now());
+    }
+
+    /// Notify the pacer that a frame was sent at a specific time (for testing).
+    pub fn on_frame_sent_at(&mut self, now: Instant) {
+        if let Some(limit) = &mut self.rate_limit {
+            limit.consume(now);
+        }
+        self.last_frame_sent = Some(now);
         self.state_change_time = None;
         self.ack_pending_since = None;
         self.data_pending = false;
     }
 
+    /// Record the size of a sent frame (for delivery-rate pacing).
+    pub fn on_bytes_sent(&mut self, bytes: usize) {
+        self.bytes_in_flight += bytes;
+        self.last_frame_bytes = bytes;
+    }
+
+    /// Feed an acknowledgment sample (for delivery-rate pacing).
+    ///
+    /// `bytes` were acknowledged over `interval`, typically the time
+    /// between sending the acked frame and receiving its ack.
+    pub fn on_bytes_acked(&mut self, bytes: usize, interval: Duration) {
+        self.on_bytes_acked_at(bytes, interval, self.clock.now());
+    }
+
+    /// Feed an acknowledgment sample at a specific time.
+    pub fn on_bytes_acked_at(&mut self, bytes: usize, interval: Duration, now: Instant) {
+        self.bytes_in_flight = self.bytes_in_flight.saturating_sub(bytes);
+        if interval.is_zero() {
+            return;
+        }
+
+        if self.delivery_rates.len() == constants::DELIVERY_RATE_SAMPLES {
+            self.delivery_rates.pop_front();
+        }
+        self.delivery_rates
+            .push_back(bytes as f64 / interval.as_secs_f64());
+        self.gain_cycle_start.get_or_insert(now);
+    }
+
+    /// Estimated bottleneck delivery rate in bytes per second.
+    ///
+    /// The maximum over recent samples, since samples only underestimate.
+    pub fn delivery_rate(&self) -> Option<f64> {
+        self.delivery_rates.iter().copied().reduce(f64::max)
+    }
+
+    /// Get the bytes currently in flight.
+    pub fn bytes_in_flight(&self) -> usize {
+        self.bytes_in_flight
+    }
+
+    /// Current pacing gain from the gain cycle.
+    fn pacing_gain_at(&self, now: Instant) -> f64 {
+        let Some(start) = self.gain_cycle_start else {
+            return 1.0;
+        };
+        if self.min_rtt_ms <= 0.0 {
+            return 1.0;
+        }
+        let elapsed_ms = now.saturating_duration_since(start).as_secs_f64() * 1000.0;
+        let phase = (elapsed_ms / self.min_rtt_ms) as usize % constants::PACING_GAIN_CYCLE.len();
+        constants::PACING_GAIN_CYCLE[phase]
+    }
+
     /// Clear pending state (e.g., after receiving ACK).
     pub fn clear_pending(&mut self) {
         self.data_pending = false;
         self.state_change_time = None;
     }
 
+    /// Get the current minimum interval between frames.
+    pub fn min_frame_interval(&self) -> Duration {
+        self.min_frame_interval_at(self.clock.now())
+    }
+
+    /// Calculate the minimum frame interval at a specific time.
+    fn min_frame_interval_at(&self, now: Instant) -> Duration {
+        match (self.mode, self.delivery_rate()) {
+            (PacingMode::DeliveryRate, Some(rate)) if self.min_rtt_ms > 0.0 => {
+                self.delivery_rate_interval(rate, now)
+            }
+            _ => self.srtt_interval(),
+        }
+    }
+
+    /// Delivery-rate interval: one frame per `frame / (gain * rate)`.
+    fn delivery_rate_interval(&self, rate: f64, now: Instant) -> Duration {
+        let pacing_rate = rate * self.pacing_gain_at(now);
+        let interval = Duration::from_secs_f64(self.last_frame_bytes as f64 / pacing_rate);
+        let interval = interval.max(constants::DELIVERY_RATE_MIN_INTERVAL);
+
+        // Hold off for a round trip once the pipe is overfull
+        let bdp = rate * self.min_rtt_ms / 1000.0;
+        if self.bytes_in_flight as f64 >= constants::IN_FLIGHT_GAIN * bdp {
+            return interval.max(Duration::from_secs_f64(self.min_rtt_ms / 1000.0));
+        }
+
+        interval
+    }
+
     /// Calculate the minimum frame interval based on SRTT.
-    fn min_frame_interval(&self) -> Duration {
+    fn srtt_interval(&self) -> Duration {
         let srtt_half_ms = self.srtt_ms / 2.0;
-        let floor_ms = constants::MIN_FRAME_INTERVAL_FLOOR.as_millis() as f64;
+        let floor_ms = self.config.min_frame_interval.as_secs_f64() * 1000.0;
         let interval_ms = f64::max(srtt_half_ms, floor_ms);
 
         // Also respect the hard frame rate cap
-        let max_interval_ms = 1000.0 / constants::MAX_FRAME_RATE_HZ as f64;
+        let max_interval_ms = 1000.0 / self.config.max_frame_rate_hz.max(1) as f64;
         let interval_ms = f64::max(interval_ms, max_interval_ms);
 
+        // Back off while the path is queuing
+        let interval_ms = interval_ms + self.queuing_delay_ms();
+
         Duration::from_secs_f64(interval_ms / 1000.0)
     }
 
     /// Determine what action to take based on current state.
     pub fn poll(&self) -> PacerAction {
-        let now = Instant::now();
+        let action = self.poll_at(self.clock.now());
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            ?action,
+            data_pending = self.data_pending,
+            ack_pending = self.ack_pending_since.is_some(),
+            "pacer poll"
+        );
+        action
+    }
 
+    /// Determine what action to take at a specific time (for testing).
+    pub fn poll_at(&self, now: Instant) -> PacerAction {
         // Check if we need to send anything at all
         let needs_send = self.data_pending || self.ack_pending_since.is_some();
         if !needs_send {
@@ -157,16 +534,21 @@ impl FramePacer {
 
         // Check minimum frame interval
         if let Some(last_sent) = self.last_frame_sent {
-            let min_interval = self.min_frame_interval();
+            let min_interval = self.min_frame_interval_at(now);
             let next_allowed = last_sent + min_interval;
             if now < next_allowed {
                 return PacerAction::WaitUntil(next_allowed);
             }
         }
 
+        // Honor the peer's rate hint once the burst is spent
+        if let Some(next_token) = self.rate_limit.as_ref().and_then(|l| l.next_token_at(now)) {
+            return PacerAction::WaitUntil(next_token);
+        }
+
         // Check collection interval for state changes
         if let Some(state_time) = self.state_change_time {
-            let collection_end = state_time + constants::COLLECTION_INTERVAL;
+            let collection_end = state_time + self.collection_interval();
             if now < collection_end && self.ack_pending_since.is_none() {
                 // Wait for collection interval, unless we have an ACK to send
                 return PacerAction::WaitUntil(collection_end);
@@ -177,7 +559,7 @@ impl FramePacer {
         if !self.data_pending
             && let Some(ack_time) = self.ack_pending_since
         {
-            let ack_deadline = ack_time + constants::DELAYED_ACK_TIMEOUT;
+            let ack_deadline = ack_time + self.config.delayed_ack_timeout;
             if now < ack_deadline {
                 // Still within delayed ACK window, wait for data
                 return PacerAction::WaitUntil(ack_deadline);
@@ -188,60 +570,175 @@ impl FramePacer {
         PacerAction::SendNow
     }
 
+    /// Determine what action to take for a frame sent for `reason`.
+    ///
+    /// Close frames bypass the frame interval, rate hint and collection
+    /// window so shutdown is not held back; every other reason behaves
+    /// like [`poll`](Self::poll).
+    pub fn poll_for(&self, reason: SendReason) -> PacerAction {
+        self.poll_for_at(reason, self.clock.now())
+    }
+
+    /// Determine what action to take for `reason` at a specific time (for testing).
+    pub fn poll_for_at(&self, reason: SendReason, now: Instant) -> PacerAction {
+        match reason {
+            SendReason::Close => PacerAction::SendNow,
+            _ => self.poll_at(now),
+        }
+    }
+
     /// Check if we should send a keepalive.
     pub fn needs_keepalive(&self, last_received: Instant) -> bool {
+        self.needs_keepalive_at(last_received, self.clock.now())
+    }
+
+    /// Check if we should send a keepalive at a specific time (for testing).
+    pub fn needs_keepalive_at(&self, last_received: Instant, now: Instant) -> bool {
         if let Some(last_sent) = self.last_frame_sent {
-            let now = Instant::now();
-            let since_sent = now.duration_since(last_sent);
-            let since_received = now.duration_since(last_received);
+            let since_sent = now.saturating_duration_since(last_sent);
+            let since_received = now.saturating_duration_since(last_received);
 
             // Send keepalive if we haven't sent anything recently
             // and the connection is still alive
-            since_sent >= constants::KEEPALIVE_INTERVAL
-                && since_received < constants::DEAD_INTERVAL
+            since_sent >= self.config.keepalive_interval
+                && since_received < self.config.dead_interval
         } else {
             false
         }
     }
 
+    /// Claim a due keepalive.
+    ///
+    /// Returns `Some(SendReason::Keepalive)` at most once per keepalive
+    /// interval; the caller should then send an ACK-only frame carrying the
+    /// current timestamp and timestamp echo, and call
+    /// [`on_frame_sent`](Self::on_frame_sent).
+    pub fn take_keepalive(&mut self, last_received: Instant) -> Option<SendReason> {
+        self.take_keepalive_at(last_received, self.clock.now())
+    }
+
+    /// Claim a due keepalive at a specific time (for testing).
+    pub fn take_keepalive_at(&mut self, last_received: Instant, now: Instant) -> Option<SendReason> {
+        if !self.needs_keepalive_at(last_received, now) {
+            return None;
+        }
+        // Claimed: don't fire again until the next idle interval
+        self.last_frame_sent = Some(now);
+        Some(SendReason::Keepalive)
+    }
+
     /// Check if the connection should be considered dead.
     pub fn is_connection_dead(&self, last_received: Instant) -> bool {
-        Instant::now().duration_since(last_received) >= constants::DEAD_INTERVAL
+        self.clock.now().saturating_duration_since(last_received) >= self.config.dead_interval
     }
 }
 
 /// Retransmission controller.
 ///
 /// Tracks retransmission state and applies exponential backoff.
+///
+/// # Thundering-herd mitigation
+///
+/// Purely deterministic backoff makes every connection that lost frames to
+/// the same event (e.g. a router hiccup) retransmit in lockstep, which
+/// amplifies the congestion that caused the loss. Each backoff interval is
+/// therefore scaled by a random factor in `[1 - jitter, 1 + jitter]`, drawn
+/// from a per-connection RNG. The unjittered interval keeps doubling as
+/// usual, so jitter never compounds across retransmits.
+///
+/// Jitter is off by default ([`constants::DEFAULT_RETRANSMIT_JITTER`]), so
+/// backoff is exact unless enabled. Use [`set_jitter`](Self::set_jitter) to
+/// turn it on, or [`with_jitter`](Self::with_jitter) with a fixed seed to
+/// make the jittered sequence reproducible.
 #[derive(Debug, Clone)]
 pub struct RetransmitController {
     /// Number of retransmits for current data.
     retransmit_count: u32,
     /// Last retransmit time.
     last_retransmit: Option<Instant>,
-    /// Current timeout (after backoff).
+    /// Current timeout (after backoff and jitter).
     current_timeout: Duration,
+    /// Current timeout after backoff, before jitter is applied.
+    backoff_timeout: Duration,
     /// Base RTO from RTT estimator.
     base_rto: Duration,
+    /// Jitter factor in `[0.0, 1.0]` (0.0 disables jitter).
+    jitter: f64,
+    /// Per-connection RNG state for jitter.
+    rng_state: u64,
+    /// RTO bounds and retransmit limit.
+    config: TransportConfig,
+    /// Time source.
+    clock: SharedClock,
 }
 
 impl RetransmitController {
     /// Create a new retransmit controller.
+    ///
+    /// Jitter starts at [`constants::DEFAULT_RETRANSMIT_JITTER`] (off), with
+    /// a randomly seeded per-connection RNG for when it is enabled.
     pub fn new(initial_rto: Duration) -> Self {
         Self {
             retransmit_count: 0,
             last_retransmit: None,
             current_timeout: initial_rto,
+            backoff_timeout: initial_rto,
             base_rto: initial_rto,
+            jitter: constants::DEFAULT_RETRANSMIT_JITTER,
+            rng_state: random_seed(),
+            config: TransportConfig::default(),
+            clock: system_clock(),
+        }
+    }
+
+    /// Create a retransmit controller starting at `config.initial_rto`,
+    /// with its RTO bounds and retransmit limit.
+    pub fn with_config(config: TransportConfig) -> Self {
+        Self {
+            config,
+            ..Self::new(config.initial_rto)
         }
     }
 
+    /// Configure backoff jitter with an explicit RNG seed.
+    ///
+    /// `jitter` is clamped to `[0.0, 1.0]`; `0.0` disables jitter entirely.
+    pub fn with_jitter(mut self, jitter: f64, seed: u64) -> Self {
+        self.set_jitter(jitter);
+        self.rng_state = seed;
+        self
+    }
+
+    /// Use a custom time source instead of the system clock.
+    pub fn with_clock(mut self, clock: SharedClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Set the jitter factor (clamped to `[0.0, 1.0]`, `0.0` disables).
+    pub fn set_jitter(&mut self, jitter: f64) {
+        self.jitter = if jitter.is_finite() {
+            jitter.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+    }
+
+    /// Get the jitter factor.
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
     /// Update the base RTO from RTT estimator.
+    ///
+    /// The RTO is clamped to the configured bounds.
     pub fn set_rto(&mut self, rto: Duration) {
+        let rto = rto.max(self.config.min_rto).min(self.config.max_rto);
         self.base_rto = rto;
         // Only update current_timeout if we're not in backoff
         if self.retransmit_count == 0 {
             self.current_timeout = rto;
+            self.backoff_timeout = rto;
         }
     }
 
@@ -251,12 +748,12 @@ impl RetransmitController {
             return false;
         }
 
-        if self.retransmit_count >= constants::MAX_RETRANSMITS {
+        if self.retransmit_count >= self.config.max_retransmits {
             return false; // Give up
         }
 
         match self.last_retransmit {
-            Some(last) => Instant::now().duration_since(last) >= self.current_timeout,
+            Some(last) => self.clock.now().saturating_duration_since(last) >= self.current_timeout,
             None => true, // First transmission
         }
     }
@@ -264,11 +761,15 @@ impl RetransmitController {
     /// Record that we're retransmitting.
     pub fn on_retransmit(&mut self) {
         self.retransmit_count += 1;
-        self.last_retransmit = Some(Instant::now());
+        self.last_retransmit = Some(self.clock.now());
 
-        // Exponential backoff
-        let new_timeout = self.current_timeout * constants::RETRANSMIT_BACKOFF;
-        self.current_timeout = new_timeout.min(super::timing::constants::MAX_RTO);
+        // Exponential backoff on the unjittered interval
+        let max_rto = self.config.max_rto;
+        let new_timeout = self.backoff_timeout * constants::RETRANSMIT_BACKOFF;
+        self.backoff_timeout = new_timeout.min(max_rto);
+
+        // Spread retransmits out so connections don't fire in lockstep
+        self.current_timeout = self.apply_jitter(self.backoff_timeout).min(max_rto);
     }
 
     /// Reset after successful acknowledgment.
@@ -276,6 +777,43 @@ impl RetransmitController {
         self.retransmit_count = 0;
         self.last_retransmit = None;
         self.current_timeout = self.base_rto;
+        self.backoff_timeout = self.base_rto;
+    }
+
+    /// When the next retransmit is due for data last sent at `sent_at`.
+    ///
+    /// Measured from the last retransmit once one happened. `None` once
+    /// the retransmit limit is reached.
+    pub fn retransmit_deadline(&self, sent_at: Instant) -> Option<Instant> {
+        if self.is_failed() {
+            return None;
+        }
+        Some(self.last_retransmit.unwrap_or(sent_at) + self.current_timeout)
+    }
+
+    /// Get the current retransmission timeout (after backoff and jitter).
+    pub fn current_timeout(&self) -> Duration {
+        self.current_timeout
+    }
+
+    /// Scale an interval by a random factor in `[1 - jitter, 1 + jitter]`.
+    fn apply_jitter(&mut self, interval: Duration) -> Duration {
+        if self.jitter == 0.0 {
+            return interval;
+        }
+        // Uniform in [-1.0, 1.0)
+        let unit = (self.next_random() >> 11) as f64 / (1u64 << 53) as f64;
+        let factor = 1.0 + self.jitter * (unit * 2.0 - 1.0);
+        interval.mul_f64(factor)
+    }
+
+    /// Advance the per-connection RNG (SplitMix64).
+    fn next_random(&mut self) -> u64 {
+        self.rng_state = self.rng_state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.rng_state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
     }
 
     /// Get the current retransmit count.
@@ -285,21 +823,33 @@ impl RetransmitController {
 
     /// Check if we've exceeded max retransmits.
     pub fn is_failed(&self) -> bool {
-        self.retransmit_count >= constants::MAX_RETRANSMITS
+        self.retransmit_count >= self.config.max_retransmits
     }
 
     /// Get time until next retransmit is allowed.
     pub fn time_until_retransmit(&self) -> Option<Duration> {
         self.last_retransmit.map(|last| {
-            let elapsed = Instant::now().duration_since(last);
+            let elapsed = self.clock.now().saturating_duration_since(last);
             self.current_timeout.saturating_sub(elapsed)
         })
     }
 }
 
+/// Produce a per-connection RNG seed without pulling in an RNG dependency.
+///
+/// `RandomState` is randomly keyed per process and per instance, which is
+/// plenty to decorrelate backoff jitter between connections.
+fn random_seed() -> u64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    RandomState::new().build_hasher().finish()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::{Clock, TestClock};
 
     #[test]
     fn test_pacer_initial_state() {
@@ -323,6 +873,24 @@ mod tests {
         assert_eq!(pacer.poll(), PacerAction::SendNow);
     }
 
+    #[test]
+    fn test_pacer_close_bypasses_collection_window() {
+        let mut pacer = FramePacer::new();
+        pacer.on_state_change();
+        let now = Instant::now();
+
+        // A state change is held back by the collection window...
+        assert!(matches!(
+            pacer.poll_for_at(SendReason::StateChange, now),
+            PacerAction::WaitUntil(_)
+        ));
+        // ...but a close frame goes out immediately
+        assert_eq!(
+            pacer.poll_for_at(SendReason::Close, now),
+            PacerAction::SendNow
+        );
+    }
+
     #[test]
     fn test_pacer_ack_only() {
         let mut pacer = FramePacer::new();
@@ -356,6 +924,72 @@ mod tests {
         assert!(min_interval >= Duration::from_millis(50));
     }
 
+    #[test]
+    fn test_pacer_widens_interval_with_queuing_delay() {
+        let mut pacer = FramePacer::new();
+        pacer.set_srtt(Duration::from_millis(100));
+        assert_eq!(pacer.min_frame_interval(), Duration::from_millis(50));
+
+        // No queuing: min-RTT equals SRTT
+        pacer.set_min_rtt(Duration::from_millis(100));
+        assert_eq!(pacer.min_frame_interval(), Duration::from_millis(50));
+
+        // 80ms of queuing delay widens the interval by the same amount
+        pacer.set_min_rtt(Duration::from_millis(20));
+        assert_eq!(pacer.min_frame_interval(), Duration::from_millis(130));
+    }
+
+    #[test]
+    fn test_delivery_rate_mode_beats_srtt_fraction() {
+        let mut srtt_pacer = FramePacer::new();
+        let mut rate_pacer = FramePacer::with_mode(PacingMode::DeliveryRate);
+        for pacer in [&mut srtt_pacer, &mut rate_pacer] {
+            pacer.set_srtt(Duration::from_millis(100));
+            pacer.set_min_rtt(Duration::from_millis(100));
+        }
+
+        // Falls back to SRTT/2 until a delivery rate is known
+        assert_eq!(rate_pacer.min_frame_interval(), srtt_pacer.min_frame_interval());
+
+        // ~1.2 MB/s path: 120 KB acked per 100ms round trip
+        let start = Instant::now();
+        for _ in 0..5 {
+            rate_pacer.on_bytes_sent(1200);
+            rate_pacer.on_bytes_acked_at(120_000, Duration::from_millis(100), start);
+        }
+        assert_eq!(rate_pacer.delivery_rate(), Some(1_200_000.0));
+
+        // Cruise phase: 1200 bytes at 1.2 MB/s is 1ms, vs 50ms for SRTT/2
+        let cruise = start + Duration::from_millis(250);
+        let interval = rate_pacer.min_frame_interval_at(cruise);
+        assert_eq!(interval, Duration::from_millis(1));
+        assert!(interval < srtt_pacer.min_frame_interval());
+    }
+
+    #[test]
+    fn test_delivery_rate_gain_cycle_and_in_flight_cap() {
+        let mut pacer = FramePacer::with_mode(PacingMode::DeliveryRate);
+        pacer.set_min_rtt(Duration::from_millis(100));
+
+        let start = Instant::now();
+        pacer.on_bytes_acked_at(60_000, Duration::from_millis(100), start);
+        pacer.on_bytes_sent(6000);
+
+        // Probe phase paces faster than the drain phase
+        let probe = pacer.min_frame_interval_at(start);
+        let drain = pacer.min_frame_interval_at(start + Duration::from_millis(150));
+        // 6000 bytes at 600 KB/s is 10ms before gain
+        assert_eq!(probe, Duration::from_millis(8));
+        assert_eq!(drain.as_micros(), 13_333);
+
+        // More than 2x BDP (2 * 600KB/s * 100ms = 120KB) in flight: wait a round trip
+        for _ in 0..20 {
+            pacer.on_bytes_sent(6000);
+        }
+        let cruise = start + Duration::from_millis(250);
+        assert_eq!(pacer.min_frame_interval_at(cruise), Duration::from_millis(100));
+    }
+
     #[test]
     fn test_pacer_frame_sent_clears_state() {
         let mut pacer = FramePacer::new();
@@ -385,6 +1019,24 @@ mod tests {
         assert_eq!(controller.retransmit_count(), 0);
     }
 
+    #[test]
+    fn test_retransmit_controller_on_test_clock() {
+        let clock = TestClock::new();
+        let mut controller = RetransmitController::new(Duration::from_millis(100))
+            .with_jitter(0.0, 0)
+            .with_clock(clock.shared());
+
+        controller.on_retransmit();
+        assert_eq!(controller.current_timeout(), Duration::from_millis(200));
+
+        clock.advance(Duration::from_millis(199));
+        assert!(!controller.should_retransmit(true));
+        assert_eq!(controller.time_until_retransmit(), Some(Duration::from_millis(1)));
+
+        clock.advance(Duration::from_millis(1));
+        assert!(controller.should_retransmit(true));
+    }
+
     #[test]
     fn test_retransmit_max_attempts() {
         let mut controller = RetransmitController::new(Duration::from_millis(1));
@@ -397,6 +1049,77 @@ mod tests {
         assert!(!controller.should_retransmit(true));
     }
 
+    #[test]
+    fn test_retransmit_jitter_band() {
+        let base = Duration::from_millis(100);
+        let mut seen = Vec::new();
+
+        for seed in 0..16u64 {
+            let mut controller = RetransmitController::new(base).with_jitter(0.25, seed);
+            controller.on_retransmit();
+
+            // Unjittered backoff is 200ms; jitter keeps us within ±25%
+            let timeout = controller.current_timeout();
+            assert!(timeout >= Duration::from_millis(150), "{:?}", timeout);
+            assert!(timeout <= Duration::from_millis(250), "{:?}", timeout);
+            seen.push(timeout);
+        }
+
+        // Different seeds should spread retransmits out
+        seen.sort();
+        seen.dedup();
+        assert!(seen.len() > 1);
+    }
+
+    #[test]
+    fn test_retransmit_jitter_does_not_compound() {
+        let mut controller =
+            RetransmitController::new(Duration::from_millis(100)).with_jitter(0.25, 42);
+
+        // Each interval stays within ±25% of the deterministic 2^n backoff
+        for n in 1..=4u32 {
+            controller.on_retransmit();
+            let expected = 100.0 * 2f64.powi(n as i32);
+            let actual = controller.current_timeout().as_secs_f64() * 1000.0;
+            assert!(actual >= expected * 0.75 && actual <= expected * 1.25);
+        }
+    }
+
+    #[test]
+    fn test_retransmit_jitter_seeded_is_reproducible() {
+        let mut a = RetransmitController::new(Duration::from_millis(100)).with_jitter(0.25, 7);
+        let mut b = RetransmitController::new(Duration::from_millis(100)).with_jitter(0.25, 7);
+
+        for _ in 0..5 {
+            a.on_retransmit();
+            b.on_retransmit();
+            assert_eq!(a.current_timeout(), b.current_timeout());
+        }
+    }
+
+    #[test]
+    fn test_retransmit_jitter_disabled() {
+        let mut controller =
+            RetransmitController::new(Duration::from_millis(100)).with_jitter(0.0, 123);
+        assert_eq!(controller.jitter(), 0.0);
+
+        controller.on_retransmit();
+        assert_eq!(controller.current_timeout(), Duration::from_millis(200));
+        controller.on_retransmit();
+        assert_eq!(controller.current_timeout(), Duration::from_millis(400));
+        controller.on_retransmit();
+        assert_eq!(controller.current_timeout(), Duration::from_millis(800));
+
+        controller.on_ack();
+        assert_eq!(controller.current_timeout(), Duration::from_millis(100));
+
+        // Jitter is opt-in
+        let mut default = RetransmitController::new(Duration::from_millis(100));
+        assert_eq!(default.jitter(), 0.0);
+        default.on_retransmit();
+        assert_eq!(default.current_timeout(), Duration::from_millis(200));
+    }
+
     #[test]
     fn test_keepalive_check() {
         let pacer = FramePacer::new();
@@ -405,6 +1128,135 @@ mod tests {
         assert!(!pacer.needs_keepalive(Instant::now()));
     }
 
+    #[test]
+    fn test_keepalive_on_test_clock() {
+        let clock = TestClock::new();
+        let interval = Duration::from_millis(10);
+        let mut pacer = FramePacer::new()
+            .with_keepalive_interval(interval)
+            .with_clock(clock.shared());
+
+        pacer.on_frame_sent();
+        let last_received = clock.now();
+
+        clock.advance(interval - Duration::from_millis(1));
+        assert!(!pacer.needs_keepalive(last_received));
+
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(
+            pacer.take_keepalive(last_received),
+            Some(SendReason::Keepalive)
+        );
+        assert_eq!(pacer.take_keepalive(last_received), None);
+
+        // Nothing heard for the dead interval
+        clock.advance(constants::DEAD_INTERVAL);
+        assert!(pacer.is_connection_dead(last_received));
+        assert!(!pacer.needs_keepalive(last_received));
+    }
+
+    #[test]
+    fn test_idle_session_one_keepalive_per_interval() {
+        let interval = Duration::from_millis(10);
+        let mut pacer = FramePacer::new().with_keepalive_interval(interval);
+        let start = Instant::now();
+        pacer.on_frame_sent_at(start);
+
+        // Idle for ten intervals, polled every millisecond; the peer keeps
+        // answering so the connection stays alive
+        let mut keepalives = Vec::new();
+        for ms in 1..=100 {
+            let now = start + Duration::from_millis(ms);
+            if let Some(reason) = pacer.take_keepalive_at(now, now) {
+                assert_eq!(reason, SendReason::Keepalive);
+                pacer.on_frame_sent_at(now);
+                keepalives.push(ms);
+            }
+        }
+
+        assert_eq!(keepalives, vec![10, 20, 30, 40, 50, 60, 70, 80, 90, 100]);
+    }
+
+    #[test]
+    fn test_no_keepalive_for_dead_connection() {
+        let mut pacer = FramePacer::new().with_keepalive_interval(Duration::from_millis(10));
+        let start = Instant::now();
+        pacer.on_frame_sent_at(start);
+
+        let now = start + constants::DEAD_INTERVAL;
+        assert_eq!(pacer.take_keepalive_at(start, now), None);
+    }
+
+    #[test]
+    fn test_config_keepalive_interval() {
+        let start = Instant::now();
+        let mut fast = FramePacer::with_config(
+            TransportConfig::new().keepalive_interval(Duration::from_millis(5)),
+        );
+        let mut default = FramePacer::new();
+        fast.on_frame_sent_at(start);
+        default.on_frame_sent_at(start);
+
+        let now = start + Duration::from_millis(5);
+        assert!(!fast.needs_keepalive_at(now, start + Duration::from_millis(4)));
+        assert!(fast.needs_keepalive_at(now, now));
+        assert!(!default.needs_keepalive_at(now, now));
+    }
+
+    #[test]
+    fn test_config_frame_interval_and_collection() {
+        let config = TransportConfig::new()
+            .min_frame_interval(Duration::from_millis(1))
+            .max_frame_rate_hz(1000)
+            .collection_interval(Duration::from_millis(2));
+        let mut pacer = FramePacer::with_config(config);
+        assert_eq!(pacer.min_frame_interval(), Duration::from_millis(1));
+
+        pacer.on_state_change();
+        let changed = pacer.state_change_time.unwrap();
+        assert_eq!(
+            pacer.poll_at(changed),
+            PacerAction::WaitUntil(changed + Duration::from_millis(2))
+        );
+        assert_eq!(pacer.poll_at(changed + Duration::from_millis(3)), PacerAction::SendNow);
+    }
+
+    #[test]
+    fn test_jitter_widens_collection_interval() {
+        let mut pacer = FramePacer::new();
+        assert_eq!(pacer.collection_interval(), constants::COLLECTION_INTERVAL);
+
+        pacer.set_jitter(Duration::from_millis(12));
+        assert_eq!(pacer.collection_interval(), Duration::from_millis(20));
+        pacer.on_state_change();
+        let changed = pacer.state_change_time.unwrap();
+        assert_eq!(
+            pacer.poll_at(changed),
+            PacerAction::WaitUntil(changed + Duration::from_millis(20))
+        );
+
+        pacer.set_jitter(Duration::from_millis(500));
+        assert_eq!(pacer.collection_interval(), constants::MAX_COLLECTION_INTERVAL);
+    }
+
+    #[test]
+    fn test_retransmit_controller_config() {
+        let config = TransportConfig::new()
+            .rto(Duration::from_millis(50), Duration::from_millis(10), Duration::from_millis(150))
+            .max_retransmits(2);
+        let mut controller = RetransmitController::with_config(config).with_jitter(0.0, 1);
+        assert_eq!(controller.current_timeout(), Duration::from_millis(50));
+
+        controller.on_retransmit();
+        controller.on_retransmit();
+        assert_eq!(controller.current_timeout(), Duration::from_millis(150));
+        assert!(controller.is_failed());
+
+        controller.on_ack();
+        controller.set_rto(Duration::from_millis(1));
+        assert_eq!(controller.current_timeout(), Duration::from_millis(10));
+    }
+
     #[test]
     fn test_connection_dead() {
         let pacer = FramePacer::new();
@@ -415,4 +1267,58 @@ mod tests {
         // Very old activity would be dead
         // (Can't easily test without mocking time)
     }
+
+    #[test]
+    fn test_rate_hint_roundtrip() {
+        let hint = RateHint::new(10, 3);
+        assert_eq!(RateHint::decode(&hint.encode()).unwrap(), hint);
+        assert!(matches!(
+            RateHint::decode(&[0x0A]),
+            Err(FrameError::TooShort { expected: 4, actual: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_rate_hint_spaces_frames_after_burst() {
+        let mut pacer = FramePacer::new();
+        pacer.set_rate_limit(RateHint::new(10, 3));
+
+        // Start past the collection interval of the first state change
+        let mut now = Instant::now() + Duration::from_secs(1);
+        let mut sends = Vec::new();
+        while sends.len() < 12 {
+            pacer.on_state_change();
+            match pacer.poll_at(now) {
+                PacerAction::SendNow => {
+                    pacer.on_frame_sent_at(now);
+                    sends.push(now);
+                }
+                PacerAction::WaitUntil(until) => now = until,
+                PacerAction::Idle => unreachable!(),
+            }
+        }
+
+        let gaps: Vec<Duration> = sends.windows(2).map(|w| w[1] - w[0]).collect();
+
+        // The burst goes out at the normal 20ms pacing
+        assert!(gaps[..2].iter().all(|&gap| gap < Duration::from_millis(50)));
+
+        // Past the burst allowance, frames are at least 100ms apart
+        let floor = Duration::from_millis(100) - Duration::from_micros(10);
+        assert!(gaps[4..].iter().all(|&gap| gap >= floor), "gaps {:?}", gaps);
+    }
+
+    #[test]
+    fn test_zero_rate_hint_removes_cap() {
+        let mut pacer = FramePacer::new();
+        pacer.set_rate_limit(RateHint::new(10, 1));
+        pacer.set_rate_limit(RateHint::new(0, 0));
+
+        let now = Instant::now() + Duration::from_secs(1);
+        pacer.on_state_change();
+        pacer.on_frame_sent_at(now);
+        pacer.on_state_change();
+        let next = now + constants::MIN_FRAME_INTERVAL_FLOOR;
+        assert_eq!(pacer.poll_at(next), PacerAction::SendNow);
+    }
 }
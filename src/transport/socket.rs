@@ -3,10 +3,15 @@
 //! Provides a high-level interface for sending and receiving NOMAD frames
 //! over UDP.
 
+use std::future::Future;
 use std::io;
-use std::net::SocketAddr;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
 use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
 
+use socket2::SockRef;
 use tokio::net::UdpSocket;
 
 use super::frame::sizes;
@@ -14,6 +19,38 @@ use super::frame::sizes;
 /// Default receive buffer size.
 pub const DEFAULT_RECV_BUFFER_SIZE: usize = 65535;
 
+/// Maximum number of segments in one GSO send (kernel `UDP_MAX_SEGMENTS`).
+pub const MAX_GSO_SEGMENTS: usize = 64;
+
+/// Maximum total size of one GSO send.
+const MAX_GSO_BYTES: usize = 65_000;
+
+/// Head start each connection attempt gets before the next address is
+/// tried (RFC 8305 "Connection Attempt Delay").
+pub const HAPPY_EYEBALLS_DELAY: Duration = Duration::from_millis(250);
+
+/// Metadata for a received datagram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvMeta {
+    /// Number of bytes received.
+    pub len: usize,
+    /// Source address of the datagram.
+    pub src: SocketAddr,
+}
+
+/// Order addresses for happy eyeballs: IPv6 first, alternating families.
+fn happy_eyeballs_order(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let (v6, v4): (Vec<_>, Vec<_>) = addrs.into_iter().partition(SocketAddr::is_ipv6);
+    let mut ordered = Vec::with_capacity(v6.len() + v4.len());
+    let (mut v6, mut v4) = (v6.into_iter(), v4.into_iter());
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => return ordered,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
 /// Async UDP socket wrapper for NOMAD.
 ///
 /// Provides convenient methods for sending/receiving frames with
@@ -59,6 +96,54 @@ impl NomadSocket {
         self.socket.local_addr()
     }
 
+    /// Set the kernel receive buffer size (`SO_RCVBUF`).
+    ///
+    /// High-rate streams can overflow the default buffer, dropping
+    /// datagrams silently. The kernel may adjust the value (Linux doubles
+    /// it for bookkeeping and caps it at `net.core.rmem_max`); read it
+    /// back with [`recv_buffer_size`](Self::recv_buffer_size).
+    pub fn set_recv_buffer_size(&self, bytes: usize) -> io::Result<()> {
+        SockRef::from(&*self.socket).set_recv_buffer_size(bytes)
+    }
+
+    /// Get the kernel receive buffer size (`SO_RCVBUF`).
+    pub fn recv_buffer_size(&self) -> io::Result<usize> {
+        SockRef::from(&*self.socket).recv_buffer_size()
+    }
+
+    /// Set the kernel send buffer size (`SO_SNDBUF`).
+    pub fn set_send_buffer_size(&self, bytes: usize) -> io::Result<()> {
+        SockRef::from(&*self.socket).set_send_buffer_size(bytes)
+    }
+
+    /// Get the kernel send buffer size (`SO_SNDBUF`).
+    pub fn send_buffer_size(&self) -> io::Result<usize> {
+        SockRef::from(&*self.socket).send_buffer_size()
+    }
+
+    /// Number of datagrams the kernel dropped for this socket.
+    ///
+    /// This is the counter `SO_RXQ_OVFL` reports (receive queue
+    /// overflows). Reading it through `recvmsg` control messages needs
+    /// unsafe code, so it is read from `/proc/net/udp` instead, on the
+    /// blocking pool since the table grows with the number of sockets.
+    /// Returns `None` on other platforms or if the counter is unavailable
+    /// (for example when `/proc` is not mounted).
+    pub async fn dropped_packets(&self) -> Option<u64> {
+        #[cfg(target_os = "linux")]
+        {
+            let socket = Arc::clone(&self.socket);
+            tokio::task::spawn_blocking(move || drops::read(&socket))
+                .await
+                .ok()
+                .flatten()
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+
     /// Connect to a remote address (for client sockets).
     ///
     /// After connecting, `send` and `recv` can be used instead of
@@ -67,11 +152,185 @@ impl NomadSocket {
         self.socket.connect(addr).await
     }
 
+    /// Connect to `host`, racing its resolved addresses (RFC 8305).
+    ///
+    /// Addresses are tried IPv6 first, alternating families. Each attempt
+    /// binds a socket of the address's family, connects it and runs
+    /// `handshake` on it; the next address starts after
+    /// [`HAPPY_EYEBALLS_DELAY`], or at once if an attempt fails. The first
+    /// handshake to succeed wins and the others are dropped. Returns its
+    /// result together with the address that won.
+    ///
+    /// UDP has no connection setup of its own, so `handshake` is what
+    /// proves a path works; it should give up (or time out) on its own.
+    ///
+    /// # Errors
+    /// Fails if `host` does not resolve, or with the last attempt's error
+    /// if every address fails.
+    pub async fn connect_happy_eyeballs<F, Fut, T>(
+        host: &str,
+        port: u16,
+        handshake: F,
+    ) -> io::Result<(T, SocketAddr)>
+    where
+        F: FnMut(NomadSocket, SocketAddr) -> Fut,
+        Fut: Future<Output = io::Result<T>>,
+    {
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+        Self::race_addresses(happy_eyeballs_order(addrs), handshake).await
+    }
+
+    /// Race handshakes over `addrs`, in order, staggered by the attempt delay.
+    async fn race_addresses<F, Fut, T>(
+        addrs: Vec<SocketAddr>,
+        mut handshake: F,
+    ) -> io::Result<(T, SocketAddr)>
+    where
+        F: FnMut(NomadSocket, SocketAddr) -> Fut,
+        Fut: Future<Output = io::Result<T>>,
+    {
+        let mut pending = addrs.into_iter();
+        let mut attempts: Vec<(SocketAddr, Pin<Box<Fut>>)> = Vec::new();
+        let mut last_error = io::Error::new(io::ErrorKind::NotFound, "no addresses resolved");
+        let mut next_start = tokio::time::Instant::now();
+
+        loop {
+            // Start the next address when its turn comes or nothing is running
+            if (attempts.is_empty() || tokio::time::Instant::now() >= next_start)
+                && let Some(addr) = pending.next()
+            {
+                match Self::connected_to(addr) {
+                    Ok(socket) => {
+                        attempts.push((addr, Box::pin(handshake(socket, addr))));
+                        next_start = tokio::time::Instant::now() + HAPPY_EYEBALLS_DELAY;
+                    }
+                    Err(e) => last_error = e,
+                }
+                continue;
+            }
+            if attempts.is_empty() {
+                return Err(last_error);
+            }
+
+            let finished = std::future::poll_fn(|cx| {
+                for (index, (_, attempt)) in attempts.iter_mut().enumerate() {
+                    if let Poll::Ready(result) = attempt.as_mut().poll(cx) {
+                        return Poll::Ready((index, result));
+                    }
+                }
+                Poll::Pending
+            });
+            let has_pending = pending.len() > 0;
+
+            tokio::select! {
+                (index, result) = finished => {
+                    let (addr, _) = attempts.swap_remove(index);
+                    match result {
+                        Ok(value) => return Ok((value, addr)),
+                        Err(e) => {
+                            last_error = e;
+                            next_start = tokio::time::Instant::now();
+                        }
+                    }
+                }
+                _ = tokio::time::sleep_until(next_start), if has_pending => {}
+            }
+        }
+    }
+
+    /// Bind a socket of `addr`'s family and connect it to `addr`.
+    fn connected_to(addr: SocketAddr) -> io::Result<Self> {
+        let local: SocketAddr = if addr.is_ipv6() {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = std::net::UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self::from_socket(UdpSocket::from_std(socket)?))
+    }
+
+    /// Move to a fresh UDP socket bound to `new_local`.
+    ///
+    /// For when the local network changes (e.g. Wi-Fi to cellular) and the
+    /// old bound interface is gone. A connected socket is reconnected to
+    /// the same peer before the swap, so on error the old socket is kept
+    /// unchanged. Session state such as the `CryptoSession` lives outside
+    /// the socket and carries over as-is; the peer sees the new source
+    /// address and migrates as usual. Kernel buffer sizes are not copied,
+    /// and handles from [`socket_arc`](Self::socket_arc) keep the old
+    /// socket.
+    pub async fn rebind(&mut self, new_local: SocketAddr) -> io::Result<()> {
+        let socket = UdpSocket::bind(new_local).await?;
+        if let Ok(peer) = self.socket.peer_addr() {
+            socket.connect(peer).await?;
+        }
+        self.socket = Arc::new(socket);
+        Ok(())
+    }
+
     /// Send data to a specific address.
     pub async fn send_to(&self, data: &[u8], addr: SocketAddr) -> io::Result<usize> {
         self.socket.send_to(data, addr).await
     }
 
+    /// Send a batch of datagrams, returning how many were sent.
+    ///
+    /// On Linux, packets that share a destination and size go out in UDP
+    /// GSO sends (one syscall per up to [`MAX_GSO_SEGMENTS`] packets);
+    /// anything else, or a kernel without GSO, falls back to one `send_to`
+    /// per packet. `sendmmsg` is not used since it needs unsafe code.
+    ///
+    /// A failure after at least one packet went out is reported as a short
+    /// count so the caller can retry `packets[sent..]`; an error is only
+    /// returned if nothing was sent.
+    pub async fn send_batch(&self, packets: &[(SocketAddr, &[u8])]) -> io::Result<usize> {
+        let mut sent = 0;
+        while sent < packets.len() {
+            let result = match gso_run(&packets[sent..]) {
+                Some(run) => self.send_gso(&packets[sent..sent + run]).await,
+                None => {
+                    let (addr, data) = packets[sent];
+                    self.socket.send_to(data, addr).await.map(|_| 1)
+                }
+            };
+            match result {
+                Ok(n) => sent += n,
+                Err(_) if sent > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Send packets sharing a destination and size as one GSO send.
+    ///
+    /// Falls back to sending the first packet alone if the kernel rejects GSO.
+    #[cfg(all(target_os = "linux", target_pointer_width = "64", target_endian = "little"))]
+    async fn send_gso(&self, packets: &[(SocketAddr, &[u8])]) -> io::Result<usize> {
+        let (addr, first) = packets[0];
+        let payload: Vec<u8> = packets.iter().flat_map(|(_, data)| data.iter().copied()).collect();
+
+        let result = self
+            .socket
+            .async_io(tokio::io::Interest::WRITABLE, || {
+                gso::send(&self.socket, &payload, first.len(), addr)
+            })
+            .await;
+        match result {
+            Ok(_) => Ok(packets.len()),
+            Err(_) => self.socket.send_to(first, addr).await.map(|_| 1),
+        }
+    }
+
+    /// GSO is unavailable on this platform: send the first packet alone.
+    #[cfg(not(all(target_os = "linux", target_pointer_width = "64", target_endian = "little")))]
+    async fn send_gso(&self, packets: &[(SocketAddr, &[u8])]) -> io::Result<usize> {
+        let (addr, data) = packets[0];
+        self.socket.send_to(data, addr).await.map(|_| 1)
+    }
+
     /// Send data to the connected address.
     pub async fn send(&self, data: &[u8]) -> io::Result<usize> {
         self.socket.send(data).await
@@ -83,6 +342,15 @@ impl NomadSocket {
         Ok((&self.recv_buffer[..len], addr))
     }
 
+    /// Receive a datagram into `buf` with its source address.
+    ///
+    /// The source address lets the caller detect roaming (see
+    /// `MigrationState`).
+    pub async fn recv_with_metadata(&self, buf: &mut [u8]) -> io::Result<RecvMeta> {
+        let (len, src) = self.socket.recv_from(buf).await?;
+        Ok(RecvMeta { len, src })
+    }
+
     /// Receive data from the connected address.
     pub async fn recv(&mut self) -> io::Result<&[u8]> {
         let len = self.socket.recv(&mut self.recv_buffer).await?;
@@ -121,6 +389,97 @@ impl NomadSocket {
     }
 }
 
+/// Length of the GSO-eligible run at the start of `packets`, if longer than one.
+///
+/// A run shares the destination and (non-zero) size of the first packet.
+fn gso_run(packets: &[(SocketAddr, &[u8])]) -> Option<usize> {
+    let (addr, first) = packets.first()?;
+    let size = first.len();
+    if size == 0 {
+        return None;
+    }
+    let limit = MAX_GSO_SEGMENTS.min(MAX_GSO_BYTES / size);
+    let run = packets
+        .iter()
+        .take(limit)
+        .take_while(|(a, data)| a == addr && data.len() == size)
+        .count();
+    (run > 1).then_some(run)
+}
+
+/// UDP GSO via `sendmsg` with a `UDP_SEGMENT` control message.
+#[cfg(all(target_os = "linux", target_pointer_width = "64", target_endian = "little"))]
+mod gso {
+    use std::io::{self, IoSlice};
+    use std::net::SocketAddr;
+
+    use socket2::{MsgHdr, SockAddr, SockRef};
+    use tokio::net::UdpSocket;
+
+    /// `SOL_UDP`
+    const SOL_UDP: i32 = 17;
+    /// `UDP_SEGMENT`
+    const UDP_SEGMENT: i32 = 103;
+    /// `sizeof(struct cmsghdr)`: `size_t` length plus two `int`s
+    const CMSG_HEADER_SIZE: usize = 16;
+    /// `CMSG_SPACE(sizeof(u16))`
+    const CMSG_SPACE: usize = 24;
+
+    /// Send `payload` to `addr`, split by the kernel into `segment`-byte datagrams.
+    pub(super) fn send(
+        socket: &UdpSocket,
+        payload: &[u8],
+        segment: usize,
+        addr: SocketAddr,
+    ) -> io::Result<usize> {
+        let mut control = [0u8; CMSG_SPACE];
+        let cmsg_len = CMSG_HEADER_SIZE + size_of::<u16>();
+        control[..8].copy_from_slice(&(cmsg_len as u64).to_le_bytes());
+        control[8..12].copy_from_slice(&SOL_UDP.to_le_bytes());
+        control[12..16].copy_from_slice(&UDP_SEGMENT.to_le_bytes());
+        control[16..18].copy_from_slice(&(segment as u16).to_le_bytes());
+
+        let addr = SockAddr::from(addr);
+        let bufs = [IoSlice::new(payload)];
+        let msg = MsgHdr::new()
+            .with_addr(&addr)
+            .with_buffers(&bufs)
+            .with_control(&control);
+        SockRef::from(socket).sendmsg(&msg, 0)
+    }
+}
+
+/// Per-socket drop counter from the kernel's UDP tables.
+#[cfg(target_os = "linux")]
+mod drops {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::MetadataExt;
+
+    use tokio::net::UdpSocket;
+
+    /// Read the `drops` column for `socket`, matched by socket inode.
+    pub(super) fn read(socket: &UdpSocket) -> Option<u64> {
+        let fd = socket.as_raw_fd();
+        let inode = std::fs::metadata(format!("/proc/self/fd/{fd}")).ok()?.ino();
+        ["/proc/net/udp", "/proc/net/udp6"]
+            .iter()
+            .filter_map(|path| std::fs::read_to_string(path).ok())
+            .find_map(|table| find_drops(&table, inode))
+    }
+
+    /// Find the drop count for `inode` in a `/proc/net/udp` style table.
+    pub(super) fn find_drops(table: &str, inode: u64) -> Option<u64> {
+        table.lines().skip(1).find_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // inode is column 9, drops the last of 13
+            if fields.len() < 13 || fields[9].parse::<u64>().ok()? != inode {
+                return None;
+            }
+            fields[12].parse().ok()
+        })
+    }
+}
+
 /// Builder for creating NOMAD sockets with custom options.
 #[derive(Debug, Clone)]
 pub struct NomadSocketBuilder {
@@ -205,6 +564,148 @@ mod tests {
         assert_eq!(from, client.local_addr().unwrap());
     }
 
+    #[tokio::test]
+    async fn test_recv_with_metadata_reports_source() {
+        let server = NomadSocket::bind("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let client = NomadSocket::bind("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+
+        client
+            .send_to(b"roaming", server.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        let mut buf = [0u8; 64];
+        let meta = server.recv_with_metadata(&mut buf).await.unwrap();
+        assert_eq!(&buf[..meta.len], b"roaming");
+        assert_eq!(meta.src, client.local_addr().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_send_batch_all_arrive() {
+        let server = NomadSocket::bind("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let client = NomadSocket::bind("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        let dest = server.local_addr().unwrap();
+
+        let payloads: Vec<Vec<u8>> = (0..64u8).map(|i| vec![i; 512]).collect();
+        let packets: Vec<_> = payloads.iter().map(|p| (dest, p.as_slice())).collect();
+        assert_eq!(client.send_batch(&packets).await.unwrap(), 64);
+
+        let mut buf = [0u8; 1024];
+        for i in 0..64u8 {
+            let meta = server.recv_with_metadata(&mut buf).await.unwrap();
+            assert_eq!(meta.len, 512);
+            assert!(buf[..meta.len].iter().all(|&b| b == i));
+        }
+    }
+
+    #[test]
+    fn test_gso_run_grouping() {
+        let a: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+        let b: SocketAddr = "127.0.0.1:2000".parse().unwrap();
+        let big = [0u8; 100];
+        let small = [0u8; 10];
+
+        assert_eq!(gso_run(&[(a, &big), (a, &big), (a, &small)]), Some(2));
+        assert_eq!(gso_run(&[(a, &big), (b, &big)]), None);
+        assert_eq!(gso_run(&[]), None);
+
+        let many = vec![(a, &big[..]); 100];
+        assert_eq!(gso_run(&many), Some(MAX_GSO_SEGMENTS));
+    }
+
+    #[test]
+    fn test_happy_eyeballs_order() {
+        let v4 = |port| SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let v6 = |port| SocketAddr::from((Ipv6Addr::LOCALHOST, port));
+        assert_eq!(
+            happy_eyeballs_order(vec![v4(1), v4(2), v6(3), v4(4)]),
+            vec![v6(3), v4(1), v4(2), v4(4)]
+        );
+    }
+
+    /// Whether `::1` can be bound; CI hosts without IPv6 skip the race tests.
+    fn ipv6_loopback_available() -> bool {
+        std::net::UdpSocket::bind((Ipv6Addr::LOCALHOST, 0)).is_ok()
+    }
+
+    /// Bind `ip` on `port`; answers "ping" with "pong" unless `silent`.
+    async fn pong_server(ip: std::net::IpAddr, port: u16, silent: bool) -> SocketAddr {
+        let socket = UdpSocket::bind((ip, port)).await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 16];
+            while let Ok((len, from)) = socket.recv_from(&mut buf).await {
+                if !silent && &buf[..len] == b"ping" {
+                    let _ = socket.send_to(b"pong", from).await;
+                }
+            }
+        });
+        addr
+    }
+
+    async fn ping(socket: NomadSocket, _addr: SocketAddr) -> io::Result<NomadSocket> {
+        socket.send(b"ping").await?;
+        let mut buf = [0u8; 16];
+        let len = socket.inner().recv(&mut buf).await?;
+        if &buf[..len] != b"pong" {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad reply"));
+        }
+        Ok(socket)
+    }
+
+    #[tokio::test]
+    async fn test_happy_eyeballs_dual_stack() {
+        if !ipv6_loopback_available() {
+            eprintln!("skipping: cannot bind ::1");
+            return;
+        }
+
+        // One port answering on both loopbacks
+        let v4 = pong_server(Ipv4Addr::LOCALHOST.into(), 0, false).await;
+        let v6 = pong_server(Ipv6Addr::LOCALHOST.into(), v4.port(), false).await;
+
+        let (socket, winner) =
+            NomadSocket::race_addresses(happy_eyeballs_order(vec![v4, v6]), ping).await.unwrap();
+        assert_eq!(winner, v6);
+        assert!(socket.local_addr().unwrap().is_ipv6());
+
+        // Through the resolver too
+        let (_, winner) = NomadSocket::connect_happy_eyeballs("127.0.0.1", v4.port(), ping)
+            .await
+            .unwrap();
+        assert_eq!(winner, v4);
+    }
+
+    #[tokio::test]
+    async fn test_happy_eyeballs_falls_back_to_ipv4() {
+        if !ipv6_loopback_available() {
+            eprintln!("skipping: cannot bind ::1");
+            return;
+        }
+
+        // IPv6 swallows the handshake; IPv4 gets its turn after the delay
+        let v4 = pong_server(Ipv4Addr::LOCALHOST.into(), 0, false).await;
+        let v6 = pong_server(Ipv6Addr::LOCALHOST.into(), 0, true).await;
+
+        let start = std::time::Instant::now();
+        let (socket, winner) =
+            NomadSocket::race_addresses(happy_eyeballs_order(vec![v4, v6]), ping).await.unwrap();
+        assert_eq!(winner, v4);
+        assert!(socket.local_addr().unwrap().is_ipv4());
+        assert!(start.elapsed() >= HAPPY_EYEBALLS_DELAY);
+
+        let result = NomadSocket::race_addresses(Vec::new(), ping).await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
     #[tokio::test]
     async fn test_socket_connected() {
         let mut server = NomadSocket::bind("127.0.0.1:0".parse().unwrap())
@@ -226,6 +727,66 @@ mod tests {
         assert_eq!(received, data);
     }
 
+    #[cfg(feature = "crypto")]
+    #[tokio::test]
+    async fn test_rebind_keeps_session() {
+        use crate::crypto::{CryptoSession, Role, SessionId, SessionKey};
+
+        let session_id = SessionId::generate();
+        let initiator_key = SessionKey::from_bytes([0x01; 32]);
+        let responder_key = SessionKey::from_bytes([0x02; 32]);
+        let mut client_crypto = CryptoSession::new(
+            session_id,
+            Role::Initiator,
+            initiator_key.clone(),
+            responder_key.clone(),
+            [0x42; 32],
+        );
+        let mut server_crypto =
+            CryptoSession::new(session_id, Role::Responder, responder_key, initiator_key, [0x42; 32]);
+
+        let mut server = NomadSocket::bind("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let mut client = NomadSocket::bind("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        client.connect(server_addr).await.unwrap();
+        let old_addr = client.local_addr().unwrap();
+
+        // One frame each way, then the client changes networks
+        let mut round_trip = async |client: &mut NomadSocket, msg: &[u8]| {
+            let (nonce, ciphertext) = client_crypto.encrypt_frame(0x03, 0, msg).unwrap();
+            let mut frame = nonce.to_le_bytes().to_vec();
+            frame.extend_from_slice(&ciphertext);
+            client.send(&frame).await.unwrap();
+
+            let (data, from) = server.recv_from().await.unwrap();
+            let nonce = u64::from_le_bytes(data[..8].try_into().unwrap());
+            let plaintext = server_crypto.decrypt_frame(0x03, 0, nonce, &data[8..]).unwrap();
+            assert_eq!(plaintext, msg);
+
+            let (nonce, ciphertext) = server_crypto.encrypt_frame(0x03, 0, &plaintext).unwrap();
+            let mut reply = nonce.to_le_bytes().to_vec();
+            reply.extend_from_slice(&ciphertext);
+            server.send_to(&reply, from).await.unwrap();
+
+            let data = client.recv().await.unwrap();
+            let reply_nonce = u64::from_le_bytes(data[..8].try_into().unwrap());
+            assert_eq!(client_crypto.decrypt_frame(0x03, 0, reply_nonce, &data[8..]).unwrap(), msg);
+            (nonce, from)
+        };
+
+        let (first_nonce, from) = round_trip(&mut client, b"before").await;
+        assert_eq!(from, old_addr);
+
+        client.rebind("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let new_addr = client.local_addr().unwrap();
+        assert_ne!(new_addr, old_addr);
+
+        // Same session: counters continue rather than restart
+        let (second_nonce, from) = round_trip(&mut client, b"after").await;
+        assert_eq!(from, new_addr);
+        assert_eq!(second_nonce, first_nonce + 1);
+    }
+
     #[test]
     fn test_socket_builder() {
         let builder = NomadSocketBuilder::new()
@@ -248,4 +809,44 @@ mod tests {
         let expected = 1200 + sizes::DATA_FRAME_HEADER_SIZE + sizes::AEAD_TAG_SIZE;
         assert_eq!(socket.max_frame_size(), expected);
     }
+
+    #[tokio::test]
+    async fn test_kernel_buffer_sizes() {
+        let socket = NomadSocket::bind("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+
+        // Below the usual rmem_max/wmem_max; Linux reports double the value
+        let requested = 128 * 1024;
+        socket.set_recv_buffer_size(requested).unwrap();
+        let actual = socket.recv_buffer_size().unwrap();
+        assert!(actual >= requested && actual <= 2 * requested, "{actual}");
+
+        socket.set_send_buffer_size(requested).unwrap();
+        let actual = socket.send_buffer_size().unwrap();
+        assert!(actual >= requested && actual <= 2 * requested, "{actual}");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_find_drops() {
+        let table = "\
+   sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  123: 0100007F:A1B2 00000000:0000 07 00000000:00000000 00:00000000 00000000     0        0 4242 2 0000000000000000 17
+  124: 0100007F:A1B3 00000000:0000 07 00000000:00000000 00:00000000 00000000     0        0 4343 2 0000000000000000 0
+";
+        assert_eq!(drops::find_drops(table, 4242), Some(17));
+        assert_eq!(drops::find_drops(table, 4343), Some(0));
+        assert_eq!(drops::find_drops(table, 1), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_dropped_packets_fresh_socket() {
+        let socket = NomadSocket::bind("127.0.0.1:0".parse().unwrap())
+            .await
+            .unwrap();
+        // Sandboxes without /proc report None rather than a count
+        assert!(matches!(socket.dropped_packets().await, Some(0) | None));
+    }
 }
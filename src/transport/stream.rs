@@ -0,0 +1,285 @@
+//! Async stream adapter for a connected NOMAD session.
+//!
+//! [`NomadStream`] wraps a [`NomadSocket`] and an established
+//! [`CryptoSession`] so tokio-based applications can consume inbound
+//! payloads as a [`Stream`] instead of hand-rolling the recv/decrypt loop.
+//! Outbound payloads go through [`poll_ready`](NomadStream::poll_ready),
+//! [`start_send`](NomadStream::start_send) and
+//! [`poll_flush`](NomadStream::poll_flush), which follow the `Sink`
+//! contract; the internal [`FramePacer`] decides when each frame may leave,
+//! and `poll_ready` stays pending until the previous frame has been sent.
+
+use std::future::{Future, poll_fn};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use futures_core::Stream;
+use tokio::io::ReadBuf;
+use tokio::time::Sleep;
+
+use crate::core::NomadError;
+use crate::crypto::CryptoSession;
+
+use super::frame::{DataFrame, SessionId};
+use super::pacing::{FramePacer, PacerAction};
+use super::socket::NomadSocket;
+
+/// Stream of decrypted payloads from, and paced sink of payloads to, one
+/// peer.
+///
+/// Frames from other addresses, and frames that must be silently dropped
+/// per the spec (bad tag, replay), are skipped. ACK-only frames carry no
+/// payload and are not yielded. The stream never ends on its own.
+pub struct NomadStream {
+    /// Underlying socket.
+    socket: NomadSocket,
+    /// Connected peer.
+    peer: SocketAddr,
+    /// Established crypto session.
+    session: CryptoSession,
+    /// Decides when outbound frames may be sent.
+    pacer: FramePacer,
+    /// Session start, for frame timestamps.
+    started: Instant,
+    /// Most recent timestamp received from the peer.
+    timestamp_echo: u32,
+    /// Receive buffer.
+    recv_buf: Vec<u8>,
+    /// Encrypted frame waiting for the pacer or the socket.
+    pending: Option<Vec<u8>>,
+    /// Timer for the pacer's next send slot.
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl NomadStream {
+    /// Wrap a socket and an established session with `peer`.
+    pub fn new(socket: NomadSocket, peer: SocketAddr, session: CryptoSession) -> Self {
+        let recv_buf = vec![0u8; socket.max_frame_size()];
+        Self {
+            socket,
+            peer,
+            session,
+            pacer: FramePacer::new(),
+            started: Instant::now(),
+            timestamp_echo: 0,
+            recv_buf,
+            pending: None,
+            delay: None,
+        }
+    }
+
+    /// Use a custom frame pacer.
+    pub fn with_pacer(mut self, pacer: FramePacer) -> Self {
+        self.pacer = pacer;
+        self
+    }
+
+    /// Get the connected peer address.
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    /// Get the crypto session.
+    pub fn session(&self) -> &CryptoSession {
+        &self.session
+    }
+
+    /// Check if a frame is still waiting to be sent.
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Wait until another payload can be accepted.
+    ///
+    /// Pending while the previously started frame is still held back by
+    /// the pacer or the socket.
+    pub fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), NomadError>> {
+        self.poll_flush(cx)
+    }
+
+    /// Encrypt a payload and queue it for sending.
+    ///
+    /// Call only after [`poll_ready`](Self::poll_ready) returned `Ready(Ok)`;
+    /// the frame leaves on the next [`poll_flush`](Self::poll_flush).
+    ///
+    /// # Errors
+    /// Returns a `Config` error if a frame is already pending, and
+    /// passes through encryption errors.
+    pub fn start_send(&mut self, payload: Vec<u8>) -> Result<(), NomadError> {
+        if self.pending.is_some() {
+            return Err(NomadError::Config(
+                "start_send called before poll_ready".to_string(),
+            ));
+        }
+
+        let mut frame = DataFrame::new(
+            SessionId::zero(),
+            0,
+            self.timestamp(),
+            self.timestamp_echo,
+            payload,
+        );
+        self.pending = Some(frame.encode(&mut self.session)?);
+        self.pacer.on_state_change();
+        Ok(())
+    }
+
+    /// Send the pending frame once the pacer allows it.
+    pub fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), NomadError>> {
+        let Some(wire) = &self.pending else {
+            return Poll::Ready(Ok(()));
+        };
+
+        while let PacerAction::WaitUntil(at) = self.pacer.poll() {
+            let at = tokio::time::Instant::from_std(at);
+            let delay = self
+                .delay
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(at)));
+            delay.as_mut().reset(at);
+            if delay.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        let sent = std::task::ready!(self.socket.inner().poll_send_to(cx, wire, self.peer))?;
+        self.pacer.on_frame_sent();
+        self.pacer.on_bytes_sent(sent);
+        self.pending = None;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Flush the pending frame; the session itself stays open.
+    pub fn poll_close(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), NomadError>> {
+        self.poll_flush(cx)
+    }
+
+    /// Send one payload, waiting for the pacer.
+    pub async fn send(&mut self, payload: Vec<u8>) -> Result<(), NomadError> {
+        poll_fn(|cx| self.poll_ready(cx)).await?;
+        self.start_send(payload)?;
+        poll_fn(|cx| self.poll_flush(cx)).await
+    }
+
+    /// Milliseconds since the session started, for frame timestamps.
+    fn timestamp(&self) -> u32 {
+        self.started.elapsed().as_millis() as u32
+    }
+}
+
+impl Stream for NomadStream {
+    type Item = Result<Vec<u8>, NomadError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            let mut buf = ReadBuf::new(&mut this.recv_buf);
+            let from = match this.socket.inner().poll_recv_from(cx, &mut buf) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                Poll::Ready(Ok(from)) => from,
+            };
+            if from != this.peer {
+                continue;
+            }
+
+            let frame = match DataFrame::decode(buf.filled(), &mut this.session) {
+                Ok(frame) => frame,
+                Err(e) if e.is_silent_drop() => continue,
+                Err(e) => return Poll::Ready(Some(Err(e.into()))),
+            };
+            this.timestamp_echo = frame.payload_header.timestamp;
+            if frame.header.flags.is_ack_only() || frame.sync_message.is_empty() {
+                continue;
+            }
+            return Poll::Ready(Some(Ok(frame.sync_message)));
+        }
+    }
+}
+
+impl std::fmt::Debug for NomadStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NomadStream")
+            .field("peer", &self.peer)
+            .field("pending", &self.pending.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{Role, SessionKey};
+
+    fn session_pair() -> (CryptoSession, CryptoSession) {
+        let session_id = crate::crypto::SessionId::generate();
+        let initiator_key = SessionKey::from_bytes([0x01; 32]);
+        let responder_key = SessionKey::from_bytes([0x02; 32]);
+        let handshake_hash = [0x42; 32];
+
+        let initiator = CryptoSession::new(
+            session_id,
+            Role::Initiator,
+            initiator_key.clone(),
+            responder_key.clone(),
+            handshake_hash,
+        );
+        let responder = CryptoSession::new(
+            session_id,
+            Role::Responder,
+            responder_key,
+            initiator_key,
+            handshake_hash,
+        );
+        (initiator, responder)
+    }
+
+    async fn next(stream: &mut NomadStream) -> Option<Result<Vec<u8>, NomadError>> {
+        poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+    }
+
+    #[tokio::test]
+    async fn test_echo_through_stream() {
+        let localhost = "127.0.0.1:0".parse().unwrap();
+        let client_socket = NomadSocket::bind(localhost).await.unwrap();
+        let server_socket = NomadSocket::bind(localhost).await.unwrap();
+        let client_addr = client_socket.local_addr().unwrap();
+        let server_addr = server_socket.local_addr().unwrap();
+        let (client_session, server_session) = session_pair();
+
+        // Minimal server: echo every payload back
+        let server = tokio::spawn(async move {
+            let mut stream = NomadStream::new(server_socket, client_addr, server_session);
+            while let Some(Ok(payload)) = next(&mut stream).await {
+                stream.send(payload).await.unwrap();
+            }
+        });
+
+        let mut client = NomadStream::new(client_socket, server_addr, client_session);
+        for message in [&b"hello"[..], b"nomad", b"stream"] {
+            client.send(message.to_vec()).await.unwrap();
+            let echoed = tokio::time::timeout(std::time::Duration::from_secs(2), next(&mut client))
+                .await
+                .expect("echo timed out")
+                .unwrap()
+                .unwrap();
+            assert_eq!(echoed, message);
+        }
+        assert!(!client.has_pending());
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_start_send_requires_ready() {
+        let localhost = "127.0.0.1:0".parse().unwrap();
+        let socket = NomadSocket::bind(localhost).await.unwrap();
+        let (session, _) = session_pair();
+        let mut stream = NomadStream::new(socket, "127.0.0.1:9".parse().unwrap(), session);
+
+        // The pacer's collection interval holds the first frame back
+        stream.start_send(b"first".to_vec()).unwrap();
+        assert!(stream.has_pending());
+        assert!(stream.start_send(b"second".to_vec()).is_err());
+    }
+}
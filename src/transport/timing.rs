@@ -2,8 +2,14 @@
 //!
 //! Implements RFC 6298 RTT estimation algorithm as specified in 2-TRANSPORT.md.
 
+use std::collections::VecDeque;
+#[cfg(feature = "metrics")]
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+#[cfg(feature = "metrics")]
+use crate::core::Metrics;
+
 /// RTT timing constants from the protocol specification.
 pub mod constants {
     use std::time::Duration;
@@ -28,12 +34,54 @@ pub mod constants {
 
     /// Minimum RTT granularity for RTO calculation.
     pub const MIN_RTO_GRANULARITY_MS: f64 = 100.0;
+
+    /// How long RTT samples are kept for windowed min-RTT queries.
+    pub const MIN_RTT_RETENTION: Duration = Duration::from_secs(10);
+
+    /// Maximum number of RTT samples kept for min-RTT tracking.
+    pub const MAX_RTT_SAMPLES: usize = 1024;
+
+    /// Window over which acknowledged bytes count toward the delivery rate.
+    pub const DELIVERY_RATE_WINDOW: Duration = Duration::from_secs(2);
+
+    /// Largest timestamp delta accepted as an RTT sample, in milliseconds.
+    ///
+    /// Anything above this (including the ~4e9 ms produced by naive
+    /// subtraction across the u32 wrap) is treated as an invalid sample.
+    pub const MAX_TIMESTAMP_DELTA_MS: u32 = MAX_RTO.as_millis() as u32;
+
+    /// Gain for jitter smoothing (1/16, per RFC 3550).
+    pub const JITTER_GAIN: f64 = 1.0 / 16.0;
+}
+
+/// Wraparound-safe difference between two u32 millisecond timestamps.
+///
+/// Timestamps are compared in serial-number order (RFC 1982): `later` is
+/// considered after `earlier` when the wrapping difference is below 2^31,
+/// so a pair straddling the ~49-day wrap still yields a small delta.
+/// Returns `None` if `later` is actually before `earlier`, or if the delta
+/// exceeds [`constants::MAX_TIMESTAMP_DELTA_MS`].
+pub fn timestamp_delta(later: u32, earlier: u32) -> Option<u32> {
+    let delta = later.wrapping_sub(earlier);
+    if delta >= 1 << 31 || delta > constants::MAX_TIMESTAMP_DELTA_MS {
+        return None;
+    }
+    Some(delta)
+}
+
+/// Check whether timestamp `a` is after `b` in serial-number order.
+fn timestamp_after(a: u32, b: u32) -> bool {
+    let delta = a.wrapping_sub(b);
+    delta != 0 && delta < 1 << 31
 }
 
 /// RTT estimator implementing RFC 6298.
 ///
 /// This struct maintains smoothed RTT (SRTT) and RTT variance (RTTVAR) values,
 /// and computes an adaptive Retransmission Timeout (RTO).
+///
+/// It also tracks a windowed minimum RTT (the path's base latency) and a
+/// rough delivery-rate estimate, for congestion-aware pacing.
 #[derive(Debug, Clone)]
 pub struct RttEstimator {
     /// Smoothed RTT in milliseconds.
@@ -44,6 +92,13 @@ pub struct RttEstimator {
     rto: Duration,
     /// Whether we've received the first RTT sample.
     initialized: bool,
+    /// Recent RTT samples (time, ms), oldest first.
+    samples: VecDeque<(Instant, f64)>,
+    /// Recent acknowledgments (time, bytes), oldest first.
+    acks: VecDeque<(Instant, u64)>,
+    /// Registry the SRTT is reported to.
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<Metrics>>,
 }
 
 impl Default for RttEstimator {
@@ -60,18 +115,66 @@ impl RttEstimator {
             rttvar: 0.0,
             rto: constants::INITIAL_RTO,
             initialized: false,
+            samples: VecDeque::new(),
+            acks: VecDeque::new(),
+            #[cfg(feature = "metrics")]
+            metrics: None,
         }
     }
 
+    /// Report each SRTT update to a metrics registry.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Update RTT estimate with a new sample.
+    pub fn update(&mut self, sample: Duration) {
+        self.update_at(sample, Instant::now());
+    }
+
+    /// Feed an RTT sample, applying Karn's algorithm.
+    ///
+    /// A sample from a retransmitted frame is ambiguous (the ack may be for
+    /// either transmission), so it is discarded and any RTO backoff stays
+    /// in place. A clean sample updates the estimate, which also resets the
+    /// backoff. Returns whether the sample was used.
+    pub fn on_rtt_sample(&mut self, sample: Duration, was_retransmitted: bool) -> bool {
+        self.on_rtt_sample_at(sample, was_retransmitted, Instant::now())
+    }
+
+    /// Feed an RTT sample taken at a specific time, applying Karn's algorithm.
+    pub fn on_rtt_sample_at(&mut self, sample: Duration, was_retransmitted: bool, now: Instant) -> bool {
+        if was_retransmitted {
+            return false;
+        }
+        self.update_at(sample, now);
+        true
+    }
+
+    /// Update RTT estimate with a sample taken at a specific time.
+    ///
+    /// The sample is trusted as-is; use [`on_rtt_sample`](Self::on_rtt_sample)
+    /// when it may come from a retransmitted frame.
     ///
     /// Implements RFC 6298 RTT calculation:
     /// - First measurement: SRTT = sample, RTTVAR = sample / 2
     /// - Subsequent: RTTVAR = 0.75 * RTTVAR + 0.25 * |SRTT - sample|
     /// - SRTT = 0.875 * SRTT + 0.125 * sample
-    pub fn update(&mut self, sample: Duration) {
+    pub fn update_at(&mut self, sample: Duration, now: Instant) {
         let sample_ms = sample.as_secs_f64() * 1000.0;
 
+        // Keep recent samples for windowed min-RTT
+        while let Some(&(at, _)) = self.samples.front() {
+            let expired = now.saturating_duration_since(at) > constants::MIN_RTT_RETENTION;
+            if !expired && self.samples.len() < constants::MAX_RTT_SAMPLES {
+                break;
+            }
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now, sample_ms));
+
         if !self.initialized {
             // First measurement
             self.srtt = sample_ms;
@@ -99,6 +202,11 @@ impl RttEstimator {
         );
 
         self.rto = Duration::from_millis(rto_ms as u64);
+
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.record_srtt(self.srtt());
+        }
     }
 
     /// Get the current smoothed RTT.
@@ -116,6 +224,62 @@ impl RttEstimator {
         Duration::from_secs_f64(self.rttvar / 1000.0)
     }
 
+    /// Get the minimum RTT observed within `window` of now.
+    ///
+    /// Windows longer than `MIN_RTT_RETENTION` are truncated to it.
+    pub fn min_rtt(&self, window: Duration) -> Option<Duration> {
+        self.min_rtt_at(window, Instant::now())
+    }
+
+    /// Get the minimum RTT observed within `window` of a specific time.
+    pub fn min_rtt_at(&self, window: Duration, now: Instant) -> Option<Duration> {
+        self.samples
+            .iter()
+            .filter(|(at, _)| now.saturating_duration_since(*at) <= window)
+            .map(|&(_, ms)| ms)
+            .reduce(f64::min)
+            .map(|ms| Duration::from_secs_f64(ms / 1000.0))
+    }
+
+    /// Record bytes acknowledged by the peer (for delivery-rate estimation).
+    pub fn on_bytes_acked(&mut self, bytes: usize) {
+        self.on_bytes_acked_at(bytes, Instant::now());
+    }
+
+    /// Record bytes acknowledged at a specific time.
+    pub fn on_bytes_acked_at(&mut self, bytes: usize, now: Instant) {
+        while let Some(&(at, _)) = self.acks.front() {
+            let expired = now.saturating_duration_since(at) >= constants::DELIVERY_RATE_WINDOW;
+            if !expired && self.acks.len() < constants::MAX_RTT_SAMPLES {
+                break;
+            }
+            self.acks.pop_front();
+        }
+        self.acks.push_back((now, bytes as u64));
+    }
+
+    /// Estimated delivery rate in bytes per second.
+    pub fn bandwidth_estimate(&self) -> Option<f64> {
+        self.bandwidth_estimate_at(Instant::now())
+    }
+
+    /// Estimated delivery rate at a specific time.
+    ///
+    /// Bytes acked within `DELIVERY_RATE_WINDOW` of `now`, divided by the
+    /// time since the oldest of those acks. The estimate decays once acks
+    /// stop, so a past burst does not pin it high. Returns `None` until acks
+    /// span a measurable interval, and again once they all age out.
+    pub fn bandwidth_estimate_at(&self, now: Instant) -> Option<f64> {
+        let mut recent = self
+            .acks
+            .iter()
+            .filter(|(at, _)| now.saturating_duration_since(*at) < constants::DELIVERY_RATE_WINDOW);
+        let &(oldest, first_bytes) = recent.next()?;
+        let bytes = first_bytes + recent.map(|(_, bytes)| bytes).sum::<u64>();
+        let elapsed = now.saturating_duration_since(oldest).as_secs_f64();
+        (elapsed > 0.0).then(|| bytes as f64 / elapsed)
+    }
+
     /// Get the current retransmission timeout.
     pub fn rto(&self) -> Duration {
         self.rto
@@ -152,6 +316,72 @@ impl RttEstimator {
     }
 }
 
+/// Interarrival jitter estimator (RFC 3550, section 6.4.1).
+///
+/// Fed with each received frame's timestamp and our own timestamp when it
+/// arrived. For consecutive frames the difference in one-way delay is
+/// `(arrival_j - arrival_i) - (sent_j - sent_i)`, which needs no clock
+/// sync since the unknown offset cancels out; its magnitude is smoothed
+/// into the jitter estimate.
+#[derive(Debug, Clone, Default)]
+pub struct JitterEstimator {
+    /// Peer and local timestamps of the last in-order frame.
+    last: Option<(u32, u32)>,
+    /// Smoothed jitter in milliseconds.
+    jitter_ms: f64,
+    /// Whether at least one delay difference has been measured.
+    initialized: bool,
+}
+
+impl JitterEstimator {
+    /// Create an estimator with no samples.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a frame sent at `peer_timestamp` that arrived at `local_timestamp`.
+    ///
+    /// Both are millisecond timestamps (peer's and ours, e.g. from
+    /// [`TimestampTracker::now`]). Reordered frames are ignored, as are
+    /// gaps too large to be a plausible delay change (see
+    /// [`timestamp_delta`]).
+    pub fn on_frame(&mut self, peer_timestamp: u32, local_timestamp: u32) {
+        let Some((last_peer, last_local)) = self.last else {
+            self.last = Some((peer_timestamp, local_timestamp));
+            return;
+        };
+        let (Some(sent), Some(arrived)) = (
+            timestamp_delta(peer_timestamp, last_peer),
+            timestamp_delta(local_timestamp, last_local),
+        ) else {
+            if timestamp_after(peer_timestamp, last_peer) {
+                self.last = Some((peer_timestamp, local_timestamp));
+            }
+            return;
+        };
+        self.last = Some((peer_timestamp, local_timestamp));
+
+        let delay_change = (f64::from(arrived) - f64::from(sent)).abs();
+        self.jitter_ms += (delay_change - self.jitter_ms) * constants::JITTER_GAIN;
+        self.initialized = true;
+    }
+
+    /// Get the smoothed jitter.
+    pub fn jitter(&self) -> Duration {
+        Duration::from_secs_f64(self.jitter_ms / 1000.0)
+    }
+
+    /// Get the smoothed jitter in milliseconds.
+    pub fn jitter_ms(&self) -> f64 {
+        self.jitter_ms
+    }
+
+    /// Check if at least one delay difference has been measured.
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+}
+
 /// Timestamp tracker for RTT measurement via timestamp echo.
 ///
 /// Each frame carries a timestamp and echoes the peer's timestamp.
@@ -162,6 +392,8 @@ pub struct TimestampTracker {
     session_start: Instant,
     /// Most recent timestamp we received from peer (for echoing).
     last_peer_timestamp: u32,
+    /// Number of times the peer's timestamp has wrapped past u32::MAX.
+    peer_epoch: u32,
     /// Our timestamp that we're waiting to be echoed.
     pending_timestamp: Option<u32>,
     /// When we sent the frame with pending_timestamp.
@@ -174,6 +406,7 @@ impl TimestampTracker {
         Self {
             session_start: Instant::now(),
             last_peer_timestamp: 0,
+            peer_epoch: 0,
             pending_timestamp: None,
             pending_send_time: None,
         }
@@ -184,6 +417,7 @@ impl TimestampTracker {
         Self {
             session_start: start,
             last_peer_timestamp: 0,
+            peer_epoch: 0,
             pending_timestamp: None,
             pending_send_time: None,
         }
@@ -199,6 +433,14 @@ impl TimestampTracker {
         self.last_peer_timestamp
     }
 
+    /// Get the peer's last timestamp extended with its wrap epoch.
+    ///
+    /// Monotonic across the u32 wrap, for comparing peer timestamps over
+    /// sessions longer than ~49 days.
+    pub fn peer_timestamp_extended(&self) -> u64 {
+        (u64::from(self.peer_epoch) << 32) | u64::from(self.last_peer_timestamp)
+    }
+
     /// Record that we're sending a frame with the given timestamp.
     pub fn on_send(&mut self, timestamp: u32) {
         self.pending_timestamp = Some(timestamp);
@@ -209,8 +451,7 @@ impl TimestampTracker {
     ///
     /// Returns an RTT sample if the echo matches our pending timestamp.
     pub fn on_receive(&mut self, peer_timestamp: u32, echo: u32) -> Option<Duration> {
-        // Update the timestamp we'll echo back
-        self.last_peer_timestamp = peer_timestamp;
+        self.update_peer_timestamp(peer_timestamp);
 
         // Check if this echoes our pending timestamp
         if let (Some(pending), Some(send_time)) = (self.pending_timestamp, self.pending_send_time)
@@ -225,6 +466,37 @@ impl TimestampTracker {
         None
     }
 
+    /// Process a received frame's timestamps against our timestamp clock.
+    ///
+    /// Like [`on_receive`](Self::on_receive), but the RTT is the
+    /// wraparound-safe delta between `now` (our current timestamp) and the
+    /// echoed one. An implausible delta consumes the pending timestamp
+    /// without producing a sample.
+    pub fn on_receive_at(&mut self, peer_timestamp: u32, echo: u32, now: u32) -> Option<Duration> {
+        self.update_peer_timestamp(peer_timestamp);
+
+        if self.pending_timestamp != Some(echo) {
+            return None;
+        }
+        self.pending_timestamp = None;
+        self.pending_send_time = None;
+        timestamp_delta(now, echo).map(|ms| Duration::from_millis(u64::from(ms)))
+    }
+
+    /// Record the peer's timestamp, ignoring reordered older ones.
+    fn update_peer_timestamp(&mut self, peer_timestamp: u32) {
+        if peer_timestamp == self.last_peer_timestamp
+            || (self.last_peer_timestamp != 0
+                && !timestamp_after(peer_timestamp, self.last_peer_timestamp))
+        {
+            return;
+        }
+        if peer_timestamp < self.last_peer_timestamp {
+            self.peer_epoch = self.peer_epoch.wrapping_add(1);
+        }
+        self.last_peer_timestamp = peer_timestamp;
+    }
+
     /// Clear the pending timestamp (e.g., on retransmission).
     pub fn clear_pending(&mut self) {
         self.pending_timestamp = None;
@@ -289,6 +561,33 @@ mod tests {
         assert!(rto2 <= constants::MAX_RTO);
     }
 
+    #[test]
+    fn test_ambiguous_sample_ignored() {
+        let mut estimator = RttEstimator::new();
+        estimator.update(Duration::from_millis(100));
+        let srtt = estimator.srtt();
+        let backed_off = estimator.backoff();
+
+        // An ack for a retransmitted frame moves nothing
+        assert!(!estimator.on_rtt_sample(Duration::from_millis(900), true));
+        assert_eq!(estimator.srtt(), srtt);
+        assert_eq!(estimator.rto(), backed_off);
+    }
+
+    #[test]
+    fn test_clean_sample_after_recovery_resets_backoff() {
+        let mut estimator = RttEstimator::new();
+        estimator.update(Duration::from_millis(100));
+        let base_rto = estimator.rto();
+        estimator.backoff();
+        estimator.backoff();
+        estimator.on_rtt_sample(Duration::from_millis(900), true);
+
+        assert!(estimator.on_rtt_sample(Duration::from_millis(120), false));
+        assert!(estimator.srtt_ms() > 100.0 && estimator.srtt_ms() < 120.0);
+        assert!(estimator.rto() < base_rto * 4);
+    }
+
     #[test]
     fn test_rtt_estimator_max_rto() {
         let mut estimator = RttEstimator::new();
@@ -313,6 +612,73 @@ mod tests {
         assert!(estimator.rto() >= constants::MIN_RTO);
     }
 
+    #[test]
+    fn test_min_rtt_survives_latency_spike() {
+        let mut estimator = RttEstimator::new();
+        let start = Instant::now();
+        let mut now = start;
+
+        for _ in 0..20 {
+            estimator.update_at(Duration::from_millis(50), now);
+            now += Duration::from_millis(100);
+        }
+        let srtt_before = estimator.srtt();
+
+        // Sudden queuing delay
+        for _ in 0..20 {
+            estimator.update_at(Duration::from_millis(300), now);
+            now += Duration::from_millis(100);
+        }
+
+        let window = Duration::from_secs(10);
+        assert_eq!(estimator.min_rtt_at(window, now), Some(Duration::from_millis(50)));
+        assert!(estimator.srtt() > srtt_before * 3);
+
+        // Once the low samples age out of a short window, min-RTT rises
+        let short = Duration::from_secs(1);
+        assert_eq!(estimator.min_rtt_at(short, now), Some(Duration::from_millis(300)));
+    }
+
+    #[test]
+    fn test_bandwidth_estimate() {
+        let mut estimator = RttEstimator::new();
+        assert_eq!(estimator.bandwidth_estimate(), None);
+
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        estimator.on_bytes_acked_at(1000, start);
+        assert_eq!(estimator.bandwidth_estimate_at(start), None);
+
+        estimator.on_bytes_acked_at(1000, at(500));
+        estimator.on_bytes_acked_at(1000, at(1000));
+        assert_eq!(estimator.bandwidth_estimate_at(at(1000)), Some(3000.0));
+    }
+
+    #[test]
+    fn test_bandwidth_estimate_decays_after_burst() {
+        let mut estimator = RttEstimator::new();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        // A 100 ms burst at 100 KB/s
+        for ms in 0..=10 {
+            estimator.on_bytes_acked_at(1000, at(ms * 10));
+        }
+        let burst = estimator.bandwidth_estimate_at(at(100)).unwrap();
+        assert!((burst - 110_000.0).abs() < 1.0);
+
+        // Quiet afterwards: the estimate falls, then expires
+        let later = estimator.bandwidth_estimate_at(at(1000)).unwrap();
+        assert!(later < burst / 5.0);
+        assert_eq!(estimator.bandwidth_estimate_at(at(3000)), None);
+
+        // Fresh acks at a slower pace replace the old ones
+        for ms in [3000, 3500, 4000, 4500] {
+            estimator.on_bytes_acked_at(500, at(ms));
+        }
+        assert_eq!(estimator.bandwidth_estimate_at(at(5000)), Some(1000.0));
+    }
+
     #[test]
     fn test_timestamp_tracker_echo() {
         let start = Instant::now();
@@ -359,4 +725,93 @@ mod tests {
         // Now we have peer's timestamp to echo
         assert_eq!(tracker.timestamp_echo(), 5000);
     }
+
+    #[test]
+    fn test_timestamp_delta_across_wrap() {
+        assert_eq!(timestamp_delta(10, u32::MAX - 5), Some(16));
+        assert_eq!(timestamp_delta(1500, 1000), Some(500));
+        // Earlier-than or implausibly large deltas are invalid samples
+        assert_eq!(timestamp_delta(u32::MAX - 5, 10), None);
+        assert_eq!(timestamp_delta(1000, 1500), None);
+        assert_eq!(
+            timestamp_delta(constants::MAX_TIMESTAMP_DELTA_MS + 1, 0),
+            None
+        );
+    }
+
+    #[test]
+    fn test_timestamp_tracker_rtt_across_wrap() {
+        let mut tracker = TimestampTracker::new();
+
+        // Sent just before the wrap, echo arrives just after
+        tracker.on_send(u32::MAX - 5);
+        let rtt = tracker.on_receive_at(100, u32::MAX - 5, 10);
+        assert_eq!(rtt, Some(Duration::from_millis(16)));
+        assert!(tracker.pending_timestamp.is_none());
+    }
+
+    #[test]
+    fn test_timestamp_tracker_rejects_implausible_rtt() {
+        let mut tracker = TimestampTracker::new();
+
+        tracker.on_send(1000);
+        // Naive subtraction would give ~4e9 ms
+        assert_eq!(tracker.on_receive_at(100, 1000, 999), None);
+        // The pending timestamp is consumed, not retried
+        assert!(tracker.pending_timestamp.is_none());
+    }
+
+    #[test]
+    fn test_timestamp_tracker_peer_timestamp_wrap() {
+        let mut tracker = TimestampTracker::new();
+
+        tracker.on_receive(u32::MAX - 1, 0);
+        assert_eq!(tracker.peer_timestamp_extended(), u64::from(u32::MAX - 1));
+
+        // Peer wraps; the echo follows and the epoch advances
+        tracker.on_receive(3, 0);
+        assert_eq!(tracker.timestamp_echo(), 3);
+        assert_eq!(tracker.peer_timestamp_extended(), (1 << 32) | 3);
+
+        // A reordered pre-wrap timestamp does not regress the echo
+        tracker.on_receive(u32::MAX - 1, 0);
+        assert_eq!(tracker.timestamp_echo(), 3);
+    }
+
+    /// Feed frames sent every 20 ms, each delayed by 50 ms plus `extra(i)`.
+    fn jitter_after(extra: impl Fn(u32) -> u32) -> JitterEstimator {
+        let mut estimator = JitterEstimator::new();
+        for i in 0..200 {
+            let sent = 1_000 + i * 20;
+            estimator.on_frame(sent, sent + 50 + extra(i));
+        }
+        estimator
+    }
+
+    #[test]
+    fn test_jitter_estimator_distinguishes_links() {
+        assert!(!JitterEstimator::new().is_initialized());
+
+        // Delay wobbles by 1 ms on a steady link, 0-40 ms on a mobile one
+        let low = jitter_after(|i| i % 2);
+        let high = jitter_after(|i| (i * 17) % 41);
+        assert!(low.is_initialized() && high.is_initialized());
+        assert!(low.jitter_ms() <= 1.0, "low jitter {}", low.jitter_ms());
+        assert!(high.jitter_ms() >= 10.0, "high jitter {}", high.jitter_ms());
+        assert!(high.jitter() > low.jitter() * 10);
+    }
+
+    #[test]
+    fn test_jitter_estimator_ignores_reordered_frames() {
+        let mut estimator = JitterEstimator::new();
+        estimator.on_frame(u32::MAX - 10, 100);
+        // Straddles the peer's timestamp wrap with no delay change
+        estimator.on_frame(9, 120);
+        assert!(estimator.is_initialized());
+        assert_eq!(estimator.jitter_ms(), 0.0);
+
+        // An older frame arriving late is not a delay sample
+        estimator.on_frame(u32::MAX - 5, 500);
+        assert_eq!(estimator.jitter_ms(), 0.0);
+    }
 }